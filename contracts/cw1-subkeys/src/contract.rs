@@ -5,8 +5,9 @@ use std::ops::{AddAssign, Sub};
 #[cfg(not(feature = "library"))]
 use cosmwasm_std::entry_point;
 use cosmwasm_std::{
-    ensure, ensure_ne, to_binary, BankMsg, Binary, Coin, CosmosMsg, Deps, DepsMut, DistributionMsg,
-    Empty, Env, MessageInfo, Order, Response, StakingMsg, StdResult,
+    ensure, ensure_ne, from_slice, to_binary, Addr, BankMsg, Binary, Coin, CosmosMsg, Deps,
+    DepsMut, DistributionMsg, Empty, Env, MessageInfo, Order, Response, StakingMsg, StdResult,
+    Storage, WasmMsg,
 };
 use cw1::CanExecuteResponse;
 use cw1_whitelist::{
@@ -18,8 +19,9 @@ use cw1_whitelist::{
     state::ADMIN_LIST,
 };
 use cw2::{get_contract_version, set_contract_version};
+use cw20::Cw20ExecuteMsg;
 use cw_storage_plus::Bound;
-use cw_utils::Expiration;
+use cw_utils::{Duration, Expiration, NativeBalance};
 use semver::Version;
 
 use crate::error::ContractError;
@@ -27,7 +29,7 @@ use crate::msg::{
     AllAllowancesResponse, AllPermissionsResponse, AllowanceInfo, ExecuteMsg, PermissionsInfo,
     QueryMsg,
 };
-use crate::state::{Allowance, Permissions, ALLOWANCES, PERMISSIONS};
+use crate::state::{cw20_denom, Allowance, AllowanceReset, Permissions, ALLOWANCES, PERMISSIONS};
 
 // version info for migration info
 const CONTRACT_NAME: &str = "crates.io:cw1-subkeys";
@@ -68,6 +70,12 @@ pub fn execute(
             amount,
             expires,
         } => execute_decrease_allowance(deps, env, info, spender, amount, expires),
+        ExecuteMsg::SetPeriodicAllowance {
+            spender,
+            amount,
+            reset,
+            expires,
+        } => execute_set_periodic_allowance(deps, env, info, spender, amount, reset, expires),
         ExecuteMsg::SetPermissions {
             spender,
             permissions,
@@ -104,17 +112,12 @@ where
                     to_address: _,
                     amount,
                 }) => {
-                    ALLOWANCES.update::<_, ContractError>(deps.storage, &info.sender, |allow| {
-                        let mut allowance = allow.ok_or(ContractError::NoAllowance {})?;
-                        ensure!(
-                            !allowance.expires.is_expired(&env.block),
-                            ContractError::NoAllowance {}
-                        );
-
-                        // Decrease allowance
-                        allowance.balance = allowance.balance.sub(amount.clone())?;
-                        Ok(allowance)
-                    })?;
+                    spend_allowance(deps.storage, &env, &info.sender, amount.clone())?;
+                }
+                CosmosMsg::Wasm(wasm_msg) => {
+                    let amount =
+                        cw20_spend(wasm_msg).ok_or(ContractError::MessageTypeRejected {})?;
+                    spend_allowance(deps.storage, &env, &info.sender, amount)?;
                 }
                 _ => {
                     return Err(ContractError::MessageTypeRejected {});
@@ -130,6 +133,54 @@ where
     Ok(res)
 }
 
+fn spend_allowance(
+    storage: &mut dyn Storage,
+    env: &Env,
+    spender: &Addr,
+    amount: Vec<Coin>,
+) -> Result<(), ContractError> {
+    ALLOWANCES.update::<_, ContractError>(storage, spender, |allow| {
+        let mut allowance = allow.ok_or(ContractError::NoAllowance {})?;
+        ensure!(
+            !allowance.expires.is_expired(&env.block),
+            ContractError::NoAllowance {}
+        );
+        allowance.refill(&env.block);
+
+        // Decrease allowance
+        allowance.balance = allowance.balance.sub(amount)?;
+        Ok(allowance)
+    })?;
+    Ok(())
+}
+
+/// Returns everything a cw20 `Transfer` or `Send` routed through the proxy spends: the cw20 amount
+/// under its `cw20:<contract>` denom, plus any attached native funds.
+/// Any other wasm message is not covered by allowances and returns None.
+pub fn cw20_spend(msg: &WasmMsg) -> Option<Vec<Coin>> {
+    match msg {
+        WasmMsg::Execute {
+            contract_addr,
+            msg,
+            funds,
+        } => {
+            let amount = match from_slice(msg).ok()? {
+                Cw20ExecuteMsg::Transfer { amount, .. } | Cw20ExecuteMsg::Send { amount, .. } => {
+                    amount
+                }
+                _ => return None,
+            };
+            let mut spend = funds.clone();
+            spend.push(Coin {
+                denom: cw20_denom(contract_addr),
+                amount,
+            });
+            Some(spend)
+        }
+        _ => None,
+    }
+}
+
 pub fn check_staking_permissions(
     staking_msg: &StakingMsg,
     permissions: Permissions,
@@ -195,6 +246,8 @@ where
         let mut allowance = allow
             .filter(|allow| !allow.expires.is_expired(&env.block))
             .unwrap_or_default();
+        // start from the current period, so the increase is not lost to a pending refill
+        allowance.refill(&env.block);
 
         if let Some(exp) = expires {
             if exp.is_expired(&env.block) {
@@ -246,6 +299,7 @@ where
             let mut allowance = allow
                 .filter(|allow| !allow.expires.is_expired(&env.block))
                 .ok_or(ContractError::NoAllowance {})?;
+            allowance.refill(&env.block);
 
             if let Some(exp) = expires {
                 if exp.is_expired(&env.block) {
@@ -259,7 +313,8 @@ where
             Ok(allowance)
         })?;
 
-    if allowance.balance.is_empty() {
+    // a periodic allowance is kept, it will be refilled with the next period
+    if allowance.balance.is_empty() && allowance.reset.is_none() {
         ALLOWANCES.remove(deps.storage, &spender_addr);
     }
 
@@ -272,6 +327,58 @@ where
     Ok(res)
 }
 
+pub fn execute_set_periodic_allowance<T>(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    spender: String,
+    amount: Vec<Coin>,
+    reset: Duration,
+    expires: Option<Expiration>,
+) -> Result<Response<T>, ContractError>
+where
+    T: Clone + fmt::Debug + PartialEq + JsonSchema,
+{
+    let cfg = ADMIN_LIST.load(deps.storage)?;
+    ensure!(cfg.is_admin(&info.sender), ContractError::Unauthorized {});
+
+    let spender_addr = deps.api.addr_validate(&spender)?;
+    ensure_ne!(
+        info.sender,
+        spender_addr,
+        ContractError::CannotSetOwnAccount {}
+    );
+    ensure!(
+        !matches!(reset, Duration::Height(0) | Duration::Time(0)),
+        ContractError::ZeroResetPeriod {}
+    );
+    let expires = expires.unwrap_or_default();
+    if expires.is_expired(&env.block) {
+        return Err(ContractError::SettingExpiredAllowance(expires));
+    }
+
+    let mut balance = NativeBalance(amount);
+    balance.normalize();
+    if balance.is_empty() {
+        ALLOWANCES.remove(deps.storage, &spender_addr);
+    } else {
+        let allowance = Allowance {
+            balance: balance.clone(),
+            expires,
+            reset: Some(AllowanceReset::new(balance.clone(), reset, &env.block)),
+        };
+        ALLOWANCES.save(deps.storage, &spender_addr, &allowance)?;
+    }
+
+    let res = Response::new()
+        .add_attribute("action", "set_periodic_allowance")
+        .add_attribute("owner", info.sender)
+        .add_attribute("spender", spender)
+        .add_attribute("amount", balance.to_string())
+        .add_attribute("reset", reset.to_string());
+    Ok(res)
+}
+
 pub fn execute_set_permissions<T>(
     deps: DepsMut,
     _env: Env,
@@ -323,10 +430,11 @@ pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
 pub fn query_allowance(deps: Deps, env: Env, spender: String) -> StdResult<Allowance> {
     // we can use unchecked here as it is a query - bad value means a miss, we never write it
     let spender = deps.api.addr_validate(&spender)?;
-    let allow = ALLOWANCES
+    let mut allow = ALLOWANCES
         .may_load(deps.storage, &spender)?
         .filter(|allow| !allow.expires.is_expired(&env.block))
         .unwrap_or_default();
+    allow.refill(&env.block);
 
     Ok(allow)
 }
@@ -365,7 +473,22 @@ fn can_execute(deps: Deps, env: Env, sender: String, msg: CosmosMsg) -> StdResul
             let allowance = ALLOWANCES.may_load(deps.storage, &sender)?;
             match allowance {
                 // if there is an allowance, we subtract the requested amount to ensure it is covered (error on underflow)
-                Some(allow) => {
+                Some(mut allow) => {
+                    allow.refill(&env.block);
+                    Ok(!allow.expires.is_expired(&env.block) && allow.balance.sub(amount).is_ok())
+                }
+                None => Ok(false),
+            }
+        }
+        CosmosMsg::Wasm(wasm_msg) => {
+            let amount = match cw20_spend(&wasm_msg) {
+                Some(amount) => amount,
+                None => return Ok(false),
+            };
+            let allowance = ALLOWANCES.may_load(deps.storage, &sender)?;
+            match allowance {
+                Some(mut allow) => {
+                    allow.refill(&env.block);
                     Ok(!allow.expires.is_expired(&env.block) && allow.balance.sub(amount).is_ok())
                 }
                 None => Ok(false),
@@ -420,10 +543,14 @@ pub fn query_all_allowances(
         })
         .take(limit)
         .map(|item| {
-            item.map(|(addr, allow)| AllowanceInfo {
-                spender: addr.into(),
-                balance: allow.balance,
-                expires: allow.expires,
+            item.map(|(addr, mut allow)| {
+                allow.refill(&env.block);
+                AllowanceInfo {
+                    spender: addr.into(),
+                    balance: allow.balance,
+                    expires: allow.expires,
+                    reset: allow.reset,
+                }
             })
         })
         .collect::<StdResult<Vec<_>>>()?;
@@ -473,7 +600,7 @@ mod tests {
     use cosmwasm_std::testing::{
         mock_dependencies, mock_env, mock_info, MockApi, MockQuerier, MockStorage,
     };
-    use cosmwasm_std::{coin, coins, OwnedDeps, StakingMsg, SubMsg, Timestamp};
+    use cosmwasm_std::{coin, coins, OwnedDeps, StakingMsg, SubMsg, Timestamp, Uint128};
 
     use cw1_whitelist::msg::AdminListResponse;
     use cw2::{get_contract_version, ContractVersion};
@@ -498,6 +625,7 @@ mod tests {
     const TOKEN: &str = "token";
     const TOKEN1: &str = "token1";
     const TOKEN2: &str = "token2";
+    const TOKEN_CONTRACT: &str = "cw20-token";
 
     const ALL_PERMS: Permissions = Permissions {
         delegate: true,
@@ -649,6 +777,15 @@ mod tests {
         }
     }
 
+    fn cw20_msg(msg: Cw20ExecuteMsg) -> CosmosMsg {
+        WasmMsg::Execute {
+            contract_addr: TOKEN_CONTRACT.to_owned(),
+            msg: to_binary(&msg).unwrap(),
+            funds: vec![],
+        }
+        .into()
+    }
+
     /// Helper function for comparing vectors or another slice-like object as they would represent
     /// set with duplications. Compares sets by first sorting elements using provided ordering.
     /// This functions reshufless elements inplace, as it should never matter as compared
@@ -700,6 +837,7 @@ mod tests {
                 Allowance {
                     balance: NativeBalance(vec![coin(1, TOKEN)]),
                     expires: Expiration::Never {},
+                    reset: None,
                 }
             );
             let allowance =
@@ -709,6 +847,7 @@ mod tests {
                 Allowance {
                     balance: NativeBalance(vec![coin(2, TOKEN)]),
                     expires: Expiration::Never {},
+                    reset: None,
                 }
             );
 
@@ -733,10 +872,47 @@ mod tests {
                 Allowance {
                     balance: NativeBalance(vec![]),
                     expires: Expiration::Never {},
+                    reset: None,
                 }
             );
         }
 
+        #[test]
+        fn query_expired_without_write() {
+            let Suite { deps, .. } = SuiteConfig::new()
+                .with_allowance(SPENDER1, coin(10, TOKEN))
+                .expire_allowances(SPENDER1, NON_EXPIRED_TIME)
+                .init();
+
+            // usable while not expired
+            let allowance =
+                query_allowance(deps.as_ref(), mock_env(), SPENDER1.to_owned()).unwrap();
+            assert_eq!(allowance.balance, NativeBalance(coins(10, TOKEN)));
+
+            // once the block passes the expiration the stored allowance is still there,
+            // but queries report it as unusable
+            let mut env = mock_env();
+            env.block.time = env.block.time.plus_seconds(1_000_000_000);
+            let allowance =
+                query_allowance(deps.as_ref(), env.clone(), SPENDER1.to_owned()).unwrap();
+            assert_eq!(allowance, Allowance::default());
+            assert!(ALLOWANCES
+                .may_load(&deps.storage, &Addr::unchecked(SPENDER1))
+                .unwrap()
+                .is_some());
+
+            let all = query_all_allowances(deps.as_ref(), env.clone(), None, None).unwrap();
+            assert_eq!(all.allowances, vec![]);
+
+            let msg = BankMsg::Send {
+                to_address: SPENDER2.to_owned(),
+                amount: coins(1, TOKEN),
+            }
+            .into();
+            let resp = query_can_execute(deps.as_ref(), env, SPENDER1.to_owned(), msg).unwrap();
+            assert_eq!(resp, CanExecuteResponse { can_execute: false });
+        }
+
         #[test]
         fn query_all() {
             let s1_allow = coin(1234, TOKEN);
@@ -784,16 +960,19 @@ mod tests {
                     spender: SPENDER1.to_owned(),
                     balance: NativeBalance(vec![s1_allow]),
                     expires: Expiration::Never {}, // Not set, expected default
+                    reset: None,
                 },
                 AllowanceInfo {
                     spender: SPENDER2.to_owned(),
                     balance: NativeBalance(vec![s2_allow]),
                     expires: s2_expire,
+                    reset: None,
                 },
                 AllowanceInfo {
                     spender: SPENDER3.to_owned(),
                     balance: NativeBalance(vec![s3_allow]),
                     expires: s3_expire,
+                    reset: None,
                 },
             ];
 
@@ -1048,6 +1227,7 @@ mod tests {
                         spender: SPENDER1.to_owned(),
                         balance: NativeBalance(vec![coin(4, TOKEN1)]),
                         expires: Expiration::Never {},
+                        reset: None,
                     }]
                 }
                 .canonical()
@@ -1090,6 +1270,7 @@ mod tests {
                         spender: SPENDER1.to_owned(),
                         balance: NativeBalance(vec![coin(4, TOKEN1)]),
                         expires: NON_EXPIRED_HEIGHT,
+                        reset: None,
                     }]
                 }
                 .canonical()
@@ -1129,6 +1310,7 @@ mod tests {
                         spender: SPENDER1.to_owned(),
                         balance: NativeBalance(vec![coin(1, TOKEN1), coin(3, TOKEN2)]),
                         expires: Expiration::Never {},
+                        reset: None,
                     }]
                 }
                 .canonical()
@@ -1169,11 +1351,13 @@ mod tests {
                             spender: SPENDER1.to_owned(),
                             balance: NativeBalance(vec![coin(1, TOKEN1)]),
                             expires: Expiration::Never {},
+                            reset: None,
                         },
                         AllowanceInfo {
                             spender: SPENDER2.to_owned(),
                             balance: NativeBalance(vec![coin(3, TOKEN1)]),
                             expires: Expiration::Never {},
+                            reset: None,
                         }
                     ]
                 }
@@ -1215,11 +1399,13 @@ mod tests {
                             spender: SPENDER1.to_owned(),
                             balance: NativeBalance(vec![coin(1, TOKEN1)]),
                             expires: Expiration::Never {},
+                            reset: None,
                         },
                         AllowanceInfo {
                             spender: SPENDER2.to_owned(),
                             balance: NativeBalance(vec![coin(3, TOKEN1)]),
                             expires: NON_EXPIRED_HEIGHT,
+                            reset: None,
                         }
                     ]
                 }
@@ -1261,6 +1447,7 @@ mod tests {
                         spender: SPENDER1.to_owned(),
                         balance: NativeBalance(vec![coin(2, TOKEN2)]),
                         expires: NON_EXPIRED_TIME,
+                        reset: None,
                     }]
                 }
                 .canonical(),
@@ -1300,6 +1487,7 @@ mod tests {
                         spender: SPENDER1.to_owned(),
                         balance: NativeBalance(vec![coin(1, TOKEN1)]),
                         expires: NON_EXPIRED_HEIGHT,
+                        reset: None,
                     }]
                 }
                 .canonical(),
@@ -1376,6 +1564,7 @@ mod tests {
                         spender: SPENDER1.to_owned(),
                         balance: NativeBalance(vec![coin(6, TOKEN1)]),
                         expires: NON_EXPIRED_HEIGHT,
+                        reset: None,
                     }]
                 }
                 .canonical()
@@ -1417,6 +1606,7 @@ mod tests {
                         spender: SPENDER1.to_owned(),
                         balance: NativeBalance(vec![coin(20, TOKEN2)]),
                         expires: NON_EXPIRED_HEIGHT,
+                        reset: None,
                     }]
                 }
                 .canonical()
@@ -1458,6 +1648,7 @@ mod tests {
                         spender: SPENDER1.to_owned(),
                         balance: NativeBalance(vec![coin(20, TOKEN2)]),
                         expires: NON_EXPIRED_HEIGHT,
+                        reset: None,
                     }]
                 }
                 .canonical()
@@ -1531,6 +1722,7 @@ mod tests {
                         spender: SPENDER1.to_owned(),
                         balance: NativeBalance(vec![coin(6, TOKEN1)]),
                         expires: NON_EXPIRED_TIME,
+                        reset: None,
                     }]
                 }
                 .canonical()
@@ -1567,6 +1759,7 @@ mod tests {
                         spender: SPENDER1.to_owned(),
                         balance: NativeBalance(vec![coin(10, TOKEN1)]),
                         expires: NON_EXPIRED_HEIGHT,
+                        reset: None,
                     }]
                 }
                 .canonical()
@@ -1602,6 +1795,7 @@ mod tests {
                         spender: SPENDER1.to_owned(),
                         balance: NativeBalance(vec![coin(10, TOKEN1)]),
                         expires: Expiration::Never {},
+                        reset: None,
                     }]
                 }
                 .canonical()
@@ -1667,6 +1861,7 @@ mod tests {
                         spender: SPENDER1.to_owned(),
                         balance: NativeBalance(vec![coin(3, TOKEN1)]),
                         expires: NON_EXPIRED_HEIGHT,
+                        reset: None,
                     }]
                 }
                 .canonical(),
@@ -1715,6 +1910,7 @@ mod tests {
                         spender: SPENDER1.to_owned(),
                         balance: NativeBalance(vec![coin(4, TOKEN1)]),
                         expires: Expiration::Never {},
+                        reset: None,
                     }]
                 }
                 .canonical()
@@ -1779,7 +1975,8 @@ mod tests {
                     allowances: vec![AllowanceInfo {
                         spender: SPENDER1.to_owned(),
                         balance: NativeBalance(coins(10, TOKEN1)),
-                        expires: Expiration::Never {}
+                        expires: Expiration::Never {},
+                        reset: None,
                     }]
                 }
                 .canonical()
@@ -1846,6 +2043,115 @@ mod tests {
             );
         }
 
+        #[test]
+        fn cw20_within_allowance() {
+            let token = cw20_denom(TOKEN_CONTRACT);
+            let Suite { mut deps, .. } = SuiteConfig::new()
+                .with_allowance(SPENDER1, coin(10, &token))
+                .init();
+
+            let msgs = vec![
+                cw20_msg(Cw20ExecuteMsg::Transfer {
+                    recipient: SPENDER2.to_owned(),
+                    amount: Uint128::new(4),
+                }),
+                cw20_msg(Cw20ExecuteMsg::Send {
+                    contract: SPENDER3.to_owned(),
+                    amount: Uint128::new(5),
+                    msg: Binary::default(),
+                }),
+            ];
+
+            let info = mock_info(SPENDER1, &[]);
+            let rsp = execute(
+                deps.as_mut(),
+                mock_env(),
+                info,
+                ExecuteMsg::Execute { msgs: msgs.clone() },
+            )
+            .unwrap();
+
+            assert_eq!(
+                rsp.messages,
+                msgs.into_iter().map(SubMsg::new).collect::<Vec<_>>()
+            );
+            assert_eq!(
+                query_allowance(deps.as_ref(), mock_env(), SPENDER1.to_owned()).unwrap(),
+                Allowance {
+                    balance: NativeBalance(vec![coin(1, &token)]),
+                    expires: Expiration::Never {},
+                    reset: None,
+                }
+            );
+        }
+
+        #[test]
+        fn cw20_over_allowance() {
+            let token = cw20_denom(TOKEN_CONTRACT);
+            let Suite { mut deps, .. } = SuiteConfig::new()
+                .with_allowance(SPENDER1, coin(10, &token))
+                .init();
+
+            let msgs = vec![cw20_msg(Cw20ExecuteMsg::Transfer {
+                recipient: SPENDER2.to_owned(),
+                amount: Uint128::new(11),
+            })];
+
+            let info = mock_info(SPENDER1, &[]);
+            execute(
+                deps.as_mut(),
+                mock_env(),
+                info,
+                ExecuteMsg::Execute { msgs },
+            )
+            .unwrap_err();
+
+            assert_eq!(
+                query_allowance(deps.as_ref(), mock_env(), SPENDER1.to_owned()).unwrap(),
+                Allowance {
+                    balance: NativeBalance(vec![coin(10, &token)]),
+                    expires: Expiration::Never {},
+                    reset: None,
+                }
+            );
+        }
+
+        #[test]
+        fn cw20_unlisted_token() {
+            // an allowance on a native token or another cw20 does not cover this one
+            let Suite { mut deps, .. } = SuiteConfig::new()
+                .with_allowance(SPENDER1, coin(10, TOKEN1))
+                .with_allowance(SPENDER1, coin(10, cw20_denom("other-token")))
+                .init();
+
+            let msgs = vec![cw20_msg(Cw20ExecuteMsg::Transfer {
+                recipient: SPENDER2.to_owned(),
+                amount: Uint128::new(1),
+            })];
+
+            let info = mock_info(SPENDER1, &[]);
+            execute(
+                deps.as_mut(),
+                mock_env(),
+                info.clone(),
+                ExecuteMsg::Execute { msgs },
+            )
+            .unwrap_err();
+
+            // other cw20 messages are never covered by an allowance
+            let msgs = vec![cw20_msg(Cw20ExecuteMsg::Burn {
+                amount: Uint128::new(1),
+            })];
+            let err = execute(
+                deps.as_mut(),
+                mock_env(),
+                info,
+                ExecuteMsg::Execute { msgs },
+            )
+            .unwrap_err();
+            assert_eq!(err, ContractError::MessageTypeRejected {});
+        }
+
         #[test]
         fn admin_without_allowance() {
             let Suite { mut deps, .. } = SuiteConfig::new().with_admin(ADMIN1).init();
@@ -1882,6 +2188,188 @@ mod tests {
         }
     }
 
+    mod periodic_allowance {
+        use super::*;
+
+        fn send(amount: u128) -> ExecuteMsg {
+            ExecuteMsg::Execute {
+                msgs: vec![BankMsg::Send {
+                    to_address: SPENDER2.to_owned(),
+                    amount: coins(amount, TOKEN1),
+                }
+                .into()],
+            }
+        }
+
+        fn set_periodic(deps: DepsMut, reset: Duration) {
+            let msg = ExecuteMsg::SetPeriodicAllowance {
+                spender: SPENDER1.to_owned(),
+                amount: coins(10, TOKEN1),
+                reset,
+                expires: None,
+            };
+            execute(deps, mock_env(), mock_info(OWNER, &[]), msg).unwrap();
+        }
+
+        #[test]
+        fn refills_every_period() {
+            let Suite { mut deps, .. } = Suite::init();
+            set_periodic(deps.as_mut(), Duration::Height(100));
+            let info = mock_info(SPENDER1, &[]);
+
+            // spending within the period
+            execute(deps.as_mut(), mock_env(), info.clone(), send(6)).unwrap();
+            let allow = query_allowance(deps.as_ref(), mock_env(), SPENDER1.to_owned()).unwrap();
+            assert_eq!(allow.balance, NativeBalance(coins(4, TOKEN1)));
+
+            // exhausting it
+            let mut env = mock_env();
+            env.block.height += 99;
+            execute(deps.as_mut(), env.clone(), info.clone(), send(5)).unwrap_err();
+            execute(deps.as_mut(), env.clone(), info.clone(), send(4)).unwrap();
+            let allow = query_allowance(deps.as_ref(), env.clone(), SPENDER1.to_owned()).unwrap();
+            assert_eq!(allow.balance, NativeBalance(vec![]));
+            // an exhausted periodic allowance is kept for the next period
+            let msg = BankMsg::Send {
+                to_address: SPENDER2.to_owned(),
+                amount: coins(1, TOKEN1),
+            }
+            .into();
+            let resp =
+                query_can_execute(deps.as_ref(), env.clone(), SPENDER1.to_owned(), msg).unwrap();
+            assert_eq!(resp, CanExecuteResponse { can_execute: false });
+
+            // the next period refills it
+            env.block.height += 1;
+            let allow = query_allowance(deps.as_ref(), env.clone(), SPENDER1.to_owned()).unwrap();
+            assert_eq!(allow.balance, NativeBalance(coins(10, TOKEN1)));
+            execute(deps.as_mut(), env.clone(), info.clone(), send(3)).unwrap();
+
+            // unspent balance does not accumulate over skipped periods, and periods stay aligned
+            env.block.height += 250;
+            execute(deps.as_mut(), env.clone(), info.clone(), send(11)).unwrap_err();
+            execute(deps.as_mut(), env.clone(), info, send(10)).unwrap();
+            let allow = query_allowance(deps.as_ref(), env, SPENDER1.to_owned()).unwrap();
+            assert_eq!(allow.balance, NativeBalance(vec![]));
+            assert_eq!(
+                allow.reset.unwrap().last_reset,
+                mock_env().block.height + 300
+            );
+        }
+
+        #[test]
+        fn refills_by_time() {
+            let Suite { mut deps, .. } = Suite::init();
+            set_periodic(deps.as_mut(), Duration::Time(3600));
+            let info = mock_info(SPENDER1, &[]);
+
+            execute(deps.as_mut(), mock_env(), info.clone(), send(10)).unwrap();
+            execute(deps.as_mut(), mock_env(), info.clone(), send(1)).unwrap_err();
+
+            let mut env = mock_env();
+            env.block.time = env.block.time.plus_seconds(3600);
+            execute(deps.as_mut(), env, info, send(10)).unwrap();
+        }
+
+        #[test]
+        fn increase_after_period_adds_to_refill() {
+            let Suite { mut deps, .. } = Suite::init();
+            set_periodic(deps.as_mut(), Duration::Height(100));
+            let info = mock_info(SPENDER1, &[]);
+            execute(deps.as_mut(), mock_env(), info, send(6)).unwrap();
+
+            // the elapsed period refills the allowance before it is increased
+            let mut env = mock_env();
+            env.block.height += 100;
+            let msg = ExecuteMsg::IncreaseAllowance {
+                spender: SPENDER1.to_owned(),
+                amount: coin(5, TOKEN1),
+                expires: None,
+            };
+            execute(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), msg).unwrap();
+            let allow = query_allowance(deps.as_ref(), env.clone(), SPENDER1.to_owned()).unwrap();
+            assert_eq!(allow.balance, NativeBalance(coins(15, TOKEN1)));
+            execute(deps.as_mut(), env, mock_info(SPENDER1, &[]), send(15)).unwrap();
+        }
+
+        #[test]
+        fn decrease_after_period_subtracts_from_refill() {
+            let Suite { mut deps, .. } = Suite::init();
+            set_periodic(deps.as_mut(), Duration::Height(100));
+            let info = mock_info(SPENDER1, &[]);
+            execute(deps.as_mut(), mock_env(), info, send(6)).unwrap();
+
+            // the elapsed period refills the allowance before it is decreased
+            let mut env = mock_env();
+            env.block.height += 100;
+            let msg = ExecuteMsg::DecreaseAllowance {
+                spender: SPENDER1.to_owned(),
+                amount: coin(3, TOKEN1),
+                expires: None,
+            };
+            execute(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), msg).unwrap();
+            let allow = query_allowance(deps.as_ref(), env.clone(), SPENDER1.to_owned()).unwrap();
+            assert_eq!(allow.balance, NativeBalance(coins(7, TOKEN1)));
+
+            // and a later query does not refill it again within the period
+            env.block.height += 99;
+            let allow = query_allowance(deps.as_ref(), env, SPENDER1.to_owned()).unwrap();
+            assert_eq!(allow.balance, NativeBalance(coins(7, TOKEN1)));
+        }
+
+        #[test]
+        fn set_periodic_allowance() {
+            let Suite { mut deps, .. } = Suite::init();
+
+            let msg = ExecuteMsg::SetPeriodicAllowance {
+                spender: SPENDER1.to_owned(),
+                amount: coins(10, TOKEN1),
+                reset: Duration::Height(100),
+                expires: None,
+            };
+            let err =
+                execute(deps.as_mut(), mock_env(), mock_info(SPENDER2, &[]), msg).unwrap_err();
+            assert_eq!(err, ContractError::Unauthorized {});
+
+            let msg = ExecuteMsg::SetPeriodicAllowance {
+                spender: SPENDER1.to_owned(),
+                amount: coins(10, TOKEN1),
+                reset: Duration::Time(0),
+                expires: None,
+            };
+            let err = execute(deps.as_mut(), mock_env(), mock_info(OWNER, &[]), msg).unwrap_err();
+            assert_eq!(err, ContractError::ZeroResetPeriod {});
+
+            set_periodic(deps.as_mut(), Duration::Height(100));
+            let allow = query_allowance(deps.as_ref(), mock_env(), SPENDER1.to_owned()).unwrap();
+            assert_eq!(
+                allow,
+                Allowance {
+                    balance: NativeBalance(coins(10, TOKEN1)),
+                    expires: Expiration::Never {},
+                    reset: Some(AllowanceReset {
+                        amount: NativeBalance(coins(10, TOKEN1)),
+                        reset: Duration::Height(100),
+                        last_reset: mock_env().block.height,
+                    }),
+                }
+            );
+
+            // an empty amount removes the allowance
+            let msg = ExecuteMsg::SetPeriodicAllowance {
+                spender: SPENDER1.to_owned(),
+                amount: vec![],
+                reset: Duration::Height(100),
+                expires: None,
+            };
+            execute(deps.as_mut(), mock_env(), mock_info(OWNER, &[]), msg).unwrap();
+            assert!(ALLOWANCES
+                .may_load(&deps.storage, &Addr::unchecked(SPENDER1))
+                .unwrap()
+                .is_none());
+        }
+    }
+
     mod custom_msg {
         use super::*;
 
@@ -2123,6 +2611,30 @@ mod tests {
             assert_eq!(resp, CanExecuteResponse { can_execute: false });
         }
 
+        #[test]
+        fn cw20_allowance() {
+            let Suite { deps, .. } = SuiteConfig::new()
+                .with_allowance(SPENDER1, coin(10, cw20_denom(TOKEN_CONTRACT)))
+                .init();
+
+            let transfer = |amount| {
+                cw20_msg(Cw20ExecuteMsg::Transfer {
+                    recipient: SPENDER2.to_owned(),
+                    amount: Uint128::new(amount),
+                })
+            };
+
+            let resp =
+                query_can_execute(deps.as_ref(), mock_env(), SPENDER1.to_owned(), transfer(10))
+                    .unwrap();
+            assert_eq!(resp, CanExecuteResponse { can_execute: true });
+
+            let resp =
+                query_can_execute(deps.as_ref(), mock_env(), SPENDER1.to_owned(), transfer(11))
+                    .unwrap();
+            assert_eq!(resp, CanExecuteResponse { can_execute: false });
+        }
+
         #[test]
         fn expired_allowance() {
             let Suite { deps, .. } = SuiteConfig::new()
@@ -2243,6 +2755,7 @@ mod tests {
         let allow = Allowance {
             balance: NativeBalance(vec![coin.clone()]),
             expires: Expiration::Never {},
+            reset: None,
         };
         let perm = Permissions {
             delegate: true,
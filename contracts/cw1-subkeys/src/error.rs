@@ -43,6 +43,9 @@ pub enum ContractError {
     #[error("Allowance already expired while setting: {0}")]
     SettingExpiredAllowance(Expiration),
 
+    #[error("Allowance reset period must be longer than zero")]
+    ZeroResetPeriod {},
+
     #[error("Semver parsing error: {0}")]
     SemVer(String),
 }
@@ -52,6 +55,9 @@ impl From<cw1_whitelist::ContractError> for ContractError {
         match err {
             cw1_whitelist::ContractError::Std(error) => ContractError::Std(error),
             cw1_whitelist::ContractError::Unauthorized {} => ContractError::Unauthorized {},
+            cw1_whitelist::ContractError::MessageTypeNotAllowed { .. } => {
+                ContractError::Unauthorized {}
+            }
         }
     }
 }
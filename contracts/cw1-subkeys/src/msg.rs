@@ -3,9 +3,9 @@ use serde::{Deserialize, Serialize};
 use std::fmt;
 
 use cosmwasm_std::{Coin, CosmosMsg, Empty};
-use cw_utils::{Expiration, NativeBalance};
+use cw_utils::{Duration, Expiration, NativeBalance};
 
-use crate::state::Permissions;
+use crate::state::{AllowanceReset, Permissions};
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
@@ -36,6 +36,16 @@ where
         expires: Option<Expiration>,
     },
 
+    /// Gives a subkey (not an admin) a recurring budget: the allowance is set to `amount` now
+    /// and refilled to `amount` once every `reset` period, however much was left.
+    /// Replaces any previous allowance of the subkey. An empty `amount` removes the allowance.
+    SetPeriodicAllowance {
+        spender: String,
+        amount: Vec<Coin>,
+        reset: Duration,
+        expires: Option<Expiration>,
+    },
+
     // Setups up permissions for a given subkey.
     SetPermissions {
         spender: String,
@@ -53,6 +63,7 @@ where
     /// Returns cw1-whitelist::AdminListResponse
     AdminList {},
     /// Get the current allowance for the given subkey (how much it can spend)
+    /// An expired allowance is reported as empty, evaluated against the current block
     /// Returns crate::state::Allowance
     Allowance { spender: String },
     /// Get the current permissions for the given subkey (how much it can spend)
@@ -62,7 +73,7 @@ where
     /// If CanExecute returns true then a call to `Execute` with the same message,
     /// before any further state changes, should also succeed.
     CanExecute { sender: String, msg: CosmosMsg<T> },
-    /// Gets all Allowances for this contract
+    /// Gets all Allowances for this contract, skipping expired ones
     /// Returns AllAllowancesResponse
     AllAllowances {
         start_after: Option<String>,
@@ -99,6 +110,7 @@ pub struct AllowanceInfo {
     pub spender: String,
     pub balance: NativeBalance,
     pub expires: Expiration,
+    pub reset: Option<AllowanceReset>,
 }
 
 #[cfg(test)]
@@ -120,10 +132,12 @@ impl AllowanceInfo {
     ///   spender: "spender2".to_owned(),
     ///   balance: NativeBalance(vec![coin(1, "token1")]),
     ///   expires: Expiration::Never {},
+    ///   reset: None,
     /// }, AllowanceInfo {
     ///   spender: "spender1".to_owned(),
     ///   balance: NativeBalance(vec![coin(2, "token2")]),
     ///   expires: Expiration::Never {},
+    ///   reset: None,
     /// }];
     ///
     /// allows.sort_by(AllowanceInfo::cmp_by_spender);
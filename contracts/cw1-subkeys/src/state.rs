@@ -2,9 +2,9 @@ use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::fmt;
 
-use cosmwasm_std::Addr;
+use cosmwasm_std::{Addr, BlockInfo};
 use cw_storage_plus::Map;
-use cw_utils::{Expiration, NativeBalance};
+use cw_utils::{Duration, Expiration, NativeBalance};
 
 // Permissions struct defines users message execution permissions.
 // Could have implemented permissions for each cosmos module(StakingPermissions, GovPermissions etc...)
@@ -32,6 +32,53 @@ impl fmt::Display for Permissions {
 pub struct Allowance {
     pub balance: NativeBalance,
     pub expires: Expiration,
+    /// If set, the balance is refilled every period instead of being spent once
+    #[serde(default)]
+    pub reset: Option<AllowanceReset>,
+}
+
+/// Turns an allowance into a recurring budget
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct AllowanceReset {
+    /// The balance is set back to this at the start of every period
+    pub amount: NativeBalance,
+    /// The length of a period
+    pub reset: Duration,
+    /// Start of the current period, as block height or unix time in seconds matching `reset`
+    pub last_reset: u64,
+}
+
+impl AllowanceReset {
+    pub fn new(amount: NativeBalance, reset: Duration, block: &BlockInfo) -> Self {
+        let last_reset = match reset {
+            Duration::Height(_) => block.height,
+            Duration::Time(_) => block.time.seconds(),
+        };
+        AllowanceReset {
+            amount,
+            reset,
+            last_reset,
+        }
+    }
+}
+
+impl Allowance {
+    /// Starts a new period if the current one has elapsed, setting the balance back to the reset
+    /// amount. Unspent balance of past periods is not carried over, and periods keep their
+    /// original alignment no matter when the refill happens.
+    pub fn refill(&mut self, block: &BlockInfo) {
+        if let Some(reset) = &mut self.reset {
+            let (now, period) = match reset.reset {
+                Duration::Height(height) => (block.height, height),
+                Duration::Time(time) => (block.time.seconds(), time),
+            };
+            let elapsed = now.saturating_sub(reset.last_reset) / period;
+            if elapsed > 0 {
+                reset.last_reset += elapsed * period;
+                self.balance = reset.amount.clone();
+            }
+        }
+    }
 }
 
 #[cfg(test)]
@@ -53,11 +100,13 @@ impl Allowance {
     /// let allow1 = Allowance {
     ///   balance: NativeBalance(vec![coin(1, "token1"), coin(0, "token2"), coin(2, "token1"), coin(3, "token3")]),
     ///   expires: Expiration::Never {},
+    ///   reset: None,
     /// };
     ///
     /// let allow2 = Allowance {
     ///   balance: NativeBalance(vec![coin(3, "token3"), coin(3, "token1")]),
     ///   expires: Expiration::Never {},
+    ///   reset: None,
     /// };
     ///
     /// assert_eq!(allow1.canonical(), allow2.canonical());
@@ -68,5 +117,10 @@ impl Allowance {
     }
 }
 
+/// Allowances on cw20 tokens are kept next to the native ones, under this denom
+pub fn cw20_denom(contract: &str) -> String {
+    format!("cw20:{}", contract)
+}
+
 pub const PERMISSIONS: Map<&Addr, Permissions> = Map::new("permissions");
 pub const ALLOWANCES: Map<&Addr, Allowance> = Map::new("allowances");
@@ -12,8 +12,10 @@ use cw1::CanExecuteResponse;
 use cw2::set_contract_version;
 
 use crate::error::ContractError;
-use crate::msg::{AdminListResponse, ExecuteMsg, InstantiateMsg, QueryMsg};
-use crate::state::{AdminList, ADMIN_LIST};
+use crate::msg::{
+    AdminListResponse, AdminMsgTypesResponse, ExecuteMsg, InstantiateMsg, MessageType, QueryMsg,
+};
+use crate::state::{AdminList, ADMIN_LIST, ADMIN_MSG_TYPES};
 
 // version info for migration info
 const CONTRACT_NAME: &str = "crates.io:cw1-whitelist";
@@ -52,6 +54,9 @@ pub fn execute(
         ExecuteMsg::Execute { msgs } => execute_execute(deps, env, info, msgs),
         ExecuteMsg::Freeze {} => execute_freeze(deps, env, info),
         ExecuteMsg::UpdateAdmins { admins } => execute_update_admins(deps, env, info, admins),
+        ExecuteMsg::UpdateAdminMsgTypes { admin, msg_types } => {
+            execute_update_admin_msg_types(deps, env, info, admin, msg_types)
+        }
     }
 }
 
@@ -66,6 +71,8 @@ where
 {
     if !can_execute(deps.as_ref(), info.sender.as_ref())? {
         Err(ContractError::Unauthorized {})
+    } else if let Some(msg_type) = first_disallowed(deps.as_ref(), &info.sender, &msgs)? {
+        Err(ContractError::MessageTypeNotAllowed { msg_type })
     } else {
         let res = Response::new()
             .add_messages(msgs)
@@ -80,7 +87,7 @@ pub fn execute_freeze(
     info: MessageInfo,
 ) -> Result<Response, ContractError> {
     let mut cfg = ADMIN_LIST.load(deps.storage)?;
-    if !cfg.can_modify(info.sender.as_ref()) {
+    if !can_modify(deps.as_ref(), &cfg, &info.sender) {
         Err(ContractError::Unauthorized {})
     } else {
         cfg.mutable = false;
@@ -98,10 +105,15 @@ pub fn execute_update_admins(
     admins: Vec<String>,
 ) -> Result<Response, ContractError> {
     let mut cfg = ADMIN_LIST.load(deps.storage)?;
-    if !cfg.can_modify(info.sender.as_ref()) {
+    if !can_modify(deps.as_ref(), &cfg, &info.sender) {
         Err(ContractError::Unauthorized {})
     } else {
-        cfg.admins = map_validate(deps.api, &admins)?;
+        let admins = map_validate(deps.api, &admins)?;
+        // drop the restrictions of removed admins, so they don't apply if they are added again
+        for removed in cfg.admins.iter().filter(|a| !admins.contains(a)) {
+            ADMIN_MSG_TYPES.remove(deps.storage, removed);
+        }
+        cfg.admins = admins;
         ADMIN_LIST.save(deps.storage, &cfg)?;
 
         let res = Response::new().add_attribute("action", "update_admins");
@@ -109,6 +121,51 @@ pub fn execute_update_admins(
     }
 }
 
+pub fn execute_update_admin_msg_types(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    admin: String,
+    msg_types: Option<Vec<MessageType>>,
+) -> Result<Response, ContractError> {
+    let cfg = ADMIN_LIST.load(deps.storage)?;
+    if !can_modify(deps.as_ref(), &cfg, &info.sender) {
+        Err(ContractError::Unauthorized {})
+    } else {
+        let admin = deps.api.addr_validate(&admin)?;
+        match msg_types {
+            Some(msg_types) => ADMIN_MSG_TYPES.save(deps.storage, &admin, &msg_types)?,
+            None => ADMIN_MSG_TYPES.remove(deps.storage, &admin),
+        }
+
+        let res = Response::new()
+            .add_attribute("action", "update_admin_msg_types")
+            .add_attribute("admin", admin);
+        Ok(res)
+    }
+}
+
+// restricted admins may only execute, so they cannot lift their own restrictions
+fn can_modify(deps: Deps, cfg: &AdminList, sender: &Addr) -> bool {
+    cfg.can_modify(sender.as_ref()) && !ADMIN_MSG_TYPES.has(deps.storage, sender)
+}
+
+/// returns the type of the first message the sender is not allowed to execute, if any
+fn first_disallowed<T>(
+    deps: Deps,
+    sender: &Addr,
+    msgs: &[CosmosMsg<T>],
+) -> StdResult<Option<MessageType>> {
+    let disallowed = ADMIN_MSG_TYPES
+        .may_load(deps.storage, sender)?
+        .and_then(|allowed| {
+            msgs.iter()
+                .map(MessageType::of)
+                .find(|msg_type| !allowed.contains(msg_type))
+        });
+    Ok(disallowed)
+}
+
 fn can_execute(deps: Deps, sender: &str) -> StdResult<bool> {
     let cfg = ADMIN_LIST.load(deps.storage)?;
     let can = cfg.is_admin(&sender);
@@ -120,6 +177,7 @@ pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
     match msg {
         QueryMsg::AdminList {} => to_binary(&query_admin_list(deps)?),
         QueryMsg::CanExecute { sender, msg } => to_binary(&query_can_execute(deps, sender, msg)?),
+        QueryMsg::AdminMsgTypes { admin } => to_binary(&query_admin_msg_types(deps, admin)?),
     }
 }
 
@@ -131,14 +189,21 @@ pub fn query_admin_list(deps: Deps) -> StdResult<AdminListResponse> {
     })
 }
 
+pub fn query_admin_msg_types(deps: Deps, admin: String) -> StdResult<AdminMsgTypesResponse> {
+    let admin = deps.api.addr_validate(&admin)?;
+    Ok(AdminMsgTypesResponse {
+        msg_types: ADMIN_MSG_TYPES.may_load(deps.storage, &admin)?,
+    })
+}
+
 pub fn query_can_execute(
     deps: Deps,
     sender: String,
-    _msg: CosmosMsg,
+    msg: CosmosMsg,
 ) -> StdResult<CanExecuteResponse> {
-    Ok(CanExecuteResponse {
-        can_execute: can_execute(deps, &sender)?,
-    })
+    let can_execute = can_execute(deps, &sender)?
+        && first_disallowed(deps, &Addr::unchecked(sender), &[msg])?.is_none();
+    Ok(CanExecuteResponse { can_execute })
 }
 
 #[cfg(test)]
@@ -266,6 +331,92 @@ mod tests {
         assert_eq!(res.attributes, [("action", "execute")]);
     }
 
+    #[test]
+    fn admin_msg_types_restrict_execute() {
+        let mut deps = mock_dependencies();
+
+        let alice = "alice";
+        let bob = "bob";
+
+        // instantiate the contract
+        let instantiate_msg = InstantiateMsg {
+            admins: vec![alice.to_string(), bob.to_string()],
+            mutable: true,
+        };
+        let info = mock_info(alice, &[]);
+        instantiate(deps.as_mut(), mock_env(), info, instantiate_msg).unwrap();
+
+        // alice restricts bob to bank messages
+        let msg = ExecuteMsg::UpdateAdminMsgTypes {
+            admin: bob.to_string(),
+            msg_types: Some(vec![MessageType::Bank]),
+        };
+        execute(deps.as_mut(), mock_env(), mock_info(alice, &[]), msg).unwrap();
+        let res = query_admin_msg_types(deps.as_ref(), bob.to_string()).unwrap();
+        assert_eq!(res.msg_types, Some(vec![MessageType::Bank]));
+
+        let send_msg: CosmosMsg = BankMsg::Send {
+            to_address: bob.to_string(),
+            amount: coins(10000, "DAI"),
+        }
+        .into();
+        let wasm_msg: CosmosMsg = WasmMsg::Execute {
+            contract_addr: "some contract".into(),
+            msg: to_binary(&ExecuteMsg::<Empty>::Freeze {}).unwrap(),
+            funds: vec![],
+        }
+        .into();
+
+        // bob can send tokens
+        let msg = ExecuteMsg::Execute {
+            msgs: vec![send_msg.clone()],
+        };
+        let res = execute(deps.as_mut(), mock_env(), mock_info(bob, &[]), msg).unwrap();
+        assert_eq!(res.messages, vec![SubMsg::new(send_msg.clone())]);
+        let res = query_can_execute(deps.as_ref(), bob.to_string(), send_msg.clone()).unwrap();
+        assert!(res.can_execute);
+
+        // but not execute a contract, not even along with a send
+        let msg = ExecuteMsg::Execute {
+            msgs: vec![send_msg, wasm_msg.clone()],
+        };
+        let err = execute(deps.as_mut(), mock_env(), mock_info(bob, &[]), msg.clone()).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::MessageTypeNotAllowed {
+                msg_type: MessageType::Wasm
+            }
+        );
+        let res = query_can_execute(deps.as_ref(), bob.to_string(), wasm_msg.clone()).unwrap();
+        assert!(!res.can_execute);
+
+        // alice is unrestricted
+        execute(deps.as_mut(), mock_env(), mock_info(alice, &[]), msg).unwrap();
+        let res = query_can_execute(deps.as_ref(), alice.to_string(), wasm_msg).unwrap();
+        assert!(res.can_execute);
+
+        // bob cannot lift his restriction, or change the admins
+        let msg = ExecuteMsg::UpdateAdminMsgTypes {
+            admin: bob.to_string(),
+            msg_types: None,
+        };
+        let err = execute(deps.as_mut(), mock_env(), mock_info(bob, &[]), msg).unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+        let msg = ExecuteMsg::UpdateAdmins {
+            admins: vec![bob.to_string()],
+        };
+        let err = execute(deps.as_mut(), mock_env(), mock_info(bob, &[]), msg).unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+
+        // removing bob drops his restriction
+        let msg = ExecuteMsg::UpdateAdmins {
+            admins: vec![alice.to_string()],
+        };
+        execute(deps.as_mut(), mock_env(), mock_info(alice, &[]), msg).unwrap();
+        let res = query_admin_msg_types(deps.as_ref(), bob.to_string()).unwrap();
+        assert_eq!(res.msg_types, None);
+    }
+
     #[test]
     fn can_execute_query_works() {
         let mut deps = mock_dependencies();
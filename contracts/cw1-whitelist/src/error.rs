@@ -1,4 +1,6 @@
 use cosmwasm_std::StdError;
+
+use crate::msg::MessageType;
 use thiserror::Error;
 
 #[derive(Error, Debug, PartialEq)]
@@ -8,4 +10,7 @@ pub enum ContractError {
 
     #[error("Unauthorized")]
     Unauthorized {},
+
+    #[error("Admin is not allowed to execute {msg_type:?} messages")]
+    MessageTypeNotAllowed { msg_type: MessageType },
 }
@@ -25,6 +25,39 @@ where
     /// UpdateAdmins will change the admin set of the contract, must be called by an existing admin,
     /// and only works if the contract is mutable
     UpdateAdmins { admins: Vec<String> },
+    /// UpdateAdminMsgTypes restricts an admin to only `Execute` the given message types, or lifts
+    /// the restriction if None. Restricted admins cannot change the configuration. Must be called
+    /// by an unrestricted admin, and only works if the contract is mutable
+    UpdateAdminMsgTypes {
+        admin: String,
+        msg_types: Option<Vec<MessageType>>,
+    },
+}
+
+/// The kind of a CosmosMsg, used to restrict what an admin may execute
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum MessageType {
+    Bank,
+    Custom,
+    Staking,
+    Distribution,
+    Wasm,
+    /// Any other message, eg. ibc or stargate
+    Other,
+}
+
+impl MessageType {
+    pub fn of<T>(msg: &CosmosMsg<T>) -> Self {
+        match msg {
+            CosmosMsg::Bank(_) => MessageType::Bank,
+            CosmosMsg::Custom(_) => MessageType::Custom,
+            CosmosMsg::Staking(_) => MessageType::Staking,
+            CosmosMsg::Distribution(_) => MessageType::Distribution,
+            CosmosMsg::Wasm(_) => MessageType::Wasm,
+            _ => MessageType::Other,
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -39,6 +72,14 @@ where
     /// If CanExecute returns true then a call to `Execute` with the same message,
     /// before any further state changes, should also succeed.
     CanExecute { sender: String, msg: CosmosMsg<T> },
+    /// Shows the message types an admin is restricted to, None if unrestricted.
+    /// Returns AdminMsgTypesResponse
+    AdminMsgTypes { admin: String },
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct AdminMsgTypesResponse {
+    pub msg_types: Option<Vec<MessageType>>,
 }
 
 #[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
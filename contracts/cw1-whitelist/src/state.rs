@@ -2,7 +2,9 @@ use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 use cosmwasm_std::Addr;
-use cw_storage_plus::Item;
+use cw_storage_plus::{Item, Map};
+
+use crate::msg::MessageType;
 
 #[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug, Default)]
 pub struct AdminList {
@@ -24,6 +26,8 @@ impl AdminList {
 }
 
 pub const ADMIN_LIST: Item<AdminList> = Item::new("admin_list");
+/// Admins listed here may only execute these message types. Admins without an entry are unrestricted
+pub const ADMIN_MSG_TYPES: Map<&Addr, Vec<MessageType>> = Map::new("admin_msg_types");
 
 #[cfg(test)]
 mod tests {
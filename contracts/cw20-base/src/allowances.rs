@@ -4,8 +4,11 @@ use cosmwasm_std::{
 };
 use cw20::{AllowanceResponse, Cw20ReceiveMsg, Expiration};
 
+use crate::contract::assert_not_frozen;
 use crate::error::ContractError;
-use crate::state::{ALLOWANCES, BALANCES, TOKEN_INFO};
+use crate::state::{
+    snapshot_total_supply, update_balance, ALLOWANCES, ALLOWANCES_SPENDER, TOKEN_INFO,
+};
 
 pub fn execute_increase_allowance(
     deps: DepsMut,
@@ -20,7 +23,7 @@ pub fn execute_increase_allowance(
         return Err(ContractError::CannotSetOwnAccount {});
     }
 
-    ALLOWANCES.update(
+    let allowance = ALLOWANCES.update(
         deps.storage,
         (&info.sender, &spender_addr),
         |allow| -> StdResult<_> {
@@ -32,6 +35,7 @@ pub fn execute_increase_allowance(
             Ok(val)
         },
     )?;
+    ALLOWANCES_SPENDER.save(deps.storage, (&spender_addr, &info.sender), &allowance)?;
 
     let res = Response::new().add_attributes(vec![
         attr("action", "increase_allowance"),
@@ -68,8 +72,10 @@ pub fn execute_decrease_allowance(
             allowance.expires = exp;
         }
         ALLOWANCES.save(deps.storage, key, &allowance)?;
+        ALLOWANCES_SPENDER.save(deps.storage, (&spender_addr, &info.sender), &allowance)?;
     } else {
         ALLOWANCES.remove(deps.storage, key);
+        ALLOWANCES_SPENDER.remove(deps.storage, (&spender_addr, &info.sender));
     }
 
     let res = Response::new().add_attributes(vec![
@@ -82,6 +88,10 @@ pub fn execute_decrease_allowance(
 }
 
 // this can be used to update a lower allowance - call bucket.update with proper keys
+//
+// This is the only place an allowance is checked, so transfer_from, send_from and burn_from
+// all share the same expiration boundary: an allowance expiring at the current height (or time)
+// is already expired, exactly as defined by `Expiration::is_expired`.
 pub fn deduct_allowance(
     storage: &mut dyn Storage,
     owner: &Addr,
@@ -89,9 +99,10 @@ pub fn deduct_allowance(
     block: &BlockInfo,
     amount: Uint128,
 ) -> Result<AllowanceResponse, ContractError> {
-    ALLOWANCES.update(storage, (owner, spender), |current| {
+    let allowance = ALLOWANCES.update(storage, (owner, spender), |current| {
         match current {
             Some(mut a) => {
+                // expired at the boundary, eg. AtHeight(h) can no longer be used in block h
                 if a.expires.is_expired(block) {
                     Err(ContractError::Expired {})
                 } else {
@@ -105,7 +116,9 @@ pub fn deduct_allowance(
             }
             None => Err(ContractError::NoAllowance {}),
         }
-    })
+    })?;
+    ALLOWANCES_SPENDER.save(storage, (spender, owner), &allowance)?;
+    Ok(allowance)
 }
 
 pub fn execute_transfer_from(
@@ -118,19 +131,22 @@ pub fn execute_transfer_from(
 ) -> Result<Response, ContractError> {
     let rcpt_addr = deps.api.addr_validate(&recipient)?;
     let owner_addr = deps.api.addr_validate(&owner)?;
+    assert_not_frozen(deps.storage, &[&owner_addr, &rcpt_addr, &info.sender])?;
 
     // deduct allowance before doing anything else have enough allowance
     deduct_allowance(deps.storage, &owner_addr, &info.sender, &env.block, amount)?;
 
-    BALANCES.update(
+    update_balance(
         deps.storage,
+        env.block.height,
         &owner_addr,
         |balance: Option<Uint128>| -> StdResult<_> {
             Ok(balance.unwrap_or_default().checked_sub(amount)?)
         },
     )?;
-    BALANCES.update(
+    update_balance(
         deps.storage,
+        env.block.height,
         &rcpt_addr,
         |balance: Option<Uint128>| -> StdResult<_> { Ok(balance.unwrap_or_default() + amount) },
     )?;
@@ -154,23 +170,26 @@ pub fn execute_burn_from(
     amount: Uint128,
 ) -> Result<Response, ContractError> {
     let owner_addr = deps.api.addr_validate(&owner)?;
+    assert_not_frozen(deps.storage, &[&owner_addr, &info.sender])?;
 
     // deduct allowance before doing anything else have enough allowance
     deduct_allowance(deps.storage, &owner_addr, &info.sender, &env.block, amount)?;
 
     // lower balance
-    BALANCES.update(
+    update_balance(
         deps.storage,
+        env.block.height,
         &owner_addr,
         |balance: Option<Uint128>| -> StdResult<_> {
             Ok(balance.unwrap_or_default().checked_sub(amount)?)
         },
     )?;
     // reduce total_supply
-    TOKEN_INFO.update(deps.storage, |mut meta| -> StdResult<_> {
+    let meta = TOKEN_INFO.update(deps.storage, |mut meta| -> StdResult<_> {
         meta.total_supply = meta.total_supply.checked_sub(amount)?;
         Ok(meta)
     })?;
+    snapshot_total_supply(deps.storage, env.block.height, meta.total_supply)?;
 
     let res = Response::new().add_attributes(vec![
         attr("action", "burn_from"),
@@ -192,20 +211,23 @@ pub fn execute_send_from(
 ) -> Result<Response, ContractError> {
     let rcpt_addr = deps.api.addr_validate(&contract)?;
     let owner_addr = deps.api.addr_validate(&owner)?;
+    assert_not_frozen(deps.storage, &[&owner_addr, &rcpt_addr, &info.sender])?;
 
     // deduct allowance before doing anything else have enough allowance
     deduct_allowance(deps.storage, &owner_addr, &info.sender, &env.block, amount)?;
 
     // move the tokens to the contract
-    BALANCES.update(
+    update_balance(
         deps.storage,
+        env.block.height,
         &owner_addr,
         |balance: Option<Uint128>| -> StdResult<_> {
             Ok(balance.unwrap_or_default().checked_sub(amount)?)
         },
     )?;
-    BALANCES.update(
+    update_balance(
         deps.storage,
+        env.block.height,
         &rcpt_addr,
         |balance: Option<Uint128>| -> StdResult<_> { Ok(balance.unwrap_or_default() + amount) },
     )?;
@@ -270,6 +292,9 @@ mod tests {
             }],
             mint: None,
             marketing: None,
+            expected_decimals: None,
+            snapshot_balances: None,
+            freezer: None,
         };
         let info = mock_info("creator", &[]);
         let env = mock_env();
@@ -741,4 +766,57 @@ mod tests {
         let err = execute(deps.as_mut(), env, info, msg).unwrap_err();
         assert_eq!(err, ContractError::Expired {});
     }
+
+    #[test]
+    fn all_from_operations_share_expiration_boundary() {
+        let mut deps = mock_dependencies_with_balance(&[]);
+        let owner = String::from("addr0001");
+        let spender = String::from("addr0002");
+        let rcpt = String::from("addr0003");
+        let contract = String::from("cool-dex");
+        do_instantiate(deps.as_mut(), &owner, Uint128::new(999999));
+
+        // allowance expires in the next block (default env height is 12_345)
+        let env = mock_env();
+        let msg = ExecuteMsg::IncreaseAllowance {
+            spender: spender.clone(),
+            amount: Uint128::new(77777),
+            expires: Some(Expiration::AtHeight(env.block.height + 1)),
+        };
+        let info = mock_info(owner.as_ref(), &[]);
+        execute(deps.as_mut(), env, info, msg).unwrap();
+
+        let ops = vec![
+            ExecuteMsg::TransferFrom {
+                owner: owner.clone(),
+                recipient: rcpt,
+                amount: Uint128::new(1),
+            },
+            ExecuteMsg::SendFrom {
+                owner: owner.clone(),
+                contract,
+                amount: Uint128::new(1),
+                msg: Binary::from(r#"{"some":123}"#.as_bytes()),
+            },
+            ExecuteMsg::BurnFrom {
+                owner,
+                amount: Uint128::new(1),
+            },
+        ];
+
+        // all of them work the block before the expiration
+        for msg in ops.clone() {
+            let info = mock_info(spender.as_ref(), &[]);
+            execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        }
+
+        // and all of them are rejected at exactly the expiration height
+        let mut env = mock_env();
+        env.block.height += 1;
+        for msg in ops {
+            let info = mock_info(spender.as_ref(), &[]);
+            let err = execute(deps.as_mut(), env.clone(), info, msg).unwrap_err();
+            assert_eq!(err, ContractError::Expired {});
+        }
+    }
 }
@@ -1,7 +1,8 @@
 #[cfg(not(feature = "library"))]
 use cosmwasm_std::entry_point;
 use cosmwasm_std::{
-    to_binary, Binary, Deps, DepsMut, Env, MessageInfo, Response, StdError, StdResult, Uint128,
+    to_binary, Addr, Binary, ContractResult, Deps, DepsMut, Env, MessageInfo, Reply, Response,
+    StdError, StdResult, Storage, SubMsg, Uint128,
 };
 
 use cw2::set_contract_version;
@@ -14,10 +15,16 @@ use crate::allowances::{
     execute_burn_from, execute_decrease_allowance, execute_increase_allowance, execute_send_from,
     execute_transfer_from, query_allowance,
 };
-use crate::enumerable::{query_all_accounts, query_all_allowances};
+use crate::enumerable::{query_all_accounts, query_all_allowances, query_all_spender_allowances};
 use crate::error::ContractError;
-use crate::msg::{ExecuteMsg, InstantiateMsg, QueryMsg};
-use crate::state::{MinterData, TokenInfo, BALANCES, LOGO, MARKETING_INFO, TOKEN_INFO};
+use crate::msg::{
+    ExecuteMsg, FrozenResponse, InstantiateMsg, MintHookMsg, QueryMsg, TotalSupplyResponse,
+};
+use crate::state::{
+    balance_at_height, snapshot_total_supply, total_supply_at_height, update_balance, MinterData,
+    TokenInfo, BALANCES, BALANCE_SNAPSHOTS, FREEZER, FROZEN, LOGO, MARKETING_INFO, MINT_HOOKS,
+    SNAPSHOT_BALANCES, TOKEN_INFO, TOTAL_SUPPLY_SNAPSHOTS,
+};
 
 // version info for migration info
 const CONTRACT_NAME: &str = "crates.io:cw20-base";
@@ -25,6 +32,14 @@ const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
 
 const LOGO_SIZE_CAP: usize = 5 * 1024;
 
+/// Upper bound on the recipients of a TransferBatch, to keep its gas cost bounded
+pub const MAX_BATCH_TRANSFERS: usize = 100;
+
+/// Upper bound on the memo of a Transfer or Send, in bytes
+pub const MAX_MEMO_LEN: usize = 256;
+
+pub const MINT_HOOK_REPLY_ID: u64 = 1;
+
 /// Checks if data starts with XML preamble
 fn verify_xml_preamble(data: &[u8]) -> Result<(), ContractError> {
     // The easiest way to perform this check would be just match on regex, however regex
@@ -89,22 +104,44 @@ fn verify_logo(logo: &Logo) -> Result<(), ContractError> {
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn instantiate(
     mut deps: DepsMut,
-    _env: Env,
+    env: Env,
     _info: MessageInfo,
     msg: InstantiateMsg,
 ) -> Result<Response, ContractError> {
     set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
     // check valid token info
     msg.validate()?;
+    if let Some(expected) = msg.expected_decimals {
+        if expected != msg.decimals {
+            return Err(ContractError::DecimalsMismatch {
+                expected,
+                actual: msg.decimals,
+            });
+        }
+    }
     // create initial accounts
     let total_supply = create_accounts(&mut deps, &msg.initial_balances)?;
 
+    if msg.snapshot_balances.unwrap_or(false) {
+        SNAPSHOT_BALANCES.save(deps.storage, &true)?;
+        for row in &msg.initial_balances {
+            let address = deps.api.addr_validate(&row.address)?;
+            let balance = BALANCES.load(deps.storage, &address)?;
+            BALANCE_SNAPSHOTS.save(deps.storage, &address, &balance, env.block.height)?;
+        }
+        TOTAL_SUPPLY_SNAPSHOTS.save(deps.storage, &total_supply, env.block.height)?;
+    }
+
     if let Some(limit) = msg.get_cap() {
         if total_supply > limit {
             return Err(StdError::generic_err("Initial supply greater than cap").into());
         }
     }
 
+    if let Some(freezer) = msg.freezer {
+        FREEZER.save(deps.storage, &deps.api.addr_validate(&freezer)?)?;
+    }
+
     let mint = match msg.mint {
         Some(m) => Some(MinterData {
             minter: deps.api.addr_validate(&m.minter)?,
@@ -169,15 +206,21 @@ pub fn execute(
     msg: ExecuteMsg,
 ) -> Result<Response, ContractError> {
     match msg {
-        ExecuteMsg::Transfer { recipient, amount } => {
-            execute_transfer(deps, env, info, recipient, amount)
+        ExecuteMsg::Transfer {
+            recipient,
+            amount,
+            memo,
+        } => execute_transfer(deps, env, info, recipient, amount, memo),
+        ExecuteMsg::TransferBatch { transfers } => {
+            execute_transfer_batch(deps, env, info, transfers)
         }
         ExecuteMsg::Burn { amount } => execute_burn(deps, env, info, amount),
         ExecuteMsg::Send {
             contract,
             amount,
             msg,
-        } => execute_send(deps, env, info, contract, amount, msg),
+            memo,
+        } => execute_send(deps, env, info, contract, amount, msg, memo),
         ExecuteMsg::Mint { recipient, amount } => execute_mint(deps, env, info, recipient, amount),
         ExecuteMsg::IncreaseAllowance {
             spender,
@@ -207,66 +250,184 @@ pub fn execute(
             marketing,
         } => execute_update_marketing(deps, env, info, project, description, marketing),
         ExecuteMsg::UploadLogo(logo) => execute_upload_logo(deps, env, info, logo),
+        ExecuteMsg::AddMintHook { addr } => execute_add_mint_hook(deps, info, addr),
+        ExecuteMsg::RemoveMintHook { addr } => execute_remove_mint_hook(deps, info, addr),
+        ExecuteMsg::Freeze { address } => execute_set_frozen(deps, info, address, true),
+        ExecuteMsg::Unfreeze { address } => execute_set_frozen(deps, info, address, false),
+    }
+}
+
+/// Errors if any of the accounts is frozen. Every path that moves tokens must check all
+/// accounts involved: the owner, the recipient and whoever acts on the owner's behalf.
+pub fn assert_not_frozen(storage: &dyn Storage, accounts: &[&Addr]) -> Result<(), ContractError> {
+    match accounts.iter().find(|addr| FROZEN.has(storage, addr)) {
+        Some(addr) => Err(ContractError::AccountFrozen {
+            address: addr.to_string(),
+        }),
+        None => Ok(()),
+    }
+}
+
+pub fn execute_set_frozen(
+    deps: DepsMut,
+    info: MessageInfo,
+    address: String,
+    frozen: bool,
+) -> Result<Response, ContractError> {
+    match FREEZER.may_load(deps.storage)? {
+        Some(freezer) if freezer == info.sender => {}
+        _ => return Err(ContractError::Unauthorized {}),
+    }
+    let addr = deps.api.addr_validate(&address)?;
+    let action = if frozen {
+        FROZEN.save(deps.storage, &addr, &())?;
+        "freeze"
+    } else {
+        FROZEN.remove(deps.storage, &addr);
+        "unfreeze"
+    };
+
+    let res = Response::new()
+        .add_attribute("action", action)
+        .add_attribute("address", address)
+        .add_attribute("sender", info.sender);
+    Ok(res)
+}
+
+/// Rejects memos over MAX_MEMO_LEN, so they cannot bloat the events
+fn check_memo(memo: &Option<String>) -> Result<(), ContractError> {
+    match memo {
+        Some(memo) if memo.len() > MAX_MEMO_LEN => {
+            Err(ContractError::MemoTooLong { max: MAX_MEMO_LEN })
+        }
+        _ => Ok(()),
     }
 }
 
 pub fn execute_transfer(
     deps: DepsMut,
-    _env: Env,
+    env: Env,
     info: MessageInfo,
     recipient: String,
     amount: Uint128,
+    memo: Option<String>,
 ) -> Result<Response, ContractError> {
     if amount == Uint128::zero() {
         return Err(ContractError::InvalidZeroAmount {});
     }
+    check_memo(&memo)?;
 
     let rcpt_addr = deps.api.addr_validate(&recipient)?;
+    assert_not_frozen(deps.storage, &[&info.sender, &rcpt_addr])?;
 
-    BALANCES.update(
+    update_balance(
         deps.storage,
+        env.block.height,
         &info.sender,
         |balance: Option<Uint128>| -> StdResult<_> {
             Ok(balance.unwrap_or_default().checked_sub(amount)?)
         },
     )?;
-    BALANCES.update(
+    update_balance(
         deps.storage,
+        env.block.height,
         &rcpt_addr,
         |balance: Option<Uint128>| -> StdResult<_> { Ok(balance.unwrap_or_default() + amount) },
     )?;
 
-    let res = Response::new()
+    let mut res = Response::new()
         .add_attribute("action", "transfer")
         .add_attribute("from", info.sender)
         .add_attribute("to", recipient)
         .add_attribute("amount", amount);
+    if let Some(memo) = memo {
+        res = res.add_attribute("memo", memo);
+    }
+    Ok(res)
+}
+
+pub fn execute_transfer_batch(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    transfers: Vec<(String, Uint128)>,
+) -> Result<Response, ContractError> {
+    if transfers.len() > MAX_BATCH_TRANSFERS {
+        return Err(ContractError::TooManyTransfers {
+            max: MAX_BATCH_TRANSFERS,
+        });
+    }
+
+    // validate everything before the first write, so a bad entry leaves all balances untouched
+    let mut total = Uint128::zero();
+    let mut validated = Vec::with_capacity(transfers.len());
+    for (recipient, amount) in transfers {
+        if amount == Uint128::zero() {
+            return Err(ContractError::InvalidZeroAmount {});
+        }
+        total = total.checked_add(amount).map_err(StdError::overflow)?;
+        validated.push((deps.api.addr_validate(&recipient)?, amount));
+    }
+    if total == Uint128::zero() {
+        return Err(ContractError::InvalidZeroAmount {});
+    }
+    let mut accounts = vec![&info.sender];
+    accounts.extend(validated.iter().map(|(addr, _)| addr));
+    assert_not_frozen(deps.storage, &accounts)?;
+
+    update_balance(
+        deps.storage,
+        env.block.height,
+        &info.sender,
+        |balance: Option<Uint128>| -> StdResult<_> {
+            Ok(balance.unwrap_or_default().checked_sub(total)?)
+        },
+    )?;
+    for (rcpt_addr, amount) in &validated {
+        update_balance(
+            deps.storage,
+            env.block.height,
+            rcpt_addr,
+            |balance: Option<Uint128>| -> StdResult<_> {
+                Ok(balance.unwrap_or_default() + *amount)
+            },
+        )?;
+    }
+
+    let res = Response::new()
+        .add_attribute("action", "transfer_batch")
+        .add_attribute("from", info.sender)
+        .add_attribute("recipients", validated.len().to_string())
+        .add_attribute("amount", total);
     Ok(res)
 }
 
 pub fn execute_burn(
     deps: DepsMut,
-    _env: Env,
+    env: Env,
     info: MessageInfo,
     amount: Uint128,
 ) -> Result<Response, ContractError> {
     if amount == Uint128::zero() {
         return Err(ContractError::InvalidZeroAmount {});
     }
+    assert_not_frozen(deps.storage, &[&info.sender])?;
 
     // lower balance
-    BALANCES.update(
+    update_balance(
         deps.storage,
+        env.block.height,
         &info.sender,
         |balance: Option<Uint128>| -> StdResult<_> {
             Ok(balance.unwrap_or_default().checked_sub(amount)?)
         },
     )?;
     // reduce total_supply
-    TOKEN_INFO.update(deps.storage, |mut info| -> StdResult<_> {
+    let token = TOKEN_INFO.update(deps.storage, |mut info| -> StdResult<_> {
         info.total_supply = info.total_supply.checked_sub(amount)?;
         Ok(info)
     })?;
+    snapshot_total_supply(deps.storage, env.block.height, token.total_supply)?;
 
     let res = Response::new()
         .add_attribute("action", "burn")
@@ -277,7 +438,7 @@ pub fn execute_burn(
 
 pub fn execute_mint(
     deps: DepsMut,
-    _env: Env,
+    env: Env,
     info: MessageInfo,
     recipient: String,
     amount: Uint128,
@@ -299,63 +460,127 @@ pub fn execute_mint(
         }
     }
     TOKEN_INFO.save(deps.storage, &config)?;
+    snapshot_total_supply(deps.storage, env.block.height, config.total_supply)?;
 
     // add amount to recipient balance
     let rcpt_addr = deps.api.addr_validate(&recipient)?;
-    BALANCES.update(
+    assert_not_frozen(deps.storage, &[&rcpt_addr])?;
+    update_balance(
         deps.storage,
+        env.block.height,
         &rcpt_addr,
         |balance: Option<Uint128>| -> StdResult<_> { Ok(balance.unwrap_or_default() + amount) },
     )?;
 
+    // notify all hooks, a failing hook is caught in reply and does not revert the mint
+    let hook_msg = MintHookMsg {
+        recipient: recipient.clone(),
+        amount,
+    };
+    let messages = MINT_HOOKS.prepare_hooks(deps.storage, |h| {
+        hook_msg
+            .clone()
+            .into_cosmos_msg(h)
+            .map(|msg| SubMsg::reply_on_error(msg, MINT_HOOK_REPLY_ID))
+    })?;
+
     let res = Response::new()
+        .add_submessages(messages)
         .add_attribute("action", "mint")
         .add_attribute("to", recipient)
         .add_attribute("amount", amount);
     Ok(res)
 }
 
+fn assert_minter(deps: Deps, info: &MessageInfo) -> Result<(), ContractError> {
+    let config = TOKEN_INFO.load(deps.storage)?;
+    match config.mint {
+        Some(m) if m.minter == info.sender => Ok(()),
+        _ => Err(ContractError::Unauthorized {}),
+    }
+}
+
+pub fn execute_add_mint_hook(
+    deps: DepsMut,
+    info: MessageInfo,
+    addr: String,
+) -> Result<Response, ContractError> {
+    assert_minter(deps.as_ref(), &info)?;
+    let hook = deps.api.addr_validate(&addr)?;
+    MINT_HOOKS.add_hook(deps.storage, hook)?;
+
+    let res = Response::new()
+        .add_attribute("action", "add_mint_hook")
+        .add_attribute("hook", addr)
+        .add_attribute("sender", info.sender);
+    Ok(res)
+}
+
+pub fn execute_remove_mint_hook(
+    deps: DepsMut,
+    info: MessageInfo,
+    addr: String,
+) -> Result<Response, ContractError> {
+    assert_minter(deps.as_ref(), &info)?;
+    let hook = deps.api.addr_validate(&addr)?;
+    MINT_HOOKS.remove_hook(deps.storage, hook)?;
+
+    let res = Response::new()
+        .add_attribute("action", "remove_mint_hook")
+        .add_attribute("hook", addr)
+        .add_attribute("sender", info.sender);
+    Ok(res)
+}
+
 pub fn execute_send(
     deps: DepsMut,
-    _env: Env,
+    env: Env,
     info: MessageInfo,
     contract: String,
     amount: Uint128,
     msg: Binary,
+    memo: Option<String>,
 ) -> Result<Response, ContractError> {
     if amount == Uint128::zero() {
         return Err(ContractError::InvalidZeroAmount {});
     }
+    check_memo(&memo)?;
 
     let rcpt_addr = deps.api.addr_validate(&contract)?;
+    assert_not_frozen(deps.storage, &[&info.sender, &rcpt_addr])?;
 
     // move the tokens to the contract
-    BALANCES.update(
+    update_balance(
         deps.storage,
+        env.block.height,
         &info.sender,
         |balance: Option<Uint128>| -> StdResult<_> {
             Ok(balance.unwrap_or_default().checked_sub(amount)?)
         },
     )?;
-    BALANCES.update(
+    update_balance(
         deps.storage,
+        env.block.height,
         &rcpt_addr,
         |balance: Option<Uint128>| -> StdResult<_> { Ok(balance.unwrap_or_default() + amount) },
     )?;
 
-    let res = Response::new()
+    let mut res = Response::new()
         .add_attribute("action", "send")
         .add_attribute("from", &info.sender)
         .add_attribute("to", &contract)
-        .add_attribute("amount", amount)
-        .add_message(
-            Cw20ReceiveMsg {
-                sender: info.sender.into(),
-                amount,
-                msg,
-            }
-            .into_cosmos_msg(contract)?,
-        );
+        .add_attribute("amount", amount);
+    if let Some(memo) = memo {
+        res = res.add_attribute("memo", memo);
+    }
+    let res = res.add_message(
+        Cw20ReceiveMsg {
+            sender: info.sender.into(),
+            amount,
+            msg,
+        }
+        .into_cosmos_msg(contract)?,
+    );
     Ok(res)
 }
 
@@ -447,12 +672,32 @@ pub fn execute_upload_logo(
     Ok(res)
 }
 
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn reply(_deps: DepsMut, _env: Env, reply: Reply) -> Result<Response, ContractError> {
+    if reply.id != MINT_HOOK_REPLY_ID {
+        return Err(ContractError::UnknownReplyId { id: reply.id });
+    }
+    // the failed hook was reverted on its own, just log it and keep the mint
+    let res = match reply.result {
+        ContractResult::Ok(_) => Response::new(),
+        ContractResult::Err(err) => Response::new()
+            .add_attribute("action", "mint_hook_failed")
+            .add_attribute("error", err),
+    };
+    Ok(res)
+}
+
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
     match msg {
         QueryMsg::Balance { address } => to_binary(&query_balance(deps, address)?),
+        QueryMsg::BalanceAt { address, height } => {
+            to_binary(&query_balance_at(deps, address, height)?)
+        }
+        QueryMsg::TotalSupplyAt { height } => to_binary(&query_total_supply_at(deps, height)?),
         QueryMsg::TokenInfo {} => to_binary(&query_token_info(deps)?),
         QueryMsg::Minter {} => to_binary(&query_minter(deps)?),
+        QueryMsg::MintHooks {} => to_binary(&MINT_HOOKS.query_hooks(deps)?),
         QueryMsg::Allowance { owner, spender } => {
             to_binary(&query_allowance(deps, owner, spender)?)
         }
@@ -461,14 +706,31 @@ pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
             start_after,
             limit,
         } => to_binary(&query_all_allowances(deps, owner, start_after, limit)?),
+        QueryMsg::AllSpenderAllowances {
+            spender,
+            start_after,
+            limit,
+        } => to_binary(&query_all_spender_allowances(
+            deps,
+            spender,
+            start_after,
+            limit,
+        )?),
         QueryMsg::AllAccounts { start_after, limit } => {
             to_binary(&query_all_accounts(deps, start_after, limit)?)
         }
         QueryMsg::MarketingInfo {} => to_binary(&query_marketing_info(deps)?),
         QueryMsg::DownloadLogo {} => to_binary(&query_download_logo(deps)?),
+        QueryMsg::Frozen { address } => to_binary(&query_frozen(deps, address)?),
     }
 }
 
+pub fn query_frozen(deps: Deps, address: String) -> StdResult<FrozenResponse> {
+    let address = deps.api.addr_validate(&address)?;
+    let frozen = FROZEN.has(deps.storage, &address);
+    Ok(FrozenResponse { frozen })
+}
+
 pub fn query_balance(deps: Deps, address: String) -> StdResult<BalanceResponse> {
     let address = deps.api.addr_validate(&address)?;
     let balance = BALANCES
@@ -477,6 +739,17 @@ pub fn query_balance(deps: Deps, address: String) -> StdResult<BalanceResponse>
     Ok(BalanceResponse { balance })
 }
 
+pub fn query_balance_at(deps: Deps, address: String, height: u64) -> StdResult<BalanceResponse> {
+    let address = deps.api.addr_validate(&address)?;
+    let balance = balance_at_height(deps.storage, &address, height)?;
+    Ok(BalanceResponse { balance })
+}
+
+pub fn query_total_supply_at(deps: Deps, height: u64) -> StdResult<TotalSupplyResponse> {
+    let total_supply = total_supply_at_height(deps.storage, height)?;
+    Ok(TotalSupplyResponse { total_supply })
+}
+
 pub fn query_token_info(deps: Deps) -> StdResult<TokenInfoResponse> {
     let info = TOKEN_INFO.load(deps.storage)?;
     let res = TokenInfoResponse {
@@ -524,7 +797,9 @@ mod tests {
     use cosmwasm_std::testing::{
         mock_dependencies, mock_dependencies_with_balance, mock_env, mock_info,
     };
-    use cosmwasm_std::{coins, from_binary, Addr, CosmosMsg, StdError, SubMsg, WasmMsg};
+    use cosmwasm_std::{attr, coins, from_binary, Addr, CosmosMsg, StdError, SubMsg, WasmMsg};
+
+    use cw_controllers::{HookError, HooksResponse};
 
     use super::*;
     use crate::msg::InstantiateMarketingInfo;
@@ -574,6 +849,9 @@ mod tests {
             }],
             mint: mint.clone(),
             marketing: None,
+            expected_decimals: None,
+            snapshot_balances: None,
+            freezer: None,
         };
         let info = mock_info("creator", &[]);
         let env = mock_env();
@@ -614,6 +892,9 @@ mod tests {
                 }],
                 mint: None,
                 marketing: None,
+                expected_decimals: None,
+                snapshot_balances: None,
+                freezer: None,
             };
             let info = mock_info("creator", &[]);
             let env = mock_env();
@@ -635,6 +916,44 @@ mod tests {
             );
         }
 
+        #[test]
+        fn expected_decimals() {
+            let mut deps = mock_dependencies();
+            let mut instantiate_msg = InstantiateMsg {
+                name: "Cash Token".to_string(),
+                symbol: "CASH".to_string(),
+                decimals: 6,
+                initial_balances: vec![],
+                mint: None,
+                marketing: None,
+                expected_decimals: Some(9),
+                snapshot_balances: None,
+                freezer: None,
+            };
+            let info = mock_info("creator", &[]);
+
+            // mismatch is rejected
+            let err = instantiate(
+                deps.as_mut(),
+                mock_env(),
+                info.clone(),
+                instantiate_msg.clone(),
+            )
+            .unwrap_err();
+            assert_eq!(
+                err,
+                ContractError::DecimalsMismatch {
+                    expected: 9,
+                    actual: 6
+                }
+            );
+
+            // matching decimals are accepted
+            instantiate_msg.decimals = 9;
+            instantiate(deps.as_mut(), mock_env(), info, instantiate_msg).unwrap();
+            assert_eq!(query_token_info(deps.as_ref()).unwrap().decimals, 9);
+        }
+
         #[test]
         fn mintable() {
             let mut deps = mock_dependencies();
@@ -654,6 +973,9 @@ mod tests {
                     cap: Some(limit),
                 }),
                 marketing: None,
+                expected_decimals: None,
+                snapshot_balances: None,
+                freezer: None,
             };
             let info = mock_info("creator", &[]);
             let env = mock_env();
@@ -701,6 +1023,9 @@ mod tests {
                     cap: Some(limit),
                 }),
                 marketing: None,
+                expected_decimals: None,
+                snapshot_balances: None,
+                freezer: None,
             };
             let info = mock_info("creator", &[]);
             let env = mock_env();
@@ -729,6 +1054,9 @@ mod tests {
                         marketing: Some("marketing".to_owned()),
                         logo: Some(Logo::Url("url".to_owned())),
                     }),
+                    expected_decimals: None,
+                    snapshot_balances: None,
+                    freezer: None,
                 };
 
                 let info = mock_info("creator", &[]);
@@ -769,6 +1097,9 @@ mod tests {
                         marketing: Some("m".to_owned()),
                         logo: Some(Logo::Url("url".to_owned())),
                     }),
+                    expected_decimals: None,
+                    snapshot_balances: None,
+                    freezer: None,
                 };
 
                 let info = mock_info("creator", &[]);
@@ -785,6 +1116,95 @@ mod tests {
         }
     }
 
+    #[test]
+    fn mint_hooks() {
+        let mut deps = mock_dependencies();
+
+        let genesis = String::from("genesis");
+        let minter = String::from("minter");
+        let hook1 = String::from("hook1");
+        let hook2 = String::from("hook2");
+        do_instantiate_with_minter(deps.as_mut(), &genesis, Uint128::new(1234), &minter, None);
+
+        // only the minter can register hooks
+        let msg = ExecuteMsg::AddMintHook {
+            addr: hook1.clone(),
+        };
+        let info = mock_info(genesis.as_ref(), &[]);
+        let err = execute(deps.as_mut(), mock_env(), info, msg.clone()).unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+        let info = mock_info(minter.as_ref(), &[]);
+        execute(deps.as_mut(), mock_env(), info.clone(), msg.clone()).unwrap();
+        let err = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::Hook(HookError::HookAlreadyRegistered {})
+        );
+        let msg = ExecuteMsg::AddMintHook {
+            addr: hook2.clone(),
+        };
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let hooks: HooksResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::MintHooks {}).unwrap())
+                .unwrap();
+        assert_eq!(hooks.hooks, vec![hook1.clone(), hook2.clone()]);
+
+        // every hook is notified on mint
+        let amount = Uint128::new(500);
+        let msg = ExecuteMsg::Mint {
+            recipient: genesis.clone(),
+            amount,
+        };
+        let info = mock_info(minter.as_ref(), &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        let hook_msg = MintHookMsg {
+            recipient: genesis.clone(),
+            amount,
+        };
+        assert_eq!(
+            res.messages,
+            vec![
+                SubMsg::reply_on_error(
+                    hook_msg.clone().into_cosmos_msg(&hook1).unwrap(),
+                    MINT_HOOK_REPLY_ID
+                ),
+                SubMsg::reply_on_error(
+                    hook_msg.into_cosmos_msg(&hook2).unwrap(),
+                    MINT_HOOK_REPLY_ID
+                ),
+            ]
+        );
+        assert_eq!(get_balance(deps.as_ref(), &genesis), Uint128::new(1734));
+
+        // a failing hook is only logged
+        let failure = Reply {
+            id: MINT_HOOK_REPLY_ID,
+            result: ContractResult::Err("hook broke".to_string()),
+        };
+        let res = reply(deps.as_mut(), mock_env(), failure).unwrap();
+        assert_eq!(
+            res.attributes,
+            vec![
+                attr("action", "mint_hook_failed"),
+                attr("error", "hook broke"),
+            ]
+        );
+
+        // only the minter can remove hooks
+        let msg = ExecuteMsg::RemoveMintHook {
+            addr: hook1.clone(),
+        };
+        let info = mock_info(hook1.as_ref(), &[]);
+        let err = execute(deps.as_mut(), mock_env(), info, msg.clone()).unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+        let info = mock_info(minter.as_ref(), &[]);
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let hooks = MINT_HOOKS.query_hooks(deps.as_ref()).unwrap();
+        assert_eq!(hooks.hooks, vec![hook2]);
+    }
+
     #[test]
     fn can_mint_by_minter() {
         let mut deps = mock_dependencies();
@@ -891,6 +1311,9 @@ mod tests {
             ],
             mint: None,
             marketing: None,
+            expected_decimals: None,
+            snapshot_balances: None,
+            freezer: None,
         };
         let info = mock_info("creator", &[]);
         let env = mock_env();
@@ -947,6 +1370,167 @@ mod tests {
         assert_eq!(loaded.balance, Uint128::zero());
     }
 
+    #[test]
+    fn balance_snapshots() {
+        let mut deps = mock_dependencies();
+        let addr1 = String::from("addr0001");
+        let addr2 = String::from("addr0002");
+        let start = mock_env().block.height;
+
+        let instantiate_msg = InstantiateMsg {
+            name: "Vote Token".to_string(),
+            symbol: "VOTE".to_string(),
+            decimals: 6,
+            initial_balances: vec![Cw20Coin {
+                address: addr1.clone(),
+                amount: Uint128::new(1000),
+            }],
+            mint: None,
+            marketing: None,
+            expected_decimals: None,
+            snapshot_balances: Some(true),
+            freezer: None,
+        };
+        let info = mock_info("creator", &[]);
+        instantiate(deps.as_mut(), mock_env(), info, instantiate_msg).unwrap();
+
+        let transfer_at = |deps: DepsMut, height: u64, from: &str, to: &str, amount: u128| {
+            let mut env = mock_env();
+            env.block.height = height;
+            let msg = ExecuteMsg::Transfer {
+                recipient: to.to_string(),
+                amount: Uint128::new(amount),
+                memo: None,
+            };
+            execute(deps, env, mock_info(from, &[]), msg).unwrap();
+        };
+        // two transfers in the same block, then one in a later block
+        transfer_at(deps.as_mut(), start + 1, &addr1, &addr2, 100);
+        transfer_at(deps.as_mut(), start + 1, &addr1, &addr2, 50);
+        transfer_at(deps.as_mut(), start + 5, &addr2, &addr1, 30);
+
+        let balance_at = |deps: Deps, address: &str, height: u64| {
+            query_balance_at(deps, address.to_string(), height)
+                .unwrap()
+                .balance
+                .u128()
+        };
+        // balances at the start of each block
+        let expected = [
+            (start, 0, 0),
+            (start + 1, 1000, 0),
+            (start + 2, 850, 150),
+            (start + 5, 850, 150),
+            (start + 6, 880, 120),
+            (start + 100, 880, 120),
+        ];
+        for (height, balance1, balance2) in expected {
+            assert_eq!(
+                balance_at(deps.as_ref(), &addr1, height),
+                balance1,
+                "{}",
+                height
+            );
+            assert_eq!(
+                balance_at(deps.as_ref(), &addr2, height),
+                balance2,
+                "{}",
+                height
+            );
+        }
+        assert_eq!(get_balance(deps.as_ref(), &addr1), Uint128::new(880));
+
+        // not available without the flag
+        let mut deps = mock_dependencies();
+        do_instantiate(deps.as_mut(), &addr1, Uint128::new(1000));
+        let err = query_balance_at(deps.as_ref(), addr1, start).unwrap_err();
+        assert_eq!(
+            err,
+            StdError::generic_err("Balance snapshots are not enabled")
+        );
+    }
+
+    #[test]
+    fn total_supply_snapshots() {
+        let mut deps = mock_dependencies();
+        let minter = String::from("minter");
+        let holder = String::from("holder");
+        let start = mock_env().block.height;
+
+        let instantiate_msg = InstantiateMsg {
+            name: "Vote Token".to_string(),
+            symbol: "VOTE".to_string(),
+            decimals: 6,
+            initial_balances: vec![Cw20Coin {
+                address: holder.clone(),
+                amount: Uint128::new(1000),
+            }],
+            mint: Some(MinterResponse {
+                minter: minter.clone(),
+                cap: None,
+            }),
+            marketing: None,
+            expected_decimals: None,
+            snapshot_balances: Some(true),
+            freezer: None,
+        };
+        let info = mock_info("creator", &[]);
+        instantiate(deps.as_mut(), mock_env(), info, instantiate_msg).unwrap();
+
+        let execute_at = |deps: DepsMut, height: u64, sender: &str, msg: ExecuteMsg| {
+            let mut env = mock_env();
+            env.block.height = height;
+            execute(deps, env, mock_info(sender, &[]), msg).unwrap();
+        };
+        let mint = |amount: u128| ExecuteMsg::Mint {
+            recipient: holder.clone(),
+            amount: Uint128::new(amount),
+        };
+        let burn = |amount: u128| ExecuteMsg::Burn {
+            amount: Uint128::new(amount),
+        };
+        // a mint and a burn in the same block, the last write counts for the block
+        execute_at(deps.as_mut(), start + 1, &minter, mint(500));
+        execute_at(deps.as_mut(), start + 1, &holder, burn(200));
+        // a single burn some blocks later, then a mint
+        execute_at(deps.as_mut(), start + 4, &holder, burn(300));
+        execute_at(deps.as_mut(), start + 6, &minter, mint(50));
+
+        let supply_at = |height: u64| {
+            query_total_supply_at(deps.as_ref(), height)
+                .unwrap()
+                .total_supply
+                .u128()
+        };
+        // supply at the start of each block
+        let expected = [
+            (start, 0),
+            (start + 1, 1000),
+            (start + 2, 1300),
+            (start + 4, 1300),
+            (start + 5, 1000),
+            (start + 6, 1000),
+            (start + 7, 1050),
+            (start + 100, 1050),
+        ];
+        for (height, total_supply) in expected {
+            assert_eq!(supply_at(height), total_supply, "{}", height);
+        }
+        assert_eq!(
+            query_token_info(deps.as_ref()).unwrap().total_supply,
+            Uint128::new(1050)
+        );
+
+        // not available without the flag
+        let mut deps = mock_dependencies();
+        do_instantiate(deps.as_mut(), &holder, Uint128::new(1000));
+        let err = query_total_supply_at(deps.as_ref(), start).unwrap_err();
+        assert_eq!(
+            err,
+            StdError::generic_err("Balance snapshots are not enabled")
+        );
+    }
+
     #[test]
     fn transfer() {
         let mut deps = mock_dependencies_with_balance(&coins(2, "token"));
@@ -964,6 +1548,7 @@ mod tests {
         let msg = ExecuteMsg::Transfer {
             recipient: addr2.clone(),
             amount: Uint128::zero(),
+            memo: None,
         };
         let err = execute(deps.as_mut(), env, info, msg).unwrap_err();
         assert_eq!(err, ContractError::InvalidZeroAmount {});
@@ -974,6 +1559,7 @@ mod tests {
         let msg = ExecuteMsg::Transfer {
             recipient: addr2.clone(),
             amount: too_much,
+            memo: None,
         };
         let err = execute(deps.as_mut(), env, info, msg).unwrap_err();
         assert!(matches!(err, ContractError::Std(StdError::Overflow { .. })));
@@ -984,6 +1570,7 @@ mod tests {
         let msg = ExecuteMsg::Transfer {
             recipient: addr1.clone(),
             amount: transfer,
+            memo: None,
         };
         let err = execute(deps.as_mut(), env, info, msg).unwrap_err();
         assert!(matches!(err, ContractError::Std(StdError::Overflow { .. })));
@@ -994,6 +1581,7 @@ mod tests {
         let msg = ExecuteMsg::Transfer {
             recipient: addr2.clone(),
             amount: transfer,
+            memo: None,
         };
         let res = execute(deps.as_mut(), env, info, msg).unwrap();
         assert_eq!(res.messages.len(), 0);
@@ -1007,6 +1595,80 @@ mod tests {
         );
     }
 
+    #[test]
+    fn transfer_batch() {
+        let mut deps = mock_dependencies();
+        let addr1 = String::from("addr0001");
+        let addr2 = String::from("addr0002");
+        let addr3 = String::from("addr0003");
+        let amount1 = Uint128::from(12340000u128);
+        do_instantiate(deps.as_mut(), &addr1, amount1);
+
+        // valid batch to multiple recipients
+        let info = mock_info(addr1.as_ref(), &[]);
+        let msg = ExecuteMsg::TransferBatch {
+            transfers: vec![
+                (addr2.clone(), Uint128::new(1000)),
+                (addr3.clone(), Uint128::new(2000)),
+                (addr2.clone(), Uint128::new(500)),
+            ],
+        };
+        let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        assert_eq!(res.messages.len(), 0);
+        assert_eq!(
+            res.attributes,
+            vec![
+                attr("action", "transfer_batch"),
+                attr("from", addr1.as_str()),
+                attr("recipients", "3"),
+                attr("amount", "3500"),
+            ]
+        );
+        let remainder = amount1.checked_sub(Uint128::new(3500)).unwrap();
+        assert_eq!(get_balance(deps.as_ref(), &addr1), remainder);
+        assert_eq!(get_balance(deps.as_ref(), &addr2), Uint128::new(1500));
+        assert_eq!(get_balance(deps.as_ref(), &addr3), Uint128::new(2000));
+
+        // an invalid recipient midway leaves all balances untouched
+        let info = mock_info(addr1.as_ref(), &[]);
+        let msg = ExecuteMsg::TransferBatch {
+            transfers: vec![
+                (addr2.clone(), Uint128::new(1000)),
+                ("x".to_string(), Uint128::new(2000)),
+            ],
+        };
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+        assert_eq!(get_balance(deps.as_ref(), &addr1), remainder);
+        assert_eq!(get_balance(deps.as_ref(), &addr2), Uint128::new(1500));
+
+        // so does a batch exceeding the balance
+        let info = mock_info(addr1.as_ref(), &[]);
+        let msg = ExecuteMsg::TransferBatch {
+            transfers: vec![
+                (addr2.clone(), Uint128::new(1000)),
+                (addr3.clone(), amount1),
+            ],
+        };
+        let err = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+        assert!(matches!(err, ContractError::Std(StdError::Overflow { .. })));
+        assert_eq!(get_balance(deps.as_ref(), &addr1), remainder);
+        assert_eq!(get_balance(deps.as_ref(), &addr2), Uint128::new(1500));
+        assert_eq!(get_balance(deps.as_ref(), &addr3), Uint128::new(2000));
+
+        // the batch length is capped
+        let info = mock_info(addr1.as_ref(), &[]);
+        let msg = ExecuteMsg::TransferBatch {
+            transfers: vec![(addr2, Uint128::new(1)); MAX_BATCH_TRANSFERS + 1],
+        };
+        let err = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::TooManyTransfers {
+                max: MAX_BATCH_TRANSFERS
+            }
+        );
+    }
+
     #[test]
     fn burn() {
         let mut deps = mock_dependencies_with_balance(&coins(2, "token"));
@@ -1056,6 +1718,145 @@ mod tests {
         );
     }
 
+    #[test]
+    fn frozen_accounts() {
+        let mut deps = mock_dependencies();
+        let addr1 = String::from("addr0001");
+        let addr2 = String::from("addr0002");
+        let freezer = String::from("freezer");
+        let amount = Uint128::new(10000);
+        let instantiate_msg = InstantiateMsg {
+            name: "Auto Gen".to_string(),
+            symbol: "AUTO".to_string(),
+            decimals: 3,
+            initial_balances: vec![
+                Cw20Coin {
+                    address: addr1.clone(),
+                    amount,
+                },
+                Cw20Coin {
+                    address: addr2.clone(),
+                    amount,
+                },
+            ],
+            mint: None,
+            marketing: None,
+            expected_decimals: None,
+            snapshot_balances: None,
+            freezer: Some(freezer.clone()),
+        };
+        let info = mock_info("creator", &[]);
+        instantiate(deps.as_mut(), mock_env(), info, instantiate_msg).unwrap();
+
+        // only the freezer can freeze
+        let info = mock_info(addr2.as_ref(), &[]);
+        let msg = ExecuteMsg::Freeze {
+            address: addr1.clone(),
+        };
+        let err = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+
+        let info = mock_info(freezer.as_ref(), &[]);
+        let msg = ExecuteMsg::Freeze {
+            address: addr1.clone(),
+        };
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        let frozen = query_frozen(deps.as_ref(), addr1.clone()).unwrap();
+        assert!(frozen.frozen);
+        let frozen = query_frozen(deps.as_ref(), addr2.clone()).unwrap();
+        assert!(!frozen.frozen);
+        let frozen_err = ContractError::AccountFrozen {
+            address: addr1.clone(),
+        };
+
+        // a frozen account cannot send nor burn
+        let info = mock_info(addr1.as_ref(), &[]);
+        let msg = ExecuteMsg::Transfer {
+            recipient: addr2.clone(),
+            amount: Uint128::new(100),
+            memo: None,
+        };
+        let err = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+        assert_eq!(err, frozen_err);
+        let info = mock_info(addr1.as_ref(), &[]);
+        let msg = ExecuteMsg::Burn {
+            amount: Uint128::new(100),
+        };
+        let err = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+        assert_eq!(err, frozen_err);
+
+        // nor receive
+        let info = mock_info(addr2.as_ref(), &[]);
+        let msg = ExecuteMsg::Transfer {
+            recipient: addr1.clone(),
+            amount: Uint128::new(100),
+            memo: None,
+        };
+        let err = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+        assert_eq!(err, frozen_err);
+        let info = mock_info(addr2.as_ref(), &[]);
+        let msg = ExecuteMsg::TransferBatch {
+            transfers: vec![
+                ("addr0003".to_string(), Uint128::new(100)),
+                (addr1.clone(), Uint128::new(100)),
+            ],
+        };
+        let err = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+        assert_eq!(err, frozen_err);
+
+        // an allowance does not get around the freeze
+        let info = mock_info(addr1.as_ref(), &[]);
+        let msg = ExecuteMsg::IncreaseAllowance {
+            spender: addr2.clone(),
+            amount: Uint128::new(100),
+            expires: None,
+        };
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        let info = mock_info(addr2.as_ref(), &[]);
+        let msg = ExecuteMsg::TransferFrom {
+            owner: addr1.clone(),
+            recipient: addr2.clone(),
+            amount: Uint128::new(100),
+        };
+        let err = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+        assert_eq!(err, frozen_err);
+        assert_eq!(get_balance(deps.as_ref(), &addr1), amount);
+        assert_eq!(get_balance(deps.as_ref(), &addr2), amount);
+
+        // unfreezing restores all of it
+        let info = mock_info(freezer.as_ref(), &[]);
+        let msg = ExecuteMsg::Unfreeze {
+            address: addr1.clone(),
+        };
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        let frozen = query_frozen(deps.as_ref(), addr1.clone()).unwrap();
+        assert!(!frozen.frozen);
+
+        let info = mock_info(addr1.as_ref(), &[]);
+        let msg = ExecuteMsg::Transfer {
+            recipient: addr2.clone(),
+            amount: Uint128::new(300),
+            memo: None,
+        };
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        let info = mock_info(addr2.as_ref(), &[]);
+        let msg = ExecuteMsg::Transfer {
+            recipient: addr1.clone(),
+            amount: Uint128::new(100),
+            memo: None,
+        };
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        let info = mock_info(addr2.as_ref(), &[]);
+        let msg = ExecuteMsg::TransferFrom {
+            owner: addr1.clone(),
+            recipient: addr2.clone(),
+            amount: Uint128::new(100),
+        };
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        assert_eq!(get_balance(deps.as_ref(), &addr1), Uint128::new(9700));
+        assert_eq!(get_balance(deps.as_ref(), &addr2), Uint128::new(10300));
+    }
+
     #[test]
     fn send() {
         let mut deps = mock_dependencies_with_balance(&coins(2, "token"));
@@ -1075,6 +1876,7 @@ mod tests {
             contract: contract.clone(),
             amount: Uint128::zero(),
             msg: send_msg.clone(),
+            memo: None,
         };
         let err = execute(deps.as_mut(), env, info, msg).unwrap_err();
         assert_eq!(err, ContractError::InvalidZeroAmount {});
@@ -1086,6 +1888,7 @@ mod tests {
             contract: contract.clone(),
             amount: too_much,
             msg: send_msg.clone(),
+            memo: None,
         };
         let err = execute(deps.as_mut(), env, info, msg).unwrap_err();
         assert!(matches!(err, ContractError::Std(StdError::Overflow { .. })));
@@ -1097,6 +1900,7 @@ mod tests {
             contract: contract.clone(),
             amount: transfer,
             msg: send_msg.clone(),
+            memo: None,
         };
         let res = execute(deps.as_mut(), env, info, msg).unwrap();
         assert_eq!(res.messages.len(), 1);
@@ -1130,6 +1934,86 @@ mod tests {
         );
     }
 
+    #[test]
+    fn memo_is_emitted_and_bounded() {
+        let mut deps = mock_dependencies();
+        let addr1 = String::from("addr0001");
+        let addr2 = String::from("addr0002");
+        let contract = String::from("addr0003");
+        let amount1 = Uint128::from(12340000u128);
+        do_instantiate(deps.as_mut(), &addr1, amount1);
+        let info = mock_info(addr1.as_ref(), &[]);
+
+        // the memo only shows up as an attribute
+        let msg = ExecuteMsg::Transfer {
+            recipient: addr2.clone(),
+            amount: Uint128::new(1000),
+            memo: Some("invoice 42".to_string()),
+        };
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        assert!(res.attributes.contains(&attr("memo", "invoice 42")));
+        assert_eq!(get_balance(deps.as_ref(), &addr2), Uint128::new(1000));
+
+        let msg = ExecuteMsg::Send {
+            contract: contract.clone(),
+            amount: Uint128::new(500),
+            msg: Binary::default(),
+            memo: Some("invoice 43".to_string()),
+        };
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        assert!(res.attributes.contains(&attr("memo", "invoice 43")));
+        assert_eq!(get_balance(deps.as_ref(), &contract), Uint128::new(500));
+
+        // no memo, no attribute
+        let msg = ExecuteMsg::Transfer {
+            recipient: addr2.clone(),
+            amount: Uint128::new(1000),
+            memo: None,
+        };
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        assert!(res.attributes.iter().all(|a| a.key != "memo"));
+
+        // a memo of MAX_MEMO_LEN is fine, one byte more is rejected without moving tokens
+        let msg = ExecuteMsg::Transfer {
+            recipient: addr2.clone(),
+            amount: Uint128::new(1000),
+            memo: Some("a".repeat(MAX_MEMO_LEN)),
+        };
+        execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        let msg = ExecuteMsg::Transfer {
+            recipient: addr2.clone(),
+            amount: Uint128::new(1000),
+            memo: Some("a".repeat(MAX_MEMO_LEN + 1)),
+        };
+        let err = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap_err();
+        assert_eq!(err, ContractError::MemoTooLong { max: MAX_MEMO_LEN });
+        let msg = ExecuteMsg::Send {
+            contract: contract.clone(),
+            amount: Uint128::new(500),
+            msg: Binary::default(),
+            memo: Some("a".repeat(MAX_MEMO_LEN + 1)),
+        };
+        let err = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+        assert_eq!(err, ContractError::MemoTooLong { max: MAX_MEMO_LEN });
+        assert_eq!(get_balance(deps.as_ref(), &addr2), Uint128::new(3000));
+        assert_eq!(get_balance(deps.as_ref(), &contract), Uint128::new(500));
+
+        // cw20 spec messages without a memo are still accepted
+        let spec_msg = cw20::Cw20ExecuteMsg::Transfer {
+            recipient: addr2.clone(),
+            amount: Uint128::new(1),
+        };
+        let parsed: ExecuteMsg = from_binary(&to_binary(&spec_msg).unwrap()).unwrap();
+        assert_eq!(
+            parsed,
+            ExecuteMsg::Transfer {
+                recipient: addr2,
+                amount: Uint128::new(1),
+                memo: None,
+            }
+        );
+    }
+
     mod marketing {
         use super::*;
 
@@ -1148,6 +2032,9 @@ mod tests {
                     marketing: Some("marketing".to_owned()),
                     logo: Some(Logo::Url("url".to_owned())),
                 }),
+                expected_decimals: None,
+                snapshot_balances: None,
+                freezer: None,
             };
 
             let info = mock_info("creator", &[]);
@@ -1202,6 +2089,9 @@ mod tests {
                     marketing: Some("creator".to_owned()),
                     logo: Some(Logo::Url("url".to_owned())),
                 }),
+                expected_decimals: None,
+                snapshot_balances: None,
+                freezer: None,
             };
 
             let info = mock_info("creator", &[]);
@@ -1255,6 +2145,9 @@ mod tests {
                     marketing: Some("creator".to_owned()),
                     logo: Some(Logo::Url("url".to_owned())),
                 }),
+                expected_decimals: None,
+                snapshot_balances: None,
+                freezer: None,
             };
 
             let info = mock_info("creator", &[]);
@@ -1308,6 +2201,9 @@ mod tests {
                     marketing: Some("creator".to_owned()),
                     logo: Some(Logo::Url("url".to_owned())),
                 }),
+                expected_decimals: None,
+                snapshot_balances: None,
+                freezer: None,
             };
 
             let info = mock_info("creator", &[]);
@@ -1361,6 +2257,9 @@ mod tests {
                     marketing: Some("creator".to_owned()),
                     logo: Some(Logo::Url("url".to_owned())),
                 }),
+                expected_decimals: None,
+                snapshot_balances: None,
+                freezer: None,
             };
 
             let info = mock_info("creator", &[]);
@@ -1414,6 +2313,9 @@ mod tests {
                     marketing: Some("creator".to_owned()),
                     logo: Some(Logo::Url("url".to_owned())),
                 }),
+                expected_decimals: None,
+                snapshot_balances: None,
+                freezer: None,
             };
 
             let info = mock_info("creator", &[]);
@@ -1467,6 +2369,9 @@ mod tests {
                     marketing: Some("creator".to_owned()),
                     logo: Some(Logo::Url("url".to_owned())),
                 }),
+                expected_decimals: None,
+                snapshot_balances: None,
+                freezer: None,
             };
 
             let info = mock_info("creator", &[]);
@@ -1524,6 +2429,9 @@ mod tests {
                     marketing: Some("creator".to_owned()),
                     logo: Some(Logo::Url("url".to_owned())),
                 }),
+                expected_decimals: None,
+                snapshot_balances: None,
+                freezer: None,
             };
 
             let info = mock_info("creator", &[]);
@@ -1577,6 +2485,9 @@ mod tests {
                     marketing: Some("creator".to_owned()),
                     logo: Some(Logo::Url("url".to_owned())),
                 }),
+                expected_decimals: None,
+                snapshot_balances: None,
+                freezer: None,
             };
 
             let info = mock_info("creator", &[]);
@@ -1626,6 +2537,9 @@ mod tests {
                     marketing: Some("creator".to_owned()),
                     logo: Some(Logo::Url("url".to_owned())),
                 }),
+                expected_decimals: None,
+                snapshot_balances: None,
+                freezer: None,
             };
 
             let info = mock_info("creator", &[]);
@@ -1676,6 +2590,9 @@ mod tests {
                     marketing: Some("creator".to_owned()),
                     logo: Some(Logo::Url("url".to_owned())),
                 }),
+                expected_decimals: None,
+                snapshot_balances: None,
+                freezer: None,
             };
 
             let info = mock_info("creator", &[]);
@@ -1727,6 +2644,9 @@ mod tests {
                     marketing: Some("creator".to_owned()),
                     logo: Some(Logo::Url("url".to_owned())),
                 }),
+                expected_decimals: None,
+                snapshot_balances: None,
+                freezer: None,
             };
 
             let info = mock_info("creator", &[]);
@@ -1777,6 +2697,9 @@ mod tests {
                     marketing: Some("creator".to_owned()),
                     logo: Some(Logo::Url("url".to_owned())),
                 }),
+                expected_decimals: None,
+                snapshot_balances: None,
+                freezer: None,
             };
 
             let info = mock_info("creator", &[]);
@@ -1834,6 +2757,9 @@ mod tests {
                     marketing: Some("creator".to_owned()),
                     logo: Some(Logo::Url("url".to_owned())),
                 }),
+                expected_decimals: None,
+                snapshot_balances: None,
+                freezer: None,
             };
 
             let info = mock_info("creator", &[]);
@@ -1884,6 +2810,9 @@ mod tests {
                     marketing: Some("creator".to_owned()),
                     logo: Some(Logo::Url("url".to_owned())),
                 }),
+                expected_decimals: None,
+                snapshot_balances: None,
+                freezer: None,
             };
 
             let info = mock_info("creator", &[]);
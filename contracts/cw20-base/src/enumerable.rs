@@ -1,7 +1,10 @@
 use cosmwasm_std::{Deps, Order, StdResult};
-use cw20::{AllAccountsResponse, AllAllowancesResponse, AllowanceInfo};
+use cw20::{
+    AllAccountsResponse, AllAllowancesResponse, AllSpenderAllowancesResponse, AllowanceInfo,
+    SpenderAllowanceInfo,
+};
 
-use crate::state::{ALLOWANCES, BALANCES};
+use crate::state::{ALLOWANCES, ALLOWANCES_SPENDER, BALANCES};
 use cw_storage_plus::Bound;
 
 // settings for pagination
@@ -33,6 +36,31 @@ pub fn query_all_allowances(
     Ok(AllAllowancesResponse { allowances })
 }
 
+pub fn query_all_spender_allowances(
+    deps: Deps,
+    spender: String,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<AllSpenderAllowancesResponse> {
+    let spender_addr = deps.api.addr_validate(&spender)?;
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive);
+
+    let allowances = ALLOWANCES_SPENDER
+        .prefix(&spender_addr)
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| {
+            item.map(|(addr, allow)| SpenderAllowanceInfo {
+                owner: addr.into(),
+                allowance: allow.allowance,
+                expires: allow.expires,
+            })
+        })
+        .collect::<StdResult<_>>()?;
+    Ok(AllSpenderAllowancesResponse { allowances })
+}
+
 pub fn query_all_accounts(
     deps: Deps,
     start_after: Option<String>,
@@ -73,6 +101,9 @@ mod tests {
             }],
             mint: None,
             marketing: None,
+            expected_decimals: None,
+            snapshot_balances: None,
+            freezer: None,
         };
         let info = mock_info("creator", &[]);
         let env = mock_env();
@@ -143,6 +174,112 @@ mod tests {
         assert_eq!(&allow.allowance, &allow2);
     }
 
+    #[test]
+    fn query_all_spender_allowances_works() {
+        let mut deps = mock_dependencies_with_balance(&coins(2, "token"));
+
+        // these are in alphabetical order different from insert order
+        let owner1 = String::from("owner01");
+        let owner2 = String::from("owner02");
+        let owner3 = String::from("aaaowner");
+        let spender = String::from("spender");
+        let other = String::from("other");
+        let env = mock_env();
+        do_instantiate(deps.as_mut(), &owner1, Uint128::new(12340000));
+
+        // no allowance to start
+        let allowances =
+            query_all_spender_allowances(deps.as_ref(), spender.clone(), None, None).unwrap();
+        assert_eq!(allowances.allowances, vec![]);
+
+        // three owners grant to spender, one also grants to another spender
+        for (owner, amount) in [(&owner1, 1000u128), (&owner2, 2000), (&owner3, 3000)] {
+            let info = mock_info(owner.as_ref(), &[]);
+            let msg = ExecuteMsg::IncreaseAllowance {
+                spender: spender.clone(),
+                amount: Uint128::new(amount),
+                expires: None,
+            };
+            execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+        }
+        let info = mock_info(owner1.as_ref(), &[]);
+        let msg = ExecuteMsg::IncreaseAllowance {
+            spender: other.clone(),
+            amount: Uint128::new(777),
+            expires: Some(Expiration::AtHeight(5432)),
+        };
+        execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+
+        // first page
+        let allowances =
+            query_all_spender_allowances(deps.as_ref(), spender.clone(), None, Some(2)).unwrap();
+        assert_eq!(
+            allowances.allowances,
+            vec![
+                SpenderAllowanceInfo {
+                    owner: owner3.clone(),
+                    allowance: Uint128::new(3000),
+                    expires: Expiration::Never {},
+                },
+                SpenderAllowanceInfo {
+                    owner: owner1.clone(),
+                    allowance: Uint128::new(1000),
+                    expires: Expiration::Never {},
+                },
+            ]
+        );
+
+        // second page
+        let allowances = query_all_spender_allowances(
+            deps.as_ref(),
+            spender.clone(),
+            Some(owner1.clone()),
+            Some(2),
+        )
+        .unwrap();
+        assert_eq!(allowances.allowances.len(), 1);
+        assert_eq!(allowances.allowances[0].owner, owner2);
+        assert_eq!(allowances.allowances[0].allowance, Uint128::new(2000));
+
+        // the other spender only sees its own grant
+        let allowances = query_all_spender_allowances(deps.as_ref(), other, None, None).unwrap();
+        assert_eq!(allowances.allowances.len(), 1);
+        assert_eq!(allowances.allowances[0].owner, owner1);
+        assert_eq!(allowances.allowances[0].expires, Expiration::AtHeight(5432));
+
+        // spending and decreasing keep the index in sync
+        let info = mock_info(spender.as_ref(), &[]);
+        let msg = ExecuteMsg::BurnFrom {
+            owner: owner1.clone(),
+            amount: Uint128::new(400),
+        };
+        execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+        let info = mock_info(owner3.as_ref(), &[]);
+        let msg = ExecuteMsg::DecreaseAllowance {
+            spender: spender.clone(),
+            amount: Uint128::new(3000),
+            expires: None,
+        };
+        execute(deps.as_mut(), env, info, msg).unwrap();
+
+        let allowances = query_all_spender_allowances(deps.as_ref(), spender, None, None).unwrap();
+        assert_eq!(
+            allowances.allowances,
+            vec![
+                SpenderAllowanceInfo {
+                    owner: owner1,
+                    allowance: Uint128::new(600),
+                    expires: Expiration::Never {},
+                },
+                SpenderAllowanceInfo {
+                    owner: owner2,
+                    allowance: Uint128::new(2000),
+                    expires: Expiration::Never {},
+                },
+            ]
+        );
+    }
+
     #[test]
     fn query_all_accounts_works() {
         let mut deps = mock_dependencies_with_balance(&coins(2, "token"));
@@ -166,6 +303,7 @@ mod tests {
             ExecuteMsg::Transfer {
                 recipient: acct2,
                 amount: Uint128::new(222222),
+                memo: None,
             },
         )
         .unwrap();
@@ -176,6 +314,7 @@ mod tests {
             ExecuteMsg::Transfer {
                 recipient: acct3,
                 amount: Uint128::new(333333),
+                memo: None,
             },
         )
         .unwrap();
@@ -186,6 +325,7 @@ mod tests {
             ExecuteMsg::Transfer {
                 recipient: acct4,
                 amount: Uint128::new(444444),
+                memo: None,
             },
         )
         .unwrap();
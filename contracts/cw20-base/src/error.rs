@@ -1,4 +1,5 @@
 use cosmwasm_std::StdError;
+use cw_controllers::HookError;
 use thiserror::Error;
 
 #[derive(Error, Debug, PartialEq)]
@@ -6,6 +7,9 @@ pub enum ContractError {
     #[error("{0}")]
     Std(#[from] StdError),
 
+    #[error("{0}")]
+    Hook(#[from] HookError),
+
     #[error("Unauthorized")]
     Unauthorized {},
 
@@ -32,4 +36,19 @@ pub enum ContractError {
 
     #[error("Invalid png header")]
     InvalidPngHeader {},
+
+    #[error("Cannot transfer to more than {max} recipients at once")]
+    TooManyTransfers { max: usize },
+
+    #[error("Memo is longer than {max} bytes")]
+    MemoTooLong { max: usize },
+
+    #[error("Decimals mismatch: expected {expected}, got {actual}")]
+    DecimalsMismatch { expected: u8, actual: u8 },
+
+    #[error("Unknown reply id: {id}")]
+    UnknownReplyId { id: u64 },
+
+    #[error("Account {address} is frozen")]
+    AccountFrozen { address: String },
 }
@@ -1,10 +1,8 @@
-use cosmwasm_std::{StdError, StdResult, Uint128};
-use cw20::{Cw20Coin, Logo, MinterResponse};
+use cosmwasm_std::{to_binary, Binary, CosmosMsg, StdError, StdResult, Uint128, WasmMsg};
+use cw20::{Cw20Coin, Expiration, Logo, MinterResponse};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-pub use cw20::Cw20ExecuteMsg as ExecuteMsg;
-
 #[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq)]
 pub struct InstantiateMarketingInfo {
     pub project: Option<String>,
@@ -21,6 +19,15 @@ pub struct InstantiateMsg {
     pub initial_balances: Vec<Cw20Coin>,
     pub mint: Option<MinterResponse>,
     pub marketing: Option<InstantiateMarketingInfo>,
+    /// If set, instantiation fails unless `decimals` equals this value. Lets a deployer pin
+    /// the decimals a bridge or registry expects for this token.
+    pub expected_decimals: Option<u8>,
+    /// If set, the history of every balance is kept, so `BalanceAt` can be queried, eg. for
+    /// voting. This costs extra storage on every transfer, so leave it off otherwise.
+    pub snapshot_balances: Option<bool>,
+    /// If set, this address can freeze individual accounts, eg. for compliance. Frozen accounts
+    /// can neither send, burn nor receive tokens until they are unfrozen.
+    pub freezer: Option<String>,
 }
 
 impl InstantiateMsg {
@@ -68,12 +75,143 @@ fn is_valid_symbol(symbol: &str) -> bool {
     true
 }
 
+/// All messages of the cw20 spec (`cw20::Cw20ExecuteMsg`), with the same JSON encoding,
+/// plus the cw20-base specific extensions. The optional `memo` of Transfer and Send can be
+/// left out, so cw20 spec messages are accepted as is.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ExecuteMsg {
+    /// Transfer is a base message to move tokens to another account without triggering actions.
+    /// The memo (at most MAX_MEMO_LEN bytes) is only emitted as an attribute.
+    Transfer {
+        recipient: String,
+        amount: Uint128,
+        memo: Option<String>,
+    },
+    /// Moves tokens to many accounts at once. Either all transfers succeed or none do.
+    /// Limited to MAX_BATCH_TRANSFERS entries.
+    TransferBatch { transfers: Vec<(String, Uint128)> },
+    /// Burn is a base message to destroy tokens forever
+    Burn { amount: Uint128 },
+    /// Send is a base message to transfer tokens to a contract and trigger an action
+    /// on the receiving contract. The memo (at most MAX_MEMO_LEN bytes) is only emitted as an
+    /// attribute, it is not passed on to the receiving contract.
+    Send {
+        contract: String,
+        amount: Uint128,
+        msg: Binary,
+        memo: Option<String>,
+    },
+    /// Only with "approval" extension. Allows spender to access an additional amount tokens
+    /// from the owner's (env.sender) account. If expires is Some(), overwrites current allowance
+    /// expiration with this one.
+    IncreaseAllowance {
+        spender: String,
+        amount: Uint128,
+        expires: Option<Expiration>,
+    },
+    /// Only with "approval" extension. Lowers the spender's access of tokens
+    /// from the owner's (env.sender) account by amount. If expires is Some(), overwrites current
+    /// allowance expiration with this one.
+    DecreaseAllowance {
+        spender: String,
+        amount: Uint128,
+        expires: Option<Expiration>,
+    },
+    /// Only with "approval" extension. Transfers amount tokens from owner -> recipient
+    /// if `env.sender` has sufficient pre-approval.
+    TransferFrom {
+        owner: String,
+        recipient: String,
+        amount: Uint128,
+    },
+    /// Only with "approval" extension. Sends amount tokens from owner -> contract
+    /// if `env.sender` has sufficient pre-approval.
+    SendFrom {
+        owner: String,
+        contract: String,
+        amount: Uint128,
+        msg: Binary,
+    },
+    /// Only with "approval" extension. Destroys tokens forever
+    BurnFrom { owner: String, amount: Uint128 },
+    /// Only with the "mintable" extension. If authorized, creates amount new tokens
+    /// and adds to the recipient balance.
+    Mint { recipient: String, amount: Uint128 },
+    /// Only with the "marketing" extension. If authorized, updates marketing metadata.
+    /// Setting None/null for any of these will leave it unchanged.
+    /// Setting Some("") will clear this field on the contract storage
+    UpdateMarketing {
+        /// A URL pointing to the project behind this token.
+        project: Option<String>,
+        /// A longer description of the token and it's utility. Designed for tooltips or such
+        description: Option<String>,
+        /// The address (if any) who can update this data structure
+        marketing: Option<String>,
+    },
+    /// If set as the "marketing" role on the contract, upload a new URL, SVG, or PNG for the token
+    UploadLogo(Logo),
+    /// Only with the "mintable" extension. The minter registers a contract to receive
+    /// a MintHookMsg on every successful mint.
+    AddMintHook { addr: String },
+    /// Only with the "mintable" extension. The minter removes a registered mint hook.
+    RemoveMintHook { addr: String },
+    /// Only if instantiated with a freezer. The freezer blocks all transfers from and to address.
+    Freeze { address: String },
+    /// Only if instantiated with a freezer. The freezer lifts the freeze of address.
+    Unfreeze { address: String },
+}
+
+/// MintHookMsg should be de/serialized under `MintHook()` variant in a ExecuteMsg.
+/// It is sent to every registered mint hook after tokens were minted. A failing hook
+/// does not revert the mint.
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+#[serde(rename_all = "snake_case")]
+pub struct MintHookMsg {
+    pub recipient: String,
+    pub amount: Uint128,
+}
+
+impl MintHookMsg {
+    /// serializes the message
+    pub fn into_binary(self) -> StdResult<Binary> {
+        let msg = MintHookExecuteMsg::MintHook(self);
+        to_binary(&msg)
+    }
+
+    /// creates a cosmos_msg sending this struct to the named contract
+    pub fn into_cosmos_msg<T: Into<String>>(self, contract_addr: T) -> StdResult<CosmosMsg> {
+        let msg = self.into_binary()?;
+        let execute = WasmMsg::Execute {
+            contract_addr: contract_addr.into(),
+            msg,
+            funds: vec![],
+        };
+        Ok(execute.into())
+    }
+}
+
+// This is just a helper to properly serialize the above message
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+#[serde(rename_all = "snake_case")]
+enum MintHookExecuteMsg {
+    MintHook(MintHookMsg),
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum QueryMsg {
     /// Returns the current balance of the given address, 0 if unset.
     /// Return type: BalanceResponse.
     Balance { address: String },
+    /// Only if instantiated with `snapshot_balances`.
+    /// Returns the balance of the given address at the start of the block at height, 0 if unset.
+    /// Return type: BalanceResponse.
+    BalanceAt { address: String, height: u64 },
+    /// Only if instantiated with `snapshot_balances`.
+    /// Returns the total supply at the start of the block at height.
+    /// Return type: TotalSupplyResponse.
+    TotalSupplyAt { height: u64 },
     /// Returns metadata on the contract - name, decimals, supply, etc.
     /// Return type: TokenInfoResponse.
     TokenInfo {},
@@ -81,6 +219,10 @@ pub enum QueryMsg {
     /// Returns who can mint and the hard cap on maximum tokens after minting.
     /// Return type: MinterResponse.
     Minter {},
+    /// Only with "mintable" extension.
+    /// Returns the contracts notified on every mint.
+    /// Return type: HooksResponse.
+    MintHooks {},
     /// Only with "allowance" extension.
     /// Returns how much spender can use from owner account, 0 if unset.
     /// Return type: AllowanceResponse.
@@ -93,6 +235,14 @@ pub enum QueryMsg {
         start_after: Option<String>,
         limit: Option<u32>,
     },
+    /// Only with "enumerable" extension (and "allowances")
+    /// Returns all allowances granted to this spender, by owner. Supports pagination.
+    /// Return type: AllSpenderAllowancesResponse.
+    AllSpenderAllowances {
+        spender: String,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
     /// Only with "enumerable" extension
     /// Returns all accounts that have balances. Supports pagination.
     /// Return type: AllAccountsResponse.
@@ -110,4 +260,17 @@ pub enum QueryMsg {
     /// contract.
     /// Return type: DownloadLogoResponse.
     DownloadLogo {},
+    /// Returns whether the given address is frozen.
+    /// Return type: FrozenResponse.
+    Frozen { address: String },
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct TotalSupplyResponse {
+    pub total_supply: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct FrozenResponse {
+    pub frozen: bool,
 }
@@ -1,8 +1,9 @@
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use cosmwasm_std::{Addr, Uint128};
-use cw_storage_plus::{Item, Map};
+use cosmwasm_std::{Addr, StdError, StdResult, Storage, Uint128};
+use cw_controllers::Hooks;
+use cw_storage_plus::{Item, Map, SnapshotItem, SnapshotMap, Strategy};
 
 use cw20::{AllowanceResponse, Logo, MarketingInfoResponse};
 
@@ -33,4 +34,79 @@ pub const TOKEN_INFO: Item<TokenInfo> = Item::new("token_info");
 pub const MARKETING_INFO: Item<MarketingInfoResponse> = Item::new("marketing_info");
 pub const LOGO: Item<Logo> = Item::new("logo");
 pub const BALANCES: Map<&Addr, Uint128> = Map::new("balance");
+// set on instantiate. If true, every balance change is also recorded in BALANCE_SNAPSHOTS
+pub const SNAPSHOT_BALANCES: Item<bool> = Item::new("snapshot_balances");
+// history of BALANCES, only written if SNAPSHOT_BALANCES is set
+pub const BALANCE_SNAPSHOTS: SnapshotMap<&Addr, Uint128> = SnapshotMap::new(
+    "balance_snapshot",
+    "balance_snapshot__checkpoints",
+    "balance_snapshot__changelog",
+    Strategy::EveryBlock,
+);
+// history of TokenInfo::total_supply, only written if SNAPSHOT_BALANCES is set
+pub const TOTAL_SUPPLY_SNAPSHOTS: SnapshotItem<Uint128> = SnapshotItem::new(
+    "total_supply_snapshot",
+    "total_supply_snapshot__checkpoints",
+    "total_supply_snapshot__changelog",
+    Strategy::EveryBlock,
+);
+// set on instantiate. The only address that can freeze and unfreeze accounts
+pub const FREEZER: Item<Addr> = Item::new("freezer");
+// accounts that can neither send nor receive tokens until unfrozen, managed by FREEZER
+pub const FROZEN: Map<&Addr, ()> = Map::new("frozen");
+// contracts notified with a MintHookMsg on every mint, managed by the minter
+pub const MINT_HOOKS: Hooks = Hooks::new("mint_hooks");
 pub const ALLOWANCES: Map<(&Addr, &Addr), AllowanceResponse> = Map::new("allowance");
+// reverse index of ALLOWANCES, keyed by (spender, owner). Every write to ALLOWANCES must be mirrored here
+pub const ALLOWANCES_SPENDER: Map<(&Addr, &Addr), AllowanceResponse> =
+    Map::new("allowance_spender");
+
+/// Updates a balance like `BALANCES.update`, and records the new balance in BALANCE_SNAPSHOTS
+/// if snapshots are enabled. Every balance change must go through this.
+pub fn update_balance<A, E>(
+    storage: &mut dyn Storage,
+    height: u64,
+    address: &Addr,
+    action: A,
+) -> Result<Uint128, E>
+where
+    A: FnOnce(Option<Uint128>) -> Result<Uint128, E>,
+    E: From<StdError>,
+{
+    let balance = BALANCES.update(storage, address, action)?;
+    if SNAPSHOT_BALANCES.may_load(storage)?.unwrap_or(false) {
+        BALANCE_SNAPSHOTS.save(storage, address, &balance, height)?;
+    }
+    Ok(balance)
+}
+
+/// Records a new total supply in TOTAL_SUPPLY_SNAPSHOTS if snapshots are enabled.
+/// Must be called on every mint and burn.
+pub fn snapshot_total_supply(
+    storage: &mut dyn Storage,
+    height: u64,
+    total_supply: Uint128,
+) -> StdResult<()> {
+    if SNAPSHOT_BALANCES.may_load(storage)?.unwrap_or(false) {
+        TOTAL_SUPPLY_SNAPSHOTS.save(storage, &total_supply, height)?;
+    }
+    Ok(())
+}
+
+/// The balance of address at the start of the block at height
+pub fn balance_at_height(storage: &dyn Storage, address: &Addr, height: u64) -> StdResult<Uint128> {
+    if !SNAPSHOT_BALANCES.may_load(storage)?.unwrap_or(false) {
+        return Err(StdError::generic_err("Balance snapshots are not enabled"));
+    }
+    let balance = BALANCE_SNAPSHOTS.may_load_at_height(storage, address, height)?;
+    Ok(balance.unwrap_or_default())
+}
+
+/// The total supply at the start of the block at height
+pub fn total_supply_at_height(storage: &dyn Storage, height: u64) -> StdResult<Uint128> {
+    if !SNAPSHOT_BALANCES.may_load(storage)?.unwrap_or(false) {
+        return Err(StdError::generic_err("Balance snapshots are not enabled"));
+    }
+    let total_supply = TOTAL_SUPPLY_SNAPSHOTS.may_load_at_height(storage, height)?;
+    Ok(total_supply.unwrap_or_default())
+}
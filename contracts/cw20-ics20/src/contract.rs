@@ -1,28 +1,64 @@
 #[cfg(not(feature = "library"))]
 use cosmwasm_std::entry_point;
 use cosmwasm_std::{
-    ensure_eq, from_binary, to_binary, Addr, Binary, Deps, DepsMut, Env, IbcMsg, IbcQuery,
-    MessageInfo, Order, PortIdResponse, Response, StdResult,
+    coins, ensure_eq, from_binary, to_binary, to_vec, Addr, BankMsg, Binary, Coin,
+    ContractInfoResponse, CosmosMsg, Deps, DepsMut, Env, IbcEndpoint, IbcMsg, IbcTimeout,
+    IbcTimeoutBlock, MessageInfo, Order, Reply, Response, StdError, StdResult, Storage, SubMsg,
+    Timestamp, Uint128, WasmMsg, WasmQuery,
 };
 
 use cw2::{get_contract_version, set_contract_version};
-use cw20::{Cw20Coin, Cw20ReceiveMsg};
+use cw20::{BalanceResponse, Cw20Coin, Cw20ExecuteMsg, Cw20QueryMsg, Cw20ReceiveMsg};
 use cw_storage_plus::Bound;
 
 use crate::amount::Amount;
 use crate::error::ContractError;
-use crate::ibc::Ics20Packet;
+use crate::ibc::{
+    check_gas_limit, check_not_denied, cw20_balance, encode_packet_data, parse_denom_trace,
+    parse_voucher_denom, refund_closed_channel, simulate_refund, transfer_msg, Ics20Packet,
+    ICS20_VERSIONS, PULL_TOKEN_ID, SEND_PACKET_ID,
+};
 use crate::msg::{
-    AllowMsg, AllowedInfo, AllowedResponse, ChannelResponse, ConfigResponse, ExecuteMsg, InitMsg,
-    ListAllowedResponse, ListChannelsResponse, MigrateMsg, PortResponse, QueryMsg, TransferMsg,
+    AckEncoding, AllowMsg, AllowedInfo, AllowedResponse, ChannelDenomResponse,
+    ChannelFeaturesResponse, ChannelFullResponse, ChannelResponse, CheckInvariantsResponse,
+    ConfigResponse, DenomReconciliation, ExecuteMsg, GlobalCapResponse, IbcFee, InitMsg,
+    InvariantViolation, ListAllowedResponse, ListChannelsResponse, LocalDenomResponse, MigrateMsg,
+    MinTransferResponse, MultiChannelEntry, MultiChannelResponse, PendingChangeInfo,
+    PendingChangesResponse, PendingDeliveryResponse, PortResponse, QueryMsg,
+    RecentFailuresResponse, ReconcileResponse, SimulateSendResponse, SimulateTimeoutResponse,
+    SupportedVersionsResponse, TransferMsg, VolumeBucket, VolumeBucketsResponse,
+    CHANNEL_RESPONSE_VERSION, MAX_MULTI_CHANNEL,
+};
+use crate::state::{
+    ack_encoding, check_channel_enabled, check_denom_cap, check_global_cap, check_in_flight,
+    check_min_transfer, increase_channel_balance, increase_in_flight, increase_in_flight_amount,
+    is_paused, load_delivery, local_amount, local_denom, rebuild_connection_index,
+    rebuild_global_outstanding, remove_delivery, total_outstanding, update_fee_totals,
+    update_global_outstanding, wire_amount, wire_denom, AllowInfo, Config, FeeEscrow,
+    PendingChange, PendingDelivery, PendingPull, ACK_ENCODING, ADMIN_CLOSED, ALLOW_LIST,
+    BOUND_PORT, CHANNELS_BY_CONNECTION, CHANNEL_ENABLED, CHANNEL_INFO, CHANNEL_STATE, CLOSE_CURSOR,
+    CONFIG, DENOM_ALIAS, DENOM_ALIAS_WIRE, DENY_LIST, EXPECTED_COUNTERPARTY, GLOBAL_CAP,
+    GLOBAL_OUTSTANDING, IN_FLIGHT, IN_FLIGHT_AMOUNT, MAX_RECENT_FAILURES, MAX_VOLUME_BUCKETS,
+    MINTED_DENOMS, MINTED_TOKENS, MIN_TRANSFER, NATIVE_GAS_LIMIT, PAUSED, PENDING_CHANGES,
+    PENDING_CHANGE_COUNT, PENDING_DELIVERY, PENDING_FEE, PENDING_PULL, PENDING_REFUND_TO,
+    RECEIVER_ALLOW_LIST, RECENT_FAILURES, RECOVERY, TRANSFER_KEYS, VOLUME_BUCKETS,
 };
-use crate::state::{AllowInfo, Config, ALLOW_LIST, CHANNEL_INFO, CHANNEL_STATE, CONFIG};
-use cw_utils::{nonpayable, one_coin};
+use cw_utils::{nonpayable, one_coin, Expiration, NativeBalance};
+use std::collections::BTreeMap;
 
 // version info for migration info
 const CONTRACT_NAME: &str = "crates.io:cw20-ics20";
 const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
 
+/// used if no max_memo_len is set on instantiate
+pub const DEFAULT_MAX_MEMO_LEN: u32 = 32768;
+
+/// used if no max_timeout_height_offset is set on instantiate. About a week of 6 second blocks
+pub const DEFAULT_MAX_TIMEOUT_HEIGHT_OFFSET: u64 = 100_000;
+
+/// how long (in seconds) an idempotency key blocks another transfer of the same sender
+pub const IDEMPOTENCY_WINDOW: u64 = 24 * 60 * 60;
+
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn instantiate(
     deps: DepsMut,
@@ -33,18 +69,52 @@ pub fn instantiate(
     set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
     let cfg = Config {
         default_timeout: msg.default_timeout,
-        gov_contract: deps.api.addr_validate(&msg.gov_contract)?,
+        gov_contract: deps.api.addr_validate(&msg.gov_contract).map_err(|_| {
+            ContractError::InvalidAdmin {
+                address: msg.gov_contract.clone(),
+            }
+        })?,
+        max_memo_len: msg.max_memo_len.unwrap_or(DEFAULT_MAX_MEMO_LEN),
+        allow_partial_release: msg.allow_partial_release.unwrap_or(false),
+        allow_all_cw20: msg.allow_all_cw20.unwrap_or(false),
+        default_gas_limit: msg.default_gas_limit,
+        max_timeout_height_offset: msg
+            .max_timeout_height_offset
+            .unwrap_or(DEFAULT_MAX_TIMEOUT_HEIGHT_OFFSET),
+        max_denoms_per_channel: msg.max_denoms_per_channel,
+        restrict_receivers: msg.restrict_receivers.unwrap_or(false),
+        admin_timelock: msg.admin_timelock,
+        // a zero length bucket would never end, treat it like no buckets
+        volume_bucket: msg.volume_bucket.filter(|len| *len > 0),
+        max_packet_size: msg.max_packet_size,
+        claim_grace_period: None,
+        max_in_flight: msg.max_in_flight,
     };
     CONFIG.save(deps.storage, &cfg)?;
 
     // add all allows
     for allowed in msg.allowlist {
-        let contract = deps.api.addr_validate(&allowed.contract)?;
+        let contract = deps.api.addr_validate(&allowed.contract).map_err(|_| {
+            ContractError::InvalidAllowListEntry {
+                address: allowed.contract.clone(),
+            }
+        })?;
         let info = AllowInfo {
             gas_limit: allowed.gas_limit,
+            fee_on_transfer: allowed.fee_on_transfer.unwrap_or(false),
+            verify_balance: allowed.verify_balance.unwrap_or(false),
         };
         ALLOW_LIST.save(deps.storage, &contract, &info)?;
     }
+
+    // pin the counterparties of known channels
+    for expected in msg.expected_counterparties.unwrap_or_default() {
+        let endpoint = IbcEndpoint {
+            port_id: expected.counterparty_port,
+            channel_id: expected.counterparty_channel,
+        };
+        EXPECTED_COUNTERPARTY.save(deps.storage, &expected.channel_id, &endpoint)?;
+    }
     Ok(Response::default())
 }
 
@@ -54,17 +124,117 @@ pub fn execute(
     env: Env,
     info: MessageInfo,
     msg: ExecuteMsg,
+) -> Result<Response, ContractError> {
+    // with a timelock, gov can only make critical changes through ProposeChange
+    if msg.is_critical() {
+        let cfg = CONFIG.load(deps.storage)?;
+        if cfg.admin_timelock.is_some() && info.sender == cfg.gov_contract {
+            return Err(ContractError::TimelockRequired {});
+        }
+    }
+    execute_msg(deps, env, info, msg)
+}
+
+// everything but the timelock check, so ApplyPendingChange can run the proposed change
+fn execute_msg(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    msg: ExecuteMsg,
 ) -> Result<Response, ContractError> {
     match msg {
         ExecuteMsg::Receive(msg) => execute_receive(deps, env, info, msg),
         ExecuteMsg::Transfer(msg) => {
-            let coin = one_coin(&info)?;
+            let coin = transfer_coin(&info, msg.fee.as_ref())?;
             execute_transfer(deps, env, msg, Amount::Native(coin), info.sender)
         }
+        ExecuteMsg::TransferCw20 {
+            token,
+            amount,
+            transfer,
+        } => execute_transfer_cw20(deps, env, info, token, amount, transfer),
         ExecuteMsg::Allow(allow) => execute_allow(deps, env, info, allow),
+        ExecuteMsg::Deny { contract } => execute_set_denied(deps, info, contract, true),
+        ExecuteMsg::Undeny { contract } => execute_set_denied(deps, info, contract, false),
+        ExecuteMsg::AllowReceiver { address } => {
+            execute_set_receiver_allowed(deps, info, address, true)
+        }
+        ExecuteMsg::DisallowReceiver { address } => {
+            execute_set_receiver_allowed(deps, info, address, false)
+        }
+        ExecuteMsg::SetDenomAlias { denom, alias } => {
+            execute_set_denom_alias(deps, info, denom, alias)
+        }
+        ExecuteMsg::ProposeChange { change } => execute_propose_change(deps, env, info, *change),
+        ExecuteMsg::ApplyPendingChange { id } => execute_apply_pending_change(deps, env, info, id),
+        ExecuteMsg::Pause {} => execute_set_paused(deps, info, true),
+        ExecuteMsg::Unpause {} => execute_set_paused(deps, info, false),
+        ExecuteMsg::DisableChannel { channel_id } => {
+            execute_set_channel_enabled(deps, info, channel_id, false)
+        }
+        ExecuteMsg::EnableChannel { channel_id } => {
+            execute_set_channel_enabled(deps, info, channel_id, true)
+        }
+        ExecuteMsg::ForceRefund {
+            channel_id,
+            denom,
+            recipient,
+        } => execute_force_refund(deps, info, channel_id, denom, recipient),
+        ExecuteMsg::SetRecovery { address } => execute_set_recovery(deps, info, address),
+        ExecuteMsg::SweepUntracked { denom, recipient } => {
+            execute_sweep_untracked(deps, env, info, denom, recipient)
+        }
+        ExecuteMsg::SetAckEncoding {
+            channel_id,
+            encoding,
+        } => execute_set_ack_encoding(deps, info, channel_id, encoding),
+        ExecuteMsg::MigrateChannelState { from, to } => {
+            execute_migrate_channel_state(deps, info, from, to)
+        }
+        ExecuteMsg::AdminCloseChannel { channel_id } => {
+            execute_admin_close_channel(deps, info, channel_id)
+        }
+        ExecuteMsg::ContinueClose { channel_id } => execute_continue_close(deps, info, channel_id),
+        ExecuteMsg::SetMinTransfer { denom, amount } => {
+            execute_set_min_transfer(deps, info, denom, amount)
+        }
+        ExecuteMsg::SetGlobalCap { denom, cap } => execute_set_global_cap(deps, info, denom, cap),
+        ExecuteMsg::SetMintedToken {
+            channel_id,
+            denom,
+            token,
+        } => execute_set_minted_token(deps, info, channel_id, denom, token),
+        ExecuteMsg::SetNativeGasLimit { denom, gas_limit } => {
+            execute_set_native_gas_limit(deps, info, denom, gas_limit)
+        }
+        ExecuteMsg::SetClaimGracePeriod { grace_period } => {
+            execute_set_claim_grace_period(deps, info, grace_period)
+        }
+        ExecuteMsg::Claim { id } => execute_claim(deps, info, id),
+        ExecuteMsg::SweepUnclaimed { id, recipient } => {
+            execute_sweep_unclaimed(deps, env, info, id, recipient)
+        }
+        ExecuteMsg::PayShares { msgs } => execute_pay_shares(env, info, msgs),
     }
 }
 
+/// The coin to transfer out of the funds sent. Without a relayer fee, exactly one coin must be
+/// sent. With one, the fee is taken out of the funds first, and exactly one coin must be left.
+fn transfer_coin(info: &MessageInfo, fee: Option<&IbcFee>) -> Result<Coin, ContractError> {
+    let fee = match fee {
+        Some(fee) if !fee.is_empty() => fee.total(),
+        _ => return Ok(one_coin(info)?),
+    };
+    let mut funds = NativeBalance(info.funds.clone());
+    funds.normalize();
+    let rest = (funds - fee.into_vec()).map_err(|_| ContractError::InsufficientFee {})?;
+    let rest = MessageInfo {
+        sender: info.sender.clone(),
+        funds: rest.into_vec(),
+    };
+    Ok(one_coin(&rest)?)
+}
+
 pub fn execute_receive(
     deps: DepsMut,
     env: Env,
@@ -74,6 +244,16 @@ pub fn execute_receive(
     nonpayable(&info)?;
 
     let msg: TransferMsg = from_binary(&wrapper.msg)?;
+    // relayer fees are paid in native coins, which cannot come along with cw20 tokens
+    if matches!(&msg.fee, Some(fee) if !fee.is_empty()) {
+        return Err(ContractError::InsufficientFee {});
+    }
+    // the cw20 contract moved the tokens before calling us, so we cannot tell what arrived
+    if takes_fee_on_transfer(deps.as_ref(), &info.sender)? {
+        return Err(ContractError::UseTransferCw20 {
+            token: info.sender.into(),
+        });
+    }
     let amount = Amount::Cw20(Cw20Coin {
         address: info.sender.to_string(),
         amount: wrapper.amount,
@@ -82,59 +262,197 @@ pub fn execute_receive(
     execute_transfer(deps, env, msg, amount, api.addr_validate(&wrapper.sender)?)
 }
 
-pub fn execute_transfer(
+fn takes_fee_on_transfer(deps: Deps, token: &Addr) -> StdResult<bool> {
+    Ok(ALLOW_LIST
+        .may_load(deps.storage, token)?
+        .map(|allow| allow.fee_on_transfer)
+        .unwrap_or(false))
+}
+
+/// Sends a fee-on-transfer token. We note our balance, pull the tokens in with TransferFrom,
+/// and only escrow and send the difference once the reply sees our balance after.
+/// Tokens the contract already held, tracked or not, are never counted as received this way.
+pub fn execute_transfer_cw20(
     deps: DepsMut,
     env: Env,
-    msg: TransferMsg,
-    amount: Amount,
-    sender: Addr,
+    info: MessageInfo,
+    token: String,
+    amount: Uint128,
+    transfer: TransferMsg,
 ) -> Result<Response, ContractError> {
-    if amount.is_empty() {
-        return Err(ContractError::NoFunds {});
+    nonpayable(&info)?;
+    // relayer fees are paid in native coins, which cannot come along with cw20 tokens
+    if matches!(&transfer.fee, Some(fee) if !fee.is_empty()) {
+        return Err(ContractError::InsufficientFee {});
     }
-    // ensure the requested channel is registered
-    if !CHANNEL_INFO.has(deps.storage, &msg.channel) {
-        return Err(ContractError::NoSuchChannel { id: msg.channel });
+    if amount.is_zero() {
+        return Err(ContractError::NoFunds {});
     }
-
-    // if cw20 token, ensure it is whitelisted
-    if let Amount::Cw20(coin) = &amount {
-        let addr = deps.api.addr_validate(&coin.address)?;
-        ALLOW_LIST
-            .may_load(deps.storage, &addr)?
-            .ok_or(ContractError::NotOnAllowList)?;
+    let token = deps.api.addr_validate(&token)?;
+    let before = cw20_balance(deps.as_ref(), &token, &env.contract.address)?;
+    let pull = WasmMsg::Execute {
+        contract_addr: token.to_string(),
+        msg: to_binary(&Cw20ExecuteMsg::TransferFrom {
+            owner: info.sender.to_string(),
+            recipient: env.contract.address.to_string(),
+            amount,
+        })?,
+        funds: vec![],
     };
+    PENDING_PULL.save(
+        deps.storage,
+        &PendingPull {
+            token,
+            sender: info.sender,
+            amount,
+            before,
+            transfer,
+        },
+    )?;
+    Ok(Response::new()
+        .add_submessage(SubMsg::reply_on_success(pull, PULL_TOKEN_ID))
+        .add_attribute("action", "pull_cw20"))
+}
 
-    // delta from user is in seconds
-    let timeout_delta = match msg.timeout {
-        Some(t) => t,
-        None => CONFIG.load(deps.storage)?.default_timeout,
-    };
-    // timeout is in nanoseconds
-    let timeout = env.block.time.plus_seconds(timeout_delta);
+/// The tokens of a TransferCw20 were pulled in. Sends what arrived, which is never more than the
+/// amount pulled. Failing here reverts the pull as well.
+pub(crate) fn reply_pull_token(
+    deps: DepsMut,
+    env: Env,
+    _reply: Reply,
+) -> Result<Response, ContractError> {
+    let pull = PENDING_PULL.load(deps.storage)?;
+    PENDING_PULL.remove(deps.storage);
+    let after = cw20_balance(deps.as_ref(), &pull.token, &env.contract.address)?;
+    let received = after.saturating_sub(pull.before).min(pull.amount);
+    let amount = Amount::Cw20(Cw20Coin {
+        address: pull.token.into(),
+        amount: received,
+    });
+    execute_transfer(deps, env, pull.transfer, amount, pull.sender)
+}
+
+/// A timeout height must be above the current height, or the packet would time out right away.
+/// Heights further ahead than max_offset are lowered to current + max_offset, so the tokens
+/// cannot be stuck in flight for much longer than intended.
+pub(crate) fn clamp_timeout_height(
+    block: IbcTimeoutBlock,
+    current: u64,
+    max_offset: u64,
+) -> Result<IbcTimeoutBlock, ContractError> {
+    if block.height <= current {
+        return Err(ContractError::InvalidTimeout {
+            height: block.height,
+            current,
+        });
+    }
+    Ok(IbcTimeoutBlock {
+        revision: block.revision,
+        height: block.height.min(current.saturating_add(max_offset)),
+    })
+}
 
+pub fn execute_transfer(
+    deps: DepsMut,
+    env: Env,
+    msg: TransferMsg,
+    amount: Amount,
+    sender: Addr,
+) -> Result<Response, ContractError> {
+    // aliased native tokens are escrowed and sent in their wire denom
+    let amount = wire_amount(deps.storage, amount)?;
+    // wrapped tokens we mint go back under the voucher denom the remote chain gave them
+    let minted = minted_denom(deps.as_ref(), &env, &msg.channel, &amount)?;
     // build ics20 packet
     let packet = Ics20Packet::new(
         amount.amount(),
-        amount.denom(),
+        minted.clone().unwrap_or_else(|| amount.denom()),
         sender.as_ref(),
         &msg.remote_address,
-    );
-    packet.validate()?;
+    )
+    .with_memo(msg.memo);
+    validate_transfer(deps.as_ref(), &msg.channel, &amount, &packet)?;
+    let refund_to = msg
+        .refund_to
+        .map(|addr| deps.api.addr_validate(&addr))
+        .transpose()?;
+    if let Some(key) = &msg.idempotency_key {
+        check_idempotency_key(deps.storage, env.block.time, &sender, key)?;
+    }
+
+    let config = CONFIG.load(deps.storage)?;
+    // delta from user is in seconds
+    let timeout_delta = msg.timeout.unwrap_or(config.default_timeout);
+    // timeout is in nanoseconds
+    let timestamp = env.block.time.plus_seconds(timeout_delta);
+    let timeout = match msg.timeout_height {
+        Some(block) => {
+            let block =
+                clamp_timeout_height(block, env.block.height, config.max_timeout_height_offset)?;
+            IbcTimeout::with_both(block, timestamp)
+        }
+        None => IbcTimeout::with_timestamp(timestamp),
+    };
+
+    let res = match (&minted, &amount) {
+        // wrapped tokens are burned rather than escrowed, on_packet_failure mints them back
+        (Some(_), Amount::Cw20(coin)) => {
+            let burn = Cw20ExecuteMsg::Burn {
+                amount: packet.amount,
+            };
+            Response::new().add_message(WasmMsg::Execute {
+                contract_addr: coin.address.clone(),
+                msg: to_binary(&burn)?,
+                funds: vec![],
+            })
+        }
+        _ => {
+            // Escrow the tokens now rather than on ack, so a receive of these tokens that is
+            // relayed before our ack cannot observe a balance that was never committed.
+            // If the send fails (error ack or timeout), on_packet_failure reduces the balance again.
+            increase_channel_balance(deps.storage, &msg.channel, &packet.denom, packet.amount)?;
+            increase_in_flight_amount(deps.storage, &msg.channel, &packet.denom, packet.amount)?;
+            Response::new()
+        }
+    };
+
+    // the packet takes up a slot until it is acked or times out
+    increase_in_flight(deps.storage, &msg.channel)?;
 
     // prepare ibc message
-    let msg = IbcMsg::SendPacket {
-        channel_id: msg.channel,
-        data: to_binary(&packet)?,
-        timeout: timeout.into(),
+    let send = IbcMsg::SendPacket {
+        channel_id: msg.channel.clone(),
+        data: encode_packet_data(deps.storage, &msg.channel, &packet)?,
+        timeout,
+    };
+
+    // The relayer fee is escrowed and the refund address kept until the ack or timeout. They are
+    // keyed by the packet sequence, which we only learn in the reply.
+    let mut needs_sequence = false;
+    if let Some(fee) = msg.fee.filter(|fee| !fee.is_empty()) {
+        update_fee_totals(deps.storage, &fee.total().into_vec(), true)?;
+        let escrow = FeeEscrow {
+            channel: msg.channel.clone(),
+            sender,
+            fee,
+        };
+        PENDING_FEE.save(deps.storage, &escrow)?;
+        needs_sequence = true;
+    }
+    if let Some(refund_to) = refund_to {
+        PENDING_REFUND_TO.save(deps.storage, &(msg.channel, refund_to))?;
+        needs_sequence = true;
+    }
+    let res = if needs_sequence {
+        res.add_submessage(SubMsg::reply_on_success(send, SEND_PACKET_ID))
+    } else {
+        res.add_message(send)
     };
 
-    // Note: we update local state when we get ack - do not count this transfer towards anything until acked
     // similar event messages like ibctransfer module
 
     // send response
-    let res = Response::new()
-        .add_message(msg)
+    let res = res
         .add_attribute("action", "transfer")
         .add_attribute("sender", &packet.sender)
         .add_attribute("receiver", &packet.receiver)
@@ -143,6 +461,105 @@ pub fn execute_transfer(
     Ok(res)
 }
 
+/// The voucher denom amount is sent as over channel, if it is a wrapped token we mint there.
+/// The remote chain prefixed it with our end of the channel when it sent the tokens over.
+fn minted_denom(
+    deps: Deps,
+    env: &Env,
+    channel: &str,
+    amount: &Amount,
+) -> StdResult<Option<String>> {
+    let token = match amount {
+        Amount::Cw20(coin) => &coin.address,
+        Amount::Native(_) => return Ok(None),
+    };
+    match MINTED_DENOMS.may_load(deps.storage, (channel, token))? {
+        Some(denom) => {
+            let port = query_port(deps, env.clone())?.port_id;
+            Ok(Some(format!("{}/{}/{}", port, channel, denom)))
+        }
+        None => Ok(None),
+    }
+}
+
+/// All the checks a transfer must pass before anything is escrowed.
+/// This is shared with the SimulateSend query, so the simulation cannot drift from the real path.
+pub fn validate_transfer(
+    deps: Deps,
+    channel: &str,
+    amount: &Amount,
+    packet: &Ics20Packet,
+) -> Result<(), ContractError> {
+    if is_paused(deps.storage)? {
+        return Err(ContractError::Paused);
+    }
+    if amount.is_empty() {
+        return Err(ContractError::NoFunds {});
+    }
+    // ensure the requested channel is registered
+    let channel_info = CHANNEL_INFO
+        .may_load(deps.storage, channel)?
+        .ok_or_else(|| ContractError::NoSuchChannel { id: channel.into() })?;
+    check_channel_enabled(deps.storage, channel)?;
+
+    // if cw20 token, ensure it is not denied and is whitelisted
+    check_not_denied(deps, amount)?;
+    check_gas_limit(deps, amount)?;
+
+    if !channel_info.supports_u128() {
+        packet.validate()?;
+    }
+    let config = CONFIG.load(deps.storage)?;
+    packet.validate_memo(config.max_memo_len)?;
+    if let Some(max) = config.max_packet_size {
+        let size = to_vec(packet)?.len();
+        if size > max as usize {
+            return Err(ContractError::PacketTooLarge { size, max });
+        }
+    }
+    check_min_transfer(deps.storage, &packet.denom, packet.amount)?;
+    check_global_cap(deps.storage, &packet.denom, packet.amount)?;
+    check_denom_cap(
+        deps.storage,
+        channel,
+        &packet.denom,
+        config.max_denoms_per_channel,
+    )?;
+    check_in_flight(deps.storage, channel, config.max_in_flight)?;
+    Ok(())
+}
+
+/// Rejects a transfer whose idempotency key the sender used within IDEMPOTENCY_WINDOW.
+/// The sender's expired keys are pruned on the way, so storage stays bounded.
+fn check_idempotency_key(
+    storage: &mut dyn Storage,
+    now: Timestamp,
+    sender: &Addr,
+    key: &str,
+) -> Result<(), ContractError> {
+    let expired = TRANSFER_KEYS
+        .prefix(sender)
+        .range(storage, None, None, Order::Ascending)
+        .filter(|r| matches!(r, Ok((_, reusable_at)) if *reusable_at <= now))
+        .map(|r| r.map(|(k, _)| k))
+        .collect::<StdResult<Vec<_>>>()?;
+    for k in expired {
+        TRANSFER_KEYS.remove(storage, (sender, &k));
+    }
+
+    if TRANSFER_KEYS.has(storage, (sender, key)) {
+        return Err(ContractError::DuplicateTransfer {
+            key: key.to_string(),
+        });
+    }
+    TRANSFER_KEYS.save(
+        storage,
+        (sender, key),
+        &now.plus_seconds(IDEMPOTENCY_WINDOW),
+    )?;
+    Ok(())
+}
+
 /// The gov contract can allow new contracts, or increase the gas limit on existing contracts.
 /// It cannot block or reduce the limit to avoid forcible sticking tokens in the channel.
 pub fn execute_allow(
@@ -155,20 +572,27 @@ pub fn execute_allow(
     ensure_eq!(info.sender, cfg.gov_contract, ContractError::Unauthorized);
 
     let contract = deps.api.addr_validate(&allow.contract)?;
-    let set = AllowInfo {
-        gas_limit: allow.gas_limit,
-    };
     ALLOW_LIST.update(deps.storage, &contract, |old| {
-        if let Some(old) = old {
+        if let Some(old) = &old {
             // we must ensure it increases the limit
-            match (old.gas_limit, set.gas_limit) {
+            match (old.gas_limit, allow.gas_limit) {
                 (None, Some(_)) => return Err(ContractError::CannotLowerGas),
                 (Some(old), Some(new)) if new < old => return Err(ContractError::CannotLowerGas),
                 _ => {}
             };
         }
+        let fee_on_transfer = allow
+            .fee_on_transfer
+            .or_else(|| old.as_ref().map(|old| old.fee_on_transfer))
+            .unwrap_or(false);
+        let verify_balance = allow
+            .verify_balance
+            .or_else(|| old.map(|old| old.verify_balance))
+            .unwrap_or(false);
         Ok(AllowInfo {
             gas_limit: allow.gas_limit,
+            fee_on_transfer,
+            verify_balance,
         })
     })?;
 
@@ -185,293 +609,3136 @@ pub fn execute_allow(
     Ok(res)
 }
 
-#[cfg_attr(not(feature = "library"), entry_point)]
-pub fn migrate(deps: DepsMut, _env: Env, _msg: MigrateMsg) -> Result<Response, ContractError> {
-    let version = get_contract_version(deps.storage)?;
-    if version.contract != CONTRACT_NAME {
-        return Err(ContractError::CannotMigrate {
-            previous_contract: version.contract,
-        });
-    }
-    Ok(Response::default())
+pub fn execute_set_paused(
+    deps: DepsMut,
+    info: MessageInfo,
+    paused: bool,
+) -> Result<Response, ContractError> {
+    let cfg = CONFIG.load(deps.storage)?;
+    ensure_eq!(info.sender, cfg.gov_contract, ContractError::Unauthorized);
+
+    PAUSED.save(deps.storage, &paused)?;
+    let action = if paused { "pause" } else { "unpause" };
+    Ok(Response::new().add_attribute("action", action))
 }
 
-#[cfg_attr(not(feature = "library"), entry_point)]
-pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
-    match msg {
-        QueryMsg::Port {} => to_binary(&query_port(deps)?),
-        QueryMsg::ListChannels {} => to_binary(&query_list(deps)?),
-        QueryMsg::Channel { id } => to_binary(&query_channel(deps, id)?),
-        QueryMsg::Config {} => to_binary(&query_config(deps)?),
-        QueryMsg::Allowed { contract } => to_binary(&query_allowed(deps, contract)?),
-        QueryMsg::ListAllowed { start_after, limit } => {
-            to_binary(&list_allowed(deps, start_after, limit)?)
-        }
+/// Puts a cw20 token on the deny list, or takes it off. A denied token cannot be sent or
+/// received, even if it is allowed
+pub fn execute_set_denied(
+    deps: DepsMut,
+    info: MessageInfo,
+    contract: String,
+    denied: bool,
+) -> Result<Response, ContractError> {
+    let cfg = CONFIG.load(deps.storage)?;
+    ensure_eq!(info.sender, cfg.gov_contract, ContractError::Unauthorized);
+
+    let contract = deps.api.addr_validate(&contract)?;
+    if denied {
+        DENY_LIST.save(deps.storage, &contract, &())?;
+    } else {
+        DENY_LIST.remove(deps.storage, &contract);
     }
+    let action = if denied { "deny" } else { "undeny" };
+    Ok(Response::new()
+        .add_attribute("action", action)
+        .add_attribute("contract", contract))
 }
 
-fn query_port(deps: Deps) -> StdResult<PortResponse> {
-    let query = IbcQuery::PortId {}.into();
-    let PortIdResponse { port_id } = deps.querier.query(&query)?;
-    Ok(PortResponse { port_id })
+pub fn execute_set_receiver_allowed(
+    deps: DepsMut,
+    info: MessageInfo,
+    address: String,
+    allowed: bool,
+) -> Result<Response, ContractError> {
+    let cfg = CONFIG.load(deps.storage)?;
+    ensure_eq!(info.sender, cfg.gov_contract, ContractError::Unauthorized);
+
+    let address = deps.api.addr_validate(&address)?;
+    if allowed {
+        RECEIVER_ALLOW_LIST.save(deps.storage, &address, &())?;
+    } else {
+        RECEIVER_ALLOW_LIST.remove(deps.storage, &address);
+    }
+    let action = if allowed {
+        "allow_receiver"
+    } else {
+        "disallow_receiver"
+    };
+    Ok(Response::new()
+        .add_attribute("action", action)
+        .add_attribute("address", address))
 }
 
-fn query_list(deps: Deps) -> StdResult<ListChannelsResponse> {
-    let channels = CHANNEL_INFO
-        .range_raw(deps.storage, None, None, Order::Ascending)
-        .map(|r| r.map(|(_, v)| v))
-        .collect::<StdResult<_>>()?;
-    Ok(ListChannelsResponse { channels })
+pub fn execute_set_denom_alias(
+    deps: DepsMut,
+    info: MessageInfo,
+    denom: String,
+    alias: Option<String>,
+) -> Result<Response, ContractError> {
+    let cfg = CONFIG.load(deps.storage)?;
+    ensure_eq!(info.sender, cfg.gov_contract, ContractError::Unauthorized);
+
+    // each alias can only map back to one wire denom
+    if let Some(alias) = &alias {
+        if let Some(other) = DENOM_ALIAS_WIRE.may_load(deps.storage, alias)? {
+            if other != denom {
+                return Err(ContractError::AliasTaken {
+                    alias: alias.clone(),
+                    denom: other,
+                });
+            }
+        }
+    }
+    if let Some(old) = DENOM_ALIAS.may_load(deps.storage, &denom)? {
+        DENOM_ALIAS_WIRE.remove(deps.storage, &old);
+    }
+    match &alias {
+        Some(alias) => {
+            DENOM_ALIAS.save(deps.storage, &denom, alias)?;
+            DENOM_ALIAS_WIRE.save(deps.storage, alias, &denom)?;
+        }
+        None => DENOM_ALIAS.remove(deps.storage, &denom),
+    }
+
+    Ok(Response::new()
+        .add_attribute("action", "set_denom_alias")
+        .add_attribute("denom", denom)
+        .add_attribute("alias", alias.unwrap_or_default()))
 }
 
-// make public for ibc tests
-pub fn query_channel(deps: Deps, id: String) -> StdResult<ChannelResponse> {
-    let info = CHANNEL_INFO.load(deps.storage, &id)?;
-    // this returns Vec<(outstanding, total)>
-    let state = CHANNEL_STATE
-        .prefix(&id)
-        .range(deps.storage, None, None, Order::Ascending)
-        .map(|r| {
-            r.map(|(denom, v)| {
-                let outstanding = Amount::from_parts(denom.clone(), v.outstanding);
-                let total = Amount::from_parts(denom, v.total_sent);
-                (outstanding, total)
-            })
-        })
-        .collect::<StdResult<Vec<_>>>()?;
-    // we want (Vec<outstanding>, Vec<total>)
-    let (balances, total_sent) = state.into_iter().unzip();
+pub fn execute_set_minted_token(
+    deps: DepsMut,
+    info: MessageInfo,
+    channel_id: String,
+    denom: String,
+    token: Option<String>,
+) -> Result<Response, ContractError> {
+    let cfg = CONFIG.load(deps.storage)?;
+    ensure_eq!(info.sender, cfg.gov_contract, ContractError::Unauthorized);
+    let token = token.map(|t| deps.api.addr_validate(&t)).transpose()?;
 
-    Ok(ChannelResponse {
-        info,
-        balances,
-        total_sent,
-    })
+    // each token can only be minted for one denom per channel
+    if let Some(token) = &token {
+        if let Some(other) = MINTED_DENOMS.may_load(deps.storage, (&channel_id, token.as_str()))? {
+            if other != denom {
+                return Err(ContractError::MintedTokenTaken {
+                    token: token.to_string(),
+                    denom: other,
+                });
+            }
+        }
+    }
+    if let Some(old) = MINTED_TOKENS.may_load(deps.storage, (&channel_id, &denom))? {
+        MINTED_DENOMS.remove(deps.storage, (&channel_id, old.as_str()));
+    }
+    match &token {
+        Some(token) => {
+            MINTED_TOKENS.save(deps.storage, (&channel_id, &denom), token)?;
+            MINTED_DENOMS.save(deps.storage, (&channel_id, token.as_str()), &denom)?;
+        }
+        None => MINTED_TOKENS.remove(deps.storage, (&channel_id, &denom)),
+    }
+
+    Ok(Response::new()
+        .add_attribute("action", "set_minted_token")
+        .add_attribute("channel_id", channel_id)
+        .add_attribute("denom", denom)
+        .add_attribute("token", token.map(String::from).unwrap_or_default()))
 }
 
-fn query_config(deps: Deps) -> StdResult<ConfigResponse> {
+pub fn execute_propose_change(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    change: ExecuteMsg,
+) -> Result<Response, ContractError> {
     let cfg = CONFIG.load(deps.storage)?;
-    let res = ConfigResponse {
-        default_timeout: cfg.default_timeout,
-        gov_contract: cfg.gov_contract.into(),
+    ensure_eq!(info.sender, cfg.gov_contract, ContractError::Unauthorized);
+    if !change.is_critical() {
+        return Err(ContractError::NotCritical {});
+    }
+
+    let id = PENDING_CHANGE_COUNT
+        .may_load(deps.storage)?
+        .unwrap_or_default()
+        + 1;
+    PENDING_CHANGE_COUNT.save(deps.storage, &id)?;
+    let timelock = cfg.admin_timelock.unwrap_or_default();
+    let effective_at = Expiration::AtTime(env.block.time.plus_seconds(timelock));
+    let pending = PendingChange {
+        change,
+        effective_at,
     };
-    Ok(res)
+    PENDING_CHANGES.save(deps.storage, id, &pending)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "propose_change")
+        .add_attribute("change_id", id.to_string())
+        .add_attribute("effective_at", effective_at.to_string()))
 }
 
-fn query_allowed(deps: Deps, contract: String) -> StdResult<AllowedResponse> {
-    let addr = deps.api.addr_validate(&contract)?;
-    let info = ALLOW_LIST.may_load(deps.storage, &addr)?;
-    let res = match info {
-        None => AllowedResponse {
-            is_allowed: false,
-            gas_limit: None,
-        },
-        Some(a) => AllowedResponse {
-            is_allowed: true,
-            gas_limit: a.gas_limit,
-        },
-    };
-    Ok(res)
+pub fn execute_apply_pending_change(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    id: u64,
+) -> Result<Response, ContractError> {
+    let cfg = CONFIG.load(deps.storage)?;
+    ensure_eq!(info.sender, cfg.gov_contract, ContractError::Unauthorized);
+    let pending = PENDING_CHANGES
+        .may_load(deps.storage, id)?
+        .ok_or(ContractError::NoPendingChange { id })?;
+    if !pending.effective_at.is_expired(&env.block) {
+        return Err(ContractError::TimelockNotExpired {
+            effective_at: pending.effective_at,
+        });
+    }
+
+    PENDING_CHANGES.remove(deps.storage, id);
+    let res = execute_msg(deps, env, info, pending.change)?;
+    Ok(res.add_attribute("change_id", id.to_string()))
 }
 
-// settings for pagination
-const MAX_LIMIT: u32 = 30;
-const DEFAULT_LIMIT: u32 = 10;
+/// Acks and timeouts of packets in flight are still processed on a disabled channel
+pub fn execute_set_channel_enabled(
+    deps: DepsMut,
+    info: MessageInfo,
+    channel_id: String,
+    enabled: bool,
+) -> Result<Response, ContractError> {
+    let cfg = CONFIG.load(deps.storage)?;
+    ensure_eq!(info.sender, cfg.gov_contract, ContractError::Unauthorized);
+    if !CHANNEL_INFO.has(deps.storage, &channel_id) {
+        return Err(ContractError::NoSuchChannel { id: channel_id });
+    }
 
-fn list_allowed(
-    deps: Deps,
-    start_after: Option<String>,
-    limit: Option<u32>,
-) -> StdResult<ListAllowedResponse> {
-    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
-    let start = match start_after {
-        Some(x) => Some(Bound::exclusive(deps.api.addr_validate(&x)?.into_string())),
-        None => None,
+    if enabled {
+        CHANNEL_ENABLED.remove(deps.storage, &channel_id);
+    } else {
+        CHANNEL_ENABLED.save(deps.storage, &channel_id, &false)?;
+    }
+    let action = if enabled {
+        "enable_channel"
+    } else {
+        "disable_channel"
     };
+    Ok(Response::new()
+        .add_attribute("action", action)
+        .add_attribute("channel", channel_id))
+}
 
-    let allow = ALLOW_LIST
-        .range(deps.storage, start, None, Order::Ascending)
-        .take(limit)
-        .map(|item| {
-            item.map(|(addr, allow)| AllowedInfo {
-                contract: addr.into(),
-                gas_limit: allow.gas_limit,
-            })
-        })
+pub fn execute_set_ack_encoding(
+    deps: DepsMut,
+    info: MessageInfo,
+    channel_id: String,
+    encoding: AckEncoding,
+) -> Result<Response, ContractError> {
+    let cfg = CONFIG.load(deps.storage)?;
+    ensure_eq!(info.sender, cfg.gov_contract, ContractError::Unauthorized);
+    if !CHANNEL_INFO.has(deps.storage, &channel_id) {
+        return Err(ContractError::NoSuchChannel { id: channel_id });
+    }
+
+    ACK_ENCODING.save(deps.storage, &channel_id, &encoding)?;
+    let encoding = match encoding {
+        AckEncoding::Json => "json",
+        AckEncoding::Raw => "raw",
+    };
+    Ok(Response::new()
+        .add_attribute("action", "set_ack_encoding")
+        .add_attribute("channel", channel_id)
+        .add_attribute("encoding", encoding))
+}
+
+/// Escape hatch for a channel in a bad state, eg. when the counterparty is gone.
+/// Only allowed while paused, so it cannot race regular transfers.
+pub fn execute_force_refund(
+    deps: DepsMut,
+    info: MessageInfo,
+    channel_id: String,
+    denom: String,
+    recipient: Option<String>,
+) -> Result<Response, ContractError> {
+    let cfg = CONFIG.load(deps.storage)?;
+    ensure_eq!(info.sender, cfg.gov_contract, ContractError::Unauthorized);
+    if !is_paused(deps.storage)? {
+        return Err(ContractError::NotPaused);
+    }
+    let recipient = recovery_recipient(deps.as_ref(), recipient)?;
+
+    let mut state = CHANNEL_STATE
+        .may_load(deps.storage, (&channel_id, &denom))?
+        .unwrap_or_default();
+    if state.outstanding.is_zero() {
+        return Err(ContractError::NoFunds {});
+    }
+    let amount = Amount::from_parts(denom.clone(), state.outstanding);
+    state.outstanding = Uint128::zero();
+    CHANNEL_STATE.save(deps.storage, (&channel_id, &denom), &state)?;
+    update_global_outstanding(deps.storage, &denom, amount.amount(), false)?;
+
+    // a plain message, so a failing payout reverts the whole refund
+    let res = Response::new()
+        .add_message(transfer_msg(
+            local_amount(deps.storage, amount.clone())?,
+            recipient.to_string(),
+        ))
+        .add_attribute("action", "force_refund")
+        .add_attribute("sender", info.sender)
+        .add_attribute("channel", channel_id)
+        .add_attribute("denom", denom)
+        .add_attribute("amount", amount.amount())
+        .add_attribute("recipient", recipient);
+    Ok(res)
+}
+
+pub fn execute_migrate_channel_state(
+    deps: DepsMut,
+    info: MessageInfo,
+    from: String,
+    to: String,
+) -> Result<Response, ContractError> {
+    let cfg = CONFIG.load(deps.storage)?;
+    ensure_eq!(info.sender, cfg.gov_contract, ContractError::Unauthorized);
+    if !is_paused(deps.storage)? {
+        return Err(ContractError::NotPaused);
+    }
+    for channel_id in [&from, &to] {
+        if !CHANNEL_INFO.has(deps.storage, channel_id) {
+            return Err(ContractError::NoSuchChannel {
+                id: channel_id.clone(),
+            });
+        }
+    }
+    // acks and timeouts are settled against the channel a packet was sent on, so its escrow
+    // (and the fees and refund addresses kept for the packet) must stay there until they arrive
+    let in_flight = IN_FLIGHT.may_load(deps.storage, &from)?.unwrap_or_default();
+    if from != to && in_flight > 0 {
+        return Err(ContractError::PacketsInFlight {
+            channel: from,
+            count: in_flight,
+        });
+    }
+
+    // wrapped tokens keep being minted for their remote denom on the new channel
+    let minted = MINTED_TOKENS
+        .prefix(&from)
+        .range(deps.storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<_>>>()?;
+    for (denom, token) in &minted {
+        if let Some(other) = MINTED_TOKENS.may_load(deps.storage, (&to, denom))? {
+            if &other != token {
+                return Err(ContractError::MintedTokenTaken {
+                    token: other.into(),
+                    denom: denom.clone(),
+                });
+            }
+        }
+        if let Some(other) = MINTED_DENOMS.may_load(deps.storage, (&to, token.as_str()))? {
+            if &other != denom {
+                return Err(ContractError::MintedTokenTaken {
+                    token: token.to_string(),
+                    denom: other,
+                });
+            }
+        }
+    }
+    for (denom, token) in &minted {
+        MINTED_TOKENS.remove(deps.storage, (&from, denom));
+        MINTED_DENOMS.remove(deps.storage, (&from, token.as_str()));
+    }
+    for (denom, token) in &minted {
+        MINTED_TOKENS.save(deps.storage, (&to, denom), token)?;
+        MINTED_DENOMS.save(deps.storage, (&to, token.as_str()), denom)?;
+    }
+
+    // clear the old channel first, so migrating a channel onto itself changes nothing
+    let states = CHANNEL_STATE
+        .prefix(&from)
+        .range(deps.storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<_>>>()?;
+    for (denom, _) in &states {
+        CHANNEL_STATE.remove(deps.storage, (&from, denom));
+    }
+    // the outstanding total over all channels stays the same
+    for (denom, state) in &states {
+        CHANNEL_STATE.update(
+            deps.storage,
+            (&to, denom),
+            |orig| -> Result<_, ContractError> {
+                let mut cur = orig.unwrap_or_default();
+                cur.outstanding = cur
+                    .outstanding
+                    .checked_add(state.outstanding)
+                    .or(Err(ContractError::AmountOverflow {}))?;
+                cur.total_sent = cur
+                    .total_sent
+                    .checked_add(state.total_sent)
+                    .or(Err(ContractError::AmountOverflow {}))?;
+                Ok(cur)
+            },
+        )?;
+    }
+
+    let res = Response::new()
+        .add_attribute("action", "migrate_channel_state")
+        .add_attribute("from", from)
+        .add_attribute("to", to)
+        .add_attribute("denoms", states.len().to_string());
+    Ok(res)
+}
+
+pub fn execute_set_recovery(
+    deps: DepsMut,
+    info: MessageInfo,
+    address: Option<String>,
+) -> Result<Response, ContractError> {
+    let cfg = CONFIG.load(deps.storage)?;
+    ensure_eq!(info.sender, cfg.gov_contract, ContractError::Unauthorized);
+
+    let res = Response::new().add_attribute("action", "set_recovery");
+    match address {
+        Some(address) => {
+            let addr = deps.api.addr_validate(&address)?;
+            // accounts can hold anything, but a contract must at least claim an interface
+            let is_contract = deps
+                .querier
+                .query::<ContractInfoResponse>(
+                    &WasmQuery::ContractInfo {
+                        contract_addr: addr.to_string(),
+                    }
+                    .into(),
+                )
+                .is_ok();
+            if is_contract && cw2::CONTRACT.query(&deps.querier, addr.clone()).is_err() {
+                return Err(ContractError::InvalidRecovery { address });
+            }
+            RECOVERY.save(deps.storage, &addr)?;
+            Ok(res.add_attribute("recovery", addr))
+        }
+        None => {
+            RECOVERY.remove(deps.storage);
+            Ok(res.add_attribute("recovery", ""))
+        }
+    }
+}
+
+/// The given recipient, or the recovery address if none is given
+fn recovery_recipient(deps: Deps, recipient: Option<String>) -> Result<Addr, ContractError> {
+    match recipient {
+        Some(recipient) => Ok(deps.api.addr_validate(&recipient)?),
+        None => RECOVERY
+            .may_load(deps.storage)?
+            .ok_or(ContractError::NoRecovery {}),
+    }
+}
+
+pub fn execute_sweep_untracked(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    denom: String,
+    recipient: Option<String>,
+) -> Result<Response, ContractError> {
+    let cfg = CONFIG.load(deps.storage)?;
+    ensure_eq!(info.sender, cfg.gov_contract, ContractError::Unauthorized);
+    let recipient = recovery_recipient(deps.as_ref(), recipient)?;
+
+    let balance = deps
+        .querier
+        .query_balance(&env.contract.address, &denom)?
+        .amount;
+    let tracked = total_outstanding(deps.storage, &wire_denom(deps.storage, &denom)?)?;
+    // anything up to the tracked amount is escrow
+    let surplus = balance.saturating_sub(tracked);
+
+    let mut res = Response::new()
+        .add_attribute("action", "sweep_untracked")
+        .add_attribute("denom", &denom)
+        .add_attribute("amount", surplus)
+        .add_attribute("recipient", &recipient);
+    if !surplus.is_zero() {
+        res = res.add_message(BankMsg::Send {
+            to_address: recipient.into(),
+            amount: coins(surplus.u128(), denom),
+        });
+    }
+    Ok(res)
+}
+
+/// Only allowed while paused. Disables the channel and refunds its escrow like a close by the
+/// counterparty does, then closes it. Refunds of more than MAX_CLOSE_REFUNDS denoms are continued
+/// with ContinueClose.
+pub fn execute_admin_close_channel(
+    deps: DepsMut,
+    info: MessageInfo,
+    channel_id: String,
+) -> Result<Response, ContractError> {
+    let cfg = CONFIG.load(deps.storage)?;
+    ensure_eq!(info.sender, cfg.gov_contract, ContractError::Unauthorized);
+    if !is_paused(deps.storage)? {
+        return Err(ContractError::NotPaused);
+    }
+    if !CHANNEL_INFO.has(deps.storage, &channel_id) {
+        return Err(ContractError::NoSuchChannel { id: channel_id });
+    }
+
+    CHANNEL_ENABLED.save(deps.storage, &channel_id, &false)?;
+    ADMIN_CLOSED.save(deps.storage, &channel_id, &())?;
+    let (msgs, done) = refund_closed_channel(deps.storage, &channel_id, None)?;
+    let close = IbcMsg::CloseChannel {
+        channel_id: channel_id.clone(),
+    };
+
+    let res = Response::new()
+        .add_messages(msgs)
+        .add_message(close)
+        .add_attribute("action", "admin_close_channel")
+        .add_attribute("channel", channel_id)
+        .add_attribute("done", done.to_string());
+    Ok(res)
+}
+
+/// Refunds the next batch of a channel that was closed with more than MAX_CLOSE_REFUNDS denoms
+pub fn execute_continue_close(
+    deps: DepsMut,
+    info: MessageInfo,
+    channel_id: String,
+) -> Result<Response, ContractError> {
+    let cfg = CONFIG.load(deps.storage)?;
+    ensure_eq!(info.sender, cfg.gov_contract, ContractError::Unauthorized);
+
+    let cursor = CLOSE_CURSOR
+        .may_load(deps.storage, &channel_id)?
+        .ok_or_else(|| ContractError::NoPendingClose {
+            id: channel_id.clone(),
+        })?;
+    let (msgs, done) = refund_closed_channel(deps.storage, &channel_id, Some(cursor))?;
+
+    let res = Response::new()
+        .add_messages(msgs)
+        .add_attribute("action", "continue_close")
+        .add_attribute("channel", channel_id)
+        .add_attribute("done", done.to_string());
+    Ok(res)
+}
+
+pub fn execute_set_min_transfer(
+    deps: DepsMut,
+    info: MessageInfo,
+    denom: String,
+    amount: Option<Uint128>,
+) -> Result<Response, ContractError> {
+    let cfg = CONFIG.load(deps.storage)?;
+    ensure_eq!(info.sender, cfg.gov_contract, ContractError::Unauthorized);
+
+    let res = Response::new()
+        .add_attribute("action", "set_min_transfer")
+        .add_attribute("denom", &denom);
+    match amount {
+        Some(amount) => {
+            MIN_TRANSFER.save(deps.storage, &denom, &amount)?;
+            Ok(res.add_attribute("amount", amount))
+        }
+        None => {
+            MIN_TRANSFER.remove(deps.storage, &denom);
+            Ok(res.add_attribute("amount", "none"))
+        }
+    }
+}
+
+pub fn execute_set_native_gas_limit(
+    deps: DepsMut,
+    info: MessageInfo,
+    denom: String,
+    gas_limit: Option<u64>,
+) -> Result<Response, ContractError> {
+    let cfg = CONFIG.load(deps.storage)?;
+    ensure_eq!(info.sender, cfg.gov_contract, ContractError::Unauthorized);
+
+    let res = Response::new()
+        .add_attribute("action", "set_native_gas_limit")
+        .add_attribute("denom", &denom);
+    match gas_limit {
+        Some(gas_limit) => {
+            NATIVE_GAS_LIMIT.save(deps.storage, &denom, &gas_limit)?;
+            Ok(res.add_attribute("gas_limit", gas_limit.to_string()))
+        }
+        None => {
+            NATIVE_GAS_LIMIT.remove(deps.storage, &denom);
+            Ok(res.add_attribute("gas_limit", "none"))
+        }
+    }
+}
+
+pub fn execute_set_claim_grace_period(
+    deps: DepsMut,
+    info: MessageInfo,
+    grace_period: Option<u64>,
+) -> Result<Response, ContractError> {
+    let mut cfg = CONFIG.load(deps.storage)?;
+    ensure_eq!(info.sender, cfg.gov_contract, ContractError::Unauthorized);
+    cfg.claim_grace_period = grace_period;
+    CONFIG.save(deps.storage, &cfg)?;
+
+    let grace_period = grace_period.map_or_else(|| "none".to_string(), |p| p.to_string());
+    Ok(Response::new()
+        .add_attribute("action", "set_claim_grace_period")
+        .add_attribute("grace_period", grace_period))
+}
+
+pub fn execute_claim(deps: DepsMut, info: MessageInfo, id: u64) -> Result<Response, ContractError> {
+    if is_paused(deps.storage)? {
+        return Err(ContractError::Paused);
+    }
+    let delivery = load_delivery(deps.storage, id)?;
+    ensure_eq!(info.sender, delivery.receiver, ContractError::Unauthorized);
+    remove_delivery(deps.storage, id, &delivery)?;
+
+    let msg = delivery_msg(deps.storage, &delivery, delivery.receiver.to_string())?;
+    Ok(Response::new()
+        .add_message(msg)
+        .add_attribute("action", "claim")
+        .add_attribute("delivery_id", id.to_string())
+        .add_attribute("receiver", delivery.receiver)
+        .add_attribute("denom", delivery.amount.denom())
+        .add_attribute("amount", delivery.amount.amount()))
+}
+
+pub fn execute_sweep_unclaimed(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    id: u64,
+    recipient: Option<String>,
+) -> Result<Response, ContractError> {
+    let cfg = CONFIG.load(deps.storage)?;
+    ensure_eq!(info.sender, cfg.gov_contract, ContractError::Unauthorized);
+    let delivery = load_delivery(deps.storage, id)?;
+    if !delivery.claimable_until.is_expired(&env.block) {
+        return Err(ContractError::ClaimGraceNotExpired {
+            claimable_until: delivery.claimable_until,
+        });
+    }
+    remove_delivery(deps.storage, id, &delivery)?;
+
+    let res = Response::new()
+        .add_attribute("action", "sweep_unclaimed")
+        .add_attribute("delivery_id", id.to_string())
+        .add_attribute("denom", delivery.amount.denom())
+        .add_attribute("amount", delivery.amount.amount());
+    // nothing was minted yet, so there is nothing to sweep
+    if delivery.mint {
+        return Ok(res);
+    }
+    let recipient = recovery_recipient(deps.as_ref(), recipient)?;
+    let msg = delivery_msg(deps.storage, &delivery, recipient.to_string())?;
+    Ok(res.add_message(msg).add_attribute("recipient", recipient))
+}
+
+pub fn execute_pay_shares(
+    env: Env,
+    info: MessageInfo,
+    msgs: Vec<CosmosMsg>,
+) -> Result<Response, ContractError> {
+    ensure_eq!(
+        info.sender,
+        env.contract.address,
+        ContractError::Unauthorized
+    );
+    Ok(Response::new()
+        .add_messages(msgs)
+        .add_attribute("action", "pay_shares"))
+}
+
+/// Pays out a held delivery, minting it if it is a wrapped token we are the minter of
+fn delivery_msg(
+    storage: &dyn Storage,
+    delivery: &PendingDelivery,
+    recipient: String,
+) -> StdResult<CosmosMsg> {
+    if !delivery.mint {
+        return Ok(transfer_msg(
+            local_amount(storage, delivery.amount.clone())?,
+            recipient,
+        ));
+    }
+    let (token, amount) = match &delivery.amount {
+        Amount::Cw20(coin) => (coin.address.clone(), coin.amount),
+        Amount::Native(coin) => (coin.denom.clone(), coin.amount),
+    };
+    let mint = Cw20ExecuteMsg::Mint { recipient, amount };
+    Ok(WasmMsg::Execute {
+        contract_addr: token,
+        msg: to_binary(&mint)?,
+        funds: vec![],
+    }
+    .into())
+}
+
+pub fn execute_set_global_cap(
+    deps: DepsMut,
+    info: MessageInfo,
+    denom: String,
+    cap: Option<Uint128>,
+) -> Result<Response, ContractError> {
+    let cfg = CONFIG.load(deps.storage)?;
+    ensure_eq!(info.sender, cfg.gov_contract, ContractError::Unauthorized);
+
+    let res = Response::new()
+        .add_attribute("action", "set_global_cap")
+        .add_attribute("denom", &denom);
+    match cap {
+        Some(cap) => {
+            GLOBAL_CAP.save(deps.storage, &denom, &cap)?;
+            Ok(res.add_attribute("cap", cap))
+        }
+        None => {
+            GLOBAL_CAP.remove(deps.storage, &denom);
+            Ok(res.add_attribute("cap", "none"))
+        }
+    }
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn migrate(deps: DepsMut, _env: Env, _msg: MigrateMsg) -> Result<Response, ContractError> {
+    let version = get_contract_version(deps.storage)?;
+    if version.contract != CONTRACT_NAME {
+        return Err(ContractError::CannotMigrate {
+            previous_contract: version.contract,
+        });
+    }
+    // older versions did not keep a running total of the outstanding balances
+    rebuild_global_outstanding(deps.storage)?;
+    // nor did they index the channels by connection
+    rebuild_connection_index(deps.storage)?;
+    Ok(Response::default())
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
+    match msg {
+        QueryMsg::Port {} => to_binary(&query_port(deps, env)?),
+        QueryMsg::ListChannels {} => to_binary(&query_list(deps)?),
+        QueryMsg::ChannelsByConnection { connection_id } => {
+            to_binary(&query_channels_by_connection(deps, connection_id)?)
+        }
+        QueryMsg::Channel { id } => to_binary(&query_channel(deps, id)?),
+        QueryMsg::ChannelDenom { channel_id, denom } => {
+            to_binary(&query_channel_denom(deps, channel_id, denom)?)
+        }
+        QueryMsg::SimulateSend {
+            channel_id,
+            amount,
+            receiver,
+        } => to_binary(&query_simulate_send(deps, channel_id, amount, receiver)?),
+        QueryMsg::SimulateTimeout {
+            channel_id,
+            sender,
+            denom,
+            amount,
+        } => to_binary(&query_simulate_timeout(
+            deps, channel_id, sender, denom, amount,
+        )),
+        QueryMsg::LocalDenom {
+            voucher,
+            src_port,
+            src_channel,
+        } => to_binary(&query_local_denom(voucher, src_port, src_channel)),
+        QueryMsg::ChannelFeatures { channel_id } => {
+            to_binary(&query_channel_features(deps, channel_id)?)
+        }
+        QueryMsg::ChannelFull { channel_id } => to_binary(&query_channel_full(deps, channel_id)?),
+        QueryMsg::MultiChannel { channel_ids } => {
+            to_binary(&query_multi_channel(deps, channel_ids)?)
+        }
+        QueryMsg::SupportedVersions {} => to_binary(&query_supported_versions()),
+        QueryMsg::Config {} => to_binary(&query_config(deps)?),
+        QueryMsg::Allowed { contract } => to_binary(&query_allowed(deps, contract)?),
+        QueryMsg::ListAllowed { start_after, limit } => {
+            to_binary(&list_allowed(deps, start_after, limit)?)
+        }
+        QueryMsg::MinTransfer { denom } => to_binary(&query_min_transfer(deps, denom)?),
+        QueryMsg::GlobalCap { denom } => to_binary(&query_global_cap(deps, denom)?),
+        QueryMsg::PendingChanges {} => to_binary(&query_pending_changes(deps)?),
+        QueryMsg::RecentFailures { limit } => to_binary(&query_recent_failures(deps, limit)?),
+        QueryMsg::VolumeBuckets {
+            channel_id,
+            denom,
+            from,
+            to,
+        } => to_binary(&query_volume_buckets(deps, channel_id, denom, from, to)?),
+        QueryMsg::Reconcile { denoms } => to_binary(&query_reconcile(deps, env, denoms)?),
+        QueryMsg::CheckInvariants {} => to_binary(&query_check_invariants(deps)?),
+        QueryMsg::PendingDelivery { id } => to_binary(&query_pending_delivery(deps, id)?),
+    }
+}
+
+/// Lists ICS20_VERSIONS, the same versions the channel handshake accepts
+pub fn query_supported_versions() -> SupportedVersionsResponse {
+    let versions = ICS20_VERSIONS.iter().map(|v| v.to_string()).collect();
+    SupportedVersionsResponse { versions }
+}
+
+/// The port bound by this contract, as recorded when the first channel connected.
+/// Before that, it is derived from the contract address like wasmd does.
+fn query_port(deps: Deps, env: Env) -> StdResult<PortResponse> {
+    if let Some(port_id) = BOUND_PORT.may_load(deps.storage)? {
+        return Ok(PortResponse { port_id });
+    }
+    if env.contract.address.as_str().is_empty() {
+        return Err(StdError::generic_err(
+            "Cannot derive the port without a contract address",
+        ));
+    }
+    let port_id = format!("wasm.{}", env.contract.address);
+    Ok(PortResponse { port_id })
+}
+
+fn query_list(deps: Deps) -> StdResult<ListChannelsResponse> {
+    let channels = CHANNEL_INFO
+        .range_raw(deps.storage, None, None, Order::Ascending)
+        .map(|r| r.map(|(_, v)| v))
         .collect::<StdResult<_>>()?;
-    Ok(ListAllowedResponse { allow })
+    Ok(ListChannelsResponse { channels })
 }
 
-#[cfg(test)]
-mod test {
-    use super::*;
-    use crate::test_helpers::*;
+fn query_channels_by_connection(
+    deps: Deps,
+    connection_id: String,
+) -> StdResult<ListChannelsResponse> {
+    let channels = CHANNELS_BY_CONNECTION
+        .prefix(&connection_id)
+        .keys(deps.storage, None, None, Order::Ascending)
+        .map(|id| CHANNEL_INFO.load(deps.storage, &id?))
+        .collect::<StdResult<_>>()?;
+    Ok(ListChannelsResponse { channels })
+}
+
+// make public for ibc tests
+pub fn query_channel(deps: Deps, id: String) -> StdResult<ChannelResponse> {
+    let info = CHANNEL_INFO.load(deps.storage, &id)?;
+    // this returns Vec<(outstanding, total)>
+    let mut state = CHANNEL_STATE
+        .prefix(&id)
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|r| {
+            r.map(|(denom, v)| {
+                let outstanding = Amount::from_parts(denom.clone(), v.outstanding);
+                let total = Amount::from_parts(denom, v.total_sent);
+                (outstanding, total)
+            })
+        })
+        .collect::<StdResult<Vec<_>>>()?;
+    // sort by denom ourselves rather than rely on the iteration order of the store,
+    // so repeated queries are byte-identical and clients can diff them
+    state.sort_by_key(|(outstanding, _)| outstanding.denom());
+    // we want (Vec<outstanding>, Vec<total>)
+    let (balances, total_sent): (Vec<Amount>, Vec<Amount>) = state.into_iter().unzip();
+    let denom_traces = balances
+        .iter()
+        .map(|balance| parse_denom_trace(&balance.denom()))
+        .collect();
+
+    Ok(ChannelResponse {
+        response_version: CHANNEL_RESPONSE_VERSION,
+        info,
+        balances,
+        total_sent,
+        denom_traces,
+    })
+}
+
+pub fn query_multi_channel(
+    deps: Deps,
+    channel_ids: Vec<String>,
+) -> StdResult<MultiChannelResponse> {
+    if channel_ids.len() > MAX_MULTI_CHANNEL {
+        return Err(StdError::generic_err(format!(
+            "Cannot query more than {} channels at once",
+            MAX_MULTI_CHANNEL
+        )));
+    }
+    let channels = channel_ids
+        .into_iter()
+        .map(|channel_id| {
+            let channel = if CHANNEL_INFO.has(deps.storage, &channel_id) {
+                Some(query_channel(deps, channel_id.clone())?)
+            } else {
+                None
+            };
+            Ok(MultiChannelEntry {
+                channel_id,
+                channel,
+            })
+        })
+        .collect::<StdResult<_>>()?;
+    Ok(MultiChannelResponse { channels })
+}
+
+pub fn query_channel_features(
+    deps: Deps,
+    channel_id: String,
+) -> StdResult<ChannelFeaturesResponse> {
+    let info = CHANNEL_INFO.load(deps.storage, &channel_id)?;
+    Ok(ChannelFeaturesResponse {
+        supports_memo: info.supports_memo(),
+        supports_u128: info.supports_u128(),
+        version: info.version,
+        ack_encoding: ack_encoding(deps.storage, &channel_id)?,
+    })
+}
+
+pub fn query_channel_full(deps: Deps, channel_id: String) -> StdResult<ChannelFullResponse> {
+    let enabled = CHANNEL_ENABLED
+        .may_load(deps.storage, &channel_id)?
+        .unwrap_or(true);
+    Ok(ChannelFullResponse {
+        channel: query_channel(deps, channel_id.clone())?,
+        features: query_channel_features(deps, channel_id)?,
+        enabled,
+    })
+}
+
+// a single load rather than iterating over the whole channel
+pub fn query_channel_denom(
+    deps: Deps,
+    channel_id: String,
+    denom: String,
+) -> StdResult<ChannelDenomResponse> {
+    let state = CHANNEL_STATE
+        .may_load(deps.storage, (&channel_id, &denom))?
+        .unwrap_or_default();
+    Ok(ChannelDenomResponse {
+        balance: Amount::from_parts(denom.clone(), state.outstanding),
+        total_sent: Amount::from_parts(denom, state.total_sent),
+    })
+}
+
+fn query_simulate_send(
+    deps: Deps,
+    channel_id: String,
+    amount: Amount,
+    receiver: String,
+) -> StdResult<SimulateSendResponse> {
+    let amount = wire_amount(deps.storage, amount)?;
+    // the sender is not checked by any validation, so we can leave it empty
+    let packet = Ics20Packet::new(amount.amount(), amount.denom(), "", &receiver);
+    let res = match validate_transfer(deps, &channel_id, &amount, &packet) {
+        Ok(()) => SimulateSendResponse {
+            accepted: true,
+            error: None,
+        },
+        Err(err) => SimulateSendResponse {
+            accepted: false,
+            error: Some(err.to_string()),
+        },
+    };
+    Ok(res)
+}
+
+fn query_simulate_timeout(
+    deps: Deps,
+    channel_id: String,
+    sender: String,
+    denom: String,
+    amount: Uint128,
+) -> SimulateTimeoutResponse {
+    match simulate_refund(deps, &channel_id, &sender, &denom, amount) {
+        Ok((refund, gas_limit)) => SimulateTimeoutResponse {
+            refundable: true,
+            refund: Some(refund),
+            gas_limit,
+            error: None,
+        },
+        Err(err) => SimulateTimeoutResponse {
+            refundable: false,
+            refund: None,
+            gas_limit: None,
+            error: Some(err.to_string()),
+        },
+    }
+}
+
+fn query_local_denom(voucher: String, src_port: String, src_channel: String) -> LocalDenomResponse {
+    let src = IbcEndpoint {
+        port_id: src_port,
+        channel_id: src_channel,
+    };
+    match parse_voucher_denom(&voucher, &src) {
+        Ok(denom) => LocalDenomResponse {
+            denom: Some(denom.to_string()),
+            error: None,
+        },
+        Err(err) => LocalDenomResponse {
+            denom: None,
+            error: Some(err.to_string()),
+        },
+    }
+}
+
+fn query_config(deps: Deps) -> StdResult<ConfigResponse> {
+    let cfg = CONFIG.load(deps.storage)?;
+    let res = ConfigResponse {
+        default_timeout: cfg.default_timeout,
+        gov_contract: cfg.gov_contract.into(),
+        max_memo_len: cfg.max_memo_len,
+        allow_partial_release: cfg.allow_partial_release,
+        allow_all_cw20: cfg.allow_all_cw20,
+        default_gas_limit: cfg.default_gas_limit,
+        max_timeout_height_offset: cfg.max_timeout_height_offset,
+        max_denoms_per_channel: cfg.max_denoms_per_channel,
+        restrict_receivers: cfg.restrict_receivers,
+        admin_timelock: cfg.admin_timelock,
+        volume_bucket: cfg.volume_bucket,
+        max_packet_size: cfg.max_packet_size,
+        paused: is_paused(deps.storage)?,
+        recovery: RECOVERY.may_load(deps.storage)?.map(Into::into),
+        claim_grace_period: cfg.claim_grace_period,
+        max_in_flight: cfg.max_in_flight,
+    };
+    Ok(res)
+}
+
+fn query_pending_delivery(deps: Deps, id: u64) -> StdResult<PendingDeliveryResponse> {
+    let delivery = PENDING_DELIVERY.load(deps.storage, id)?;
+    let amount = if delivery.mint {
+        delivery.amount
+    } else {
+        local_amount(deps.storage, delivery.amount)?
+    };
+    Ok(PendingDeliveryResponse {
+        channel: delivery.channel,
+        receiver: delivery.receiver.into(),
+        amount,
+        claimable_until: delivery.claimable_until,
+    })
+}
+
+fn query_pending_changes(deps: Deps) -> StdResult<PendingChangesResponse> {
+    let changes = PENDING_CHANGES
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|item| {
+            item.map(|(id, pending)| PendingChangeInfo {
+                id,
+                change: pending.change,
+                effective_at: pending.effective_at,
+            })
+        })
+        .collect::<StdResult<_>>()?;
+    Ok(PendingChangesResponse { changes })
+}
+
+fn query_reconcile(deps: Deps, env: Env, denoms: Vec<String>) -> StdResult<ReconcileResponse> {
+    let denoms = denoms
+        .into_iter()
+        .map(|denom| {
+            // aliased native tokens are tracked in the wire denom, but held in the local one
+            let wire = wire_denom(deps.storage, &denom)?;
+            let tracked = total_outstanding(deps.storage, &wire)?;
+            // a contract cannot list its cw20 holdings, but can query them one by one
+            let actual = match Amount::from_parts(denom.clone(), Uint128::zero()) {
+                Amount::Native(_) => {
+                    let local = local_denom(deps.storage, &wire)?;
+                    deps.querier
+                        .query_balance(&env.contract.address, local)?
+                        .amount
+                }
+                Amount::Cw20(coin) => {
+                    let query = Cw20QueryMsg::Balance {
+                        address: env.contract.address.to_string(),
+                    };
+                    let res: BalanceResponse =
+                        deps.querier.query_wasm_smart(coin.address, &query)?;
+                    res.balance
+                }
+            };
+            Ok(DenomReconciliation {
+                denom,
+                tracked,
+                actual,
+                surplus: actual.saturating_sub(tracked),
+                shortfall: tracked.saturating_sub(actual),
+            })
+        })
+        .collect::<StdResult<_>>()?;
+    Ok(ReconcileResponse { denoms })
+}
+
+fn query_check_invariants(deps: Deps) -> StdResult<CheckInvariantsResponse> {
+    let mut violations = vec![];
+    let mut channel_totals = BTreeMap::<String, Uint128>::new();
+    for item in CHANNEL_STATE.range(deps.storage, None, None, Order::Ascending) {
+        let ((channel_id, denom), state) = item?;
+        // sends are escrowed right away, but only count as sent once acknowledged
+        let in_flight = IN_FLIGHT_AMOUNT
+            .may_load(deps.storage, (&channel_id, &denom))?
+            .unwrap_or_default();
+        if state.outstanding > state.total_sent.saturating_add(in_flight) {
+            violations.push(InvariantViolation::OutstandingExceedsSent {
+                channel_id,
+                denom: denom.clone(),
+                outstanding: state.outstanding,
+                total_sent: state.total_sent,
+                in_flight,
+            });
+        }
+        *channel_totals.entry(denom).or_default() += state.outstanding;
+    }
+
+    // compare both ways, a denom may be missing from either side
+    for item in GLOBAL_OUTSTANDING.range(deps.storage, None, None, Order::Ascending) {
+        let (denom, global) = item?;
+        let channels = channel_totals.remove(&denom).unwrap_or_default();
+        if global != channels {
+            violations.push(InvariantViolation::GlobalOutstandingMismatch {
+                denom,
+                global,
+                channels,
+            });
+        }
+    }
+    for (denom, channels) in channel_totals {
+        if !channels.is_zero() {
+            violations.push(InvariantViolation::GlobalOutstandingMismatch {
+                denom,
+                global: Uint128::zero(),
+                channels,
+            });
+        }
+    }
+    Ok(CheckInvariantsResponse { violations })
+}
+
+fn query_min_transfer(deps: Deps, denom: String) -> StdResult<MinTransferResponse> {
+    let amount = MIN_TRANSFER.may_load(deps.storage, &denom)?;
+    Ok(MinTransferResponse { amount })
+}
+
+fn query_global_cap(deps: Deps, denom: String) -> StdResult<GlobalCapResponse> {
+    let cap = GLOBAL_CAP.may_load(deps.storage, &denom)?;
+    let outstanding = GLOBAL_OUTSTANDING
+        .may_load(deps.storage, &denom)?
+        .unwrap_or_default();
+    Ok(GlobalCapResponse { cap, outstanding })
+}
+
+fn query_recent_failures(deps: Deps, limit: Option<u32>) -> StdResult<RecentFailuresResponse> {
+    let limit = limit.map_or(MAX_RECENT_FAILURES, u64::from) as usize;
+    let failures = RECENT_FAILURES
+        .range(deps.storage, None, None, Order::Descending)
+        .take(limit)
+        .map(|item| item.map(|(_, failure)| failure))
+        .collect::<StdResult<_>>()?;
+    Ok(RecentFailuresResponse { failures })
+}
+
+fn query_volume_buckets(
+    deps: Deps,
+    channel_id: String,
+    denom: String,
+    from: u64,
+    to: u64,
+) -> StdResult<VolumeBucketsResponse> {
+    let bucket_len = CONFIG
+        .load(deps.storage)?
+        .volume_bucket
+        .ok_or_else(|| StdError::generic_err("Volume buckets are not enabled"))?;
+    let buckets = VOLUME_BUCKETS
+        .prefix((&channel_id, &denom))
+        .range(
+            deps.storage,
+            Some(Bound::inclusive_int(from)),
+            Some(Bound::exclusive_int(to)),
+            Order::Ascending,
+        )
+        .take(MAX_VOLUME_BUCKETS)
+        .map(|item| item.map(|(start, amount)| VolumeBucket { start, amount }))
+        .collect::<StdResult<_>>()?;
+    Ok(VolumeBucketsResponse {
+        bucket_len,
+        buckets,
+    })
+}
+
+fn query_allowed(deps: Deps, contract: String) -> StdResult<AllowedResponse> {
+    let addr = deps.api.addr_validate(&contract)?;
+    let info = ALLOW_LIST.may_load(deps.storage, &addr)?;
+    let res = match info {
+        None => AllowedResponse {
+            is_allowed: false,
+            gas_limit: None,
+            fee_on_transfer: false,
+            verify_balance: false,
+        },
+        Some(a) => AllowedResponse {
+            is_allowed: true,
+            gas_limit: a.gas_limit,
+            fee_on_transfer: a.fee_on_transfer,
+            verify_balance: a.verify_balance,
+        },
+    };
+    Ok(res)
+}
+
+// settings for pagination
+const MAX_LIMIT: u32 = 30;
+const DEFAULT_LIMIT: u32 = 10;
+
+fn list_allowed(
+    deps: Deps,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<ListAllowedResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = match start_after {
+        Some(x) => Some(Bound::exclusive(deps.api.addr_validate(&x)?.into_string())),
+        None => None,
+    };
+
+    let allow = ALLOW_LIST
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| {
+            item.map(|(addr, allow)| AllowedInfo {
+                contract: addr.into(),
+                gas_limit: allow.gas_limit,
+                fee_on_transfer: allow.fee_on_transfer,
+                verify_balance: allow.verify_balance,
+            })
+        })
+        .collect::<StdResult<_>>()?;
+    Ok(ListAllowedResponse { allow })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::state::ChannelState;
+    use crate::test_helpers::*;
+
+    use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info, MOCK_CONTRACT_ADDR};
+    use cosmwasm_std::{
+        attr, coin, coins, BankMsg, ContractResult, CosmosMsg, IbcChannel, IbcChannelConnectMsg,
+        IbcChannelOpenMsg, IbcMsg, StdError, SubMsgExecutionResponse, SystemError, SystemResult,
+        Uint128, WasmQuery,
+    };
+    use cw2::ContractVersion;
+
+    use crate::ibc::{
+        ibc_channel_connect, ibc_channel_open, reply, ICS20_2_VERSION, ICS20_ORDERING,
+        ICS20_VERSION,
+    };
+    use crate::msg::DenomTrace;
+    use cw_utils::PaymentError;
+
+    #[test]
+    fn setup_and_query() {
+        let deps = setup(&["channel-3", "channel-7"], &[]);
+
+        let raw_list = query(deps.as_ref(), mock_env(), QueryMsg::ListChannels {}).unwrap();
+        let list_res: ListChannelsResponse = from_binary(&raw_list).unwrap();
+        assert_eq!(2, list_res.channels.len());
+        assert_eq!(mock_channel_info("channel-3"), list_res.channels[0]);
+        assert_eq!(mock_channel_info("channel-7"), list_res.channels[1]);
+
+        let raw_channel = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::Channel {
+                id: "channel-3".to_string(),
+            },
+        )
+        .unwrap();
+        let chan_res: ChannelResponse = from_binary(&raw_channel).unwrap();
+        assert_eq!(chan_res.info, mock_channel_info("channel-3"));
+        assert_eq!(0, chan_res.total_sent.len());
+        assert_eq!(0, chan_res.balances.len());
+        assert_eq!(0, chan_res.denom_traces.len());
+
+        let err = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::Channel {
+                id: "channel-10".to_string(),
+            },
+        )
+        .unwrap_err();
+        assert_eq!(err, StdError::not_found("cw20_ics20::state::ChannelInfo"));
+    }
+
+    #[test]
+    fn query_channel_sorts_by_denom() {
+        let mut deps = setup(&["channel-3"], &[]);
+        for denom in ["uosmo", "cw20:token", "transfer/channel-9/uatom", "uatom"] {
+            increase_channel_balance(deps.as_mut().storage, "channel-3", denom, Uint128::new(5))
+                .unwrap();
+        }
+
+        let res = query_channel(deps.as_ref(), "channel-3".to_string()).unwrap();
+        let denoms = |amounts: &[Amount]| amounts.iter().map(Amount::denom).collect::<Vec<_>>();
+        let sorted = vec!["cw20:token", "transfer/channel-9/uatom", "uatom", "uosmo"];
+        assert_eq!(denoms(&res.balances), sorted);
+        assert_eq!(denoms(&res.total_sent), sorted);
+        let trace = res
+            .denom_traces
+            .iter()
+            .map(|trace| trace.base_denom.as_str());
+        assert_eq!(
+            trace.collect::<Vec<_>>(),
+            vec!["cw20:token", "uatom", "uatom", "uosmo"]
+        );
+
+        // repeated queries are byte-identical
+        let msg = QueryMsg::Channel {
+            id: "channel-3".to_string(),
+        };
+        let first = query(deps.as_ref(), mock_env(), msg.clone()).unwrap();
+        assert_eq!(first, query(deps.as_ref(), mock_env(), msg).unwrap());
+    }
+
+    #[test]
+    fn query_multi_channel() {
+        let mut deps = setup(&["channel-3", "channel-7"], &[]);
+        increase_channel_balance(deps.as_mut().storage, "channel-7", "ucosm", Uint128::new(5))
+            .unwrap();
+
+        let ids = ["channel-7", "channel-1", "channel-3"];
+        let msg = QueryMsg::MultiChannel {
+            channel_ids: ids.iter().map(|id| id.to_string()).collect(),
+        };
+        let res: MultiChannelResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+        let expected = ids
+            .iter()
+            .map(|id| MultiChannelEntry {
+                channel_id: id.to_string(),
+                channel: query_channel(deps.as_ref(), id.to_string()).ok(),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(res.channels, expected);
+        assert_eq!(
+            res.channels[0].channel.as_ref().unwrap().balances,
+            vec![Amount::native(5, "ucosm")]
+        );
+        assert_eq!(res.channels[1].channel, None);
+        assert!(res.channels[2].channel.is_some());
+
+        // the list is bounded
+        let msg = QueryMsg::MultiChannel {
+            channel_ids: vec!["channel-3".to_string(); MAX_MULTI_CHANNEL + 1],
+        };
+        query(deps.as_ref(), mock_env(), msg).unwrap_err();
+    }
+
+    #[test]
+    fn query_port_before_and_after_connect() {
+        let port = |deps: Deps, env: Env| -> StdResult<String> {
+            let res: PortResponse = from_binary(&query(deps, env, QueryMsg::Port {})?)?;
+            Ok(res.port_id)
+        };
+
+        // before any channel connected, the port is derived from the contract address
+        let mut deps = setup(&[], &[]);
+        let derived = port(deps.as_ref(), mock_env()).unwrap();
+        assert_eq!(derived, format!("wasm.{}", MOCK_CONTRACT_ADDR));
+        assert_eq!(derived, CONTRACT_PORT);
+        let mut env = mock_env();
+        env.contract.address = Addr::unchecked("");
+        port(deps.as_ref(), env.clone()).unwrap_err();
+
+        // once connected, the port the channel is bound to is returned
+        add_channel(deps.as_mut(), "channel-1");
+        assert_eq!(port(deps.as_ref(), env).unwrap(), CONTRACT_PORT);
+    }
+
+    #[test]
+    fn channels_by_connection() {
+        let mut deps = setup(&["channel-3", "channel-7"], &[]);
+        // channel-9 connects over another connection
+        let channel = IbcChannel::new(
+            IbcEndpoint {
+                port_id: CONTRACT_PORT.into(),
+                channel_id: "channel-9".into(),
+            },
+            IbcEndpoint {
+                port_id: REMOTE_PORT.into(),
+                channel_id: "channel-95".into(),
+            },
+            ICS20_ORDERING,
+            ICS20_VERSION,
+            "connection-7",
+        );
+        let open_msg = IbcChannelOpenMsg::new_init(channel.clone());
+        ibc_channel_open(deps.as_mut(), mock_env(), open_msg).unwrap();
+        let connect_msg = IbcChannelConnectMsg::new_ack(channel, ICS20_VERSION);
+        ibc_channel_connect(deps.as_mut(), mock_env(), connect_msg).unwrap();
+
+        let channel_ids = |deps: Deps, connection_id: &str| -> Vec<String> {
+            let msg = QueryMsg::ChannelsByConnection {
+                connection_id: connection_id.to_string(),
+            };
+            let res: ListChannelsResponse =
+                from_binary(&query(deps, mock_env(), msg).unwrap()).unwrap();
+            res.channels.into_iter().map(|c| c.id).collect()
+        };
+        assert_eq!(
+            channel_ids(deps.as_ref(), CONNECTION_ID),
+            vec!["channel-3", "channel-7"]
+        );
+        assert_eq!(
+            channel_ids(deps.as_ref(), "connection-7"),
+            vec!["channel-9"]
+        );
+        assert!(channel_ids(deps.as_ref(), "connection-8").is_empty());
+
+        // channels stored before the index existed are indexed on migrate
+        CHANNELS_BY_CONNECTION.remove(deps.as_mut().storage, (CONNECTION_ID, "channel-7"));
+        assert_eq!(channel_ids(deps.as_ref(), CONNECTION_ID), vec!["channel-3"]);
+        migrate(deps.as_mut(), mock_env(), MigrateMsg {}).unwrap();
+        assert_eq!(
+            channel_ids(deps.as_ref(), CONNECTION_ID),
+            vec!["channel-3", "channel-7"]
+        );
+    }
+
+    #[test]
+    fn migrate_from_config_without_memo_limit() {
+        let mut deps = setup(&["channel-3"], &[]);
+
+        // the config as stored before memos were supported
+        let old_config = br#"{"default_timeout":3600,"gov_contract":"gov"}"#;
+        deps.as_mut().storage.set(b"ics20_config", old_config);
+        migrate(deps.as_mut(), mock_env(), MigrateMsg {}).unwrap();
+
+        let cfg = query_config(deps.as_ref()).unwrap();
+        assert_eq!(cfg.default_timeout, 3600);
+        assert_eq!(cfg.gov_contract, "gov");
+        assert_eq!(cfg.max_memo_len, DEFAULT_MAX_MEMO_LEN);
+        assert!(!cfg.allow_partial_release);
+        assert_eq!(
+            cfg.max_timeout_height_offset,
+            DEFAULT_MAX_TIMEOUT_HEIGHT_OFFSET
+        );
+    }
+
+    #[test]
+    fn instantiate_validates_addresses() {
+        let instantiate = |msg: InitMsg| {
+            let mut deps = mock_dependencies();
+            let info = mock_info("anyone", &[]);
+            instantiate(deps.as_mut(), mock_env(), info, msg)
+        };
+
+        // a valid config is stored
+        let deps = setup_with_init(&[], mock_init_msg(&[("my-token", 123456)]));
+        let cfg = query_config(deps.as_ref()).unwrap();
+        assert_eq!(cfg.gov_contract, "gov");
+        let allowed = query_allowed(deps.as_ref(), "my-token".to_string()).unwrap();
+        assert!(allowed.is_allowed);
+
+        // an empty or malformed gov contract is rejected
+        for address in ["", "x"] {
+            let mut msg = mock_init_msg(&[]);
+            msg.gov_contract = address.to_string();
+            let err = instantiate(msg).unwrap_err();
+            assert_eq!(
+                err,
+                ContractError::InvalidAdmin {
+                    address: address.to_string()
+                }
+            );
+        }
+
+        // so is a bad allow list entry
+        let err = instantiate(mock_init_msg(&[("my-token", 123456), ("", 5000)])).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::InvalidAllowListEntry {
+                address: "".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn proper_checks_on_execute_native() {
+        let send_channel = "channel-5";
+        let mut deps = setup(&[send_channel, "channel-10"], &[]);
+
+        let mut transfer = TransferMsg {
+            channel: send_channel.to_string(),
+            remote_address: "foreign-address".to_string(),
+            timeout: None,
+            timeout_height: None,
+            memo: None,
+            idempotency_key: None,
+            fee: None,
+            refund_to: None,
+        };
+
+        // works with proper funds
+        let msg = ExecuteMsg::Transfer(transfer.clone());
+        let info = mock_info("foobar", &coins(1234567, "ucosm"));
+        let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        assert_eq!(res.messages[0].gas_limit, None);
+        assert_eq!(1, res.messages.len());
+        if let CosmosMsg::Ibc(IbcMsg::SendPacket {
+            channel_id,
+            data,
+            timeout,
+        }) = &res.messages[0].msg
+        {
+            let expected_timeout = mock_env().block.time.plus_seconds(DEFAULT_TIMEOUT);
+            assert_eq!(timeout, &expected_timeout.into());
+            assert_eq!(channel_id.as_str(), send_channel);
+            let msg: Ics20Packet = from_binary(data).unwrap();
+            assert_eq!(msg.amount, Uint128::new(1234567));
+            assert_eq!(msg.denom.as_str(), "ucosm");
+            assert_eq!(msg.sender.as_str(), "foobar");
+            assert_eq!(msg.receiver.as_str(), "foreign-address");
+        } else {
+            panic!("Unexpected return message: {:?}", res.messages[0]);
+        }
+
+        // reject with no funds
+        let msg = ExecuteMsg::Transfer(transfer.clone());
+        let info = mock_info("foobar", &[]);
+        let err = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+        assert_eq!(err, ContractError::Payment(PaymentError::NoFunds {}));
+
+        // reject with multiple tokens funds
+        let msg = ExecuteMsg::Transfer(transfer.clone());
+        let info = mock_info("foobar", &[coin(1234567, "ucosm"), coin(54321, "uatom")]);
+        let err = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+        assert_eq!(err, ContractError::Payment(PaymentError::MultipleDenoms {}));
+
+        // reject with bad channel id
+        transfer.channel = "channel-45".to_string();
+        let msg = ExecuteMsg::Transfer(transfer);
+        let info = mock_info("foobar", &coins(1234567, "ucosm"));
+        let err = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::NoSuchChannel {
+                id: "channel-45".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn proper_checks_on_execute_cw20() {
+        let send_channel = "channel-15";
+        let cw20_addr = "my-token";
+        let mut deps = setup(&["channel-3", send_channel], &[(cw20_addr, 123456)]);
+
+        let transfer = TransferMsg {
+            channel: send_channel.to_string(),
+            remote_address: "foreign-address".to_string(),
+            timeout: Some(7777),
+            timeout_height: None,
+            memo: None,
+            idempotency_key: None,
+            fee: None,
+            refund_to: None,
+        };
+        let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+            sender: "my-account".into(),
+            amount: Uint128::new(888777666),
+            msg: to_binary(&transfer).unwrap(),
+        });
+
+        // works with proper funds
+        let info = mock_info(cw20_addr, &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, msg.clone()).unwrap();
+        assert_eq!(1, res.messages.len());
+        assert_eq!(res.messages[0].gas_limit, None);
+        if let CosmosMsg::Ibc(IbcMsg::SendPacket {
+            channel_id,
+            data,
+            timeout,
+        }) = &res.messages[0].msg
+        {
+            let expected_timeout = mock_env().block.time.plus_seconds(7777);
+            assert_eq!(timeout, &expected_timeout.into());
+            assert_eq!(channel_id.as_str(), send_channel);
+            let msg: Ics20Packet = from_binary(data).unwrap();
+            assert_eq!(msg.amount, Uint128::new(888777666));
+            assert_eq!(msg.denom, format!("cw20:{}", cw20_addr));
+            assert_eq!(msg.sender.as_str(), "my-account");
+            assert_eq!(msg.receiver.as_str(), "foreign-address");
+        } else {
+            panic!("Unexpected return message: {:?}", res.messages[0]);
+        }
+
+        // reject with tokens funds
+        let info = mock_info("foobar", &coins(1234567, "ucosm"));
+        let err = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+        assert_eq!(err, ContractError::Payment(PaymentError::NonPayable {}));
+    }
+
+    #[test]
+    fn execute_cw20_fails_if_not_whitelisted() {
+        let send_channel = "channel-15";
+        let mut deps = setup(&["channel-3", send_channel], &[]);
+
+        let cw20_addr = "my-token";
+        let transfer = TransferMsg {
+            channel: send_channel.to_string(),
+            remote_address: "foreign-address".to_string(),
+            timeout: Some(7777),
+            timeout_height: None,
+            memo: None,
+            idempotency_key: None,
+            fee: None,
+            refund_to: None,
+        };
+        let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+            sender: "my-account".into(),
+            amount: Uint128::new(888777666),
+            msg: to_binary(&transfer).unwrap(),
+        });
+
+        // the hook of a token that is not on the allow list is rejected
+        let info = mock_info(cw20_addr, &[]);
+        let err = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+        assert_eq!(err, ContractError::NotOnAllowList);
+    }
+
+    #[test]
+    fn query_channel_denom_matches_channel() {
+        let send_channel = "channel-5";
+        let cw20_addr = "my-token";
+        let mut deps = setup(&[send_channel], &[(cw20_addr, 123456)]);
+
+        let transfer = TransferMsg {
+            channel: send_channel.to_string(),
+            remote_address: "foreign-address".to_string(),
+            timeout: None,
+            timeout_height: None,
+            memo: None,
+            idempotency_key: None,
+            fee: None,
+            refund_to: None,
+        };
+        let msg = ExecuteMsg::Transfer(transfer.clone());
+        let info = mock_info("foobar", &coins(1234567, "ucosm"));
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+            sender: "my-account".into(),
+            amount: Uint128::new(888777666),
+            msg: to_binary(&transfer).unwrap(),
+        });
+        let info = mock_info(cw20_addr, &[]);
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let full = query_channel(deps.as_ref(), send_channel.to_string()).unwrap();
+        assert_eq!(2, full.balances.len());
+        for (balance, total_sent) in full.balances.into_iter().zip(full.total_sent) {
+            let raw = query(
+                deps.as_ref(),
+                mock_env(),
+                QueryMsg::ChannelDenom {
+                    channel_id: send_channel.to_string(),
+                    denom: balance.denom(),
+                },
+            )
+            .unwrap();
+            let single: ChannelDenomResponse = from_binary(&raw).unwrap();
+            assert_eq!(single.balance, balance);
+            assert_eq!(single.total_sent, total_sent);
+        }
+
+        // unknown denoms are reported as zero
+        let single =
+            query_channel_denom(deps.as_ref(), send_channel.to_string(), "uatom".to_string())
+                .unwrap();
+        assert_eq!(single.balance, Amount::native(0, "uatom"));
+        assert_eq!(single.total_sent, Amount::native(0, "uatom"));
+    }
+
+    #[test]
+    fn memo_length_is_bounded_on_send() {
+        let send_channel = "channel-5";
+        let mut deps = setup(&[send_channel], &[]);
+
+        // exactly the max is fine
+        let mut transfer = TransferMsg {
+            channel: send_channel.to_string(),
+            remote_address: "foreign-address".to_string(),
+            timeout: None,
+            timeout_height: None,
+            memo: Some("m".repeat(DEFAULT_MAX_MEMO_LEN as usize)),
+            idempotency_key: None,
+            fee: None,
+            refund_to: None,
+        };
+        let msg = ExecuteMsg::Transfer(transfer.clone());
+        let info = mock_info("foobar", &coins(1234567, "ucosm"));
+        let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        if let CosmosMsg::Ibc(IbcMsg::SendPacket { data, .. }) = &res.messages[0].msg {
+            let packet: Ics20Packet = from_binary(data).unwrap();
+            assert_eq!(packet.memo, transfer.memo);
+        } else {
+            panic!("Unexpected return message: {:?}", res.messages[0]);
+        }
+
+        // one more byte is rejected, and nothing is escrowed
+        transfer.memo = Some("m".repeat(DEFAULT_MAX_MEMO_LEN as usize + 1));
+        let msg = ExecuteMsg::Transfer(transfer);
+        let info = mock_info("foobar", &coins(1234567, "uatom"));
+        let err = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::MemoTooLong {
+                max: DEFAULT_MAX_MEMO_LEN
+            }
+        );
+        let state =
+            query_channel_denom(deps.as_ref(), send_channel.to_string(), "uatom".to_string())
+                .unwrap();
+        assert_eq!(state.balance, Amount::native(0, "uatom"));
+    }
+
+    #[test]
+    fn packet_size_is_bounded_on_send() {
+        let send_channel = "channel-5";
+        let memo = "m".repeat(100);
+        let packet = Ics20Packet::new(Uint128::new(1234567), "ucosm", "foobar", "foreign-address")
+            .with_memo(Some(memo.clone()));
+        let max = to_vec(&packet).unwrap().len() as u32;
+        let mut init = mock_init_msg(&[]);
+        init.max_packet_size = Some(max);
+        let mut deps = setup_with_init(&[send_channel], init);
+
+        // exactly the max is fine
+        let mut transfer = TransferMsg {
+            channel: send_channel.to_string(),
+            remote_address: "foreign-address".to_string(),
+            timeout: None,
+            timeout_height: None,
+            memo: Some(memo),
+            idempotency_key: None,
+            fee: None,
+            refund_to: None,
+        };
+        let msg = ExecuteMsg::Transfer(transfer.clone());
+        let info = mock_info("foobar", &coins(1234567, "ucosm"));
+        let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        if let CosmosMsg::Ibc(IbcMsg::SendPacket { data, .. }) = &res.messages[0].msg {
+            assert_eq!(data.len(), max as usize);
+        } else {
+            panic!("Unexpected return message: {:?}", res.messages[0]);
+        }
+
+        // one more byte is rejected, and nothing is escrowed
+        transfer.memo = Some("m".repeat(101));
+        let msg = ExecuteMsg::Transfer(transfer);
+        let info = mock_info("foobar", &coins(1234567, "ucosm"));
+        let err = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::PacketTooLarge {
+                size: max as usize + 1,
+                max
+            }
+        );
+        let state =
+            query_channel_denom(deps.as_ref(), send_channel.to_string(), "ucosm".to_string())
+                .unwrap();
+        assert_eq!(state.balance, Amount::native(1234567, "ucosm"));
+    }
+
+    #[test]
+    fn timeout_height_is_checked_and_clamped() {
+        let send_channel = "channel-5";
+        let mut deps = setup(&[send_channel], &[]);
+        let env = mock_env();
+        let height = env.block.height;
+        let timestamp = env.block.time.plus_seconds(DEFAULT_TIMEOUT);
+
+        let transfer_at = |height: u64| {
+            ExecuteMsg::Transfer(TransferMsg {
+                channel: send_channel.to_string(),
+                remote_address: "foreign-address".to_string(),
+                timeout: None,
+                timeout_height: Some(IbcTimeoutBlock {
+                    revision: 2,
+                    height,
+                }),
+                memo: None,
+                idempotency_key: None,
+                fee: None,
+                refund_to: None,
+            })
+        };
+        let sent_timeout = |res: Response| match &res.messages[0].msg {
+            CosmosMsg::Ibc(IbcMsg::SendPacket { timeout, .. }) => timeout.clone(),
+            msg => panic!("Unexpected return message: {:?}", msg),
+        };
+        let info = mock_info("foobar", &coins(1234, "ucosm"));
+
+        // the current height or below would time out right away
+        for past in [height, height - 1] {
+            let err =
+                execute(deps.as_mut(), env.clone(), info.clone(), transfer_at(past)).unwrap_err();
+            assert_eq!(
+                err,
+                ContractError::InvalidTimeout {
+                    height: past,
+                    current: height
+                }
+            );
+        }
+
+        // a reasonable height is kept, along with the default timestamp
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            info.clone(),
+            transfer_at(height + 100),
+        )
+        .unwrap();
+        let expected = IbcTimeout::with_both(
+            IbcTimeoutBlock {
+                revision: 2,
+                height: height + 100,
+            },
+            timestamp,
+        );
+        assert_eq!(sent_timeout(res), expected);
+
+        // a height too far ahead is lowered to the max window
+        let res = execute(deps.as_mut(), env, info, transfer_at(u64::MAX)).unwrap();
+        let expected = IbcTimeout::with_both(
+            IbcTimeoutBlock {
+                revision: 2,
+                height: height + DEFAULT_MAX_TIMEOUT_HEIGHT_OFFSET,
+            },
+            timestamp,
+        );
+        assert_eq!(sent_timeout(res), expected);
+    }
+
+    #[test]
+    fn channel_response_is_versioned() {
+        let deps = setup(&["channel-3"], &[]);
+
+        // no serde default here, so this fails if the field is missing
+        #[derive(serde::Deserialize)]
+        struct Versioned {
+            response_version: u16,
+        }
+
+        let msg = QueryMsg::Channel {
+            id: "channel-3".to_string(),
+        };
+        let raw = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let res: Versioned = from_binary(&raw).unwrap();
+        assert_eq!(res.response_version, CHANNEL_RESPONSE_VERSION);
+    }
+
+    #[test]
+    fn query_channel_includes_denom_traces() {
+        let send_channel = "channel-5";
+        let mut deps = setup(&[send_channel], &[]);
+
+        let single_hop = "transfer/channel-3/uatom";
+        let multi_hop = "transfer/channel-3/transfer/channel-7/ujuno";
+        for denom in [single_hop, multi_hop] {
+            let transfer = TransferMsg {
+                channel: send_channel.to_string(),
+                remote_address: "foreign-address".to_string(),
+                timeout: None,
+                timeout_height: None,
+                memo: None,
+                idempotency_key: None,
+                fee: None,
+                refund_to: None,
+            };
+            let info = mock_info("foobar", &coins(1000, denom));
+            execute(
+                deps.as_mut(),
+                mock_env(),
+                info,
+                ExecuteMsg::Transfer(transfer),
+            )
+            .unwrap();
+        }
+
+        let res = query_channel(deps.as_ref(), send_channel.to_string()).unwrap();
+        // raw denoms are still there
+        let denoms: Vec<_> = res.balances.iter().map(|b| b.denom()).collect();
+        assert_eq!(denoms, vec![multi_hop, single_hop]);
+        assert_eq!(
+            res.denom_traces,
+            vec![
+                DenomTrace {
+                    base_denom: "ujuno".to_string(),
+                    path: vec![
+                        ("transfer".to_string(), "channel-3".to_string()),
+                        ("transfer".to_string(), "channel-7".to_string()),
+                    ],
+                },
+                DenomTrace {
+                    base_denom: "uatom".to_string(),
+                    path: vec![("transfer".to_string(), "channel-3".to_string())],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn simulate_send() {
+        let send_channel = "channel-5";
+        let cw20_addr = "my-token";
+        let deps = setup(&[send_channel], &[(cw20_addr, 123456)]);
+
+        let simulate = |channel: &str, amount: Amount| -> SimulateSendResponse {
+            let msg = QueryMsg::SimulateSend {
+                channel_id: channel.to_string(),
+                amount,
+                receiver: "foreign-address".to_string(),
+            };
+            from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap()
+        };
+        let rejected = |err: ContractError| SimulateSendResponse {
+            accepted: false,
+            error: Some(err.to_string()),
+        };
+        let accepted = SimulateSendResponse {
+            accepted: true,
+            error: None,
+        };
+
+        // valid native and cw20 sends are accepted
+        assert_eq!(
+            simulate(send_channel, Amount::native(1234, "ucosm")),
+            accepted
+        );
+        assert_eq!(
+            simulate(send_channel, Amount::cw20(1234, cw20_addr)),
+            accepted
+        );
+
+        // every failure of the real path is predicted
+        assert_eq!(
+            simulate(send_channel, Amount::native(0, "ucosm")),
+            rejected(ContractError::NoFunds {})
+        );
+        assert_eq!(
+            simulate("channel-45", Amount::native(1234, "ucosm")),
+            rejected(ContractError::NoSuchChannel {
+                id: "channel-45".to_string()
+            })
+        );
+        assert_eq!(
+            simulate(send_channel, Amount::cw20(1234, "other-token")),
+            rejected(ContractError::NotOnAllowList)
+        );
+        assert_eq!(
+            simulate(send_channel, Amount::native(u64::MAX as u128 + 1, "ucosm")),
+            rejected(ContractError::AmountOverflow {})
+        );
+
+        // and nothing was stored
+        let state = query_channel(deps.as_ref(), send_channel.to_string()).unwrap();
+        assert_eq!(0, state.balances.len());
+    }
+
+    #[test]
+    fn simulate_timeout() {
+        let send_channel = "channel-5";
+        let cw20_addr = "my-token";
+        let cw20_denom = format!("cw20:{}", cw20_addr);
+        let mut deps = setup(&[send_channel], &[(cw20_addr, 123456)]);
+
+        // escrow some tokens on the channel
+        let transfer = TransferMsg {
+            channel: send_channel.to_string(),
+            remote_address: "foreign-address".to_string(),
+            timeout: None,
+            timeout_height: None,
+            memo: None,
+            idempotency_key: None,
+            fee: None,
+            refund_to: None,
+        };
+        let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+            sender: "local-sender".to_string(),
+            amount: Uint128::new(5000),
+            msg: to_binary(&transfer).unwrap(),
+        });
+        execute(deps.as_mut(), mock_env(), mock_info(cw20_addr, &[]), msg).unwrap();
+
+        let simulate = |deps: Deps, amount: u128| -> SimulateTimeoutResponse {
+            let msg = QueryMsg::SimulateTimeout {
+                channel_id: send_channel.to_string(),
+                sender: "local-sender".to_string(),
+                denom: cw20_denom.clone(),
+                amount: Uint128::new(amount),
+            };
+            from_binary(&query(deps, mock_env(), msg).unwrap()).unwrap()
+        };
+        let rejected = |err: ContractError| SimulateTimeoutResponse {
+            refundable: false,
+            refund: None,
+            gas_limit: None,
+            error: Some(err.to_string()),
+        };
+
+        // the escrowed amount is refunded with the registered gas limit
+        assert_eq!(
+            simulate(deps.as_ref(), 5000),
+            SimulateTimeoutResponse {
+                refundable: true,
+                refund: Some(Amount::cw20(5000, cw20_addr)),
+                gas_limit: Some(123456),
+                error: None,
+            }
+        );
+        // but not more than is escrowed
+        assert_eq!(
+            simulate(deps.as_ref(), 5001),
+            rejected(ContractError::InsufficientFunds {
+                available: Uint128::new(5000),
+                requested: Uint128::new(5001),
+            })
+        );
+
+        // once the token is taken off the allow list, the refund would fail
+        ALLOW_LIST.remove(deps.as_mut().storage, &Addr::unchecked(cw20_addr));
+        assert_eq!(
+            simulate(deps.as_ref(), 5000),
+            rejected(ContractError::NotOnAllowList)
+        );
+
+        // and nothing was changed
+        let state = query_channel(deps.as_ref(), send_channel.to_string()).unwrap();
+        assert_eq!(state.balances, vec![Amount::cw20(5000, cw20_addr)]);
+    }
+
+    #[test]
+    fn channel_full() {
+        let send_channel = "channel-9";
+        let mut deps = setup(&[send_channel, "channel-1"], &[]);
+
+        let transfer = TransferMsg {
+            channel: send_channel.to_string(),
+            remote_address: "foreign-address".to_string(),
+            timeout: None,
+            timeout_height: None,
+            memo: None,
+            idempotency_key: None,
+            fee: None,
+            refund_to: None,
+        };
+        let info = mock_info("local-sender", &coins(1234, "ucosm"));
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            info,
+            ExecuteMsg::Transfer(transfer),
+        )
+        .unwrap();
+        let msg = ExecuteMsg::DisableChannel {
+            channel_id: send_channel.to_string(),
+        };
+        execute(deps.as_mut(), mock_env(), mock_info("gov", &[]), msg).unwrap();
+
+        let full = |channel: &str| -> ChannelFullResponse {
+            let msg = QueryMsg::ChannelFull {
+                channel_id: channel.to_string(),
+            };
+            from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap()
+        };
+
+        // the same data as the separate queries
+        let res = full(send_channel);
+        assert_eq!(
+            res.channel,
+            query_channel(deps.as_ref(), send_channel.to_string()).unwrap()
+        );
+        assert_eq!(
+            res.features,
+            query_channel_features(deps.as_ref(), send_channel.to_string()).unwrap()
+        );
+        assert_eq!(res.channel.balances, vec![Amount::native(1234, "ucosm")]);
+        assert!(!res.enabled);
+
+        let res = full("channel-1");
+        assert_eq!(
+            res.channel,
+            query_channel(deps.as_ref(), "channel-1".to_string()).unwrap()
+        );
+        assert!(res.enabled);
+
+        // unknown channel
+        let msg = QueryMsg::ChannelFull {
+            channel_id: "channel-3".to_string(),
+        };
+        query(deps.as_ref(), mock_env(), msg).unwrap_err();
+    }
+
+    #[test]
+    fn channel_features() {
+        let v1_channel = "channel-1";
+        let v2_channel = "channel-2";
+        let mut deps = setup(&[v1_channel], &[]);
+        add_channel_with_version(deps.as_mut(), v2_channel, ICS20_2_VERSION);
+
+        let features = |channel: &str| -> ChannelFeaturesResponse {
+            let msg = QueryMsg::ChannelFeatures {
+                channel_id: channel.to_string(),
+            };
+            from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap()
+        };
+        assert_eq!(
+            features(v1_channel),
+            ChannelFeaturesResponse {
+                version: ICS20_VERSION.to_string(),
+                supports_memo: false,
+                supports_u128: false,
+                ack_encoding: AckEncoding::Json,
+            }
+        );
+        assert_eq!(
+            features(v2_channel),
+            ChannelFeaturesResponse {
+                version: ICS20_2_VERSION.to_string(),
+                supports_memo: true,
+                supports_u128: true,
+                ack_encoding: AckEncoding::Json,
+            }
+        );
+
+        // unknown channel
+        let msg = QueryMsg::ChannelFeatures {
+            channel_id: "channel-3".to_string(),
+        };
+        query(deps.as_ref(), mock_env(), msg).unwrap_err();
+
+        // amounts above u64 max can only be sent on ics20-2 channels
+        let big = Amount::native(u64::MAX as u128 + 1, "ucosm");
+        let simulate = |channel: &str| -> SimulateSendResponse {
+            let msg = QueryMsg::SimulateSend {
+                channel_id: channel.to_string(),
+                amount: big.clone(),
+                receiver: "foreign-address".to_string(),
+            };
+            from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap()
+        };
+        assert!(!simulate(v1_channel).accepted);
+        assert!(simulate(v2_channel).accepted);
+    }
+
+    #[test]
+    fn denoms_per_channel_are_capped() {
+        let send_channel = "channel-5";
+        let other_channel = "channel-7";
+        let mut init_msg = mock_init_msg(&[]);
+        init_msg.max_denoms_per_channel = Some(2);
+        let mut deps = setup_with_init(&[send_channel, other_channel], init_msg);
+
+        let transfer = |channel: &str| {
+            ExecuteMsg::Transfer(TransferMsg {
+                channel: channel.to_string(),
+                remote_address: "foreign-address".to_string(),
+                timeout: None,
+                timeout_height: None,
+                memo: None,
+                idempotency_key: None,
+                fee: None,
+                refund_to: None,
+            })
+        };
+        let info = |denom: &str| mock_info("foobar", &coins(1234, denom));
+
+        // two denoms fit
+        for denom in ["ucosm", "uatom"] {
+            execute(
+                deps.as_mut(),
+                mock_env(),
+                info(denom),
+                transfer(send_channel),
+            )
+            .unwrap();
+        }
+
+        // a third one is rejected
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            info("ujuno"),
+            transfer(send_channel),
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::TooManyDenoms { max: 2 });
+
+        // but the tracked denoms can still be sent
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            info("ucosm"),
+            transfer(send_channel),
+        )
+        .unwrap();
+
+        // and the cap is per channel
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            info("ujuno"),
+            transfer(other_channel),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn min_transfer_on_send() {
+        let send_channel = "channel-5";
+        let mut deps = setup(&[send_channel], &[]);
+
+        let set_min = |amount: Option<u128>| ExecuteMsg::SetMinTransfer {
+            denom: "ucosm".to_string(),
+            amount: amount.map(Uint128::new),
+        };
+        let transfer = |amount: u128| -> (MessageInfo, ExecuteMsg) {
+            let msg = ExecuteMsg::Transfer(TransferMsg {
+                channel: send_channel.to_string(),
+                remote_address: "foreign-address".to_string(),
+                timeout: None,
+                timeout_height: None,
+                memo: None,
+                idempotency_key: None,
+                fee: None,
+                refund_to: None,
+            });
+            (mock_info("foobar", &coins(amount, "ucosm")), msg)
+        };
+
+        // only gov can set it
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("foobar", &[]),
+            set_min(Some(100)),
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized);
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("gov", &[]),
+            set_min(Some(100)),
+        )
+        .unwrap();
+        let min: MinTransferResponse = from_binary(
+            &query(
+                deps.as_ref(),
+                mock_env(),
+                QueryMsg::MinTransfer {
+                    denom: "ucosm".to_string(),
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(min.amount, Some(Uint128::new(100)));
+
+        // below the floor is rejected, the floor itself is fine
+        let (info, msg) = transfer(99);
+        let err = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::BelowMinTransfer {
+                min: Uint128::new(100)
+            }
+        );
+        let (info, msg) = transfer(100);
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // other denoms are unrestricted
+        let msg = ExecuteMsg::Transfer(TransferMsg {
+            channel: send_channel.to_string(),
+            remote_address: "foreign-address".to_string(),
+            timeout: None,
+            timeout_height: None,
+            memo: None,
+            idempotency_key: None,
+            fee: None,
+            refund_to: None,
+        });
+        let info = mock_info("foobar", &coins(1, "uatom"));
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // and removing the floor lifts the restriction
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("gov", &[]),
+            set_min(None),
+        )
+        .unwrap();
+        let (info, msg) = transfer(1);
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+    }
+
+    // the cw20 "my-token" reports the given balance for anyone
+    fn set_cw20_balance(deps: &mut MockDeps, balance: u128) {
+        deps.querier.update_wasm(move |query| match query {
+            WasmQuery::Smart { contract_addr, .. } if contract_addr == "my-token" => {
+                let res = BalanceResponse {
+                    balance: Uint128::new(balance),
+                };
+                SystemResult::Ok(ContractResult::Ok(to_binary(&res).unwrap()))
+            }
+            _ => panic!("Unexpected query: {:?}", query),
+        });
+    }
+
+    #[test]
+    fn fee_on_transfer_escrows_net_amount() {
+        let send_channel = "channel-3";
+        let cw20_addr = "my-token";
+        let mut deps = setup(&[send_channel], &[]);
+        let msg = ExecuteMsg::Allow(AllowMsg {
+            contract: cw20_addr.to_string(),
+            gas_limit: None,
+            fee_on_transfer: Some(true),
+            verify_balance: None,
+        });
+        execute(deps.as_mut(), mock_env(), mock_info("gov", &[]), msg).unwrap();
+        let allowed = query_allowed(deps.as_ref(), cw20_addr.to_string()).unwrap();
+        assert!(allowed.fee_on_transfer);
+
+        let transfer = TransferMsg {
+            channel: send_channel.to_string(),
+            remote_address: "foreign-address".to_string(),
+            timeout: None,
+            timeout_height: None,
+            memo: None,
+            idempotency_key: None,
+            fee: None,
+            refund_to: None,
+        };
+        // a Receive cannot tell what arrived, so it is rejected
+        let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+            sender: "my-account".into(),
+            amount: Uint128::new(1000),
+            msg: to_binary(&transfer).unwrap(),
+        });
+        let err = execute(deps.as_mut(), mock_env(), mock_info(cw20_addr, &[]), msg).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::UseTransferCw20 {
+                token: cw20_addr.into()
+            }
+        );
+
+        // pulls amount in, then sends what the balance grew by
+        let send = |deps: &mut MockDeps, before: u128, after: u128, amount: u128| -> Uint128 {
+            set_cw20_balance(deps, before);
+            let msg = ExecuteMsg::TransferCw20 {
+                token: cw20_addr.into(),
+                amount: Uint128::new(amount),
+                transfer: transfer.clone(),
+            };
+            let info = mock_info("my-account", &[]);
+            let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+            assert_eq!(res.messages.len(), 1);
+            assert_eq!(res.messages[0].id, PULL_TOKEN_ID);
+            let expected = to_binary(&Cw20ExecuteMsg::TransferFrom {
+                owner: "my-account".into(),
+                recipient: MOCK_CONTRACT_ADDR.into(),
+                amount: Uint128::new(amount),
+            })
+            .unwrap();
+            match &res.messages[0].msg {
+                CosmosMsg::Wasm(WasmMsg::Execute { msg, .. }) => assert_eq!(msg, &expected),
+                msg => panic!("Unexpected message: {:?}", msg),
+            }
+
+            set_cw20_balance(deps, after);
+            let pulled = Reply {
+                id: PULL_TOKEN_ID,
+                result: ContractResult::Ok(SubMsgExecutionResponse {
+                    events: vec![],
+                    data: None,
+                }),
+            };
+            let res = reply(deps.as_mut(), mock_env(), pulled).unwrap();
+            match &res.messages[0].msg {
+                CosmosMsg::Ibc(IbcMsg::SendPacket { data, .. }) => {
+                    let packet: Ics20Packet = from_binary(data).unwrap();
+                    packet.amount
+                }
+                msg => panic!("Unexpected message: {:?}", msg),
+            }
+        };
+
+        // 1000 stray tokens were sent to the contract directly, they must not hide the 5% fee:
+        // only 950 of 1000 arrived
+        assert_eq!(send(&mut deps, 1000, 1950, 1000), Uint128::new(950));
+        let state = query_channel_denom(
+            deps.as_ref(),
+            send_channel.to_string(),
+            "cw20:my-token".into(),
+        )
+        .unwrap();
+        assert_eq!(state.balance, Amount::cw20(950, cw20_addr));
+
+        // the next send only counts what this pull added
+        assert_eq!(send(&mut deps, 1950, 1950 + 475, 500), Uint128::new(475));
+        let state = query_channel_denom(
+            deps.as_ref(),
+            send_channel.to_string(),
+            "cw20:my-token".into(),
+        )
+        .unwrap();
+        assert_eq!(state.balance, Amount::cw20(1425, cw20_addr));
+        assert_eq!(PENDING_PULL.may_load(&deps.storage).unwrap(), None);
+
+        // re-allowing without the flag keeps it
+        let msg = ExecuteMsg::Allow(AllowMsg {
+            contract: cw20_addr.to_string(),
+            gas_limit: None,
+            fee_on_transfer: None,
+            verify_balance: None,
+        });
+        execute(deps.as_mut(), mock_env(), mock_info("gov", &[]), msg).unwrap();
+        let allowed = query_allowed(deps.as_ref(), cw20_addr.to_string()).unwrap();
+        assert!(allowed.fee_on_transfer);
+    }
+
+    #[test]
+    fn reconcile() {
+        let mut deps = setup(&["channel-3", "channel-7"], &[]);
+        let cw20_addr = "my-token";
+
+        // escrow 1000 ucosm over two channels and 500 of a cw20
+        for (channel, amount) in [("channel-3", 600), ("channel-7", 400)] {
+            let msg = ExecuteMsg::Transfer(TransferMsg {
+                channel: channel.to_string(),
+                remote_address: "foreign-address".to_string(),
+                timeout: None,
+                timeout_height: None,
+                memo: None,
+                idempotency_key: None,
+                fee: None,
+                refund_to: None,
+            });
+            let info = mock_info("foobar", &coins(amount, "ucosm"));
+            execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        }
+        let msg = ExecuteMsg::Allow(AllowMsg {
+            contract: cw20_addr.to_string(),
+            gas_limit: None,
+            fee_on_transfer: None,
+            verify_balance: None,
+        });
+        execute(deps.as_mut(), mock_env(), mock_info("gov", &[]), msg).unwrap();
+        let transfer = TransferMsg {
+            channel: "channel-3".to_string(),
+            remote_address: "foreign-address".to_string(),
+            timeout: None,
+            timeout_height: None,
+            memo: None,
+            idempotency_key: None,
+            fee: None,
+            refund_to: None,
+        };
+        let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+            sender: "my-account".into(),
+            amount: Uint128::new(500),
+            msg: to_binary(&transfer).unwrap(),
+        });
+        execute(deps.as_mut(), mock_env(), mock_info(cw20_addr, &[]), msg).unwrap();
+
+        let reconcile = |deps: Deps| -> Vec<DenomReconciliation> {
+            let msg = QueryMsg::Reconcile {
+                denoms: vec!["ucosm".to_string(), "cw20:my-token".to_string()],
+            };
+            let res: ReconcileResponse =
+                from_binary(&query(deps, mock_env(), msg).unwrap()).unwrap();
+            res.denoms
+        };
+        let expected = |denom: &str, tracked: u128, actual: u128| DenomReconciliation {
+            denom: denom.to_string(),
+            tracked: Uint128::new(tracked),
+            actual: Uint128::new(actual),
+            surplus: Uint128::new(actual.saturating_sub(tracked)),
+            shortfall: Uint128::new(tracked.saturating_sub(actual)),
+        };
+        // balanced
+        deps.querier
+            .update_balance(MOCK_CONTRACT_ADDR, coins(1000, "ucosm"));
+        set_cw20_balance(&mut deps, 500);
+        assert_eq!(
+            reconcile(deps.as_ref()),
+            vec![
+                expected("ucosm", 1000, 1000),
+                expected("cw20:my-token", 500, 500)
+            ]
+        );
+
+        // someone sent ucosm directly, and the cw20 balance is short
+        deps.querier
+            .update_balance(MOCK_CONTRACT_ADDR, coins(1250, "ucosm"));
+        set_cw20_balance(&mut deps, 450);
+        let res = reconcile(deps.as_ref());
+        assert_eq!(
+            res,
+            vec![
+                expected("ucosm", 1000, 1250),
+                expected("cw20:my-token", 500, 450)
+            ]
+        );
+        assert_eq!(res[0].surplus, Uint128::new(250));
+        assert_eq!(res[1].shortfall, Uint128::new(50));
+    }
+
+    #[test]
+    fn check_invariants() {
+        let mut deps = setup(&["channel-3", "channel-7"], &[]);
+        let check = |deps: Deps| -> Vec<InvariantViolation> {
+            let msg = QueryMsg::CheckInvariants {};
+            let res: CheckInvariantsResponse =
+                from_binary(&query(deps, mock_env(), msg).unwrap()).unwrap();
+            res.violations
+        };
+
+        // a consistent state has no violations
+        let state = ChannelState {
+            outstanding: Uint128::new(600),
+            total_sent: Uint128::new(1000),
+        };
+        CHANNEL_STATE
+            .save(deps.as_mut().storage, ("channel-3", "ucosm"), &state)
+            .unwrap();
+        rebuild_global_outstanding(deps.as_mut().storage).unwrap();
+        assert_eq!(check(deps.as_ref()), vec![]);
+
+        // more outstanding than was ever sent, which the global total does not know about
+        let state = ChannelState {
+            outstanding: Uint128::new(500),
+            total_sent: Uint128::new(200),
+        };
+        CHANNEL_STATE
+            .save(deps.as_mut().storage, ("channel-7", "ucosm"), &state)
+            .unwrap();
+        // and a global total left behind for a denom no channel holds
+        GLOBAL_OUTSTANDING
+            .save(deps.as_mut().storage, "uatom", &Uint128::new(50))
+            .unwrap();
+        assert_eq!(
+            check(deps.as_ref()),
+            vec![
+                InvariantViolation::OutstandingExceedsSent {
+                    channel_id: "channel-7".to_string(),
+                    denom: "ucosm".to_string(),
+                    outstanding: Uint128::new(500),
+                    total_sent: Uint128::new(200),
+                    in_flight: Uint128::zero(),
+                },
+                InvariantViolation::GlobalOutstandingMismatch {
+                    denom: "uatom".to_string(),
+                    global: Uint128::new(50),
+                    channels: Uint128::zero(),
+                },
+                InvariantViolation::GlobalOutstandingMismatch {
+                    denom: "ucosm".to_string(),
+                    global: Uint128::new(600),
+                    channels: Uint128::new(1100),
+                },
+            ]
+        );
+
+        // rebuilding the totals fixes the mismatches, the channel balance is still reported
+        rebuild_global_outstanding(deps.as_mut().storage).unwrap();
+        assert_eq!(check(deps.as_ref()).len(), 1);
+    }
+
+    #[test]
+    fn local_denom() {
+        let deps = setup(&["channel-5"], &[]);
+
+        let local_denom = |voucher: &str, src_channel: &str| -> LocalDenomResponse {
+            let msg = QueryMsg::LocalDenom {
+                voucher: voucher.to_string(),
+                src_port: REMOTE_PORT.to_string(),
+                src_channel: src_channel.to_string(),
+            };
+            from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap()
+        };
+
+        // a token of ours that is returning
+        assert_eq!(
+            local_denom("transfer/channel-55/uatom", "channel-55"),
+            LocalDenomResponse {
+                denom: Some("uatom".to_string()),
+                error: None,
+            }
+        );
+        assert_eq!(
+            local_denom("transfer/channel-55/cw20:my-token", "channel-55"),
+            LocalDenomResponse {
+                denom: Some("cw20:my-token".to_string()),
+                error: None,
+            }
+        );
+
+        // a foreign token cannot be received
+        assert_eq!(
+            local_denom("uosmo", "channel-55"),
+            LocalDenomResponse {
+                denom: None,
+                error: Some(ContractError::NoForeignTokens {}.to_string()),
+            }
+        );
+        // nor one of ours coming from another channel
+        assert_eq!(
+            local_denom("transfer/channel-7/uatom", "channel-55"),
+            LocalDenomResponse {
+                denom: None,
+                error: Some(
+                    ContractError::FromOtherChannel {
+                        channel: "channel-7".to_string()
+                    }
+                    .to_string()
+                ),
+            }
+        );
+    }
 
-    use cosmwasm_std::testing::{mock_env, mock_info};
-    use cosmwasm_std::{coin, coins, CosmosMsg, IbcMsg, StdError, Uint128};
+    #[test]
+    fn idempotent_transfers() {
+        let send_channel = "channel-5";
+        let mut deps = setup(&[send_channel], &[]);
 
-    use cw_utils::PaymentError;
+        let transfer = |key: &str| {
+            ExecuteMsg::Transfer(TransferMsg {
+                channel: send_channel.to_string(),
+                remote_address: "foreign-address".to_string(),
+                timeout: None,
+                timeout_height: None,
+                memo: None,
+                idempotency_key: Some(key.to_string()),
+                fee: None,
+                refund_to: None,
+            })
+        };
+        let info = mock_info("foobar", &coins(1234, "ucosm"));
 
-    #[test]
-    fn setup_and_query() {
-        let deps = setup(&["channel-3", "channel-7"], &[]);
+        execute(deps.as_mut(), mock_env(), info.clone(), transfer("k1")).unwrap();
 
-        let raw_list = query(deps.as_ref(), mock_env(), QueryMsg::ListChannels {}).unwrap();
-        let list_res: ListChannelsResponse = from_binary(&raw_list).unwrap();
-        assert_eq!(2, list_res.channels.len());
-        assert_eq!(mock_channel_info("channel-3"), list_res.channels[0]);
-        assert_eq!(mock_channel_info("channel-7"), list_res.channels[1]);
+        // a retry within the window is rejected
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_seconds(IDEMPOTENCY_WINDOW - 1);
+        let err = execute(deps.as_mut(), env.clone(), info.clone(), transfer("k1")).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::DuplicateTransfer {
+                key: "k1".to_string()
+            }
+        );
+        // but another key, or the same key of another sender, is fine
+        execute(deps.as_mut(), env.clone(), info.clone(), transfer("k2")).unwrap();
+        let other = mock_info("other", &coins(1234, "ucosm"));
+        execute(deps.as_mut(), env, other, transfer("k1")).unwrap();
 
-        let raw_channel = query(
-            deps.as_ref(),
-            mock_env(),
-            QueryMsg::Channel {
-                id: "channel-3".to_string(),
-            },
-        )
-        .unwrap();
-        let chan_res: ChannelResponse = from_binary(&raw_channel).unwrap();
-        assert_eq!(chan_res.info, mock_channel_info("channel-3"));
-        assert_eq!(0, chan_res.total_sent.len());
-        assert_eq!(0, chan_res.balances.len());
+        // only one k1 transfer of foobar was escrowed
+        let state = query_channel(deps.as_ref(), send_channel.to_string()).unwrap();
+        assert_eq!(state.balances, vec![Amount::native(3 * 1234, "ucosm")]);
 
-        let err = query(
-            deps.as_ref(),
-            mock_env(),
-            QueryMsg::Channel {
-                id: "channel-10".to_string(),
-            },
-        )
-        .unwrap_err();
-        assert_eq!(err, StdError::not_found("cw20_ics20::state::ChannelInfo"));
+        // once expired, the key can be reused and the expired entries are pruned
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_seconds(2 * IDEMPOTENCY_WINDOW);
+        execute(deps.as_mut(), env, info, transfer("k1")).unwrap();
+        let foobar = Addr::unchecked("foobar");
+        let keys: Vec<_> = TRANSFER_KEYS
+            .prefix(&foobar)
+            .keys(&deps.storage, None, None, Order::Ascending)
+            .collect::<StdResult<_>>()
+            .unwrap();
+        assert_eq!(keys, vec!["k1".to_string()]);
     }
 
     #[test]
-    fn proper_checks_on_execute_native() {
+    fn force_refund() {
         let send_channel = "channel-5";
-        let mut deps = setup(&[send_channel, "channel-10"], &[]);
+        let mut deps = setup(&[send_channel], &[]);
 
-        let mut transfer = TransferMsg {
+        let transfer = TransferMsg {
             channel: send_channel.to_string(),
             remote_address: "foreign-address".to_string(),
             timeout: None,
+            timeout_height: None,
+            memo: None,
+            idempotency_key: None,
+            fee: None,
+            refund_to: None,
         };
-
-        // works with proper funds
         let msg = ExecuteMsg::Transfer(transfer.clone());
         let info = mock_info("foobar", &coins(1234567, "ucosm"));
-        let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
-        assert_eq!(res.messages[0].gas_limit, None);
-        assert_eq!(1, res.messages.len());
-        if let CosmosMsg::Ibc(IbcMsg::SendPacket {
-            channel_id,
-            data,
-            timeout,
-        }) = &res.messages[0].msg
-        {
-            let expected_timeout = mock_env().block.time.plus_seconds(DEFAULT_TIMEOUT);
-            assert_eq!(timeout, &expected_timeout.into());
-            assert_eq!(channel_id.as_str(), send_channel);
-            let msg: Ics20Packet = from_binary(data).unwrap();
-            assert_eq!(msg.amount, Uint128::new(1234567));
-            assert_eq!(msg.denom.as_str(), "ucosm");
-            assert_eq!(msg.sender.as_str(), "foobar");
-            assert_eq!(msg.receiver.as_str(), "foreign-address");
-        } else {
-            panic!("Unexpected return message: {:?}", res.messages[0]);
-        }
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
 
-        // reject with no funds
-        let msg = ExecuteMsg::Transfer(transfer.clone());
-        let info = mock_info("foobar", &[]);
-        let err = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
-        assert_eq!(err, ContractError::Payment(PaymentError::NoFunds {}));
+        let refund = ExecuteMsg::ForceRefund {
+            channel_id: send_channel.to_string(),
+            denom: "ucosm".to_string(),
+            recipient: Some("rescue".to_string()),
+        };
 
-        // reject with multiple tokens funds
-        let msg = ExecuteMsg::Transfer(transfer.clone());
-        let info = mock_info("foobar", &[coin(1234567, "ucosm"), coin(54321, "uatom")]);
-        let err = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
-        assert_eq!(err, ContractError::Payment(PaymentError::MultipleDenoms {}));
+        // only while paused
+        let info = mock_info("gov", &[]);
+        let err = execute(deps.as_mut(), mock_env(), info, refund.clone()).unwrap_err();
+        assert_eq!(err, ContractError::NotPaused);
 
-        // reject with bad channel id
-        transfer.channel = "channel-45".to_string();
+        // only gov can pause
+        let info = mock_info("foobar", &[]);
+        let err = execute(deps.as_mut(), mock_env(), info, ExecuteMsg::Pause {}).unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized);
+        let info = mock_info("gov", &[]);
+        execute(deps.as_mut(), mock_env(), info, ExecuteMsg::Pause {}).unwrap();
+
+        // no new transfers while paused
         let msg = ExecuteMsg::Transfer(transfer);
         let info = mock_info("foobar", &coins(1234567, "ucosm"));
         let err = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+        assert_eq!(err, ContractError::Paused);
+
+        // only gov can refund
+        let info = mock_info("foobar", &[]);
+        let err = execute(deps.as_mut(), mock_env(), info, refund.clone()).unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized);
+
+        let info = mock_info("gov", &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, refund.clone()).unwrap();
         assert_eq!(
-            err,
-            ContractError::NoSuchChannel {
-                id: "channel-45".to_string()
-            }
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: "rescue".to_string(),
+                amount: coins(1234567, "ucosm"),
+            })
         );
+        assert!(res.attributes.contains(&attr("action", "force_refund")));
+        assert!(res.attributes.contains(&attr("amount", "1234567")));
+        let state =
+            query_channel_denom(deps.as_ref(), send_channel.to_string(), "ucosm".into()).unwrap();
+        assert_eq!(state.balance, Amount::native(0, "ucosm"));
+
+        // nothing left to refund
+        let info = mock_info("gov", &[]);
+        let err = execute(deps.as_mut(), mock_env(), info, refund).unwrap_err();
+        assert_eq!(err, ContractError::NoFunds {});
+    }
+
+    // "multisig" is a contract that implements cw2, "plain-contract" one that does not.
+    // Any other address is an account
+    fn mock_recovery_contracts(deps: &mut MockDeps) {
+        deps.querier.update_wasm(|query| match query {
+            WasmQuery::ContractInfo { contract_addr }
+                if contract_addr == "multisig" || contract_addr == "plain-contract" =>
+            {
+                let info =
+                    br#"{"code_id":1,"creator":"gov","admin":null,"pinned":false,"ibc_port":null}"#;
+                SystemResult::Ok(ContractResult::Ok(Binary::from(&info[..])))
+            }
+            WasmQuery::Raw { contract_addr, .. } if contract_addr == "multisig" => {
+                let version = ContractVersion {
+                    contract: "crates.io:cw3-fixed-multisig".to_string(),
+                    version: "0.11.1".to_string(),
+                };
+                SystemResult::Ok(ContractResult::Ok(to_binary(&version).unwrap()))
+            }
+            WasmQuery::Raw { .. } => SystemResult::Ok(ContractResult::Ok(Binary::default())),
+            WasmQuery::ContractInfo { contract_addr } => {
+                SystemResult::Err(SystemError::NoSuchContract {
+                    addr: contract_addr.clone(),
+                })
+            }
+            _ => panic!("Unexpected query: {:?}", query),
+        });
     }
 
     #[test]
-    fn proper_checks_on_execute_cw20() {
-        let send_channel = "channel-15";
-        let cw20_addr = "my-token";
-        let mut deps = setup(&["channel-3", send_channel], &[(cw20_addr, 123456)]);
+    fn recovery_address() {
+        let send_channel = "channel-5";
+        let mut deps = setup(&[send_channel], &[]);
+        mock_recovery_contracts(&mut deps);
 
         let transfer = TransferMsg {
             channel: send_channel.to_string(),
             remote_address: "foreign-address".to_string(),
-            timeout: Some(7777),
+            timeout: None,
+            timeout_height: None,
+            memo: None,
+            idempotency_key: None,
+            fee: None,
+            refund_to: None,
         };
-        let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
-            sender: "my-account".into(),
-            amount: Uint128::new(888777666),
-            msg: to_binary(&transfer).unwrap(),
-        });
+        let info = mock_info("foobar", &coins(1234567, "ucosm"));
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            info,
+            ExecuteMsg::Transfer(transfer),
+        )
+        .unwrap();
+        let gov = mock_info("gov", &[]);
+        execute(deps.as_mut(), mock_env(), gov.clone(), ExecuteMsg::Pause {}).unwrap();
 
-        // works with proper funds
-        let info = mock_info(cw20_addr, &[]);
-        let res = execute(deps.as_mut(), mock_env(), info, msg.clone()).unwrap();
-        assert_eq!(1, res.messages.len());
-        assert_eq!(res.messages[0].gas_limit, None);
-        if let CosmosMsg::Ibc(IbcMsg::SendPacket {
-            channel_id,
-            data,
-            timeout,
-        }) = &res.messages[0].msg
-        {
-            let expected_timeout = mock_env().block.time.plus_seconds(7777);
-            assert_eq!(timeout, &expected_timeout.into());
-            assert_eq!(channel_id.as_str(), send_channel);
-            let msg: Ics20Packet = from_binary(data).unwrap();
-            assert_eq!(msg.amount, Uint128::new(888777666));
-            assert_eq!(msg.denom, format!("cw20:{}", cw20_addr));
-            assert_eq!(msg.sender.as_str(), "my-account");
-            assert_eq!(msg.receiver.as_str(), "foreign-address");
-        } else {
-            panic!("Unexpected return message: {:?}", res.messages[0]);
+        // without a recipient, a recovery address must be set
+        let refund = ExecuteMsg::ForceRefund {
+            channel_id: send_channel.to_string(),
+            denom: "ucosm".to_string(),
+            recipient: None,
+        };
+        let err = execute(deps.as_mut(), mock_env(), gov.clone(), refund.clone()).unwrap_err();
+        assert_eq!(err, ContractError::NoRecovery {});
+
+        let set_recovery = |address: &str| ExecuteMsg::SetRecovery {
+            address: Some(address.to_string()),
+        };
+        // only gov can set it
+        let info = mock_info("foobar", &[]);
+        let err = execute(deps.as_mut(), mock_env(), info, set_recovery("rescue")).unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized);
+
+        // a contract that does not implement cw2 is rejected, accounts and cw2 contracts are fine
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            gov.clone(),
+            set_recovery("plain-contract"),
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::InvalidRecovery {
+                address: "plain-contract".to_string()
+            }
+        );
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            gov.clone(),
+            set_recovery("rescue"),
+        )
+        .unwrap();
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            gov.clone(),
+            set_recovery("multisig"),
+        )
+        .unwrap();
+        let config: ConfigResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::Config {}).unwrap()).unwrap();
+        assert_eq!(config.recovery, Some("multisig".to_string()));
+
+        // the refund goes to the multisig
+        let res = execute(deps.as_mut(), mock_env(), gov.clone(), refund).unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: "multisig".to_string(),
+                amount: coins(1234567, "ucosm"),
+            })
+        );
+
+        // and can be removed again
+        let msg = ExecuteMsg::SetRecovery { address: None };
+        execute(deps.as_mut(), mock_env(), gov, msg).unwrap();
+        let config: ConfigResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::Config {}).unwrap()).unwrap();
+        assert_eq!(config.recovery, None);
+    }
+
+    #[test]
+    fn migrate_channel_state() {
+        let (old, new, other) = ("channel-3", "channel-7", "channel-8");
+        let mut deps = setup(&[old, new, other], &[]);
+
+        let send = |deps: DepsMut, channel: &str, amount: u128, denom: &str| {
+            let transfer = TransferMsg {
+                channel: channel.to_string(),
+                remote_address: "foreign-address".to_string(),
+                timeout: None,
+                timeout_height: None,
+                memo: None,
+                idempotency_key: None,
+                fee: None,
+                refund_to: None,
+            };
+            let info = mock_info("foobar", &coins(amount, denom));
+            execute(deps, mock_env(), info, ExecuteMsg::Transfer(transfer)).unwrap();
+        };
+        // ucosm is tracked on both channels, uatom and ujuno only on one each
+        send(deps.as_mut(), old, 1000, "ucosm");
+        send(deps.as_mut(), old, 500, "uatom");
+        send(deps.as_mut(), new, 300, "ucosm");
+        send(deps.as_mut(), new, 70, "ujuno");
+        send(deps.as_mut(), other, 9, "ucosm");
+        // the packets sent on the old channel were all acknowledged
+        IN_FLIGHT.remove(deps.as_mut().storage, old);
+
+        let migrate = ExecuteMsg::MigrateChannelState {
+            from: old.to_string(),
+            to: new.to_string(),
+        };
+        let gov = mock_info("gov", &[]);
+
+        // only gov, only while paused, only between known channels
+        let info = mock_info("foobar", &[]);
+        let err = execute(deps.as_mut(), mock_env(), info, migrate.clone()).unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized);
+        let err = execute(deps.as_mut(), mock_env(), gov.clone(), migrate.clone()).unwrap_err();
+        assert_eq!(err, ContractError::NotPaused);
+        execute(deps.as_mut(), mock_env(), gov.clone(), ExecuteMsg::Pause {}).unwrap();
+        let msg = ExecuteMsg::MigrateChannelState {
+            from: old.to_string(),
+            to: "channel-9".to_string(),
+        };
+        let err = execute(deps.as_mut(), mock_env(), gov.clone(), msg).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::NoSuchChannel {
+                id: "channel-9".to_string()
+            }
+        );
+
+        // migrating a channel onto itself changes nothing
+        let msg = ExecuteMsg::MigrateChannelState {
+            from: new.to_string(),
+            to: new.to_string(),
+        };
+        execute(deps.as_mut(), mock_env(), gov.clone(), msg).unwrap();
+        let state = query_channel(deps.as_ref(), new.to_string()).unwrap();
+        assert_eq!(
+            state.balances,
+            vec![Amount::native(300, "ucosm"), Amount::native(70, "ujuno")]
+        );
+
+        let res = execute(deps.as_mut(), mock_env(), gov, migrate).unwrap();
+        assert_eq!(0, res.messages.len());
+        assert!(res.attributes.contains(&attr("denoms", "2")));
+
+        // overlapping denoms are added up, the others are moved over
+        let state = query_channel(deps.as_ref(), new.to_string()).unwrap();
+        assert_eq!(
+            state.balances,
+            vec![
+                Amount::native(500, "uatom"),
+                Amount::native(1300, "ucosm"),
+                Amount::native(70, "ujuno"),
+            ]
+        );
+        // the old channel is empty, the others are untouched
+        let state = query_channel(deps.as_ref(), old.to_string()).unwrap();
+        assert!(state.balances.is_empty());
+        assert!(state.total_sent.is_empty());
+        let state = query_channel(deps.as_ref(), other.to_string()).unwrap();
+        assert_eq!(state.balances, vec![Amount::native(9, "ucosm")]);
+        assert_eq!(
+            total_outstanding(deps.as_ref().storage, "ucosm").unwrap(),
+            Uint128::new(1309)
+        );
+    }
+
+    #[test]
+    fn sweep_untracked() {
+        let mut deps = setup(&["channel-3", "channel-7"], &[]);
+
+        // escrow 1000 ucosm over two channels
+        for (channel, amount) in [("channel-3", 600), ("channel-7", 400)] {
+            let msg = ExecuteMsg::Transfer(TransferMsg {
+                channel: channel.to_string(),
+                remote_address: "foreign-address".to_string(),
+                timeout: None,
+                timeout_height: None,
+                memo: None,
+                idempotency_key: None,
+                fee: None,
+                refund_to: None,
+            });
+            let info = mock_info("foobar", &coins(amount, "ucosm"));
+            execute(deps.as_mut(), mock_env(), info, msg).unwrap();
         }
+        let sweep = ExecuteMsg::SweepUntracked {
+            denom: "ucosm".to_string(),
+            recipient: Some("rescue".to_string()),
+        };
 
-        // reject with tokens funds
-        let info = mock_info("foobar", &coins(1234567, "ucosm"));
-        let err = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
-        assert_eq!(err, ContractError::Payment(PaymentError::NonPayable {}));
+        // balance equals the escrow, nothing to sweep
+        deps.querier
+            .update_balance(MOCK_CONTRACT_ADDR, coins(1000, "ucosm"));
+        let info = mock_info("gov", &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, sweep.clone()).unwrap();
+        assert_eq!(res.messages.len(), 0);
+        assert!(res.attributes.contains(&attr("amount", "0")));
+
+        // someone bank-sent 250 ucosm
+        deps.querier
+            .update_balance(MOCK_CONTRACT_ADDR, coins(1250, "ucosm"));
+
+        // only gov can sweep
+        let info = mock_info("foobar", &[]);
+        let err = execute(deps.as_mut(), mock_env(), info, sweep.clone()).unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized);
+
+        let info = mock_info("gov", &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, sweep).unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: "rescue".to_string(),
+                amount: coins(250, "ucosm"),
+            })
+        );
+
+        // escrow is untouched
+        let state =
+            query_channel_denom(deps.as_ref(), "channel-3".to_string(), "ucosm".into()).unwrap();
+        assert_eq!(state.balance, Amount::native(600, "ucosm"));
     }
 
     #[test]
-    fn execute_cw20_fails_if_not_whitelisted() {
-        let send_channel = "channel-15";
-        let mut deps = setup(&["channel-3", send_channel], &[]);
+    fn timelocked_gov_cannot_drain_escrow() {
+        let mut init_msg = mock_init_msg(&[]);
+        init_msg.admin_timelock = Some(3600);
+        let mut deps = setup_with_init(&["channel-1", "channel-2"], init_msg);
+        let gov = mock_info("gov", &[]);
 
-        let cw20_addr = "my-token";
         let transfer = TransferMsg {
-            channel: send_channel.to_string(),
-            remote_address: "foreign-address".to_string(),
-            timeout: Some(7777),
+            channel: "channel-1".to_string(),
+            remote_address: "remote-rcpt".to_string(),
+            timeout: None,
+            timeout_height: None,
+            memo: None,
+            idempotency_key: None,
+            fee: None,
+            refund_to: None,
         };
-        let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
-            sender: "my-account".into(),
-            amount: Uint128::new(888777666),
-            msg: to_binary(&transfer).unwrap(),
-        });
+        let info = mock_info("local-sender", &coins(1000, "ucosm"));
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            info,
+            ExecuteMsg::Transfer(transfer),
+        )
+        .unwrap();
+        execute(deps.as_mut(), mock_env(), gov.clone(), ExecuteMsg::Pause {}).unwrap();
 
-        // works with proper funds
-        let info = mock_info(cw20_addr, &[]);
-        let err = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
-        assert_eq!(err, ContractError::NotOnAllowList);
+        // paying an address of its choosing, or moving the escrow elsewhere, must be proposed
+        let attacker = || Some("attacker".to_string());
+        let drains = vec![
+            ExecuteMsg::ForceRefund {
+                channel_id: "channel-1".to_string(),
+                denom: "ucosm".to_string(),
+                recipient: attacker(),
+            },
+            ExecuteMsg::SweepUntracked {
+                denom: "ucosm".to_string(),
+                recipient: attacker(),
+            },
+            ExecuteMsg::SweepUnclaimed {
+                id: 1,
+                recipient: attacker(),
+            },
+            ExecuteMsg::MigrateChannelState {
+                from: "channel-1".to_string(),
+                to: "channel-2".to_string(),
+            },
+        ];
+        for drain in drains {
+            let err = execute(deps.as_mut(), mock_env(), gov.clone(), drain).unwrap_err();
+            assert_eq!(err, ContractError::TimelockRequired {});
+        }
+
+        // paying the recovery address stays immediate, and there is none yet
+        let refund = ExecuteMsg::ForceRefund {
+            channel_id: "channel-1".to_string(),
+            denom: "ucosm".to_string(),
+            recipient: None,
+        };
+        let err = execute(deps.as_mut(), mock_env(), gov, refund).unwrap_err();
+        assert_eq!(err, ContractError::NoRecovery {});
+
+        let state = query_channel(deps.as_ref(), "channel-1".to_string()).unwrap();
+        assert_eq!(state.balances, vec![Amount::native(1000, "ucosm")]);
+    }
+
+    #[test]
+    fn critical_changes_need_timelock() {
+        let mut init_msg = mock_init_msg(&[]);
+        init_msg.admin_timelock = Some(3600);
+        let mut deps = setup_with_init(&[], init_msg);
+        let gov = mock_info("gov", &[]);
+        let deny = ExecuteMsg::Deny {
+            contract: "bad-token".to_string(),
+        };
+
+        // gov cannot deny directly, others are still unauthorized
+        let err = execute(deps.as_mut(), mock_env(), gov.clone(), deny.clone()).unwrap_err();
+        assert_eq!(err, ContractError::TimelockRequired {});
+        let info = mock_info("foobar", &[]);
+        let err = execute(deps.as_mut(), mock_env(), info, deny.clone()).unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized);
+
+        // pausing is not critical, it cannot be proposed and runs immediately
+        let propose = |change: ExecuteMsg| ExecuteMsg::ProposeChange {
+            change: Box::new(change),
+        };
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            gov.clone(),
+            propose(ExecuteMsg::Pause {}),
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::NotCritical {});
+        execute(deps.as_mut(), mock_env(), gov.clone(), ExecuteMsg::Pause {}).unwrap();
+
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            gov.clone(),
+            propose(deny.clone()),
+        )
+        .unwrap();
+        assert!(res.attributes.contains(&attr("change_id", "1")));
+        let effective_at = Expiration::AtTime(mock_env().block.time.plus_seconds(3600));
+        let pending: PendingChangesResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::PendingChanges {}).unwrap())
+                .unwrap();
+        assert_eq!(
+            pending.changes,
+            vec![PendingChangeInfo {
+                id: 1,
+                change: deny,
+                effective_at,
+            }]
+        );
+
+        // too early
+        let apply = ExecuteMsg::ApplyPendingChange { id: 1 };
+        let err = execute(deps.as_mut(), mock_env(), gov.clone(), apply.clone()).unwrap_err();
+        assert_eq!(err, ContractError::TimelockNotExpired { effective_at });
+        assert!(!DENY_LIST.has(&deps.storage, &Addr::unchecked("bad-token")));
+
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_seconds(3600);
+        let res = execute(deps.as_mut(), env.clone(), gov.clone(), apply.clone()).unwrap();
+        assert!(res.attributes.contains(&attr("change_id", "1")));
+        assert!(DENY_LIST.has(&deps.storage, &Addr::unchecked("bad-token")));
+
+        // it can only be applied once
+        let err = execute(deps.as_mut(), env, gov, apply).unwrap_err();
+        assert_eq!(err, ContractError::NoPendingChange { id: 1 });
+        let pending: PendingChangesResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::PendingChanges {}).unwrap())
+                .unwrap();
+        assert!(pending.changes.is_empty());
     }
 }
@@ -2,8 +2,8 @@ use std::num::TryFromIntError;
 use std::string::FromUtf8Error;
 use thiserror::Error;
 
-use cosmwasm_std::StdError;
-use cw_utils::PaymentError;
+use cosmwasm_std::{StdError, Uint128};
+use cw_utils::{Expiration, PaymentError};
 
 /// Never is a placeholder to ensure we don't return any errors
 #[derive(Error, Debug)]
@@ -23,17 +23,31 @@ pub enum ContractError {
     #[error("Didn't send any funds")]
     NoFunds {},
 
-    #[error("Amount larger than 2**64, not supported by ics20 packets")]
+    #[error("Amount larger than 2**64, not supported by ics20-1 packets")]
     AmountOverflow {},
 
-    #[error("Only supports channel with ibc version ics20-1, got {version}")]
+    #[error("Only supports channel with ibc version ics20-1 or ics20-2, got {version}")]
     InvalidIbcVersion { version: String },
 
+    #[error("Unsupported packet version {version}, only ics20-1 and ics20-2 packets are accepted")]
+    UnsupportedPacketVersion { version: String },
+
+    #[error(
+        "Payload is addressed from port {source_port} to {destination_port}, not over this channel"
+    )]
+    PayloadPortMismatch {
+        source_port: String,
+        destination_port: String,
+    },
+
     #[error("Only supports unordered channel")]
     OnlyOrderedChannel {},
 
-    #[error("Insufficient funds to redeem voucher on channel")]
-    InsufficientFunds {},
+    #[error("Insufficient funds to redeem voucher on channel: {available} available, {requested} requested")]
+    InsufficientFunds {
+        available: Uint128,
+        requested: Uint128,
+    },
 
     #[error("Only accepts tokens that originate on this chain, not native tokens of remote chain")]
     NoForeignTokens {},
@@ -58,6 +72,185 @@ pub enum ContractError {
 
     #[error("You can only send cw20 tokens that have been explicitly allowed by governance")]
     NotOnAllowList,
+
+    #[error("Memo is longer than the maximum of {max} bytes")]
+    MemoTooLong { max: u32 },
+
+    #[error("Channel {id} already exists with a different counterparty")]
+    ChannelAlreadyExists { id: String },
+
+    #[error("The contract is paused")]
+    Paused,
+
+    #[error("This can only be done while the contract is paused")]
+    NotPaused,
+
+    #[error("Amount is below the minimum transfer of {min}")]
+    BelowMinTransfer { min: Uint128 },
+
+    #[error("Channel {id} has no pending refunds after close")]
+    NoPendingClose { id: String },
+
+    #[error("A transfer with idempotency key {key} was already sent recently")]
+    DuplicateTransfer { key: String },
+
+    #[error("{0}")]
+    PayoutFailed(String),
+
+    #[error("Payout of {expected} tokens only moved {moved} out of our balance")]
+    PayoutNotMoved { expected: Uint128, moved: Uint128 },
+
+    #[error("Channel {id} is disabled")]
+    ChannelDisabled { id: String },
+
+    #[error("Timeout height {height} is not after the current height {current}")]
+    InvalidTimeout { height: u64, current: u64 },
+
+    #[error("Invalid gov contract address: '{address}'")]
+    InvalidAdmin { address: String },
+
+    #[error("Invalid allow list contract address: '{address}'")]
+    InvalidAllowListEntry { address: String },
+
+    #[error("Channel already holds the maximum of {max} denoms")]
+    TooManyDenoms { max: u32 },
+
+    #[error("ics20 channels cannot be closed from this side")]
+    CannotCloseChannel {},
+
+    #[error("The funds sent do not cover the transfer and its relayer fee")]
+    InsufficientFee {},
+
+    #[error("Outstanding {denom} would exceed the global cap of {cap}")]
+    GlobalCapExceeded { denom: String, cap: Uint128 },
+
+    #[error("Cannot receive tokens to the contract itself")]
+    SelfReceive {},
+
+    #[error("Token {contract} is on the deny list")]
+    TokenDenied { contract: String },
+
+    #[error("No recipient given and no recovery address set")]
+    NoRecovery {},
+
+    #[error("Recovery contract {address} does not implement cw2")]
+    InvalidRecovery { address: String },
+
+    #[error("Receiver {receiver} is not on the receiver allow list")]
+    ReceiverNotAllowed { receiver: String },
+
+    #[error("{alias} is already the alias of {denom}")]
+    AliasTaken { alias: String, denom: String },
+
+    #[error("Critical changes must be proposed with ProposeChange")]
+    TimelockRequired {},
+
+    #[error("Only critical changes can be proposed")]
+    NotCritical {},
+
+    #[error("Change cannot be applied before {effective_at}")]
+    TimelockNotExpired { effective_at: Expiration },
+
+    #[error("No pending change with id {id}")]
+    NoPendingChange { id: u64 },
+
+    #[error("{token} is already minted for {denom}")]
+    MintedTokenTaken { token: String, denom: String },
+
+    #[error("Packet is {size} bytes, more than the maximum of {max}")]
+    PacketTooLarge { size: usize, max: u32 },
+
+    #[error("No pending delivery with id {id}")]
+    NoPendingDelivery { id: u64 },
+
+    #[error("Delivery can be claimed until {claimable_until}")]
+    ClaimGraceNotExpired { claimable_until: Expiration },
+
+    #[error("Denom {denom} was never sent over channel {channel}")]
+    DenomNotRegistered { channel: String, denom: String },
+
+    #[error("Split memo shares must add up to 10000 bps, not {bps}")]
+    InvalidSplit { bps: u64 },
+
+    #[error("Channel {channel} already has the maximum of {max} packets in flight")]
+    TooManyInFlight { channel: String, max: u32 },
+
+    #[error("Channel {channel} still has {count} packets in flight")]
+    PacketsInFlight { channel: String, count: u32 },
+
+    #[error("Token {token} takes a fee on transfer and must be sent with TransferCw20")]
+    UseTransferCw20 { token: String },
+
+    #[error("Channel {id} must connect to {expected}, not {actual}")]
+    UnexpectedCounterparty {
+        id: String,
+        expected: String,
+        actual: String,
+    },
+}
+
+impl ContractError {
+    /// A stable numeric code for off-chain tooling, sent in failure acknowledgements on
+    /// ics20-2 channels. Codes are never reused, new variants get the next free one.
+    pub fn code(&self) -> u32 {
+        match self {
+            ContractError::Std(_) => 1,
+            ContractError::Payment(_) => 2,
+            ContractError::NoSuchChannel { .. } => 3,
+            ContractError::NoFunds {} => 4,
+            ContractError::AmountOverflow {} => 5,
+            ContractError::InvalidIbcVersion { .. } => 6,
+            ContractError::OnlyOrderedChannel {} => 7,
+            ContractError::InsufficientFunds { .. } => 8,
+            ContractError::NoForeignTokens {} => 9,
+            ContractError::FromOtherPort { .. } => 10,
+            ContractError::FromOtherChannel { .. } => 11,
+            ContractError::CannotMigrate { .. } => 12,
+            ContractError::UnknownReplyId { .. } => 13,
+            ContractError::CannotLowerGas => 14,
+            ContractError::Unauthorized => 15,
+            ContractError::NotOnAllowList => 16,
+            ContractError::MemoTooLong { .. } => 17,
+            ContractError::ChannelAlreadyExists { .. } => 18,
+            ContractError::Paused => 19,
+            ContractError::NotPaused => 20,
+            ContractError::BelowMinTransfer { .. } => 21,
+            ContractError::NoPendingClose { .. } => 22,
+            ContractError::DuplicateTransfer { .. } => 23,
+            ContractError::PayoutFailed(_) => 24,
+            ContractError::ChannelDisabled { .. } => 25,
+            ContractError::InvalidTimeout { .. } => 26,
+            ContractError::InvalidAdmin { .. } => 27,
+            ContractError::InvalidAllowListEntry { .. } => 28,
+            ContractError::TooManyDenoms { .. } => 29,
+            ContractError::CannotCloseChannel {} => 30,
+            ContractError::InsufficientFee {} => 31,
+            ContractError::GlobalCapExceeded { .. } => 32,
+            ContractError::SelfReceive {} => 33,
+            ContractError::UnexpectedCounterparty { .. } => 34,
+            ContractError::TokenDenied { .. } => 35,
+            ContractError::NoRecovery {} => 36,
+            ContractError::InvalidRecovery { .. } => 37,
+            ContractError::ReceiverNotAllowed { .. } => 38,
+            ContractError::AliasTaken { .. } => 39,
+            ContractError::TimelockRequired {} => 40,
+            ContractError::NotCritical {} => 41,
+            ContractError::TimelockNotExpired { .. } => 42,
+            ContractError::NoPendingChange { .. } => 43,
+            ContractError::UnsupportedPacketVersion { .. } => 44,
+            ContractError::MintedTokenTaken { .. } => 45,
+            ContractError::PacketTooLarge { .. } => 46,
+            ContractError::PayoutNotMoved { .. } => 47,
+            ContractError::PayloadPortMismatch { .. } => 48,
+            ContractError::NoPendingDelivery { .. } => 49,
+            ContractError::ClaimGraceNotExpired { .. } => 50,
+            ContractError::DenomNotRegistered { .. } => 51,
+            ContractError::InvalidSplit { .. } => 52,
+            ContractError::TooManyInFlight { .. } => 53,
+            ContractError::PacketsInFlight { .. } => 54,
+            ContractError::UseTransferCw20 { .. } => 55,
+        }
+    }
 }
 
 impl From<FromUtf8Error> for ContractError {
@@ -71,3 +264,170 @@ impl From<TryFromIntError> for ContractError {
         ContractError::AmountOverflow {}
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn documented_codes() {
+        let id = || "channel-1".to_string();
+        let cases = vec![
+            (ContractError::Std(StdError::generic_err("boom")), 1),
+            (ContractError::Payment(PaymentError::NoFunds {}), 2),
+            (ContractError::NoSuchChannel { id: id() }, 3),
+            (ContractError::NoFunds {}, 4),
+            (ContractError::AmountOverflow {}, 5),
+            (ContractError::InvalidIbcVersion { version: id() }, 6),
+            (ContractError::OnlyOrderedChannel {}, 7),
+            (
+                ContractError::InsufficientFunds {
+                    available: Uint128::new(1),
+                    requested: Uint128::new(2),
+                },
+                8,
+            ),
+            (ContractError::NoForeignTokens {}, 9),
+            (ContractError::FromOtherPort { port: id() }, 10),
+            (ContractError::FromOtherChannel { channel: id() }, 11),
+            (
+                ContractError::CannotMigrate {
+                    previous_contract: id(),
+                },
+                12,
+            ),
+            (ContractError::UnknownReplyId { id: 7 }, 13),
+            (ContractError::CannotLowerGas, 14),
+            (ContractError::Unauthorized, 15),
+            (ContractError::NotOnAllowList, 16),
+            (ContractError::MemoTooLong { max: 10 }, 17),
+            (ContractError::ChannelAlreadyExists { id: id() }, 18),
+            (ContractError::Paused, 19),
+            (ContractError::NotPaused, 20),
+            (
+                ContractError::BelowMinTransfer {
+                    min: Uint128::new(5),
+                },
+                21,
+            ),
+            (ContractError::NoPendingClose { id: id() }, 22),
+            (ContractError::DuplicateTransfer { key: id() }, 23),
+            (ContractError::PayoutFailed("failed".to_string()), 24),
+            (ContractError::ChannelDisabled { id: id() }, 25),
+            (
+                ContractError::InvalidTimeout {
+                    height: 1,
+                    current: 2,
+                },
+                26,
+            ),
+            (ContractError::InvalidAdmin { address: id() }, 27),
+            (ContractError::InvalidAllowListEntry { address: id() }, 28),
+            (ContractError::TooManyDenoms { max: 3 }, 29),
+            (ContractError::CannotCloseChannel {}, 30),
+            (ContractError::InsufficientFee {}, 31),
+            (
+                ContractError::GlobalCapExceeded {
+                    denom: "ucosm".into(),
+                    cap: Uint128::new(1000),
+                },
+                32,
+            ),
+            (ContractError::SelfReceive {}, 33),
+            (
+                ContractError::UnexpectedCounterparty {
+                    id: id(),
+                    expected: "transfer/channel-1".into(),
+                    actual: "transfer/channel-2".into(),
+                },
+                34,
+            ),
+            (ContractError::TokenDenied { contract: id() }, 35),
+            (ContractError::NoRecovery {}, 36),
+            (ContractError::InvalidRecovery { address: id() }, 37),
+            (ContractError::ReceiverNotAllowed { receiver: id() }, 38),
+            (
+                ContractError::AliasTaken {
+                    alias: id(),
+                    denom: "ucosm".into(),
+                },
+                39,
+            ),
+            (ContractError::TimelockRequired {}, 40),
+            (ContractError::NotCritical {}, 41),
+            (
+                ContractError::TimelockNotExpired {
+                    effective_at: Expiration::AtHeight(5),
+                },
+                42,
+            ),
+            (ContractError::NoPendingChange { id: 7 }, 43),
+            (
+                ContractError::UnsupportedPacketVersion {
+                    version: "ics20-3".to_string(),
+                },
+                44,
+            ),
+            (
+                ContractError::MintedTokenTaken {
+                    token: id(),
+                    denom: "uatom".into(),
+                },
+                45,
+            ),
+            (ContractError::PacketTooLarge { size: 11, max: 10 }, 46),
+            (
+                ContractError::PayoutNotMoved {
+                    expected: Uint128::new(10),
+                    moved: Uint128::zero(),
+                },
+                47,
+            ),
+            (
+                ContractError::PayloadPortMismatch {
+                    source_port: "transfer".to_string(),
+                    destination_port: "transfer".to_string(),
+                },
+                48,
+            ),
+            (ContractError::NoPendingDelivery { id: 3 }, 49),
+            (
+                ContractError::ClaimGraceNotExpired {
+                    claimable_until: Expiration::AtHeight(100),
+                },
+                50,
+            ),
+            (
+                ContractError::DenomNotRegistered {
+                    channel: id(),
+                    denom: "uatom".into(),
+                },
+                51,
+            ),
+            (ContractError::InvalidSplit { bps: 9999 }, 52),
+            (
+                ContractError::TooManyInFlight {
+                    channel: id(),
+                    max: 10,
+                },
+                53,
+            ),
+            (
+                ContractError::PacketsInFlight {
+                    channel: id(),
+                    count: 3,
+                },
+                54,
+            ),
+            (
+                ContractError::UseTransferCw20 {
+                    token: "cw20-token".to_string(),
+                },
+                55,
+            ),
+        ];
+        for (err, code) in cases {
+            assert_eq!(err.code(), code, "{:?}", err);
+        }
+    }
+}
@@ -1,19 +1,44 @@
 use schemars::JsonSchema;
+use serde::de::IgnoredAny;
 use serde::{Deserialize, Serialize};
 
 use cosmwasm_std::{
-    attr, entry_point, from_binary, to_binary, BankMsg, Binary, ContractResult, Deps, DepsMut, Env,
-    IbcBasicResponse, IbcChannel, IbcChannelCloseMsg, IbcChannelConnectMsg, IbcChannelOpenMsg,
-    IbcEndpoint, IbcOrder, IbcPacket, IbcPacketAckMsg, IbcPacketReceiveMsg, IbcPacketTimeoutMsg,
-    IbcReceiveResponse, Reply, Response, StdResult, SubMsg, Uint128, WasmMsg,
+    attr, entry_point, from_binary, to_binary, to_vec, Addr, Attribute, BankMsg, Binary,
+    ContractResult, CosmosMsg, Deps, DepsMut, Env, IbcBasicResponse, IbcChannel,
+    IbcChannelCloseMsg, IbcChannelConnectMsg, IbcChannelOpenMsg, IbcEndpoint, IbcOrder, IbcPacket,
+    IbcPacketAckMsg, IbcPacketReceiveMsg, IbcPacketTimeoutMsg, IbcReceiveResponse, Order, Reply,
+    Response, StdError, StdResult, Storage, SubMsg, Uint128, WasmMsg,
 };
 
 use crate::amount::Amount;
+use crate::contract::reply_pull_token;
 use crate::error::{ContractError, Never};
-use crate::state::{ChannelInfo, ALLOW_LIST, CHANNEL_INFO, CHANNEL_STATE};
-use cw20::Cw20ExecuteMsg;
+use crate::msg::{AckEncoding, DenomTrace, ExecuteMsg};
+use crate::state::{
+    ack_encoding, check_channel_enabled, check_min_transfer, decrease_in_flight,
+    decrease_in_flight_amount, hold_delivery, increase_channel_balance, is_paused, local_amount,
+    record_receive_failure, record_successful_send, record_volume, reduce_channel_balance,
+    update_fee_totals, update_global_outstanding, BalanceCheck, ChannelInfo, Config,
+    ReceiveFailure, ReplyArgs, ADMIN_CLOSED, ALLOW_LIST, BOUND_PORT, CHANNELS_BY_CONNECTION,
+    CHANNEL_INFO, CHANNEL_STATE, CLOSE_CURSOR, CONFIG, DENY_LIST, EXPECTED_COUNTERPARTY,
+    FEE_ESCROW, MINTED_TOKENS, NATIVE_GAS_LIMIT, PENDING_FEE, PENDING_REFUND_TO, PROCESSED_ACKS,
+    RECEIVER_ALLOW_LIST, RECOVERY, REFUND_TO, REPLY_ARGS,
+};
+use cw20::{BalanceResponse, Cw20ExecuteMsg, Cw20QueryMsg};
+use cw_storage_plus::Bound;
+use cw_utils::Expiration;
+use std::cmp::min;
 
 pub const ICS20_VERSION: &str = "ics20-1";
+pub const ICS20_2_VERSION: &str = "ics20-2";
+/// Channels of this version wrap every ics20-1 packet in an IBC v2 payload, see `Ics20Payload`.
+/// Only accepted with the "ibc-v2" feature.
+pub const ICS20_PAYLOAD_VERSION: &str = "ics20-payload-1";
+/// All versions we support, highest first
+#[cfg(not(feature = "ibc-v2"))]
+pub const ICS20_VERSIONS: &[&str] = &[ICS20_2_VERSION, ICS20_VERSION];
+#[cfg(feature = "ibc-v2")]
+pub const ICS20_VERSIONS: &[&str] = &[ICS20_PAYLOAD_VERSION, ICS20_2_VERSION, ICS20_VERSION];
 pub const ICS20_ORDERING: IbcOrder = IbcOrder::Unordered;
 
 /// The format for sending an ics20 packet.
@@ -21,7 +46,7 @@ pub const ICS20_ORDERING: IbcOrder = IbcOrder::Unordered;
 /// This is compatible with the JSON serialization
 #[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug, Default)]
 pub struct Ics20Packet {
-    /// amount of tokens to transfer is encoded as a string, but limited to u64 max
+    /// amount of tokens to transfer is encoded as a string, but limited to u64 max on ics20-1
     pub amount: Uint128,
     /// the token denomination to be transferred
     pub denom: String,
@@ -29,6 +54,9 @@ pub struct Ics20Packet {
     pub receiver: String,
     /// the sender address
     pub sender: String,
+    /// optional memo, only serialized if present to stay compatible with packets without memo
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memo: Option<String>,
 }
 
 impl Ics20Packet {
@@ -38,9 +66,15 @@ impl Ics20Packet {
             amount,
             sender: sender.to_string(),
             receiver: receiver.to_string(),
+            memo: None,
         }
     }
 
+    pub fn with_memo(mut self, memo: Option<String>) -> Self {
+        self.memo = memo;
+        self
+    }
+
     pub fn validate(&self) -> Result<(), ContractError> {
         if self.amount.u128() > (u64::MAX as u128) {
             Err(ContractError::AmountOverflow {})
@@ -48,6 +82,193 @@ impl Ics20Packet {
             Ok(())
         }
     }
+
+    /// Bounds the memo, so a counterparty cannot bloat our events with it
+    pub fn validate_memo(&self, max_len: u32) -> Result<(), ContractError> {
+        match &self.memo {
+            Some(memo) if memo.len() > max_len as usize => {
+                Err(ContractError::MemoTooLong { max: max_len })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Who gets the received tokens, with their share in basis points. This is just the receiver,
+    /// unless the memo is a JSON object with a split over several recipients, which must add up
+    /// to SPLIT_BPS. Any other memo is left alone.
+    pub fn recipients(&self) -> Result<Vec<(String, u64)>, ContractError> {
+        let memo = match &self.memo {
+            Some(memo) => Binary::from(memo.as_bytes()),
+            None => return Ok(vec![(self.receiver.clone(), SPLIT_BPS)]),
+        };
+        match from_binary::<MemoFormat>(&memo) {
+            Ok(MemoFormat { split: Some(_) }) => {}
+            _ => return Ok(vec![(self.receiver.clone(), SPLIT_BPS)]),
+        }
+        let memo: SplitMemo = from_binary(&memo)?;
+        let bps = memo
+            .split
+            .iter()
+            .fold(0u64, |total, share| total.saturating_add(share.bps));
+        if bps != SPLIT_BPS {
+            return Err(ContractError::InvalidSplit { bps });
+        }
+        Ok(memo
+            .split
+            .into_iter()
+            .map(|share| (share.addr, share.bps))
+            .collect())
+    }
+}
+
+/// The shares of a split memo always add up to this many basis points
+pub const SPLIT_BPS: u64 = 10_000;
+
+/// A memo is only taken as a split if it has this field, everything else is ignored
+#[derive(Deserialize)]
+struct MemoFormat {
+    split: Option<IgnoredAny>,
+}
+
+/// A memo like {"split":[{"addr":"a","bps":7000},{"addr":"b","bps":3000}]}
+#[derive(Deserialize)]
+struct SplitMemo {
+    split: Vec<SplitShare>,
+}
+
+#[derive(Deserialize)]
+struct SplitShare {
+    addr: String,
+    bps: u64,
+}
+
+/// Splits amount by basis points, rounding each share down. The remainder goes to the first
+/// recipient, and recipients whose share comes out as zero are left out.
+pub(crate) fn split_amount(
+    recipients: Vec<(String, u64)>,
+    amount: Uint128,
+) -> Vec<(String, Uint128)> {
+    let mut shares: Vec<(String, Uint128)> = recipients
+        .into_iter()
+        .map(|(recipient, bps)| (recipient, amount.multiply_ratio(bps, SPLIT_BPS)))
+        .collect();
+    let paid = shares
+        .iter()
+        .fold(Uint128::zero(), |total, (_, share)| total + *share);
+    if let Some((_, first)) = shares.first_mut() {
+        *first += amount - paid;
+    }
+    shares.retain(|(_, share)| !share.is_zero());
+    shares
+}
+
+/// How we report packets that list several tokens rather than a single denom and amount,
+/// like the ics20-2 packets of ibc-go (not to be confused with our own ics20-2)
+pub const MULTI_TOKEN_FORMAT: &str = "multi-token";
+
+/// The fields that tell packet formats apart, everything else is ignored
+#[derive(Deserialize)]
+struct PacketFormat {
+    /// set by formats that declare their version in the packet
+    version: Option<String>,
+    /// multi-token packets carry a list of tokens
+    tokens: Option<IgnoredAny>,
+}
+
+/// The application payload of IBC v2. The ports travel with the payload rather than being
+/// bound to the channel, and the packet data is nested in `value`.
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct Ics20Payload {
+    pub source_port: String,
+    pub destination_port: String,
+    /// the version of value, always ics20-1
+    pub version: String,
+    /// the encoding of value, always application/json
+    pub encoding: String,
+    /// the json encoded Ics20Packet
+    pub value: Binary,
+}
+
+#[cfg(feature = "ibc-v2")]
+const PAYLOAD_ENCODING: &str = "application/json";
+
+/// Unwraps the ics20 data of a packet on our end of channel, which is nested in an Ics20Payload
+/// on ICS20_PAYLOAD_VERSION channels. The data of any other channel is returned as is.
+#[cfg_attr(not(feature = "ibc-v2"), allow(unused_variables))]
+pub(crate) fn packet_data(
+    storage: &dyn Storage,
+    channel: &str,
+    packet: &IbcPacket,
+) -> Result<Binary, ContractError> {
+    #[cfg(feature = "ibc-v2")]
+    if CHANNEL_INFO
+        .may_load(storage, channel)?
+        .map(|info| info.version)
+        == Some(ICS20_PAYLOAD_VERSION.to_string())
+    {
+        let payload: Ics20Payload = from_binary(&packet.data)?;
+        if payload.version != ICS20_VERSION || payload.encoding != PAYLOAD_ENCODING {
+            return Err(ContractError::UnsupportedPacketVersion {
+                version: format!("{} ({})", payload.version, payload.encoding),
+            });
+        }
+        // the ports of the payload take the place of the ones bound to the channel
+        if payload.source_port != packet.src.port_id
+            || payload.destination_port != packet.dest.port_id
+        {
+            return Err(ContractError::PayloadPortMismatch {
+                source_port: payload.source_port,
+                destination_port: payload.destination_port,
+            });
+        }
+        return Ok(payload.value);
+    }
+    Ok(packet.data.clone())
+}
+
+/// Encodes an outgoing packet for channel, wrapping it in an Ics20Payload if the channel
+/// negotiated ICS20_PAYLOAD_VERSION
+#[cfg_attr(not(feature = "ibc-v2"), allow(unused_variables))]
+pub(crate) fn encode_packet_data(
+    storage: &dyn Storage,
+    channel: &str,
+    packet: &Ics20Packet,
+) -> StdResult<Binary> {
+    let data = to_binary(packet)?;
+    #[cfg(feature = "ibc-v2")]
+    {
+        let info = CHANNEL_INFO.load(storage, channel)?;
+        if info.version == ICS20_PAYLOAD_VERSION {
+            let payload = Ics20Payload {
+                source_port: BOUND_PORT.load(storage)?,
+                destination_port: info.counterparty_endpoint.port_id,
+                version: ICS20_VERSION.to_string(),
+                encoding: PAYLOAD_ENCODING.to_string(),
+                value: data,
+            };
+            return to_binary(&payload);
+        }
+    }
+    Ok(data)
+}
+
+/// Parses the data of an incoming packet. Data in a format we recognize but cannot handle is
+/// rejected with UnsupportedPacketVersion, anything else that is not an Ics20Packet with the
+/// parse error.
+pub(crate) fn parse_packet_data(data: &Binary) -> Result<Ics20Packet, ContractError> {
+    if let Ok(format) = from_binary::<PacketFormat>(data) {
+        if format.tokens.is_some() {
+            return Err(ContractError::UnsupportedPacketVersion {
+                version: MULTI_TOKEN_FORMAT.to_string(),
+            });
+        }
+        if let Some(version) = format.version {
+            if !ICS20_VERSIONS.contains(&version.as_str()) {
+                return Err(ContractError::UnsupportedPacketVersion { version });
+            }
+        }
+    }
+    Ok(from_binary(data)?)
 }
 
 /// This is a generic ICS acknowledgement format.
@@ -60,37 +281,187 @@ pub enum Ics20Ack {
     Error(String),
 }
 
+/// The success acknowledgement of AckEncoding::Raw
+const RAW_ACK_SUCCESS: &[u8] = &[0x01];
+
 // create a serialized success message
-fn ack_success() -> Binary {
-    let res = Ics20Ack::Result(b"1".into());
+fn ack_success(encoding: AckEncoding) -> Binary {
+    match encoding {
+        AckEncoding::Json => to_binary(&Ics20Ack::Result(b"1".into())).unwrap(),
+        AckEncoding::Raw => RAW_ACK_SUCCESS.into(),
+    }
+}
+
+/// The result of a success acknowledgement, if only part of the requested amount was released
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct PartialRelease {
+    pub released: Uint128,
+    pub shortfall: Uint128,
+}
+
+// create a serialized success message, reporting what we could not release.
+// The raw encoding has no room for this, it only gets the event attribute
+fn ack_partial_success(encoding: AckEncoding, released: Uint128, shortfall: Uint128) -> Binary {
+    if encoding == AckEncoding::Raw {
+        return RAW_ACK_SUCCESS.into();
+    }
+    let partial = PartialRelease {
+        released,
+        shortfall,
+    };
+    let res = Ics20Ack::Result(to_binary(&partial).unwrap());
     to_binary(&res).unwrap()
 }
 
 // create a serialized error message
-fn ack_fail(err: String) -> Binary {
-    let res = Ics20Ack::Error(err);
-    to_binary(&res).unwrap()
+fn ack_fail(encoding: AckEncoding, err: String) -> Binary {
+    match encoding {
+        AckEncoding::Json => to_binary(&Ics20Ack::Error(err)).unwrap(),
+        AckEncoding::Raw => err.into_bytes().into(),
+    }
+}
+
+// parse the acknowledgement of a packet we sent, in the encoding of its channel
+fn parse_ack(encoding: AckEncoding, data: &Binary) -> StdResult<Ics20Ack> {
+    match encoding {
+        AckEncoding::Json => from_binary(data),
+        AckEncoding::Raw if data.as_slice() == RAW_ACK_SUCCESS => {
+            Ok(Ics20Ack::Result(data.clone()))
+        }
+        AckEncoding::Raw => Ok(Ics20Ack::Error(
+            String::from_utf8_lossy(data.as_slice()).into_owned(),
+        )),
+    }
+}
+
+/// The error of a failure acknowledgement on ics20-2 channels, JSON encoded in place of
+/// the plain error message
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct AckError {
+    /// see `ContractError::code`
+    pub code: u32,
+    pub message: String,
+}
+
+// create a serialized error message in the format the channel understands
+fn ack_error(storage: &dyn Storage, channel: &str, err: &ContractError) -> Binary {
+    let encoding = ack_encoding(storage, channel).unwrap_or_default();
+    let structured = CHANNEL_INFO
+        .may_load(storage, channel)
+        .ok()
+        .flatten()
+        .map(|info| info.supports_error_codes())
+        .unwrap_or(false);
+    if structured {
+        let ack_err = AckError {
+            code: err.code(),
+            message: err.to_string(),
+        };
+        ack_fail(
+            encoding,
+            String::from_utf8(to_vec(&ack_err).unwrap()).unwrap(),
+        )
+    } else {
+        ack_fail(encoding, err.to_string())
+    }
 }
 
 const SEND_TOKEN_ID: u64 = 1337;
+pub(crate) const SEND_PACKET_ID: u64 = 1338;
+const MINT_TOKEN_ID: u64 = 1339;
+pub(crate) const PULL_TOKEN_ID: u64 = 1340;
 
 #[cfg_attr(not(feature = "library"), entry_point)]
-pub fn reply(_deps: DepsMut, _env: Env, reply: Reply) -> Result<Response, ContractError> {
-    if reply.id != SEND_TOKEN_ID {
-        return Err(ContractError::UnknownReplyId { id: reply.id });
+pub fn reply(deps: DepsMut, env: Env, reply: Reply) -> Result<Response, ContractError> {
+    match reply.id {
+        SEND_TOKEN_ID => reply_send_token(deps, env, reply),
+        SEND_PACKET_ID => reply_send_packet(deps, reply),
+        MINT_TOKEN_ID => reply_mint_token(deps, reply),
+        PULL_TOKEN_ID => reply_pull_token(deps, env, reply),
+        id => Err(ContractError::UnknownReplyId { id }),
     }
+}
+
+// We only get a reply on success if the payout is to be checked against our balance.
+// A token that reported success without moving the full amount is handled like a failed payout,
+// putting the whole amount back into escrow.
+fn reply_send_token(deps: DepsMut, env: Env, reply: Reply) -> Result<Response, ContractError> {
+    let args = REPLY_ARGS.load(deps.storage)?;
+    let err = match (reply.result, args.balance_check) {
+        (ContractResult::Ok(_), None) => return Ok(Response::new()),
+        (ContractResult::Ok(_), Some(check)) => {
+            let after = cw20_balance(deps.as_ref(), &check.token, &env.contract.address)?;
+            let moved = check.before.saturating_sub(after);
+            if moved >= args.amount {
+                return Ok(Response::new());
+            }
+            ContractError::PayoutNotMoved {
+                expected: args.amount,
+                moved,
+            }
+        }
+        (ContractResult::Err(err), _) => ContractError::PayoutFailed(err),
+    };
+    // the payout failed, so the tokens are still here: put them back into escrow
+    increase_channel_balance(deps.storage, &args.channel, &args.denom, args.amount)?;
+    // encode an acknowledgement error
+    Ok(Response::new().set_data(ack_error(deps.storage, &args.channel, &err)))
+}
+
+pub(crate) fn cw20_balance(deps: Deps, token: &Addr, address: &Addr) -> StdResult<Uint128> {
+    let query = Cw20QueryMsg::Balance {
+        address: address.to_string(),
+    };
+    let res: BalanceResponse = deps.querier.query_wasm_smart(token, &query)?;
+    Ok(res.balance)
+}
+
+fn reply_mint_token(deps: DepsMut, reply: Reply) -> Result<Response, ContractError> {
     let res = match reply.result {
         ContractResult::Ok(_) => Response::new(),
         ContractResult::Err(err) => {
-            // encode an acknowledgement error
-            Response::new().set_data(ack_fail(err))
+            // only received tokens are minted with a reply, and they were never escrowed,
+            // so there is nothing to put back
+            let args = REPLY_ARGS.load(deps.storage)?;
+            let err = ContractError::PayoutFailed(err);
+            Response::new().set_data(ack_error(deps.storage, &args.channel, &err))
         }
     };
     Ok(res)
 }
 
+// A packet with a relayer fee was sent. Now that we know its sequence, we can key the fee
+// escrow by it, to settle it on ack or timeout.
+fn reply_send_packet(deps: DepsMut, reply: Reply) -> Result<Response, ContractError> {
+    let res = reply.result.into_result().map_err(StdError::generic_err)?;
+    let sequence = res
+        .events
+        .iter()
+        .filter(|event| event.ty == "send_packet")
+        .flat_map(|event| &event.attributes)
+        .find(|attr| attr.key == "packet_sequence")
+        .and_then(|attr| attr.value.parse::<u64>().ok())
+        .ok_or_else(|| StdError::generic_err("send_packet event has no packet_sequence"))?;
+
+    // the packet was sent with a relayer fee, a refund address or both
+    if let Some(escrow) = PENDING_FEE.may_load(deps.storage)? {
+        PENDING_FEE.remove(deps.storage);
+        FEE_ESCROW.save(deps.storage, (&escrow.channel, sequence), &escrow)?;
+    }
+    if let Some((channel, refund_to)) = PENDING_REFUND_TO.may_load(deps.storage)? {
+        PENDING_REFUND_TO.remove(deps.storage);
+        REFUND_TO.save(deps.storage, (&channel, sequence), &refund_to)?;
+    }
+    Ok(Response::new().add_attribute("packet_sequence", sequence.to_string()))
+}
+
 #[cfg_attr(not(feature = "library"), entry_point)]
 /// enforces ordering and versioning constraints
+///
+/// We cannot propose a version from here, as this cosmwasm version does not allow returning one
+/// on channel open. Instead, we only accept the version we would have selected ourselves:
+/// the counterparty's, if we support it, or ics20-1 when opening without a counterparty version.
+/// The version is persisted in ibc_channel_connect.
 pub fn ibc_channel_open(
     _deps: DepsMut,
     _env: Env,
@@ -100,6 +471,21 @@ pub fn ibc_channel_open(
     Ok(())
 }
 
+/// Selects the highest version we support out of the counterparty's proposal,
+/// defaulting to ics20-1 if there is none
+pub fn select_version(proposed: Option<&str>) -> Result<&'static str, ContractError> {
+    match proposed {
+        None => Ok(ICS20_VERSION),
+        Some(version) => ICS20_VERSIONS
+            .iter()
+            .find(|v| **v == version)
+            .copied()
+            .ok_or_else(|| ContractError::InvalidIbcVersion {
+                version: version.to_string(),
+            }),
+    }
+}
+
 #[cfg_attr(not(feature = "library"), entry_point)]
 /// record the channel in CHANNEL_INFO
 pub fn ibc_channel_connect(
@@ -115,8 +501,30 @@ pub fn ibc_channel_connect(
         id: channel.endpoint.channel_id,
         counterparty_endpoint: channel.counterparty_endpoint,
         connection_id: channel.connection_id,
+        version: channel.version,
     };
+    // a pinned channel may only connect to the counterparty it was pinned to
+    if let Some(expected) = EXPECTED_COUNTERPARTY.may_load(deps.storage, &info.id)? {
+        if expected != info.counterparty_endpoint {
+            return Err(ContractError::UnexpectedCounterparty {
+                id: info.id,
+                expected: format!("{}/{}", expected.port_id, expected.channel_id),
+                actual: format!(
+                    "{}/{}",
+                    info.counterparty_endpoint.port_id, info.counterparty_endpoint.channel_id
+                ),
+            });
+        }
+    }
+    // never silently overwrite the counterparty of a known channel, but allow a re-connect with the same data
+    if let Some(existing) = CHANNEL_INFO.may_load(deps.storage, &info.id)? {
+        if existing.counterparty_endpoint != info.counterparty_endpoint {
+            return Err(ContractError::ChannelAlreadyExists { id: info.id });
+        }
+    }
     CHANNEL_INFO.save(deps.storage, &info.id, &info)?;
+    CHANNELS_BY_CONNECTION.save(deps.storage, (&info.connection_id, &info.id), &())?;
+    BOUND_PORT.save(deps.storage, &channel.endpoint.port_id)?;
 
     Ok(IbcBasicResponse::default())
 }
@@ -125,13 +533,10 @@ fn enforce_order_and_version(
     channel: &IbcChannel,
     counterparty_version: Option<&str>,
 ) -> Result<(), ContractError> {
-    if channel.version != ICS20_VERSION {
-        return Err(ContractError::InvalidIbcVersion {
-            version: channel.version.clone(),
-        });
-    }
+    select_version(Some(&channel.version))?;
+    // both ends must agree on the version
     if let Some(version) = counterparty_version {
-        if version != ICS20_VERSION {
+        if select_version(Some(version))? != channel.version {
             return Err(ContractError::InvalidIbcVersion {
                 version: version.to_string(),
             });
@@ -143,41 +548,148 @@ fn enforce_order_and_version(
     Ok(())
 }
 
+/// How many denoms are refunded per call when a channel is closed, to stay within the gas limit
+pub const MAX_CLOSE_REFUNDS: usize = 10;
+
 #[cfg_attr(not(feature = "library"), entry_point)]
+/// The escrowed tokens can never come back over a closed channel, so we refund them to the
+/// gov contract, which can return them to their owners. If there are more than
+/// MAX_CLOSE_REFUNDS denoms, the rest is refunded by ContinueClose.
+/// Like the ibctransfer module, we never start closing a channel ourselves, only a close
+/// started by the counterparty is accepted. The exception is AdminCloseChannel, which
+/// refunds the escrow before it closes the channel.
 pub fn ibc_channel_close(
-    _deps: DepsMut,
+    deps: DepsMut,
     _env: Env,
-    _channel: IbcChannelCloseMsg,
+    msg: IbcChannelCloseMsg,
 ) -> Result<IbcBasicResponse, ContractError> {
-    // TODO: what to do here?
-    // we will have locked funds that need to be returned somehow
-    unimplemented!();
+    let channel = match msg {
+        IbcChannelCloseMsg::CloseConfirm { channel } => channel,
+        IbcChannelCloseMsg::CloseInit { channel }
+            if ADMIN_CLOSED.has(deps.storage, &channel.endpoint.channel_id) =>
+        {
+            return Ok(IbcBasicResponse::new()
+                .add_attribute("action", "close")
+                .add_attribute("channel", channel.endpoint.channel_id));
+        }
+        _ => return Err(ContractError::CannotCloseChannel {}),
+    };
+    let channel_id = channel.endpoint.channel_id;
+    let (msgs, done) = refund_closed_channel(deps.storage, &channel_id, None)?;
+
+    let res = IbcBasicResponse::new()
+        .add_messages(msgs)
+        .add_attribute("action", "close")
+        .add_attribute("channel", channel_id)
+        .add_attribute("done", done.to_string());
+    Ok(res)
+}
+
+/// Refunds the outstanding balance of up to MAX_CLOSE_REFUNDS denoms of a closed channel to the
+/// recovery address, or the gov contract if there is none, starting after the given denom. Stores a cursor if denoms are left, and clears it
+/// otherwise. Returns the payouts and whether the channel is fully drained.
+pub(crate) fn refund_closed_channel(
+    storage: &mut dyn Storage,
+    channel: &str,
+    start_after: Option<String>,
+) -> Result<(Vec<CosmosMsg>, bool), ContractError> {
+    let recipient = match RECOVERY.may_load(storage)? {
+        Some(recovery) => recovery,
+        None => CONFIG.load(storage)?.gov_contract,
+    };
+    let start = start_after.map(Bound::exclusive);
+    // take one more, to know if anything is left after this batch
+    let mut batch = CHANNEL_STATE
+        .prefix(channel)
+        .range(storage, start, None, Order::Ascending)
+        .take(MAX_CLOSE_REFUNDS + 1)
+        .collect::<StdResult<Vec<_>>>()?;
+    let done = batch.len() <= MAX_CLOSE_REFUNDS;
+    batch.truncate(MAX_CLOSE_REFUNDS);
+
+    let mut msgs = vec![];
+    for (denom, mut state) in batch.iter().cloned() {
+        if state.outstanding.is_zero() {
+            continue;
+        }
+        let amount = Amount::from_parts(denom.clone(), state.outstanding);
+        msgs.push(transfer_msg(
+            local_amount(storage, amount)?,
+            recipient.to_string(),
+        ));
+        update_global_outstanding(storage, &denom, state.outstanding, false)?;
+        state.outstanding = Uint128::zero();
+        CHANNEL_STATE.save(storage, (channel, &denom), &state)?;
+    }
+
+    match batch.last() {
+        Some((last, _)) if !done => CLOSE_CURSOR.save(storage, channel, last)?,
+        _ => CLOSE_CURSOR.remove(storage, channel),
+    }
+    Ok((msgs, done))
 }
 
 #[cfg_attr(not(feature = "library"), entry_point)]
 /// Check to see if we have any balance here
 /// We should not return an error if possible, but rather an acknowledgement of failure
 pub fn ibc_packet_receive(
-    deps: DepsMut,
-    _env: Env,
+    mut deps: DepsMut,
+    env: Env,
     msg: IbcPacketReceiveMsg,
 ) -> Result<IbcReceiveResponse, Never> {
     let packet = msg.packet;
 
-    do_ibc_packet_receive(deps, &packet).or_else(|err| {
-        Ok(IbcReceiveResponse::new()
-            .set_ack(ack_fail(err.to_string()))
-            .add_attributes(vec![
-                attr("action", "receive"),
-                attr("success", "false"),
-                attr("error", err.to_string()),
-            ]))
-    })
+    let res = do_ibc_packet_receive(deps.branch(), &env, &packet);
+    Ok(into_ack_response(deps, &env, &packet, res))
+}
+
+/// Turns the result of handling an incoming packet into a response that always acknowledges it.
+/// Errors become an error ack (and are recorded for debugging) rather than failing the
+/// transaction, so the packet does not get stuck. State the handler already wrote is kept
+/// though, so handlers must not write any state before their last fallible step.
+pub(crate) fn into_ack_response(
+    deps: DepsMut,
+    env: &Env,
+    packet: &IbcPacket,
+    result: Result<IbcReceiveResponse, ContractError>,
+) -> IbcReceiveResponse {
+    let err = match result {
+        Ok(res) => return res,
+        Err(err) => err,
+    };
+    let data: Option<Ics20Packet> = packet_data(deps.storage, &packet.dest.channel_id, packet)
+        .ok()
+        .and_then(|data| from_binary(&data).ok());
+    let failure = ReceiveFailure {
+        channel: packet.dest.channel_id.clone(),
+        denom: data.as_ref().map(|d| d.denom.clone()).unwrap_or_default(),
+        amount: data.as_ref().map(|d| d.amount).unwrap_or_default(),
+        receiver: data.map(|d| d.receiver).unwrap_or_default(),
+        error: err.to_string(),
+        height: env.block.height,
+    };
+    // this is only kept for debugging, it must not keep us from acknowledging the packet
+    let _ = record_receive_failure(deps.storage, &failure);
+    let denom = parse_voucher_denom(&failure.denom, &packet.src).unwrap_or(&failure.denom);
+    IbcReceiveResponse::new()
+        .set_ack(ack_error(deps.storage, &packet.dest.channel_id, &err))
+        .add_attributes(vec![
+            attr("action", "receive"),
+            attr("success", "false"),
+            attr("error", err.to_string()),
+        ])
+        .add_attributes(metrics_attributes(
+            &failure.channel,
+            denom,
+            failure.amount,
+            Direction::In,
+            Outcome::Failure,
+        ))
 }
 
 // Returns local denom if the denom is an encoded voucher from the expected endpoint
 // Otherwise, error
-fn parse_voucher_denom<'a>(
+pub(crate) fn parse_voucher_denom<'a>(
     voucher_denom: &'a str,
     remote_endpoint: &IbcEndpoint,
 ) -> Result<&'a str, ContractError> {
@@ -200,60 +712,235 @@ fn parse_voucher_denom<'a>(
     Ok(split_denom[2])
 }
 
+// Splits a denom into its (port, channel) hops and base denom, like the Go ibctransfer module.
+// Segments are taken as a hop as long as the second one is a valid channel id, so base denoms
+// with slashes (eg. "gamm/pool/1") are kept intact.
+pub fn parse_denom_trace(denom: &str) -> DenomTrace {
+    let segments: Vec<&str> = denom.split('/').collect();
+    let mut path = vec![];
+    let mut i = 0;
+    while i + 2 < segments.len() && is_channel_id(segments[i + 1]) {
+        path.push((segments[i].to_string(), segments[i + 1].to_string()));
+        i += 2;
+    }
+    DenomTrace {
+        base_denom: segments[i..].join("/"),
+        path,
+    }
+}
+
+fn is_channel_id(id: &str) -> bool {
+    id.strip_prefix("channel-")
+        .map(|n| n.parse::<u64>().is_ok())
+        .unwrap_or(false)
+}
+
 // this does the work of ibc_packet_receive, we wrap it to turn errors into acknowledgements
 fn do_ibc_packet_receive(
-    deps: DepsMut,
+    mut deps: DepsMut,
+    env: &Env,
     packet: &IbcPacket,
 ) -> Result<IbcReceiveResponse, ContractError> {
-    let msg: Ics20Packet = from_binary(&packet.data)?;
     let channel = packet.dest.channel_id.clone();
+    let msg = parse_packet_data(&packet_data(deps.storage, &channel, packet)?)?;
+    let config = CONFIG.load(deps.storage)?;
 
-    // If the token originated on the remote chain, it looks like "ucosm".
-    // If it originated on our chain, it looks like "port/channel/ucosm".
-    let denom = parse_voucher_denom(&msg.denom, &packet.src)?;
+    if is_paused(deps.storage)? {
+        return Err(ContractError::Paused);
+    }
+    check_channel_enabled(deps.storage, &channel)?;
+    // this must happen before we touch any escrow
+    msg.validate_memo(config.max_memo_len)?;
+    // a split memo pays several recipients instead of the receiver, each must be acceptable
+    let recipients = msg.recipients()?;
+    for (recipient, _) in &recipients {
+        check_receiver(deps.as_ref(), env, &config, recipient)?;
+    }
 
-    CHANNEL_STATE.update(
-        deps.storage,
-        (&channel, denom),
-        |orig| -> Result<_, ContractError> {
-            // this will return error if we don't have the funds there to cover the request (or no denom registered)
-            let mut cur = orig.ok_or(ContractError::InsufficientFunds {})?;
-            cur.outstanding = cur
-                .outstanding
-                .checked_sub(msg.amount)
-                .or(Err(ContractError::InsufficientFunds {}))?;
-            Ok(cur)
-        },
-    )?;
+    // held deliveries are stored by receiver address, so they must all be valid
+    if config.claim_grace_period.is_some() {
+        for (recipient, _) in &recipients {
+            deps.api.addr_validate(recipient)?;
+        }
+    }
+
+    // wrapped tokens we are the minter of are minted, all others must come out of escrow
+    let minted = MINTED_TOKENS.may_load(deps.storage, (&channel, &msg.denom))?;
+    let (to_send, gas_limit, denom, amount) = match &minted {
+        Some(token) => {
+            let to_send = Amount::cw20(msg.amount.u128(), token.as_str());
+            (to_send, None, msg.denom.as_str(), msg.amount)
+        }
+        None => escrow_to_release(deps.as_ref(), &config, &channel, &packet.src, &msg)?,
+    };
+    let shortfall = msg.amount - amount;
+    let shares = split_amount(recipients, amount);
 
-    let to_send = Amount::from_parts(denom.to_string(), msg.amount);
-    let gas_limit = check_gas_limit(deps.as_ref(), &to_send)?;
-    let send = send_amount(to_send, msg.receiver.clone(), gas_limit);
+    // with a grace period, the receivers have to claim the tokens rather than being paid right away
+    let (send, delivery_ids) = match config.claim_grace_period {
+        Some(grace_period) => {
+            let claimable_until = Expiration::AtTime(env.block.time.plus_seconds(grace_period));
+            let mut ids = vec![];
+            for (recipient, share) in shares {
+                let receiver = Addr::unchecked(recipient);
+                let held = Amount::from_parts(to_send.denom(), share);
+                let id = hold_delivery(
+                    deps.storage,
+                    &channel,
+                    receiver,
+                    held,
+                    minted.is_some(),
+                    claimable_until,
+                )?;
+                ids.push(id);
+            }
+            (None, ids)
+        }
+        None => {
+            let send = match &minted {
+                Some(token) => mint_amount(
+                    deps.storage,
+                    env,
+                    &channel,
+                    &msg.denom,
+                    token,
+                    msg.amount,
+                    shares,
+                )?,
+                None => send_amount(deps.branch(), env, &channel, to_send, shares, gas_limit)?,
+            };
+            (Some(send), vec![])
+        }
+    };
+    // an error ack keeps whatever we wrote, so the escrow is only released once all else passed
+    if minted.is_none() {
+        reduce_channel_balance(deps.storage, &channel, denom, amount)?;
+    }
 
-    let res = IbcReceiveResponse::new()
-        .set_ack(ack_success())
-        .add_submessage(send)
+    let encoding = ack_encoding(deps.storage, &channel)?;
+    let ack = if shortfall.is_zero() {
+        ack_success(encoding)
+    } else {
+        ack_partial_success(encoding, amount, shortfall)
+    };
+    let mut res = IbcReceiveResponse::new()
+        .set_ack(ack)
+        .add_submessages(send)
         .add_attribute("action", "receive")
-        .add_attribute("sender", msg.sender)
-        .add_attribute("receiver", msg.receiver)
-        .add_attribute("denom", denom)
-        .add_attribute("amount", msg.amount)
-        .add_attribute("success", "true");
+        .add_attribute("sender", &msg.sender)
+        .add_attribute("receiver", &msg.receiver)
+        .add_attribute("denom_trace", parse_denom_trace(&msg.denom).path_string())
+        .add_attribute("success", "true")
+        .add_attributes(metrics_attributes(
+            &channel,
+            denom,
+            amount,
+            Direction::In,
+            Outcome::Success,
+        ));
+    if !shortfall.is_zero() {
+        res = res.add_attribute("shortfall", shortfall);
+    }
+    for id in delivery_ids {
+        res = res.add_attribute("delivery_id", id.to_string());
+    }
 
     Ok(res)
 }
 
-fn check_gas_limit(deps: Deps, amount: &Amount) -> Result<Option<u64>, ContractError> {
+/// Errors if received tokens must not be paid to receiver
+fn check_receiver(
+    deps: Deps,
+    env: &Env,
+    config: &Config,
+    receiver: &str,
+) -> Result<(), ContractError> {
+    // paying ourselves would only strand the tokens here (or loop through a hook)
+    if receiver == env.contract.address.as_str() {
+        return Err(ContractError::SelfReceive {});
+    }
+    if config.restrict_receivers {
+        let addr = deps.api.addr_validate(receiver)?;
+        if !RECEIVER_ALLOW_LIST.has(deps.storage, &addr) {
+            return Err(ContractError::ReceiverNotAllowed {
+                receiver: receiver.to_string(),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// The escrow an incoming packet releases, without releasing it yet. Returns the tokens to pay
+/// out with their gas limit, the local denom and the amount released, which can be less than the
+/// packet amount with partial release. Errors if the escrow cannot cover the release.
+fn escrow_to_release<'a>(
+    deps: Deps,
+    config: &Config,
+    channel: &str,
+    src: &IbcEndpoint,
+    msg: &'a Ics20Packet,
+) -> Result<(Amount, Option<u64>, &'a str, Uint128), ContractError> {
+    // If the token originated on the remote chain, it looks like "ucosm".
+    // If it originated on our chain, it looks like "port/channel/ucosm".
+    let denom = parse_voucher_denom(&msg.denom, src)?;
+    check_not_denied(deps, &Amount::from_parts(denom.to_string(), msg.amount))?;
+    check_min_transfer(deps.storage, denom, msg.amount)?;
+
+    let available = CHANNEL_STATE
+        .may_load(deps.storage, (channel, denom))?
+        .ok_or_else(|| ContractError::DenomNotRegistered {
+            channel: channel.to_string(),
+            denom: denom.to_string(),
+        })?
+        .outstanding;
+    // with partial release, we pay out whatever is escrowed and report the rest as shortfall
+    let amount = if config.allow_partial_release && !available.is_zero() {
+        min(available, msg.amount)
+    } else {
+        msg.amount
+    };
+    if available < amount {
+        return Err(ContractError::InsufficientFunds {
+            available,
+            requested: msg.amount,
+        });
+    }
+    let to_send = Amount::from_parts(denom.to_string(), amount);
+    let gas_limit = check_gas_limit(deps, &to_send)?;
+    Ok((to_send, gas_limit, denom, amount))
+}
+
+/// Denied cw20 tokens are blocked before the allow list is even looked at
+pub(crate) fn check_not_denied(deps: Deps, amount: &Amount) -> Result<(), ContractError> {
+    if let Amount::Cw20(coin) = amount {
+        let addr = deps.api.addr_validate(&coin.address)?;
+        if DENY_LIST.has(deps.storage, &addr) {
+            return Err(ContractError::TokenDenied {
+                contract: coin.address.clone(),
+            });
+        }
+    }
+    Ok(())
+}
+
+pub(crate) fn check_gas_limit(deps: Deps, amount: &Amount) -> Result<Option<u64>, ContractError> {
     match amount {
         Amount::Cw20(coin) => {
             // if cw20 token, use the registered gas limit, or error if not whitelisted
             let addr = deps.api.addr_validate(&coin.address)?;
-            Ok(ALLOW_LIST
-                .may_load(deps.storage, &addr)?
-                .ok_or(ContractError::NotOnAllowList)?
-                .gas_limit)
+            if let Some(allow) = ALLOW_LIST.may_load(deps.storage, &addr)? {
+                return Ok(allow.gas_limit);
+            }
+            // unless all tokens are accepted
+            let cfg = CONFIG.load(deps.storage)?;
+            if cfg.allow_all_cw20 {
+                Ok(cfg.default_gas_limit)
+            } else {
+                Err(ContractError::NotOnAllowList)
+            }
         }
-        _ => Ok(None),
+        // native tokens use the limit configured for their denom, if any
+        Amount::Native(coin) => Ok(NATIVE_GAS_LIMIT.may_load(deps.storage, &coin.denom)?),
     }
 }
 
@@ -261,103 +948,459 @@ fn check_gas_limit(deps: Deps, amount: &Amount) -> Result<Option<u64>, ContractE
 /// check if success or failure and update balance, or return funds
 pub fn ibc_packet_ack(
     deps: DepsMut,
-    _env: Env,
+    env: Env,
     msg: IbcPacketAckMsg,
 ) -> Result<IbcBasicResponse, ContractError> {
     // TODO: trap error like in receive?
-    let ics20msg: Ics20Ack = from_binary(&msg.acknowledgement.data)?;
-    match ics20msg {
-        Ics20Ack::Result(_) => on_packet_success(deps, msg.original_packet),
-        Ics20Ack::Error(err) => on_packet_failure(deps, msg.original_packet, err),
+    if mark_processed(deps.storage, &msg.original_packet)? {
+        return Ok(duplicate_response(deps.storage, &msg.original_packet));
     }
+    let freed = decrease_in_flight(deps.storage, &msg.original_packet.src.channel_id)?;
+    let fees = settle_fee(deps.storage, &msg.original_packet, false)?;
+    let encoding = ack_encoding(deps.storage, &msg.original_packet.src.channel_id)?;
+    let ics20msg = parse_ack(encoding, &msg.acknowledgement.data)?;
+    let res = match ics20msg {
+        Ics20Ack::Result(_) => on_packet_success(deps, &env, msg.original_packet),
+        Ics20Ack::Error(err) => {
+            on_packet_failure(deps, &env, msg.original_packet, err, Outcome::Failure)
+        }
+    }?;
+    Ok(in_flight_warning(res.add_messages(fees), freed))
 }
 
 #[cfg_attr(not(feature = "library"), entry_point)]
 /// return fund to original sender (same as failure in ibc_packet_ack)
 pub fn ibc_packet_timeout(
     deps: DepsMut,
-    _env: Env,
+    env: Env,
     msg: IbcPacketTimeoutMsg,
 ) -> Result<IbcBasicResponse, ContractError> {
     // TODO: trap error like in receive?
     let packet = msg.packet;
-    on_packet_failure(deps, packet, "timeout".to_string())
+    if mark_processed(deps.storage, &packet)? {
+        return Ok(duplicate_response(deps.storage, &packet));
+    }
+    let freed = decrease_in_flight(deps.storage, &packet.src.channel_id)?;
+    let fees = settle_fee(deps.storage, &packet, true)?;
+    let res = on_packet_failure(deps, &env, packet, "timeout".to_string(), Outcome::Timeout)?;
+    Ok(in_flight_warning(res.add_messages(fees), freed))
+}
+
+/// Flags a resolved packet that had no in-flight slot to free, so the count is known to be off
+fn in_flight_warning(res: IbcBasicResponse, freed: bool) -> IbcBasicResponse {
+    if freed {
+        res
+    } else {
+        res.add_attribute("in_flight_warning", "underflow")
+    }
+}
+
+/// Pays out the relayer fee escrowed with the packet, if any. The consumed part goes to the
+/// gov contract, which pays the relayers, as this cosmwasm version does not tell us who
+/// relayed. The rest is refunded to the sender.
+fn settle_fee(
+    storage: &mut dyn Storage,
+    packet: &IbcPacket,
+    timed_out: bool,
+) -> StdResult<Vec<BankMsg>> {
+    let key = (packet.src.channel_id.as_str(), packet.sequence);
+    let escrow = match FEE_ESCROW.may_load(storage, key)? {
+        Some(escrow) => escrow,
+        None => return Ok(vec![]),
+    };
+    FEE_ESCROW.remove(storage, key);
+
+    let (consumed, refunded) = escrow.fee.settle(timed_out);
+    let gov = CONFIG.load(storage)?.gov_contract;
+    let mut msgs = vec![];
+    for (recipient, coins) in [(gov, consumed), (escrow.sender, refunded)] {
+        let coins = coins.into_vec();
+        if coins.is_empty() {
+            continue;
+        }
+        update_fee_totals(storage, &coins, false)?;
+        msgs.push(BankMsg::Send {
+            to_address: recipient.into(),
+            amount: coins,
+        });
+    }
+    Ok(msgs)
+}
+
+// Records that the ack or timeout of this packet is handled now.
+// Returns true if it was already handled before, so it must not be counted again.
+fn mark_processed(storage: &mut dyn Storage, packet: &IbcPacket) -> StdResult<bool> {
+    let key = (packet.src.channel_id.as_str(), packet.sequence);
+    if PROCESSED_ACKS.has(storage, key) {
+        return Ok(true);
+    }
+    PROCESSED_ACKS.save(storage, key, &())?;
+    Ok(false)
+}
+
+// a repeated ack or timeout is a no-op, we only report it
+fn duplicate_response(storage: &dyn Storage, packet: &IbcPacket) -> IbcBasicResponse {
+    let data: Option<Ics20Packet> = packet_data(storage, &packet.src.channel_id, packet)
+        .ok()
+        .and_then(|data| from_binary(&data).ok());
+    IbcBasicResponse::new()
+        .add_attribute("action", "acknowledge")
+        .add_attribute("sequence", packet.sequence.to_string())
+        .add_attribute("duplicate", "true")
+        .add_attributes(metrics_attributes(
+            &packet.src.channel_id,
+            data.as_ref().map(|d| d.denom.as_str()).unwrap_or_default(),
+            data.as_ref().map(|d| d.amount).unwrap_or_default(),
+            Direction::Out,
+            Outcome::Duplicate,
+        ))
+}
+
+/// Which way the tokens of a packet move, for the `direction` metrics attribute
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Direction {
+    /// packets we receive
+    In,
+    /// packets we sent, on ack or timeout
+    Out,
+}
+
+/// How handling a packet ended, for the `outcome` metrics attribute
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Outcome {
+    Success,
+    Failure,
+    Timeout,
+    /// an ack or timeout of a packet that was already handled
+    Duplicate,
+}
+
+/// The attributes every packet handler emits, with the same keys and value vocabulary on
+/// every path, so they can be scraped as metrics. Keep these stable.
+fn metrics_attributes(
+    channel: &str,
+    denom: &str,
+    amount: Uint128,
+    direction: Direction,
+    outcome: Outcome,
+) -> Vec<Attribute> {
+    let direction = match direction {
+        Direction::In => "in",
+        Direction::Out => "out",
+    };
+    let outcome = match outcome {
+        Outcome::Success => "success",
+        Outcome::Failure => "failure",
+        Outcome::Timeout => "timeout",
+        Outcome::Duplicate => "duplicate",
+    };
+    vec![
+        attr("channel", channel),
+        attr("denom", denom),
+        attr("amount", amount),
+        attr("direction", direction),
+        attr("outcome", outcome),
+    ]
 }
 
 // update the balance stored on this (channel, denom) index
-fn on_packet_success(deps: DepsMut, packet: IbcPacket) -> Result<IbcBasicResponse, ContractError> {
-    let msg: Ics20Packet = from_binary(&packet.data)?;
+fn on_packet_success(
+    deps: DepsMut,
+    env: &Env,
+    packet: IbcPacket,
+) -> Result<IbcBasicResponse, ContractError> {
+    let msg: Ics20Packet =
+        from_binary(&packet_data(deps.storage, &packet.src.channel_id, &packet)?)?;
     // similar event messages like ibctransfer module
     let attributes = vec![
         attr("action", "acknowledge"),
         attr("sender", &msg.sender),
         attr("receiver", &msg.receiver),
-        attr("denom", &msg.denom),
-        attr("amount", msg.amount),
+        attr("denom_trace", parse_denom_trace(&msg.denom).path_string()),
         attr("success", "true"),
     ];
+    let metrics = metrics_attributes(
+        &packet.src.channel_id,
+        &msg.denom,
+        msg.amount,
+        Direction::Out,
+        Outcome::Success,
+    );
 
-    let channel = packet.src.channel_id;
-    let denom = msg.denom;
-    let amount = msg.amount;
-    CHANNEL_STATE.update(deps.storage, (&channel, &denom), |orig| -> StdResult<_> {
-        let mut state = orig.unwrap_or_default();
-        state.outstanding += amount;
-        state.total_sent += amount;
-        Ok(state)
-    })?;
+    REFUND_TO.remove(deps.storage, (&packet.src.channel_id, packet.sequence));
+
+    // the tokens were already escrowed on send, we only count the success here.
+    // Wrapped tokens were burned instead, so there is no escrow to count them in
+    if minted_token(deps.storage, &packet.src, &msg.denom)?.is_none() {
+        record_successful_send(deps.storage, &packet.src.channel_id, &msg.denom, msg.amount)?;
+        decrease_in_flight_amount(deps.storage, &packet.src.channel_id, &msg.denom, msg.amount)?;
+    }
+    record_volume(
+        deps.storage,
+        &packet.src.channel_id,
+        &msg.denom,
+        msg.amount,
+        env.block.time.seconds(),
+    )?;
 
-    Ok(IbcBasicResponse::new().add_attributes(attributes))
+    Ok(IbcBasicResponse::new()
+        .add_attributes(attributes)
+        .add_attributes(metrics))
 }
 
 // return the tokens to sender
 fn on_packet_failure(
     deps: DepsMut,
+    env: &Env,
     packet: IbcPacket,
     err: String,
+    outcome: Outcome,
 ) -> Result<IbcBasicResponse, ContractError> {
-    let msg: Ics20Packet = from_binary(&packet.data)?;
+    let msg: Ics20Packet =
+        from_binary(&packet_data(deps.storage, &packet.src.channel_id, &packet)?)?;
+    // the tokens go back to the sender, unless it asked for them to go elsewhere
+    let key = (packet.src.channel_id.as_str(), packet.sequence);
+    let refund_to = match REFUND_TO.may_load(deps.storage, key)? {
+        Some(addr) => {
+            REFUND_TO.remove(deps.storage, key);
+            addr.into()
+        }
+        None => msg.sender.clone(),
+    };
 
-    let to_send = Amount::from_parts(msg.denom.clone(), msg.amount);
-    let gas_limit = check_gas_limit(deps.as_ref(), &to_send)?;
-    let send = send_amount(to_send, msg.sender.clone(), gas_limit);
+    let send = match minted_token(deps.storage, &packet.src, &msg.denom)? {
+        // wrapped tokens were burned when sending, so they are minted back. Unlike a failed
+        // payout, a failed mint leaves nothing behind to recover, so it must fail the whole
+        // ack or timeout, to be relayed again once the token mints
+        Some(token) => SubMsg::new(mint_msg(
+            env,
+            &token,
+            vec![(refund_to.clone(), msg.amount)],
+        )?),
+        None => {
+            // release the escrow we took when sending, as the tokens go back to the sender
+            reduce_channel_balance(deps.storage, &packet.src.channel_id, &msg.denom, msg.amount)?;
+            decrease_in_flight_amount(
+                deps.storage,
+                &packet.src.channel_id,
+                &msg.denom,
+                msg.amount,
+            )?;
+
+            let (to_send, gas_limit) =
+                refund_with_gas_limit(deps.as_ref(), &msg.denom, msg.amount)?;
+            send_amount(
+                deps,
+                env,
+                &packet.src.channel_id,
+                to_send,
+                vec![(refund_to.clone(), msg.amount)],
+                gas_limit,
+            )?
+        }
+    };
 
     // similar event messages like ibctransfer module
     let res = IbcBasicResponse::new()
         .add_submessage(send)
         .add_attribute("action", "acknowledge")
         .add_attribute("sender", msg.sender)
-        .add_attribute("receiver", msg.receiver)
-        .add_attribute("denom", msg.denom)
-        .add_attribute("amount", msg.amount.to_string())
+        .add_attribute("receiver", &msg.receiver)
         .add_attribute("success", "false")
-        .add_attribute("error", err);
+        .add_attribute("error", err)
+        .add_attributes(metrics_attributes(
+            &packet.src.channel_id,
+            &msg.denom,
+            msg.amount,
+            Direction::Out,
+            outcome,
+        ));
 
     Ok(res)
 }
 
-fn send_amount(amount: Amount, recipient: String, gas_limit: Option<u64>) -> SubMsg {
+/// The refund paid out to sender if a send of amount of denom over channel fails, and the gas
+/// limit used for it. Runs the checks of on_packet_failure without changing any state.
+pub(crate) fn simulate_refund(
+    deps: Deps,
+    channel: &str,
+    sender: &str,
+    denom: &str,
+    amount: Uint128,
+) -> Result<(Amount, Option<u64>), ContractError> {
+    deps.api.addr_validate(sender)?;
+    let outstanding = CHANNEL_STATE
+        .may_load(deps.storage, (channel, denom))?
+        .unwrap_or_default()
+        .outstanding;
+    if outstanding < amount {
+        return Err(ContractError::InsufficientFunds {
+            available: outstanding,
+            requested: amount,
+        });
+    }
+    refund_with_gas_limit(deps, denom, amount)
+}
+
+fn refund_with_gas_limit(
+    deps: Deps,
+    denom: &str,
+    amount: Uint128,
+) -> Result<(Amount, Option<u64>), ContractError> {
+    let to_send = Amount::from_parts(denom.to_string(), amount);
+    let gas_limit = check_gas_limit(deps, &to_send)?;
+    Ok((to_send, gas_limit))
+}
+
+// Pays out tokens released from the escrow of channel, split into shares. The reply restores the
+// escrow if this fails, for native and cw20 tokens alike. For cw20 tokens flagged with
+// verify_balance, it also does if the token did not actually move the tokens out of our balance.
+fn send_amount(
+    deps: DepsMut,
+    env: &Env,
+    channel: &str,
+    amount: Amount,
+    shares: Vec<(String, Uint128)>,
+    gas_limit: Option<u64>,
+) -> StdResult<SubMsg> {
+    // escrow is kept in the wire denom, but paid out in the local one
+    let to_send = local_amount(deps.storage, amount.clone())?;
+    let balance_check = match &to_send {
+        Amount::Cw20(coin) => {
+            let token = deps.api.addr_validate(&coin.address)?;
+            let verify = ALLOW_LIST
+                .may_load(deps.storage, &token)?
+                .map(|allow| allow.verify_balance)
+                .unwrap_or(false);
+            if verify {
+                let before = cw20_balance(deps.as_ref(), &token, &env.contract.address)?;
+                Some(BalanceCheck { token, before })
+            } else {
+                None
+            }
+        }
+        Amount::Native(_) => None,
+    };
+    let args = ReplyArgs {
+        channel: channel.to_string(),
+        denom: amount.denom(),
+        amount: amount.amount(),
+        balance_check,
+    };
+    REPLY_ARGS.save(deps.storage, &args)?;
+
+    // every share is paid with the gas limit of the token
+    let gas_limit = gas_limit.map(|limit| limit.saturating_mul(shares.len() as u64));
+    let denom = to_send.denom();
+    let msg = shares_msg(env, shares, |recipient, share| {
+        Ok(transfer_msg(
+            Amount::from_parts(denom.clone(), share),
+            recipient,
+        ))
+    })?;
+    let mut sub = if args.balance_check.is_some() {
+        SubMsg::reply_always(msg, SEND_TOKEN_ID)
+    } else {
+        SubMsg::reply_on_error(msg, SEND_TOKEN_ID)
+    };
+    sub.gas_limit = gas_limit;
+    Ok(sub)
+}
+
+// Mints received tokens of a wrapped token we are the minter of, split into shares. Nothing was
+// escrowed for them, so if this fails the reply only turns it into an error ack.
+fn mint_amount(
+    storage: &mut dyn Storage,
+    env: &Env,
+    channel: &str,
+    denom: &str,
+    token: &Addr,
+    amount: Uint128,
+    shares: Vec<(String, Uint128)>,
+) -> StdResult<SubMsg> {
+    let args = ReplyArgs {
+        channel: channel.to_string(),
+        denom: denom.to_string(),
+        amount,
+        balance_check: None,
+    };
+    REPLY_ARGS.save(storage, &args)?;
+
+    let msg = mint_msg(env, token, shares)?;
+    Ok(SubMsg::reply_on_error(msg, MINT_TOKEN_ID))
+}
+
+fn mint_msg(env: &Env, token: &Addr, shares: Vec<(String, Uint128)>) -> StdResult<CosmosMsg> {
+    shares_msg(env, shares, |recipient, share| {
+        let mint = Cw20ExecuteMsg::Mint {
+            recipient,
+            amount: share,
+        };
+        Ok(WasmMsg::Execute {
+            contract_addr: token.to_string(),
+            msg: to_binary(&mint)?,
+            funds: vec![],
+        }
+        .into())
+    })
+}
+
+// One message paying every share with pay. A single share is paid directly. Several are paid
+// through PayShares on ourselves, so they succeed or fail together and a failure never leaves
+// some recipients paid while the whole amount goes back to the sender.
+fn shares_msg(
+    env: &Env,
+    shares: Vec<(String, Uint128)>,
+    pay: impl Fn(String, Uint128) -> StdResult<CosmosMsg>,
+) -> StdResult<CosmosMsg> {
+    let mut msgs = shares
+        .into_iter()
+        .map(|(recipient, share)| pay(recipient, share))
+        .collect::<StdResult<Vec<_>>>()?;
+    if msgs.len() == 1 {
+        return Ok(msgs.remove(0));
+    }
+    let pay_shares = ExecuteMsg::PayShares { msgs };
+    Ok(WasmMsg::Execute {
+        contract_addr: env.contract.address.to_string(),
+        msg: to_binary(&pay_shares)?,
+        funds: vec![],
+    }
+    .into())
+}
+
+/// The wrapped token a packet we sent from endpoint was burned from, if its denom is one we mint
+fn minted_token(
+    storage: &dyn Storage,
+    endpoint: &IbcEndpoint,
+    denom: &str,
+) -> StdResult<Option<Addr>> {
+    let prefix = format!("{}/{}/", endpoint.port_id, endpoint.channel_id);
+    match denom.strip_prefix(&prefix) {
+        Some(remote) => MINTED_TOKENS.may_load(storage, (&endpoint.channel_id, remote)),
+        None => Ok(None),
+    }
+}
+
+/// A plain message paying out the amount, either a bank send or a cw20 transfer
+pub(crate) fn transfer_msg(amount: Amount, recipient: String) -> CosmosMsg {
     match amount {
-        Amount::Native(coin) => SubMsg::reply_on_error(
-            BankMsg::Send {
-                to_address: recipient,
-                amount: vec![coin],
-            },
-            SEND_TOKEN_ID,
-        ),
+        Amount::Native(coin) => BankMsg::Send {
+            to_address: recipient,
+            amount: vec![coin],
+        }
+        .into(),
         Amount::Cw20(coin) => {
             let msg = Cw20ExecuteMsg::Transfer {
                 recipient,
                 amount: coin.amount,
             };
-            let exec = WasmMsg::Execute {
+            WasmMsg::Execute {
                 contract_addr: coin.address,
                 msg: to_binary(&msg).unwrap(),
                 funds: vec![],
-            };
-            let mut sub = SubMsg::reply_on_error(exec, SEND_TOKEN_ID);
-            sub.gas_limit = gas_limit;
-            sub
+            }
+            .into()
         }
     }
 }
@@ -367,9 +1410,25 @@ mod test {
     use super::*;
     use crate::test_helpers::*;
 
-    use crate::contract::query_channel;
-    use cosmwasm_std::testing::mock_env;
-    use cosmwasm_std::{coins, to_vec, IbcAcknowledgement, IbcEndpoint, IbcTimeout, Timestamp};
+    use crate::contract::{
+        execute, query, query_channel, query_channel_features, DEFAULT_MAX_MEMO_LEN,
+    };
+    use crate::msg::{
+        AllowMsg, AllowedResponse, CheckInvariantsResponse, ExecuteMsg, ExpectedCounterparty,
+        GlobalCapResponse, IbcFee, InvariantViolation, PendingDeliveryResponse, QueryMsg,
+        RecentFailuresResponse, ReconcileResponse, SupportedVersionsResponse, TransferMsg,
+        VolumeBucket, VolumeBucketsResponse,
+    };
+    use crate::state::{
+        total_outstanding, IN_FLIGHT, IN_FLIGHT_AMOUNT, MAX_RECENT_FAILURES, PENDING_DELIVERY,
+        RECENT_FAILURES, VOLUME_BUCKETS,
+    };
+    use cosmwasm_std::testing::{mock_env, mock_info, MOCK_CONTRACT_ADDR};
+    use cosmwasm_std::{
+        coin, coins, to_vec, Coin, Event, IbcAcknowledgement, IbcEndpoint, IbcMsg, IbcTimeout,
+        MessageInfo, ReplyOn, SubMsgExecutionResponse, SystemResult, Timestamp, WasmQuery,
+    };
+    use cw20::Cw20ReceiveMsg;
 
     #[test]
     fn check_ack_json() {
@@ -428,12 +1487,27 @@ mod test {
         )
     }
 
+    fn not_registered(channel: &str, denom: &str) -> ContractError {
+        ContractError::DenomNotRegistered {
+            channel: channel.to_string(),
+            denom: denom.to_string(),
+        }
+    }
+
+    fn insufficient_funds(available: u128, requested: u128) -> ContractError {
+        ContractError::InsufficientFunds {
+            available: Uint128::new(available),
+            requested: Uint128::new(requested),
+        }
+    }
+
     fn mock_sent_packet(my_channel: &str, amount: u128, denom: &str, sender: &str) -> IbcPacket {
         let data = Ics20Packet {
             denom: denom.into(),
             amount: amount.into(),
             sender: sender.to_string(),
             receiver: "remote-rcpt".to_string(),
+            memo: None,
         };
         IbcPacket::new(
             to_binary(&data).unwrap(),
@@ -461,6 +1535,7 @@ mod test {
             amount: amount.into(),
             sender: "remote-sender".to_string(),
             receiver: receiver.to_string(),
+            memo: None,
         };
         print!("Packet denom: {}", &data.denom);
         IbcPacket::new(
@@ -500,11 +1575,36 @@ mod test {
         let res = ibc_packet_receive(deps.as_mut(), mock_env(), msg).unwrap();
         assert!(res.messages.is_empty());
         let ack: Ics20Ack = from_binary(&res.acknowledgement).unwrap();
-        let no_funds = Ics20Ack::Error(ContractError::InsufficientFunds {}.to_string());
+        let no_funds = Ics20Ack::Error(not_registered(send_channel, cw20_denom).to_string());
         assert_eq!(ack, no_funds);
 
+        // we send some cw20 tokens over, which are escrowed right away
+        let transfer = TransferMsg {
+            channel: send_channel.to_string(),
+            remote_address: "remote-rcpt".to_string(),
+            timeout: None,
+            timeout_height: None,
+            memo: None,
+            idempotency_key: None,
+            fee: None,
+            refund_to: None,
+        };
+        let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+            sender: "local-sender".to_string(),
+            amount: Uint128::new(987654321),
+            msg: to_binary(&transfer).unwrap(),
+        });
+        let info = mock_info(cw20_addr, &[]);
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        let state = query_channel(deps.as_ref(), send_channel.to_string()).unwrap();
+        assert_eq!(state.balances, vec![Amount::cw20(987654321, cw20_addr)]);
+        assert_eq!(state.total_sent, vec![Amount::cw20(0, cw20_addr)]);
+
         // we get a success cache (ack) for a send
-        let msg = IbcPacketAckMsg::new(IbcAcknowledgement::new(ack_success()), sent_packet);
+        let msg = IbcPacketAckMsg::new(
+            IbcAcknowledgement::new(ack_success(AckEncoding::Json)),
+            sent_packet,
+        );
         let res = ibc_packet_ack(deps.as_mut(), mock_env(), msg).unwrap();
         assert_eq!(0, res.messages.len());
 
@@ -518,6 +1618,7 @@ mod test {
         let res = ibc_packet_receive(deps.as_mut(), mock_env(), msg).unwrap();
         assert!(res.messages.is_empty());
         let ack: Ics20Ack = from_binary(&res.acknowledgement).unwrap();
+        let no_funds = Ics20Ack::Error(insufficient_funds(987654321, 1876543210).to_string());
         assert_eq!(ack, no_funds);
 
         // we can receive less than we sent
@@ -554,11 +1655,29 @@ mod test {
         let res = ibc_packet_receive(deps.as_mut(), mock_env(), msg).unwrap();
         assert!(res.messages.is_empty());
         let ack: Ics20Ack = from_binary(&res.acknowledgement).unwrap();
-        let no_funds = Ics20Ack::Error(ContractError::InsufficientFunds {}.to_string());
+        let no_funds = Ics20Ack::Error(not_registered(send_channel, denom).to_string());
         assert_eq!(ack, no_funds);
 
+        // we send some native tokens over, which are escrowed right away
+        let transfer = TransferMsg {
+            channel: send_channel.to_string(),
+            remote_address: "remote-rcpt".to_string(),
+            timeout: None,
+            timeout_height: None,
+            memo: None,
+            idempotency_key: None,
+            fee: None,
+            refund_to: None,
+        };
+        let info = mock_info("local-sender", &coins(987654321, denom));
+        let msg = ExecuteMsg::Transfer(transfer);
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
         // we get a success cache (ack) for a send
-        let msg = IbcPacketAckMsg::new(IbcAcknowledgement::new(ack_success()), sent_packet);
+        let msg = IbcPacketAckMsg::new(
+            IbcAcknowledgement::new(ack_success(AckEncoding::Json)),
+            sent_packet,
+        );
         let res = ibc_packet_ack(deps.as_mut(), mock_env(), msg).unwrap();
         assert_eq!(0, res.messages.len());
 
@@ -572,6 +1691,7 @@ mod test {
         let res = ibc_packet_receive(deps.as_mut(), mock_env(), msg).unwrap();
         assert!(res.messages.is_empty());
         let ack: Ics20Ack = from_binary(&res.acknowledgement).unwrap();
+        let no_funds = Ics20Ack::Error(insufficient_funds(987654321, 1876543210).to_string());
         assert_eq!(ack, no_funds);
 
         // we can receive less than we sent
@@ -590,4 +1710,2926 @@ mod test {
         assert_eq!(state.balances, vec![Amount::native(111111111, denom)]);
         assert_eq!(state.total_sent, vec![Amount::native(987654321, denom)]);
     }
+
+    #[test]
+    fn unregistered_denom_vs_insufficient_escrow() {
+        let send_channel = "channel-9";
+        let other_channel = "channel-7";
+        let mut deps = setup(&[other_channel, send_channel], &[]);
+        let denom = "uatom";
+
+        let receive = |deps: DepsMut, channel: &str, amount: u128| -> Ics20Ack {
+            let packet = mock_receive_packet(channel, amount, denom, "local-rcpt");
+            let msg = IbcPacketReceiveMsg::new(packet);
+            let res = ibc_packet_receive(deps, mock_env(), msg).unwrap();
+            from_binary(&res.acknowledgement).unwrap()
+        };
+
+        // a denom that was never sent out is not registered
+        let ack = receive(deps.as_mut(), send_channel, 100);
+        let err = not_registered(send_channel, denom);
+        assert_eq!(ack, Ics20Ack::Error(err.to_string()));
+        assert_eq!(err.code(), 51);
+
+        let transfer = TransferMsg {
+            channel: send_channel.to_string(),
+            remote_address: "remote-rcpt".to_string(),
+            timeout: None,
+            timeout_height: None,
+            memo: None,
+            idempotency_key: None,
+            fee: None,
+            refund_to: None,
+        };
+        let info = mock_info("local-sender", &coins(100, denom));
+        let msg = ExecuteMsg::Transfer(transfer);
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // once it was, asking for more than is escrowed is insufficient funds
+        let ack = receive(deps.as_mut(), send_channel, 101);
+        let err = insufficient_funds(100, 101);
+        assert_eq!(ack, Ics20Ack::Error(err.to_string()));
+        assert_eq!(err.code(), 8);
+
+        // and so is an escrow that was fully redeemed
+        let ack = receive(deps.as_mut(), send_channel, 100);
+        assert!(matches!(ack, Ics20Ack::Result(_)));
+        let ack = receive(deps.as_mut(), send_channel, 1);
+        assert_eq!(ack, Ics20Ack::Error(insufficient_funds(0, 1).to_string()));
+
+        // the denom is still not registered on other channels
+        let ack = receive(deps.as_mut(), other_channel, 1);
+        let err = not_registered(other_channel, denom);
+        assert_eq!(ack, Ics20Ack::Error(err.to_string()));
+
+        // both are kept apart in the recent failures
+        let msg = QueryMsg::RecentFailures { limit: None };
+        let res: RecentFailuresResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+        let errors: Vec<String> = res.failures.into_iter().map(|f| f.error).collect();
+        let expected = vec![
+            not_registered(other_channel, denom).to_string(),
+            insufficient_funds(0, 1).to_string(),
+            insufficient_funds(100, 101).to_string(),
+            not_registered(send_channel, denom).to_string(),
+        ];
+        assert_eq!(errors, expected);
+    }
+
+    #[test]
+    fn migrate_channel_state_waits_for_packets_in_flight() {
+        let (old, new) = ("channel-3", "channel-7");
+        let mut deps = setup(&[old, new], &[]);
+        let gov = mock_info("gov", &[]);
+        let denom = "uatom";
+
+        let transfer = TransferMsg {
+            channel: old.to_string(),
+            remote_address: "remote-rcpt".to_string(),
+            timeout: None,
+            timeout_height: None,
+            memo: None,
+            idempotency_key: None,
+            fee: None,
+            refund_to: None,
+        };
+        let info = mock_info("local-sender", &coins(1000, denom));
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            info,
+            ExecuteMsg::Transfer(transfer),
+        )
+        .unwrap();
+        let set_minted = ExecuteMsg::SetMintedToken {
+            channel_id: old.to_string(),
+            denom: "uremote".to_string(),
+            token: Some("wrapped".to_string()),
+        };
+        execute(deps.as_mut(), mock_env(), gov.clone(), set_minted).unwrap();
+        execute(deps.as_mut(), mock_env(), gov.clone(), ExecuteMsg::Pause {}).unwrap();
+
+        // the packet sent before the migration would be refunded from an empty channel
+        let migrate = ExecuteMsg::MigrateChannelState {
+            from: old.to_string(),
+            to: new.to_string(),
+        };
+        let err = execute(deps.as_mut(), mock_env(), gov.clone(), migrate.clone()).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::PacketsInFlight {
+                channel: old.to_string(),
+                count: 1
+            }
+        );
+
+        // once it timed out and the sender got their tokens back, the channel can be migrated
+        let packet = mock_sent_packet(old, 1000, denom, "local-sender");
+        let msg = IbcPacketTimeoutMsg::new(packet);
+        let res = ibc_packet_timeout(deps.as_mut(), mock_env(), msg).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![native_payment(1000, denom, "local-sender")]
+        );
+        execute(deps.as_mut(), mock_env(), gov, migrate).unwrap();
+
+        // the wrapped token is minted on the new channel now
+        let minted = MINTED_TOKENS
+            .may_load(&deps.storage, (new, "uremote"))
+            .unwrap();
+        assert_eq!(minted, Some(Addr::unchecked("wrapped")));
+        let minted = MINTED_TOKENS
+            .may_load(&deps.storage, (old, "uremote"))
+            .unwrap();
+        assert_eq!(minted, None);
+    }
+
+    #[test]
+    fn invariants_hold_while_sends_are_in_flight() {
+        let send_channel = "channel-9";
+        let mut deps = setup(&[send_channel], &[]);
+        let denom = "uatom";
+        let violations = |deps: Deps| -> Vec<InvariantViolation> {
+            let msg = QueryMsg::CheckInvariants {};
+            let res: CheckInvariantsResponse =
+                from_binary(&query(deps, mock_env(), msg).unwrap()).unwrap();
+            res.violations
+        };
+
+        for _ in 0..3 {
+            let transfer = TransferMsg {
+                channel: send_channel.to_string(),
+                remote_address: "remote-rcpt".to_string(),
+                timeout: None,
+                timeout_height: None,
+                memo: None,
+                idempotency_key: None,
+                fee: None,
+                refund_to: None,
+            };
+            let info = mock_info("local-sender", &coins(100, denom));
+            execute(
+                deps.as_mut(),
+                mock_env(),
+                info,
+                ExecuteMsg::Transfer(transfer),
+            )
+            .unwrap();
+        }
+        // escrowed, but nothing acknowledged as sent yet
+        assert_eq!(violations(deps.as_ref()), vec![]);
+        let in_flight = |deps: Deps| {
+            IN_FLIGHT_AMOUNT
+                .may_load(deps.storage, (send_channel, denom))
+                .unwrap()
+        };
+        assert_eq!(in_flight(deps.as_ref()), Some(Uint128::new(300)));
+
+        // one is acked, one times out
+        let acked = mock_sent_packet(send_channel, 100, denom, "local-sender");
+        let msg = IbcPacketAckMsg::new(
+            IbcAcknowledgement::new(ack_success(AckEncoding::Json)),
+            acked,
+        );
+        ibc_packet_ack(deps.as_mut(), mock_env(), msg).unwrap();
+        let mut timed_out = mock_sent_packet(send_channel, 100, denom, "local-sender");
+        timed_out.sequence = 3;
+        let msg = IbcPacketTimeoutMsg::new(timed_out);
+        ibc_packet_timeout(deps.as_mut(), mock_env(), msg).unwrap();
+        assert_eq!(violations(deps.as_ref()), vec![]);
+        assert_eq!(in_flight(deps.as_ref()), Some(Uint128::new(100)));
+
+        // once nothing is in flight, more escrow than was sent is a violation again
+        let mut last = mock_sent_packet(send_channel, 100, denom, "local-sender");
+        last.sequence = 4;
+        let msg = IbcPacketAckMsg::new(
+            IbcAcknowledgement::new(ack_success(AckEncoding::Json)),
+            last,
+        );
+        ibc_packet_ack(deps.as_mut(), mock_env(), msg).unwrap();
+        assert_eq!(in_flight(deps.as_ref()), None);
+        assert_eq!(violations(deps.as_ref()), vec![]);
+        increase_channel_balance(deps.as_mut().storage, send_channel, denom, 50u128.into())
+            .unwrap();
+        assert_eq!(
+            violations(deps.as_ref()),
+            vec![InvariantViolation::OutstandingExceedsSent {
+                channel_id: send_channel.to_string(),
+                denom: denom.to_string(),
+                outstanding: Uint128::new(250),
+                total_sent: Uint128::new(200),
+                in_flight: Uint128::zero(),
+            }]
+        );
+    }
+
+    #[test]
+    fn in_flight_cap() {
+        let send_channel = "channel-9";
+        let mut init = mock_init_msg(&[]);
+        init.max_in_flight = Some(2);
+        let mut deps = setup_with_init(&[send_channel], init);
+        let denom = "uatom";
+
+        let transfer = |deps: DepsMut| -> Result<Response, ContractError> {
+            let transfer = TransferMsg {
+                channel: send_channel.to_string(),
+                remote_address: "remote-rcpt".to_string(),
+                timeout: None,
+                timeout_height: None,
+                memo: None,
+                idempotency_key: None,
+                fee: None,
+                refund_to: None,
+            };
+            let info = mock_info("local-sender", &coins(100, denom));
+            execute(deps, mock_env(), info, ExecuteMsg::Transfer(transfer))
+        };
+        let in_flight = |deps: Deps| -> u32 {
+            IN_FLIGHT
+                .may_load(deps.storage, send_channel)
+                .unwrap()
+                .unwrap_or_default()
+        };
+        let warning = attr("in_flight_warning", "underflow");
+
+        // sends are accepted up to the cap
+        transfer(deps.as_mut()).unwrap();
+        transfer(deps.as_mut()).unwrap();
+        assert_eq!(in_flight(deps.as_ref()), 2);
+        let err = transfer(deps.as_mut()).unwrap_err();
+        let too_many = ContractError::TooManyInFlight {
+            channel: send_channel.to_string(),
+            max: 2,
+        };
+        assert_eq!(err, too_many);
+
+        // an ack frees a slot for the next send
+        let acked = mock_sent_packet(send_channel, 100, denom, "local-sender");
+        let msg = IbcPacketAckMsg::new(
+            IbcAcknowledgement::new(ack_success(AckEncoding::Json)),
+            acked,
+        );
+        let res = ibc_packet_ack(deps.as_mut(), mock_env(), msg).unwrap();
+        assert!(!res.attributes.contains(&warning));
+        assert_eq!(in_flight(deps.as_ref()), 1);
+        transfer(deps.as_mut()).unwrap();
+        assert_eq!(transfer(deps.as_mut()).unwrap_err(), too_many);
+
+        // and so does a timeout
+        let mut timed_out = mock_sent_packet(send_channel, 100, denom, "local-sender");
+        timed_out.sequence = 3;
+        let msg = IbcPacketTimeoutMsg::new(timed_out);
+        let res = ibc_packet_timeout(deps.as_mut(), mock_env(), msg).unwrap();
+        assert!(!res.attributes.contains(&warning));
+        assert_eq!(in_flight(deps.as_ref()), 1);
+
+        // resolving more packets than were counted never goes below zero, but is flagged
+        for sequence in 4..=5 {
+            let mut packet = mock_sent_packet(send_channel, 100, denom, "local-sender");
+            packet.sequence = sequence;
+            let msg = IbcPacketTimeoutMsg::new(packet);
+            let res = ibc_packet_timeout(deps.as_mut(), mock_env(), msg).unwrap();
+            let underflow = res.attributes.contains(&warning);
+            assert_eq!(underflow, sequence == 5);
+            assert_eq!(in_flight(deps.as_ref()), 0);
+        }
+
+        // the cap is still enforced from there
+        transfer(deps.as_mut()).unwrap();
+        transfer(deps.as_mut()).unwrap();
+        assert_eq!(transfer(deps.as_mut()).unwrap_err(), too_many);
+    }
+
+    #[test]
+    fn split_receive() {
+        let send_channel = "channel-9";
+        let mut deps = setup(&[send_channel], &[]);
+        let denom = "uatom";
+
+        // escrow some native tokens to receive back
+        let transfer = TransferMsg {
+            channel: send_channel.to_string(),
+            remote_address: "remote-rcpt".to_string(),
+            timeout: None,
+            timeout_height: None,
+            memo: None,
+            idempotency_key: None,
+            fee: None,
+            refund_to: None,
+        };
+        let info = mock_info("local-sender", &coins(10000, denom));
+        let msg = ExecuteMsg::Transfer(transfer);
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let receive = |deps: DepsMut, amount: u128, memo: &str| -> IbcReceiveResponse {
+            let mut packet = mock_receive_packet(send_channel, amount, denom, "local-rcpt");
+            let mut data: Ics20Packet = from_binary(&packet.data).unwrap();
+            data.memo = Some(memo.to_string());
+            packet.data = to_binary(&data).unwrap();
+            let msg = IbcPacketReceiveMsg::new(packet);
+            ibc_packet_receive(deps, mock_env(), msg).unwrap()
+        };
+        // all shares are paid together by calling ourselves
+        let pay_shares = |shares: &[(&str, u128)]| -> SubMsg {
+            let msgs = shares
+                .iter()
+                .map(|(recipient, amount)| {
+                    transfer_msg(Amount::native(*amount, denom), recipient.to_string())
+                })
+                .collect();
+            let exec = WasmMsg::Execute {
+                contract_addr: MOCK_CONTRACT_ADDR.to_string(),
+                msg: to_binary(&ExecuteMsg::PayShares { msgs }).unwrap(),
+                funds: vec![],
+            };
+            SubMsg::reply_on_error(exec, SEND_TOKEN_ID)
+        };
+
+        // a valid two-way split
+        let memo = r#"{"split":[{"addr":"alice","bps":7000},{"addr":"bob","bps":3000}]}"#;
+        let res = receive(deps.as_mut(), 1000, memo);
+        let ack: Ics20Ack = from_binary(&res.acknowledgement).unwrap();
+        assert!(matches!(ack, Ics20Ack::Result(_)));
+        assert_eq!(
+            res.messages,
+            vec![pay_shares(&[("alice", 700), ("bob", 300)])]
+        );
+
+        // shares are rounded down, and the remainder goes to the first recipient
+        let memo = r#"{"split":[{"addr":"alice","bps":3333},{"addr":"bob","bps":3333},{"addr":"carol","bps":3334}]}"#;
+        let res = receive(deps.as_mut(), 100, memo);
+        assert_eq!(
+            res.messages,
+            vec![pay_shares(&[("alice", 34), ("bob", 33), ("carol", 33)])]
+        );
+
+        // shares that do not add up to 10000 bps fail the packet and leave the escrow alone
+        let memo = r#"{"split":[{"addr":"alice","bps":5000},{"addr":"bob","bps":4000}]}"#;
+        let res = receive(deps.as_mut(), 100, memo);
+        assert!(res.messages.is_empty());
+        let ack: Ics20Ack = from_binary(&res.acknowledgement).unwrap();
+        let err = ContractError::InvalidSplit { bps: 9000 };
+        assert_eq!(ack, Ics20Ack::Error(err.to_string()));
+        let state = query_channel(deps.as_ref(), send_channel.to_string()).unwrap();
+        assert_eq!(state.balances, vec![Amount::native(8900, denom)]);
+
+        // any other memo pays the receiver as usual
+        let res = receive(deps.as_mut(), 100, r#"{"note":"split"}"#);
+        assert_eq!(res.messages, vec![native_payment(100, denom, "local-rcpt")]);
+
+        // nobody but the contract can pay out shares
+        let msg = ExecuteMsg::PayShares { msgs: vec![] };
+        let info = mock_info("alice", &[]);
+        let err = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized);
+    }
+
+    #[test]
+    fn claim_grace_period() {
+        let send_channel = "channel-9";
+        let mut deps = setup(&[send_channel], &[]);
+        let denom = "uatom";
+
+        // escrow some native tokens to receive back
+        let transfer = TransferMsg {
+            channel: send_channel.to_string(),
+            remote_address: "remote-rcpt".to_string(),
+            timeout: None,
+            timeout_height: None,
+            memo: None,
+            idempotency_key: None,
+            fee: None,
+            refund_to: None,
+        };
+        let info = mock_info("local-sender", &coins(1000, denom));
+        let msg = ExecuteMsg::Transfer(transfer);
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // only gov can set the grace period
+        let set_grace = ExecuteMsg::SetClaimGracePeriod {
+            grace_period: Some(3600),
+        };
+        let info = mock_info("anyone", &[]);
+        let err = execute(deps.as_mut(), mock_env(), info, set_grace.clone()).unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized);
+        execute(deps.as_mut(), mock_env(), mock_info("gov", &[]), set_grace).unwrap();
+
+        // received tokens are released from escrow, but held rather than paid out
+        let receive = |deps: DepsMut, amount: u128| -> u64 {
+            let packet = mock_receive_packet(send_channel, amount, denom, "local-rcpt");
+            let msg = IbcPacketReceiveMsg::new(packet);
+            let res = ibc_packet_receive(deps, mock_env(), msg).unwrap();
+            assert!(res.messages.is_empty());
+            let ack: Ics20Ack = from_binary(&res.acknowledgement).unwrap();
+            assert!(matches!(ack, Ics20Ack::Result(_)));
+            let id = res.attributes.iter().find(|a| a.key == "delivery_id");
+            id.unwrap().value.parse().unwrap()
+        };
+        let first = receive(deps.as_mut(), 300);
+        let second = receive(deps.as_mut(), 200);
+        let state = query_channel(deps.as_ref(), send_channel.to_string()).unwrap();
+        assert_eq!(state.balances, vec![Amount::native(500, denom)]);
+        // held tokens are not untracked
+        let tracked = total_outstanding(deps.as_ref().storage, denom).unwrap();
+        assert_eq!(tracked, Uint128::new(1000));
+
+        let claimable_until = Expiration::AtTime(mock_env().block.time.plus_seconds(3600));
+        let msg = QueryMsg::PendingDelivery { id: first };
+        let res: PendingDeliveryResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+        let expected = PendingDeliveryResponse {
+            channel: send_channel.to_string(),
+            receiver: "local-rcpt".to_string(),
+            amount: Amount::native(300, denom),
+            claimable_until,
+        };
+        assert_eq!(res, expected);
+
+        // only the receiver can claim, within the grace period
+        let claim = ExecuteMsg::Claim { id: first };
+        let info = mock_info("someone-else", &[]);
+        let err = execute(deps.as_mut(), mock_env(), info, claim.clone()).unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized);
+        let info = mock_info("local-rcpt", &[]);
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), claim.clone()).unwrap();
+        assert_eq!(1, res.messages.len());
+        let payout = transfer_msg(Amount::native(300, denom), "local-rcpt".to_string());
+        assert_eq!(res.messages[0].msg, payout);
+
+        // but only once
+        let err = execute(deps.as_mut(), mock_env(), info, claim).unwrap_err();
+        assert_eq!(err, ContractError::NoPendingDelivery { id: first });
+
+        // gov can only sweep the unclaimed one after the grace period
+        let sweep = ExecuteMsg::SweepUnclaimed {
+            id: second,
+            recipient: Some("rescue".to_string()),
+        };
+        let info = mock_info("gov", &[]);
+        let err = execute(deps.as_mut(), mock_env(), info.clone(), sweep.clone()).unwrap_err();
+        assert_eq!(err, ContractError::ClaimGraceNotExpired { claimable_until });
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_seconds(3600);
+        let res = execute(deps.as_mut(), env.clone(), info, sweep).unwrap();
+        assert_eq!(1, res.messages.len());
+        let payout = transfer_msg(Amount::native(200, denom), "rescue".to_string());
+        assert_eq!(res.messages[0].msg, payout);
+
+        // after which the receiver can no longer claim it
+        let info = mock_info("local-rcpt", &[]);
+        let claim = ExecuteMsg::Claim { id: second };
+        let err = execute(deps.as_mut(), env, info, claim).unwrap_err();
+        assert_eq!(err, ContractError::NoPendingDelivery { id: second });
+        let tracked = total_outstanding(deps.as_ref().storage, denom).unwrap();
+        assert_eq!(tracked, Uint128::new(500));
+    }
+
+    #[test]
+    fn claim_grace_period_with_invalid_receiver() {
+        let send_channel = "channel-9";
+        let mut deps = setup(&[send_channel], &[]);
+        let denom = "uatom";
+
+        // escrow some native tokens to receive back
+        let transfer = TransferMsg {
+            channel: send_channel.to_string(),
+            remote_address: "remote-rcpt".to_string(),
+            timeout: None,
+            timeout_height: None,
+            memo: None,
+            idempotency_key: None,
+            fee: None,
+            refund_to: None,
+        };
+        let info = mock_info("local-sender", &coins(1000, denom));
+        let msg = ExecuteMsg::Transfer(transfer);
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        let set_grace = ExecuteMsg::SetClaimGracePeriod {
+            grace_period: Some(3600),
+        };
+        execute(deps.as_mut(), mock_env(), mock_info("gov", &[]), set_grace).unwrap();
+
+        // a receiver that cannot be held for is acked as an error
+        let packet = mock_receive_packet(send_channel, 300, denom, "x");
+        let msg = IbcPacketReceiveMsg::new(packet);
+        let res = ibc_packet_receive(deps.as_mut(), mock_env(), msg).unwrap();
+        assert!(res.messages.is_empty());
+        let ack: Ics20Ack = from_binary(&res.acknowledgement).unwrap();
+        assert!(matches!(ack, Ics20Ack::Error(_)));
+
+        // so the sender gets a refund, and the escrow must still be there to return later
+        let state = query_channel(deps.as_ref(), send_channel.to_string()).unwrap();
+        assert_eq!(state.balances, vec![Amount::native(1000, denom)]);
+        let tracked = total_outstanding(deps.as_ref().storage, denom).unwrap();
+        assert_eq!(tracked, Uint128::new(1000));
+        let held = PENDING_DELIVERY
+            .range(deps.as_ref().storage, None, None, Order::Ascending)
+            .count();
+        assert_eq!(held, 0);
+    }
+
+    #[test]
+    fn into_ack_response_acks_both_branches() {
+        let mut deps = setup(&["channel-1"], &[]);
+        let packet = mock_receive_packet("channel-1", 100, "ucosm", "local-rcpt");
+
+        // a successful response is passed through untouched
+        let ok = IbcReceiveResponse::new()
+            .set_ack(ack_success(AckEncoding::Json))
+            .add_attribute("action", "receive");
+        let res = into_ack_response(deps.as_mut(), &mock_env(), &packet, Ok(ok.clone()));
+        assert_eq!(res, ok);
+        let failures = RECENT_FAILURES
+            .range(deps.as_ref().storage, None, None, Order::Ascending)
+            .count();
+        assert_eq!(failures, 0);
+
+        // an error still acknowledges the packet, with an error ack, and is recorded
+        let err = Err(ContractError::Paused);
+        let res = into_ack_response(deps.as_mut(), &mock_env(), &packet, err);
+        let ack: Ics20Ack = from_binary(&res.acknowledgement).unwrap();
+        assert_eq!(ack, Ics20Ack::Error(ContractError::Paused.to_string()));
+        assert!(res.messages.is_empty());
+        assert_eq!(res.attributes[1], attr("success", "false"));
+        let failure = RECENT_FAILURES.load(deps.as_ref().storage, 0).unwrap();
+        assert_eq!(failure.channel, "channel-1");
+        assert_eq!(failure.amount, Uint128::new(100));
+        assert_eq!(failure.error, ContractError::Paused.to_string());
+    }
+
+    #[test]
+    fn minted_tokens() {
+        let send_channel = "channel-9";
+        let mut deps = setup(&[send_channel, "channel-1"], &[]);
+        let token = "wrapped-atom";
+        let voucher = format!("{}/{}/uatom", CONTRACT_PORT, send_channel);
+
+        let mint = |recipient: &str, amount: u128| {
+            let msg = Cw20ExecuteMsg::Mint {
+                recipient: recipient.to_string(),
+                amount: Uint128::new(amount),
+            };
+            let exec = WasmMsg::Execute {
+                contract_addr: token.to_string(),
+                msg: to_binary(&msg).unwrap(),
+                funds: vec![],
+            };
+            SubMsg::reply_on_error(exec, MINT_TOKEN_ID)
+        };
+        // a packet of uatom, which is native to the remote chain
+        let foreign_packet = |channel: &str, amount: u128| {
+            let mut packet = mock_receive_packet(channel, amount, "uatom", "local-rcpt");
+            let data =
+                Ics20Packet::new(Uint128::new(amount), "uatom", "remote-sender", "local-rcpt");
+            packet.data = to_binary(&data).unwrap();
+            packet
+        };
+
+        // only gov can mint a token for a denom
+        let set_minted = |channel_id: &str, token: Option<&str>| ExecuteMsg::SetMintedToken {
+            channel_id: channel_id.to_string(),
+            denom: "uatom".to_string(),
+            token: token.map(String::from),
+        };
+        let info = mock_info("foobar", &[]);
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            info,
+            set_minted(send_channel, Some(token)),
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized);
+        let info = mock_info("gov", &[]);
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            info,
+            set_minted(send_channel, Some(token)),
+        )
+        .unwrap();
+
+        // receiving the foreign token mints it, without touching any escrow
+        let msg = IbcPacketReceiveMsg::new(foreign_packet(send_channel, 700));
+        let res = ibc_packet_receive(deps.as_mut(), mock_env(), msg).unwrap();
+        assert_eq!(res.messages, vec![mint("local-rcpt", 700)]);
+        let ack: Ics20Ack = from_binary(&res.acknowledgement).unwrap();
+        assert_eq!(ack, Ics20Ack::Result(b"1".into()));
+        let state = query_channel(deps.as_ref(), send_channel.to_string()).unwrap();
+        assert!(state.balances.is_empty());
+
+        // on a channel without a minted token, it is still rejected
+        let msg = IbcPacketReceiveMsg::new(foreign_packet("channel-1", 700));
+        let res = ibc_packet_receive(deps.as_mut(), mock_env(), msg).unwrap();
+        assert!(res.messages.is_empty());
+        let ack: Ics20Ack = from_binary(&res.acknowledgement).unwrap();
+        assert_eq!(
+            ack,
+            Ics20Ack::Error(ContractError::NoForeignTokens {}.to_string())
+        );
+
+        // sending the wrapped token back burns it, under the voucher denom
+        let allow = ExecuteMsg::Allow(AllowMsg {
+            contract: token.to_string(),
+            gas_limit: None,
+            fee_on_transfer: None,
+            verify_balance: None,
+        });
+        execute(deps.as_mut(), mock_env(), mock_info("gov", &[]), allow).unwrap();
+        let transfer = TransferMsg {
+            channel: send_channel.to_string(),
+            remote_address: "remote-rcpt".to_string(),
+            timeout: None,
+            timeout_height: None,
+            memo: None,
+            idempotency_key: None,
+            fee: None,
+            refund_to: None,
+        };
+        let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+            sender: "local-sender".to_string(),
+            amount: Uint128::new(300),
+            msg: to_binary(&transfer).unwrap(),
+        });
+        let res = execute(deps.as_mut(), mock_env(), mock_info(token, &[]), msg).unwrap();
+        assert_eq!(res.messages.len(), 2);
+        let burn = Cw20ExecuteMsg::Burn {
+            amount: Uint128::new(300),
+        };
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: token.to_string(),
+                msg: to_binary(&burn).unwrap(),
+                funds: vec![],
+            })
+        );
+        match &res.messages[1].msg {
+            CosmosMsg::Ibc(IbcMsg::SendPacket { data, .. }) => {
+                let packet: Ics20Packet = from_binary(data).unwrap();
+                assert_eq!(packet.denom, voucher);
+            }
+            msg => panic!("Unexpected message: {:?}", msg),
+        }
+        let state = query_channel(deps.as_ref(), send_channel.to_string()).unwrap();
+        assert!(state.balances.is_empty());
+
+        // a failed send mints the tokens back to the sender. Without a reply, a failing mint-back
+        // fails the timeout itself rather than losing the tokens, so it can be relayed again
+        let sent_packet = mock_sent_packet(send_channel, 300, &voucher, "local-sender");
+        let msg = IbcPacketTimeoutMsg::new(sent_packet.clone());
+        let res = ibc_packet_timeout(deps.as_mut(), mock_env(), msg).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(mint("local-sender", 300).msg)]
+        );
+        assert_eq!(res.messages[0].reply_on, ReplyOn::Never);
+
+        // a successful one is not counted as escrow
+        let mut sent_packet = sent_packet;
+        sent_packet.sequence += 1;
+        let msg = IbcPacketAckMsg::new(
+            IbcAcknowledgement::new(ack_success(AckEncoding::Json)),
+            sent_packet,
+        );
+        let res = ibc_packet_ack(deps.as_mut(), mock_env(), msg).unwrap();
+        assert!(res.messages.is_empty());
+        let state = query_channel(deps.as_ref(), send_channel.to_string()).unwrap();
+        assert!(state.total_sent.is_empty());
+
+        // the token cannot be minted for a second denom
+        let msg = ExecuteMsg::SetMintedToken {
+            channel_id: send_channel.to_string(),
+            denom: "uosmo".to_string(),
+            token: Some(token.to_string()),
+        };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("gov", &[]), msg).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::MintedTokenTaken {
+                token: token.to_string(),
+                denom: "uatom".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn native_gas_limit() {
+        let send_channel = "channel-9";
+        let mut deps = setup(&[send_channel], &[]);
+        let denom = "uatom";
+
+        let transfer = TransferMsg {
+            channel: send_channel.to_string(),
+            remote_address: "remote-rcpt".to_string(),
+            timeout: None,
+            timeout_height: None,
+            memo: None,
+            idempotency_key: None,
+            fee: None,
+            refund_to: None,
+        };
+        let info = mock_info("local-sender", &coins(1000, denom));
+        let msg = ExecuteMsg::Transfer(transfer);
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // only gov can set a gas limit
+        let set_limit = |gas_limit: Option<u64>| ExecuteMsg::SetNativeGasLimit {
+            denom: denom.to_string(),
+            gas_limit,
+        };
+        let info = mock_info("foobar", &[]);
+        let err = execute(deps.as_mut(), mock_env(), info, set_limit(Some(50000))).unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized);
+        let info = mock_info("gov", &[]);
+        execute(deps.as_mut(), mock_env(), info, set_limit(Some(50000))).unwrap();
+
+        // a release of the denom carries the gas limit
+        let recv_packet = mock_receive_packet(send_channel, 400, denom, "local-rcpt");
+        let msg = IbcPacketReceiveMsg::new(recv_packet);
+        let res = ibc_packet_receive(deps.as_mut(), mock_env(), msg).unwrap();
+        let mut expected = native_payment(400, denom, "local-rcpt");
+        expected.gas_limit = Some(50000);
+        assert_eq!(expected, res.messages[0]);
+
+        // and so does a refund
+        let sent_packet = mock_sent_packet(send_channel, 100, denom, "local-sender");
+        let msg = IbcPacketTimeoutMsg::new(sent_packet);
+        let res = ibc_packet_timeout(deps.as_mut(), mock_env(), msg).unwrap();
+        let mut expected = native_payment(100, denom, "local-sender");
+        expected.gas_limit = Some(50000);
+        assert_eq!(expected, res.messages[0]);
+
+        // once removed, releases are unlimited again
+        let info = mock_info("gov", &[]);
+        execute(deps.as_mut(), mock_env(), info, set_limit(None)).unwrap();
+        let recv_packet = mock_receive_packet(send_channel, 200, denom, "local-rcpt");
+        let msg = IbcPacketReceiveMsg::new(recv_packet);
+        let res = ibc_packet_receive(deps.as_mut(), mock_env(), msg).unwrap();
+        assert_eq!(native_payment(200, denom, "local-rcpt"), res.messages[0]);
+    }
+
+    #[test]
+    fn receive_before_ack_uses_committed_escrow() {
+        let send_channel = "channel-9";
+        let mut deps = setup(&[send_channel], &[]);
+        let denom = "uatom";
+
+        // send out some tokens, the ack has not been relayed yet
+        let transfer = TransferMsg {
+            channel: send_channel.to_string(),
+            remote_address: "remote-rcpt".to_string(),
+            timeout: None,
+            timeout_height: None,
+            memo: None,
+            idempotency_key: None,
+            fee: None,
+            refund_to: None,
+        };
+        let info = mock_info("local-sender", &coins(1000, denom));
+        let msg = ExecuteMsg::Transfer(transfer);
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // the same tokens come back before the ack lands, this must not fail
+        let recv_packet = mock_receive_packet(send_channel, 400, denom, "local-rcpt");
+        let msg = IbcPacketReceiveMsg::new(recv_packet);
+        let res = ibc_packet_receive(deps.as_mut(), mock_env(), msg).unwrap();
+        assert_eq!(1, res.messages.len());
+        assert_eq!(native_payment(400, denom, "local-rcpt"), res.messages[0]);
+        let ack: Ics20Ack = from_binary(&res.acknowledgement).unwrap();
+        assert_eq!(ack, Ics20Ack::Result(b"1".into()));
+
+        // now the ack for the original send arrives
+        let sent_packet = mock_sent_packet(send_channel, 1000, denom, "local-sender");
+        let msg = IbcPacketAckMsg::new(
+            IbcAcknowledgement::new(ack_success(AckEncoding::Json)),
+            sent_packet,
+        );
+        let res = ibc_packet_ack(deps.as_mut(), mock_env(), msg).unwrap();
+        assert_eq!(0, res.messages.len());
+
+        let state = query_channel(deps.as_ref(), send_channel.to_string()).unwrap();
+        assert_eq!(state.balances, vec![Amount::native(600, denom)]);
+        assert_eq!(state.total_sent, vec![Amount::native(1000, denom)]);
+    }
+
+    #[test]
+    fn failed_send_releases_escrow() {
+        let send_channel = "channel-9";
+        let mut deps = setup(&[send_channel], &[]);
+        let denom = "uatom";
+
+        let transfer = TransferMsg {
+            channel: send_channel.to_string(),
+            remote_address: "remote-rcpt".to_string(),
+            timeout: None,
+            timeout_height: None,
+            memo: None,
+            idempotency_key: None,
+            fee: None,
+            refund_to: None,
+        };
+        let info = mock_info("local-sender", &coins(1000, denom));
+        let msg = ExecuteMsg::Transfer(transfer);
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // error ack refunds the sender and releases the escrow
+        let sent_packet = mock_sent_packet(send_channel, 1000, denom, "local-sender");
+        let msg = IbcPacketAckMsg::new(
+            IbcAcknowledgement::new(ack_fail(AckEncoding::Json, "bad coin".into())),
+            sent_packet,
+        );
+        let res = ibc_packet_ack(deps.as_mut(), mock_env(), msg).unwrap();
+        assert_eq!(1, res.messages.len());
+        assert_eq!(native_payment(1000, denom, "local-sender"), res.messages[0]);
+
+        let state = query_channel(deps.as_ref(), send_channel.to_string()).unwrap();
+        assert_eq!(state.balances, vec![Amount::native(0, denom)]);
+        assert_eq!(state.total_sent, vec![Amount::native(0, denom)]);
+    }
+
+    #[test]
+    fn events_include_denom_trace() {
+        let send_channel = "channel-9";
+        let mut deps = setup(&[send_channel], &[]);
+        // a voucher we received over another channel before
+        let denom = "transfer/channel-3/uatom";
+
+        let transfer = TransferMsg {
+            channel: send_channel.to_string(),
+            remote_address: "remote-rcpt".to_string(),
+            timeout: None,
+            timeout_height: None,
+            memo: None,
+            idempotency_key: None,
+            fee: None,
+            refund_to: None,
+        };
+        let info = mock_info("local-sender", &coins(1000, denom));
+        let msg = ExecuteMsg::Transfer(transfer);
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let sent_packet = mock_sent_packet(send_channel, 1000, denom, "local-sender");
+        let msg = IbcPacketAckMsg::new(
+            IbcAcknowledgement::new(ack_success(AckEncoding::Json)),
+            sent_packet,
+        );
+        let res = ibc_packet_ack(deps.as_mut(), mock_env(), msg).unwrap();
+        assert!(res
+            .attributes
+            .contains(&attr("denom_trace", "transfer/channel-3")));
+
+        // it comes back with the full voucher path
+        let recv_packet = mock_receive_packet(send_channel, 400, denom, "local-rcpt");
+        let msg = IbcPacketReceiveMsg::new(recv_packet);
+        let res = ibc_packet_receive(deps.as_mut(), mock_env(), msg).unwrap();
+        assert!(res.attributes.contains(&attr("denom", denom)));
+        assert!(res.attributes.contains(&attr(
+            "denom_trace",
+            "transfer/channel-1234/transfer/channel-3"
+        )));
+    }
+
+    #[test]
+    fn min_transfer_on_receive() {
+        let send_channel = "channel-9";
+        let mut deps = setup(&[send_channel], &[]);
+        let denom = "uatom";
+
+        let transfer = TransferMsg {
+            channel: send_channel.to_string(),
+            remote_address: "remote-rcpt".to_string(),
+            timeout: None,
+            timeout_height: None,
+            memo: None,
+            idempotency_key: None,
+            fee: None,
+            refund_to: None,
+        };
+        let info = mock_info("local-sender", &coins(1000, denom));
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            info,
+            ExecuteMsg::Transfer(transfer),
+        )
+        .unwrap();
+
+        let msg = ExecuteMsg::SetMinTransfer {
+            denom: denom.to_string(),
+            amount: Some(Uint128::new(100)),
+        };
+        execute(deps.as_mut(), mock_env(), mock_info("gov", &[]), msg).unwrap();
+
+        // below the floor is acknowledged as failure, without touching the escrow
+        let recv_packet = mock_receive_packet(send_channel, 99, denom, "local-rcpt");
+        let msg = IbcPacketReceiveMsg::new(recv_packet);
+        let res = ibc_packet_receive(deps.as_mut(), mock_env(), msg).unwrap();
+        assert!(res.messages.is_empty());
+        let ack: Ics20Ack = from_binary(&res.acknowledgement).unwrap();
+        let below = ContractError::BelowMinTransfer {
+            min: Uint128::new(100),
+        };
+        assert_eq!(ack, Ics20Ack::Error(below.to_string()));
+        let state = query_channel(deps.as_ref(), send_channel.to_string()).unwrap();
+        assert_eq!(state.balances, vec![Amount::native(1000, denom)]);
+
+        // the floor itself is released
+        let recv_packet = mock_receive_packet(send_channel, 100, denom, "local-rcpt");
+        let msg = IbcPacketReceiveMsg::new(recv_packet);
+        let res = ibc_packet_receive(deps.as_mut(), mock_env(), msg).unwrap();
+        assert_eq!(1, res.messages.len());
+        let state = query_channel(deps.as_ref(), send_channel.to_string()).unwrap();
+        assert_eq!(state.balances, vec![Amount::native(900, denom)]);
+    }
+
+    #[test]
+    fn failed_payout_restores_escrow() {
+        let send_channel = "channel-9";
+        let mut deps = setup(&[send_channel], &[]);
+        let denom = "uatom";
+
+        let transfer = TransferMsg {
+            channel: send_channel.to_string(),
+            remote_address: "remote-rcpt".to_string(),
+            timeout: None,
+            timeout_height: None,
+            memo: None,
+            idempotency_key: None,
+            fee: None,
+            refund_to: None,
+        };
+        let info = mock_info("local-sender", &coins(1000, denom));
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            info,
+            ExecuteMsg::Transfer(transfer),
+        )
+        .unwrap();
+
+        // the receive releases the escrow and pays out
+        let recv_packet = mock_receive_packet(send_channel, 400, denom, "other-chain1rcpt");
+        let msg = IbcPacketReceiveMsg::new(recv_packet);
+        let res = ibc_packet_receive(deps.as_mut(), mock_env(), msg).unwrap();
+        assert_eq!(
+            native_payment(400, denom, "other-chain1rcpt"),
+            res.messages[0]
+        );
+        let state = query_channel(deps.as_ref(), send_channel.to_string()).unwrap();
+        assert_eq!(state.balances, vec![Amount::native(600, denom)]);
+
+        // but the bank send fails, so the tokens go back into escrow
+        let err = "invalid address".to_string();
+        let failed = Reply {
+            id: SEND_TOKEN_ID,
+            result: ContractResult::Err(err.clone()),
+        };
+        let res = reply(deps.as_mut(), mock_env(), failed).unwrap();
+        assert_eq!(res.data, Some(ack_fail(AckEncoding::Json, err)));
+        let state = query_channel(deps.as_ref(), send_channel.to_string()).unwrap();
+        assert_eq!(state.balances, vec![Amount::native(1000, denom)]);
+    }
+
+    #[test]
+    fn verify_balance_catches_lying_cw20() {
+        let send_channel = "channel-9";
+        let cw20_addr = "lying-token";
+        let cw20_denom = "cw20:lying-token";
+        let mut deps = setup(&[send_channel], &[]);
+        let allow = ExecuteMsg::Allow(AllowMsg {
+            contract: cw20_addr.to_string(),
+            gas_limit: None,
+            fee_on_transfer: None,
+            verify_balance: Some(true),
+        });
+        execute(deps.as_mut(), mock_env(), mock_info("gov", &[]), allow).unwrap();
+        increase_channel_balance(
+            deps.as_mut().storage,
+            send_channel,
+            cw20_denom,
+            1000u128.into(),
+        )
+        .unwrap();
+
+        // the token reports the given balance for anyone
+        let set_balance = |deps: &mut MockDeps, balance| {
+            deps.querier.update_wasm(move |query| match query {
+                WasmQuery::Smart { contract_addr, .. } if contract_addr == cw20_addr => {
+                    let res = cw20::BalanceResponse {
+                        balance: Uint128::new(balance),
+                    };
+                    SystemResult::Ok(ContractResult::Ok(to_binary(&res).unwrap()))
+                }
+                _ => panic!("Unexpected query: {:?}", query),
+            });
+        };
+        let success = || Reply {
+            id: SEND_TOKEN_ID,
+            result: ContractResult::Ok(SubMsgExecutionResponse {
+                events: vec![],
+                data: None,
+            }),
+        };
+
+        // the payout asks for a reply on success too, to check our balance
+        set_balance(&mut deps, 1000);
+        let recv_packet = mock_receive_packet(send_channel, 400, cw20_denom, "local-rcpt");
+        let msg = IbcPacketReceiveMsg::new(recv_packet.clone());
+        let res = ibc_packet_receive(deps.as_mut(), mock_env(), msg).unwrap();
+        let mut expected = cw20_payment(400, cw20_addr, "local-rcpt", None);
+        expected.reply_on = ReplyOn::Always;
+        assert_eq!(res.messages, vec![expected]);
+        let state = query_channel(deps.as_ref(), send_channel.to_string()).unwrap();
+        assert_eq!(state.balances, vec![Amount::cw20(600, cw20_addr)]);
+
+        // the token claims success, but our balance did not change
+        let res = reply(deps.as_mut(), mock_env(), success()).unwrap();
+        let err = ContractError::PayoutNotMoved {
+            expected: Uint128::new(400),
+            moved: Uint128::zero(),
+        };
+        assert_eq!(res.data, Some(ack_fail(AckEncoding::Json, err.to_string())));
+        let state = query_channel(deps.as_ref(), send_channel.to_string()).unwrap();
+        assert_eq!(state.balances, vec![Amount::cw20(1000, cw20_addr)]);
+
+        // an honest payout passes the check
+        let msg = IbcPacketReceiveMsg::new(recv_packet);
+        ibc_packet_receive(deps.as_mut(), mock_env(), msg).unwrap();
+        set_balance(&mut deps, 600);
+        let res = reply(deps.as_mut(), mock_env(), success()).unwrap();
+        assert_eq!(res.data, None);
+        let state = query_channel(deps.as_ref(), send_channel.to_string()).unwrap();
+        assert_eq!(state.balances, vec![Amount::cw20(600, cw20_addr)]);
+
+        // without the flag, nothing is queried and only errors get a reply
+        let allow = ExecuteMsg::Allow(AllowMsg {
+            contract: cw20_addr.to_string(),
+            gas_limit: None,
+            fee_on_transfer: None,
+            verify_balance: Some(false),
+        });
+        execute(deps.as_mut(), mock_env(), mock_info("gov", &[]), allow).unwrap();
+        deps.querier
+            .update_wasm(|query| panic!("Unexpected query: {:?}", query));
+        let recv_packet = mock_receive_packet(send_channel, 100, cw20_denom, "local-rcpt");
+        let msg = IbcPacketReceiveMsg::new(recv_packet);
+        let res = ibc_packet_receive(deps.as_mut(), mock_env(), msg).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![cw20_payment(100, cw20_addr, "local-rcpt", None)]
+        );
+    }
+
+    #[test]
+    fn close_init_is_rejected() {
+        let send_channel = "channel-9";
+        let mut deps = setup(&[send_channel], &[]);
+
+        // escrow some tokens
+        let transfer = TransferMsg {
+            channel: send_channel.to_string(),
+            remote_address: "remote-rcpt".to_string(),
+            timeout: None,
+            timeout_height: None,
+            memo: None,
+            idempotency_key: None,
+            fee: None,
+            refund_to: None,
+        };
+        let info = mock_info("local-sender", &coins(1000, "ucosm"));
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            info,
+            ExecuteMsg::Transfer(transfer),
+        )
+        .unwrap();
+
+        // we cannot abandon the escrow by closing from our side
+        let msg = IbcChannelCloseMsg::new_init(mock_channel(send_channel));
+        let err = ibc_channel_close(deps.as_mut(), mock_env(), msg).unwrap_err();
+        assert_eq!(err, ContractError::CannotCloseChannel {});
+        let state = query_channel(deps.as_ref(), send_channel.to_string()).unwrap();
+        assert_eq!(state.balances, vec![Amount::native(1000, "ucosm")]);
+
+        // but a close by the counterparty proceeds and refunds the escrow
+        let msg = IbcChannelCloseMsg::new_confirm(mock_channel(send_channel));
+        let res = ibc_channel_close(deps.as_mut(), mock_env(), msg).unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            transfer_msg(Amount::native(1000, "ucosm"), "gov".to_string())
+        );
+        assert!(res.attributes.contains(&attr("done", "true")));
+    }
+
+    #[test]
+    fn close_refunds_in_batches() {
+        let send_channel = "channel-9";
+        let mut deps = setup(&[send_channel], &[]);
+
+        // escrow more denoms than can be refunded at once
+        let denoms: Vec<String> = (0..MAX_CLOSE_REFUNDS + 2)
+            .map(|i| format!("denom{:02}", i))
+            .collect();
+        for denom in &denoms {
+            let transfer = TransferMsg {
+                channel: send_channel.to_string(),
+                remote_address: "remote-rcpt".to_string(),
+                timeout: None,
+                timeout_height: None,
+                memo: None,
+                idempotency_key: None,
+                fee: None,
+                refund_to: None,
+            };
+            let info = mock_info("local-sender", &coins(1000, denom));
+            execute(
+                deps.as_mut(),
+                mock_env(),
+                info,
+                ExecuteMsg::Transfer(transfer),
+            )
+            .unwrap();
+        }
+
+        let msg = IbcChannelCloseMsg::new_confirm(mock_channel(send_channel));
+        let res = ibc_channel_close(deps.as_mut(), mock_env(), msg).unwrap();
+        assert_eq!(MAX_CLOSE_REFUNDS, res.messages.len());
+        assert_eq!(
+            res.messages[0].msg,
+            transfer_msg(Amount::native(1000, &denoms[0]), "gov".to_string())
+        );
+        assert!(res.attributes.contains(&attr("done", "false")));
+        assert_eq!(
+            CLOSE_CURSOR
+                .load(&deps.storage, send_channel)
+                .unwrap()
+                .as_str(),
+            denoms[MAX_CLOSE_REFUNDS - 1].as_str()
+        );
+
+        // only gov can continue
+        let cont = ExecuteMsg::ContinueClose {
+            channel_id: send_channel.to_string(),
+        };
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("anyone", &[]),
+            cont.clone(),
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized);
+
+        // the second call refunds the rest and clears the cursor
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("gov", &[]),
+            cont.clone(),
+        )
+        .unwrap();
+        assert_eq!(2, res.messages.len());
+        assert_eq!(
+            res.messages[1].msg,
+            transfer_msg(
+                Amount::native(1000, &denoms[MAX_CLOSE_REFUNDS + 1]),
+                "gov".to_string()
+            )
+        );
+        assert!(res.attributes.contains(&attr("done", "true")));
+        assert!(!CLOSE_CURSOR.has(&deps.storage, send_channel));
+
+        let state = query_channel(deps.as_ref(), send_channel.to_string()).unwrap();
+        assert!(state.balances.iter().all(|b| b.amount().is_zero()));
+
+        // nothing left to continue
+        let err = execute(deps.as_mut(), mock_env(), mock_info("gov", &[]), cont).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::NoPendingClose {
+                id: send_channel.to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn close_refunds_to_recovery() {
+        let channels = ["channel-8", "channel-9"];
+        let mut deps = setup(&channels, &[]);
+        for channel in channels {
+            let transfer = TransferMsg {
+                channel: channel.to_string(),
+                remote_address: "remote-rcpt".to_string(),
+                timeout: None,
+                timeout_height: None,
+                memo: None,
+                idempotency_key: None,
+                fee: None,
+                refund_to: None,
+            };
+            let info = mock_info("local-sender", &coins(1000, "uatom"));
+            execute(
+                deps.as_mut(),
+                mock_env(),
+                info,
+                ExecuteMsg::Transfer(transfer),
+            )
+            .unwrap();
+        }
+
+        // without a recovery address, gov gets the refund
+        let msg = IbcChannelCloseMsg::new_confirm(mock_channel(channels[0]));
+        let res = ibc_channel_close(deps.as_mut(), mock_env(), msg).unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            transfer_msg(Amount::native(1000, "uatom"), "gov".to_string())
+        );
+
+        // once there is one, it is paid instead
+        RECOVERY
+            .save(deps.as_mut().storage, &Addr::unchecked("recovery"))
+            .unwrap();
+        let msg = IbcChannelCloseMsg::new_confirm(mock_channel(channels[1]));
+        let res = ibc_channel_close(deps.as_mut(), mock_env(), msg).unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            transfer_msg(Amount::native(1000, "uatom"), "recovery".to_string())
+        );
+    }
+
+    #[test]
+    fn duplicate_ack_is_ignored() {
+        let send_channel = "channel-9";
+        let mut deps = setup(&[send_channel], &[]);
+        let denom = "uatom";
+
+        let transfer = TransferMsg {
+            channel: send_channel.to_string(),
+            remote_address: "remote-rcpt".to_string(),
+            timeout: None,
+            timeout_height: None,
+            memo: None,
+            idempotency_key: None,
+            fee: None,
+            refund_to: None,
+        };
+        let info = mock_info("local-sender", &coins(1000, denom));
+        let msg = ExecuteMsg::Transfer(transfer);
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let sent_packet = mock_sent_packet(send_channel, 1000, denom, "local-sender");
+        let msg = IbcPacketAckMsg::new(
+            IbcAcknowledgement::new(ack_success(AckEncoding::Json)),
+            sent_packet.clone(),
+        );
+        ibc_packet_ack(deps.as_mut(), mock_env(), msg.clone()).unwrap();
+        let state = query_channel(deps.as_ref(), send_channel.to_string()).unwrap();
+        assert_eq!(state.balances, vec![Amount::native(1000, denom)]);
+        assert_eq!(state.total_sent, vec![Amount::native(1000, denom)]);
+
+        // the same ack again is only reported
+        let res = ibc_packet_ack(deps.as_mut(), mock_env(), msg).unwrap();
+        assert_eq!(0, res.messages.len());
+        assert!(res.attributes.contains(&attr("duplicate", "true")));
+        let again = query_channel(deps.as_ref(), send_channel.to_string()).unwrap();
+        assert_eq!(again, state);
+
+        // as is a timeout for the already acknowledged packet, which must not refund
+        let msg = IbcPacketTimeoutMsg::new(sent_packet);
+        let res = ibc_packet_timeout(deps.as_mut(), mock_env(), msg).unwrap();
+        assert_eq!(0, res.messages.len());
+        assert!(res.attributes.contains(&attr("duplicate", "true")));
+        let again = query_channel(deps.as_ref(), send_channel.to_string()).unwrap();
+        assert_eq!(again, state);
+    }
+
+    #[test]
+    fn memo_length_is_bounded_on_receive() {
+        let send_channel = "channel-9";
+        let mut deps = setup(&[send_channel], &[]);
+        let denom = "uatom";
+
+        let transfer = TransferMsg {
+            channel: send_channel.to_string(),
+            remote_address: "remote-rcpt".to_string(),
+            timeout: None,
+            timeout_height: None,
+            memo: None,
+            idempotency_key: None,
+            fee: None,
+            refund_to: None,
+        };
+        let info = mock_info("local-sender", &coins(1000, denom));
+        let msg = ExecuteMsg::Transfer(transfer);
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let with_memo = |memo: String| {
+            let mut packet = mock_receive_packet(send_channel, 100, denom, "local-rcpt");
+            let mut data: Ics20Packet = from_binary(&packet.data).unwrap();
+            data.memo = Some(memo);
+            packet.data = to_binary(&data).unwrap();
+            IbcPacketReceiveMsg::new(packet)
+        };
+
+        // one byte over the limit fails before touching the escrow
+        let msg = with_memo("m".repeat(DEFAULT_MAX_MEMO_LEN as usize + 1));
+        let res = ibc_packet_receive(deps.as_mut(), mock_env(), msg).unwrap();
+        assert!(res.messages.is_empty());
+        let ack: Ics20Ack = from_binary(&res.acknowledgement).unwrap();
+        let too_long = ContractError::MemoTooLong {
+            max: DEFAULT_MAX_MEMO_LEN,
+        };
+        assert_eq!(ack, Ics20Ack::Error(too_long.to_string()));
+        let state = query_channel(deps.as_ref(), send_channel.to_string()).unwrap();
+        assert_eq!(state.balances, vec![Amount::native(1000, denom)]);
+
+        // exactly at the limit is accepted
+        let msg = with_memo("m".repeat(DEFAULT_MAX_MEMO_LEN as usize));
+        let res = ibc_packet_receive(deps.as_mut(), mock_env(), msg).unwrap();
+        assert_eq!(1, res.messages.len());
+        let state = query_channel(deps.as_ref(), send_channel.to_string()).unwrap();
+        assert_eq!(state.balances, vec![Amount::native(900, denom)]);
+    }
+
+    #[test]
+    fn parse_denom_traces() {
+        let local = parse_denom_trace("ucosm");
+        assert_eq!(local.base_denom, "ucosm");
+        assert!(local.path.is_empty());
+
+        let cw20 = parse_denom_trace("cw20:token-addr");
+        assert_eq!(cw20.base_denom, "cw20:token-addr");
+        assert!(cw20.path.is_empty());
+
+        let multi = parse_denom_trace("transfer/channel-3/wasm.port/channel-17/uatom");
+        assert_eq!(multi.base_denom, "uatom");
+        assert_eq!(
+            multi.path,
+            vec![
+                ("transfer".to_string(), "channel-3".to_string()),
+                ("wasm.port".to_string(), "channel-17".to_string())
+            ]
+        );
+
+        // slashes in the base denom are not taken as a hop
+        let pool = parse_denom_trace("transfer/channel-3/gamm/pool/1");
+        assert_eq!(pool.base_denom, "gamm/pool/1");
+        assert_eq!(
+            pool.path,
+            vec![("transfer".to_string(), "channel-3".to_string())]
+        );
+    }
+
+    #[test]
+    fn reconnect_channel() {
+        let channel_id = "channel-9";
+        let mut deps = setup(&[channel_id], &[]);
+
+        // connecting again with identical data is fine
+        add_channel(deps.as_mut(), channel_id);
+        let state = query_channel(deps.as_ref(), channel_id.to_string()).unwrap();
+        assert_eq!(state.info, mock_channel_info(channel_id));
+
+        // but we do not overwrite the counterparty
+        let mut channel = mock_channel(channel_id);
+        channel.counterparty_endpoint.channel_id = "channel-666".to_string();
+        let msg = IbcChannelConnectMsg::new_ack(channel, ICS20_VERSION);
+        let err = ibc_channel_connect(deps.as_mut(), mock_env(), msg).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::ChannelAlreadyExists {
+                id: channel_id.to_string()
+            }
+        );
+        let state = query_channel(deps.as_ref(), channel_id.to_string()).unwrap();
+        assert_eq!(state.info, mock_channel_info(channel_id));
+    }
+
+    #[test]
+    fn pinned_counterparty() {
+        let mut init_msg = mock_init_msg(&[]);
+        init_msg.expected_counterparties = Some(vec![
+            ExpectedCounterparty {
+                channel_id: "channel-1".to_string(),
+                counterparty_port: REMOTE_PORT.to_string(),
+                counterparty_channel: "channel-15".to_string(),
+            },
+            ExpectedCounterparty {
+                channel_id: "channel-2".to_string(),
+                counterparty_port: REMOTE_PORT.to_string(),
+                counterparty_channel: "channel-77".to_string(),
+            },
+        ]);
+        // channel-1 matches its pin, channel-3 is not pinned
+        let mut deps = setup_with_init(&["channel-1", "channel-3"], init_msg);
+        let state = query_channel(deps.as_ref(), "channel-1".to_string()).unwrap();
+        assert_eq!(state.info, mock_channel_info("channel-1"));
+        let state = query_channel(deps.as_ref(), "channel-3".to_string()).unwrap();
+        assert_eq!(state.info, mock_channel_info("channel-3"));
+
+        // channel-2 tries to connect to another counterparty than pinned
+        let msg = IbcChannelConnectMsg::new_ack(mock_channel("channel-2"), ICS20_VERSION);
+        let err = ibc_channel_connect(deps.as_mut(), mock_env(), msg).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::UnexpectedCounterparty {
+                id: "channel-2".to_string(),
+                expected: "transfer/channel-77".to_string(),
+                actual: "transfer/channel-25".to_string(),
+            }
+        );
+        query_channel(deps.as_ref(), "channel-2".to_string()).unwrap_err();
+    }
+
+    #[test]
+    fn select_highest_version() {
+        assert_eq!(select_version(None).unwrap(), ICS20_VERSION);
+        assert_eq!(select_version(Some(ICS20_VERSION)).unwrap(), ICS20_VERSION);
+        assert_eq!(
+            select_version(Some(ICS20_2_VERSION)).unwrap(),
+            ICS20_2_VERSION
+        );
+        let err = select_version(Some("ics20-3")).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::InvalidIbcVersion {
+                version: "ics20-3".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn negotiate_version() {
+        let mut deps = setup(&[], &[]);
+
+        // both versions are accepted, if both ends agree
+        for version in [ICS20_VERSION, ICS20_2_VERSION] {
+            let channel = mock_channel_with_version("channel-1", version);
+            let msg = IbcChannelOpenMsg::new_try(channel, version);
+            ibc_channel_open(deps.as_mut(), mock_env(), msg).unwrap();
+        }
+
+        // mismatched versions are rejected
+        let channel = mock_channel_with_version("channel-1", ICS20_2_VERSION);
+        let msg = IbcChannelOpenMsg::new_try(channel, ICS20_VERSION);
+        let err = ibc_channel_open(deps.as_mut(), mock_env(), msg).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::InvalidIbcVersion {
+                version: ICS20_VERSION.to_string()
+            }
+        );
+
+        // as are unknown ones
+        let channel = mock_channel_with_version("channel-1", "ics20-3");
+        let msg = IbcChannelOpenMsg::new_init(channel);
+        let err = ibc_channel_open(deps.as_mut(), mock_env(), msg).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::InvalidIbcVersion {
+                version: "ics20-3".to_string()
+            }
+        );
+
+        // the negotiated version is stored
+        add_channel_with_version(deps.as_mut(), "channel-2", ICS20_2_VERSION);
+        let state = query_channel(deps.as_ref(), "channel-2".to_string()).unwrap();
+        assert_eq!(state.info.version, ICS20_2_VERSION);
+
+        // a counterparty offering ics20-1 is matched
+        add_channel_with_version(deps.as_mut(), "channel-3", ICS20_VERSION);
+        let state = query_channel(deps.as_ref(), "channel-3".to_string()).unwrap();
+        assert_eq!(state.info.version, ICS20_VERSION);
+    }
+
+    #[test]
+    fn supported_versions_match_handshake() {
+        let mut deps = setup(&[], &[]);
+        let msg = QueryMsg::SupportedVersions {};
+        let res: SupportedVersionsResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+        #[cfg(not(feature = "ibc-v2"))]
+        assert_eq!(res.versions, vec![ICS20_2_VERSION, ICS20_VERSION]);
+        #[cfg(feature = "ibc-v2")]
+        assert_eq!(
+            res.versions,
+            vec![ICS20_PAYLOAD_VERSION, ICS20_2_VERSION, ICS20_VERSION]
+        );
+
+        // every listed version opens a channel, on both ends of the handshake
+        for version in &res.versions {
+            let channel = mock_channel_with_version("channel-1", version);
+            let msg = IbcChannelOpenMsg::new_init(channel.clone());
+            ibc_channel_open(deps.as_mut(), mock_env(), msg).unwrap();
+            let msg = IbcChannelOpenMsg::new_try(channel, version);
+            ibc_channel_open(deps.as_mut(), mock_env(), msg).unwrap();
+        }
+
+        // and anything else is rejected
+        for version in ["ics20-0", "ics20-3", ""] {
+            let channel = mock_channel_with_version("channel-1", version);
+            let msg = IbcChannelOpenMsg::new_init(channel);
+            ibc_channel_open(deps.as_mut(), mock_env(), msg).unwrap_err();
+        }
+    }
+
+    #[cfg(feature = "ibc-v2")]
+    #[test]
+    fn payload_round_trip() {
+        let channel = "channel-9";
+        let denom = "ucosm";
+        let mut deps = setup(&[], &[]);
+        add_channel_with_version(deps.as_mut(), channel, ICS20_PAYLOAD_VERSION);
+
+        // a send is wrapped in a payload addressed from our port to the counterparty's
+        let transfer = TransferMsg {
+            channel: channel.to_string(),
+            remote_address: "remote-rcpt".to_string(),
+            timeout: None,
+            timeout_height: None,
+            memo: None,
+            idempotency_key: None,
+            fee: None,
+            refund_to: None,
+        };
+        let info = mock_info("local-sender", &coins(1000, denom));
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            info,
+            ExecuteMsg::Transfer(transfer),
+        )
+        .unwrap();
+        let data = match &res.messages[0].msg {
+            CosmosMsg::Ibc(IbcMsg::SendPacket { data, .. }) => data.clone(),
+            msg => panic!("Unexpected message: {:?}", msg),
+        };
+        let payload: Ics20Payload = from_binary(&data).unwrap();
+        assert_eq!(payload.source_port, CONTRACT_PORT);
+        assert_eq!(payload.destination_port, REMOTE_PORT);
+        assert_eq!(payload.version, ICS20_VERSION);
+        assert_eq!(payload.encoding, "application/json");
+        let sent: Ics20Packet = from_binary(&payload.value).unwrap();
+        assert_eq!(
+            sent,
+            Ics20Packet::new(Uint128::new(1000), denom, "local-sender", "remote-rcpt")
+        );
+
+        // the ack of that packet is decoded the same way
+        let mut sent_packet = mock_sent_packet(channel, 1000, denom, "local-sender");
+        sent_packet.data = data;
+        let msg = IbcPacketAckMsg::new(
+            IbcAcknowledgement::new(ack_success(AckEncoding::Json)),
+            sent_packet,
+        );
+        ibc_packet_ack(deps.as_mut(), mock_env(), msg).unwrap();
+        let state = query_channel(deps.as_ref(), channel.to_string()).unwrap();
+        assert_eq!(state.total_sent, vec![Amount::native(1000, denom)]);
+
+        // an incoming payload is unwrapped and released like any other packet
+        let wrap = |packet: &IbcPacket, source_port: &str| {
+            let payload = Ics20Payload {
+                source_port: source_port.to_string(),
+                destination_port: packet.dest.port_id.clone(),
+                version: ICS20_VERSION.to_string(),
+                encoding: "application/json".to_string(),
+                value: packet.data.clone(),
+            };
+            let mut packet = packet.clone();
+            packet.data = to_binary(&payload).unwrap();
+            packet
+        };
+        let recv_packet = mock_receive_packet(channel, 400, denom, "local-rcpt");
+        let msg = IbcPacketReceiveMsg::new(wrap(&recv_packet, REMOTE_PORT));
+        let res = ibc_packet_receive(deps.as_mut(), mock_env(), msg).unwrap();
+        assert_eq!(res.acknowledgement, ack_success(AckEncoding::Json));
+        assert_eq!(res.messages, vec![native_payment(400, denom, "local-rcpt")]);
+
+        // a payload from a port other than the packet's is rejected
+        let msg = IbcPacketReceiveMsg::new(wrap(&recv_packet, "other-port"));
+        let res = ibc_packet_receive(deps.as_mut(), mock_env(), msg).unwrap();
+        assert!(res.messages.is_empty());
+        let ack: Ics20Ack = from_binary(&res.acknowledgement).unwrap();
+        let err = ContractError::PayloadPortMismatch {
+            source_port: "other-port".to_string(),
+            destination_port: CONTRACT_PORT.to_string(),
+        };
+        assert_eq!(ack, Ics20Ack::Error(err.to_string()));
+
+        // as is a classic packet on a payload channel
+        let msg = IbcPacketReceiveMsg::new(recv_packet);
+        let res = ibc_packet_receive(deps.as_mut(), mock_env(), msg).unwrap();
+        assert!(res.messages.is_empty());
+    }
+
+    #[test]
+    fn structured_error_acks() {
+        let v1_channel = "channel-1";
+        let v2_channel = "channel-2";
+        let mut deps = setup(&[v1_channel], &[]);
+        add_channel_with_version(deps.as_mut(), v2_channel, ICS20_2_VERSION);
+
+        // nothing was sent, so neither can receive
+        let receive = |deps: DepsMut, channel: &str| -> Ics20Ack {
+            let recv_packet = mock_receive_packet(channel, 100, "uatom", "local-rcpt");
+            let msg = IbcPacketReceiveMsg::new(recv_packet);
+            let res = ibc_packet_receive(deps, mock_env(), msg).unwrap();
+            from_binary(&res.acknowledgement).unwrap()
+        };
+
+        // ics20-1 keeps the plain message
+        let err = not_registered(v1_channel, "uatom");
+        assert_eq!(
+            receive(deps.as_mut(), v1_channel),
+            Ics20Ack::Error(err.to_string())
+        );
+
+        // ics20-2 gets the code along with it
+        let ack_err = match receive(deps.as_mut(), v2_channel) {
+            Ics20Ack::Error(ack_err) => ack_err,
+            ack => panic!("Unexpected ack: {:?}", ack),
+        };
+        let err = not_registered(v2_channel, "uatom");
+        let expected = format!(
+            r#"{{"code":51,"message":"Denom uatom was never sent over channel {}"}}"#,
+            v2_channel
+        );
+        assert_eq!(ack_err, expected);
+        let parsed: AckError = from_binary(&Binary::from(ack_err.as_bytes())).unwrap();
+        assert_eq!(
+            parsed,
+            AckError {
+                code: err.code(),
+                message: err.to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn unknown_packet_encodings() {
+        let channel = "channel-1";
+        let mut deps = setup(&[channel], &[]);
+        let receive = |deps: DepsMut, data: &[u8]| -> Ics20Ack {
+            let mut packet = mock_receive_packet(channel, 100, "uatom", "local-rcpt");
+            packet.data = Binary::from(data);
+            let msg = IbcPacketReceiveMsg::new(packet);
+            let res = ibc_packet_receive(deps, mock_env(), msg).unwrap();
+            from_binary(&res.acknowledgement).unwrap()
+        };
+
+        // valid ics20-1 data is parsed, with or without an explicit version
+        let packet = mock_receive_packet(channel, 100, "uatom", "local-rcpt");
+        let data: Ics20Packet = from_binary(&packet.data).unwrap();
+        assert_eq!(parse_packet_data(&packet.data).unwrap(), data);
+        let versioned = format!(
+            r#"{{"version":"ics20-1","amount":"100","denom":"{}","receiver":"local-rcpt","sender":"remote-sender"}}"#,
+            data.denom
+        );
+        assert_eq!(
+            parse_packet_data(&Binary::from(versioned.as_bytes())).unwrap(),
+            data
+        );
+        // so it fails on the missing escrow, not on the encoding
+        assert_eq!(
+            receive(deps.as_mut(), &packet.data),
+            Ics20Ack::Error(not_registered(channel, "uatom").to_string())
+        );
+
+        // a version we do not know, even if the rest looks like an ics20-1 packet
+        let future = versioned.replace("ics20-1", "ics20-3");
+        let err = ContractError::UnsupportedPacketVersion {
+            version: "ics20-3".to_string(),
+        };
+        assert_eq!(
+            receive(deps.as_mut(), future.as_bytes()),
+            Ics20Ack::Error(err.to_string())
+        );
+
+        // a multi-token packet
+        let multi = br#"{"tokens":[{"denom":{"base":"uatom","trace":[]},"amount":"100"}],"sender":"remote-sender","receiver":"local-rcpt","memo":""}"#;
+        let err = ContractError::UnsupportedPacketVersion {
+            version: MULTI_TOKEN_FORMAT.to_string(),
+        };
+        assert_eq!(
+            receive(deps.as_mut(), multi),
+            Ics20Ack::Error(err.to_string())
+        );
+
+        // garbage still gets the parse error
+        let err = parse_packet_data(&Binary::from(b"not a packet")).unwrap_err();
+        assert!(matches!(err, ContractError::Std(StdError::ParseErr { .. })));
+        match receive(deps.as_mut(), b"not a packet") {
+            Ics20Ack::Error(msg) => assert!(msg.starts_with("Error parsing into type"), "{}", msg),
+            ack => panic!("Unexpected ack: {:?}", ack),
+        }
+    }
+
+    #[test]
+    fn allow_all_cw20() {
+        let send_channel = "channel-9";
+        let cw20_addr = "any-token";
+        let cw20_denom = "cw20:any-token";
+        let mut init = mock_init_msg(&[]);
+        init.allow_all_cw20 = Some(true);
+        init.default_gas_limit = Some(123456);
+        let mut deps = setup_with_init(&[send_channel], init);
+
+        // a token that was never registered can be sent
+        let transfer = TransferMsg {
+            channel: send_channel.to_string(),
+            remote_address: "remote-rcpt".to_string(),
+            timeout: None,
+            timeout_height: None,
+            memo: None,
+            idempotency_key: None,
+            fee: None,
+            refund_to: None,
+        };
+        let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+            sender: "local-sender".to_string(),
+            amount: Uint128::new(1000),
+            msg: to_binary(&transfer).unwrap(),
+        });
+        execute(deps.as_mut(), mock_env(), mock_info(cw20_addr, &[]), msg).unwrap();
+
+        // and is paid out with the default gas limit
+        let recv_packet = mock_receive_packet(send_channel, 400, cw20_denom, "local-rcpt");
+        let msg = IbcPacketReceiveMsg::new(recv_packet);
+        let res = ibc_packet_receive(deps.as_mut(), mock_env(), msg).unwrap();
+        assert_eq!(
+            cw20_payment(400, cw20_addr, "local-rcpt", Some(123456)),
+            res.messages[0]
+        );
+
+        // an explicit registration overrides the default
+        let allow = ExecuteMsg::Allow(AllowMsg {
+            contract: cw20_addr.to_string(),
+            gas_limit: Some(999999),
+            fee_on_transfer: None,
+            verify_balance: None,
+        });
+        execute(deps.as_mut(), mock_env(), mock_info("gov", &[]), allow).unwrap();
+        let recv_packet = mock_receive_packet(send_channel, 100, cw20_denom, "local-rcpt");
+        let msg = IbcPacketReceiveMsg::new(recv_packet);
+        let res = ibc_packet_receive(deps.as_mut(), mock_env(), msg).unwrap();
+        assert_eq!(
+            cw20_payment(100, cw20_addr, "local-rcpt", Some(999999)),
+            res.messages[0]
+        );
+    }
+
+    #[test]
+    fn disabled_channel() {
+        let send_channel = "channel-9";
+        let mut deps = setup(&[send_channel, "channel-1"], &[]);
+        let denom = "uatom";
+
+        // send some tokens, so there is something in flight
+        let transfer = TransferMsg {
+            channel: send_channel.to_string(),
+            remote_address: "remote-rcpt".to_string(),
+            timeout: None,
+            timeout_height: None,
+            memo: None,
+            idempotency_key: None,
+            fee: None,
+            refund_to: None,
+        };
+        let send = ExecuteMsg::Transfer(transfer);
+        let info = mock_info("local-sender", &coins(1000, denom));
+        execute(deps.as_mut(), mock_env(), info.clone(), send.clone()).unwrap();
+
+        // only gov can disable
+        let disable = ExecuteMsg::DisableChannel {
+            channel_id: send_channel.to_string(),
+        };
+        let err = execute(deps.as_mut(), mock_env(), info.clone(), disable.clone()).unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized);
+        execute(deps.as_mut(), mock_env(), mock_info("gov", &[]), disable).unwrap();
+        let disabled = ContractError::ChannelDisabled {
+            id: send_channel.to_string(),
+        };
+
+        // no new sends
+        let err = execute(deps.as_mut(), mock_env(), info.clone(), send.clone()).unwrap_err();
+        assert_eq!(err, disabled);
+
+        // incoming packets are acknowledged with an error, without touching the escrow
+        let recv_packet = mock_receive_packet(send_channel, 100, denom, "local-rcpt");
+        let msg = IbcPacketReceiveMsg::new(recv_packet.clone());
+        let res = ibc_packet_receive(deps.as_mut(), mock_env(), msg).unwrap();
+        assert!(res.messages.is_empty());
+        let ack: Ics20Ack = from_binary(&res.acknowledgement).unwrap();
+        assert_eq!(ack, Ics20Ack::Error(disabled.to_string()));
+        let state = query_channel(deps.as_ref(), send_channel.to_string()).unwrap();
+        assert_eq!(state.balances, vec![Amount::native(1000, denom)]);
+
+        // but the ack of the packet in flight is still processed
+        let sent_packet = mock_sent_packet(send_channel, 1000, denom, "local-sender");
+        let msg = IbcPacketAckMsg::new(
+            IbcAcknowledgement::new(ack_success(AckEncoding::Json)),
+            sent_packet,
+        );
+        ibc_packet_ack(deps.as_mut(), mock_env(), msg).unwrap();
+        let state = query_channel(deps.as_ref(), send_channel.to_string()).unwrap();
+        assert_eq!(state.total_sent, vec![Amount::native(1000, denom)]);
+
+        // other channels are not affected
+        let other = ExecuteMsg::Transfer(TransferMsg {
+            channel: "channel-1".to_string(),
+            remote_address: "remote-rcpt".to_string(),
+            timeout: None,
+            timeout_height: None,
+            memo: None,
+            idempotency_key: None,
+            fee: None,
+            refund_to: None,
+        });
+        execute(deps.as_mut(), mock_env(), info.clone(), other).unwrap();
+
+        // enabling it again restores transfers
+        let enable = ExecuteMsg::EnableChannel {
+            channel_id: send_channel.to_string(),
+        };
+        execute(deps.as_mut(), mock_env(), mock_info("gov", &[]), enable).unwrap();
+        execute(deps.as_mut(), mock_env(), info, send).unwrap();
+        let msg = IbcPacketReceiveMsg::new(recv_packet);
+        let res = ibc_packet_receive(deps.as_mut(), mock_env(), msg).unwrap();
+        assert_eq!(native_payment(100, denom, "local-rcpt"), res.messages[0]);
+    }
+
+    #[test]
+    fn partial_release() {
+        let send_channel = "channel-9";
+        let mut init = mock_init_msg(&[]);
+        init.allow_partial_release = Some(true);
+        let mut deps = setup_with_init(&[send_channel], init);
+        let denom = "uatom";
+
+        // nothing available yet, so nothing can be released
+        let recv_packet = mock_receive_packet(send_channel, 300, denom, "local-rcpt");
+        let msg = IbcPacketReceiveMsg::new(recv_packet.clone());
+        let res = ibc_packet_receive(deps.as_mut(), mock_env(), msg).unwrap();
+        assert!(res.messages.is_empty());
+        let ack: Ics20Ack = from_binary(&res.acknowledgement).unwrap();
+        let no_funds = Ics20Ack::Error(not_registered(send_channel, denom).to_string());
+        assert_eq!(ack, no_funds);
+
+        let transfer = TransferMsg {
+            channel: send_channel.to_string(),
+            remote_address: "remote-rcpt".to_string(),
+            timeout: None,
+            timeout_height: None,
+            memo: None,
+            idempotency_key: None,
+            fee: None,
+            refund_to: None,
+        };
+        let info = mock_info("local-sender", &coins(1000, denom));
+        let msg = ExecuteMsg::Transfer(transfer);
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // a receive we can cover is released fully
+        let msg = IbcPacketReceiveMsg::new(recv_packet);
+        let res = ibc_packet_receive(deps.as_mut(), mock_env(), msg).unwrap();
+        assert_eq!(native_payment(300, denom, "local-rcpt"), res.messages[0]);
+        assert_eq!(res.acknowledgement, ack_success(AckEncoding::Json));
+        assert!(!res.attributes.iter().any(|a| a.key == "shortfall"));
+
+        // a receive larger than the escrow releases what is left, and reports the rest
+        let recv_high_packet = mock_receive_packet(send_channel, 1000, denom, "local-rcpt");
+        let msg = IbcPacketReceiveMsg::new(recv_high_packet);
+        let res = ibc_packet_receive(deps.as_mut(), mock_env(), msg).unwrap();
+        assert_eq!(native_payment(700, denom, "local-rcpt"), res.messages[0]);
+        assert!(res.attributes.contains(&attr("shortfall", "300")));
+        let ack: Ics20Ack = from_binary(&res.acknowledgement).unwrap();
+        let expected = PartialRelease {
+            released: Uint128::new(700),
+            shortfall: Uint128::new(300),
+        };
+        assert_eq!(ack, Ics20Ack::Result(to_binary(&expected).unwrap()));
+
+        let state = query_channel(deps.as_ref(), send_channel.to_string()).unwrap();
+        assert_eq!(state.balances, vec![Amount::native(0, denom)]);
+    }
+
+    // sends a native transfer with a relayer fee, and replies with the packet sequence
+    fn send_with_fee(deps: DepsMut, channel: &str, funds: &[Coin], fee: IbcFee) {
+        let transfer = TransferMsg {
+            channel: channel.to_string(),
+            remote_address: "remote-rcpt".to_string(),
+            timeout: None,
+            timeout_height: None,
+            memo: None,
+            idempotency_key: None,
+            fee: Some(fee),
+            refund_to: None,
+        };
+        let info = mock_info("local-sender", funds);
+        let msg = ExecuteMsg::Transfer(transfer);
+        let res = execute(deps, mock_env(), info, msg).unwrap();
+        assert_eq!(res.messages.len(), 1);
+        assert_eq!(res.messages[0].id, SEND_PACKET_ID);
+        assert_eq!(res.messages[0].reply_on, ReplyOn::Success);
+    }
+
+    fn send_packet_reply(sequence: u64) -> Reply {
+        let event =
+            Event::new("send_packet").add_attribute("packet_sequence", sequence.to_string());
+        Reply {
+            id: SEND_PACKET_ID,
+            result: ContractResult::Ok(SubMsgExecutionResponse {
+                events: vec![event],
+                data: None,
+            }),
+        }
+    }
+
+    #[test]
+    fn relayer_fee_is_consumed_on_ack() {
+        let send_channel = "channel-9";
+        let mut deps = setup(&[send_channel], &[]);
+        let denom = "uatom";
+        let fee = IbcFee {
+            recv_fee: vec![coin(10, "ufee")],
+            ack_fee: vec![coin(5, "ufee")],
+            timeout_fee: vec![],
+        };
+
+        // the funds must cover the fee on top of the transfer
+        let transfer = TransferMsg {
+            channel: send_channel.to_string(),
+            remote_address: "remote-rcpt".to_string(),
+            timeout: None,
+            timeout_height: None,
+            memo: None,
+            idempotency_key: None,
+            fee: Some(fee.clone()),
+            refund_to: None,
+        };
+        let info = mock_info("local-sender", &[coin(1000, denom), coin(10, "ufee")]);
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            info,
+            ExecuteMsg::Transfer(transfer),
+        );
+        assert_eq!(err.unwrap_err(), ContractError::InsufficientFee {});
+
+        let funds = [coin(1000, denom), coin(15, "ufee")];
+        send_with_fee(deps.as_mut(), send_channel, &funds, fee);
+        reply(deps.as_mut(), mock_env(), send_packet_reply(2)).unwrap();
+
+        // only the transfer itself is outstanding on the channel
+        let state = query_channel(deps.as_ref(), send_channel.to_string()).unwrap();
+        assert_eq!(state.balances, vec![Amount::native(1000, denom)]);
+
+        // without a timeout fee the whole fee is consumed, nothing goes back to the sender
+        let sent_packet = mock_sent_packet(send_channel, 1000, denom, "local-sender");
+        let msg = IbcPacketAckMsg::new(
+            IbcAcknowledgement::new(ack_success(AckEncoding::Json)),
+            sent_packet,
+        );
+        let res = ibc_packet_ack(deps.as_mut(), mock_env(), msg).unwrap();
+        let paid = BankMsg::Send {
+            to_address: "gov".into(),
+            amount: coins(15, "ufee"),
+        };
+        assert_eq!(res.messages, vec![SubMsg::new(paid)]);
+    }
+
+    #[test]
+    fn refund_to_overrides_sender() {
+        let send_channel = "channel-9";
+        let mut deps = setup(&[send_channel], &[]);
+        let denom = "uatom";
+
+        let send = |deps: DepsMut, refund_to: Option<&str>| {
+            let transfer = TransferMsg {
+                channel: send_channel.to_string(),
+                remote_address: "remote-rcpt".to_string(),
+                timeout: None,
+                timeout_height: None,
+                memo: None,
+                idempotency_key: None,
+                fee: None,
+                refund_to: refund_to.map(String::from),
+            };
+            let info = mock_info("local-sender", &coins(1000, denom));
+            execute(deps, mock_env(), info, ExecuteMsg::Transfer(transfer)).unwrap()
+        };
+
+        // with a refund address, the sequence is needed to remember it
+        let res = send(deps.as_mut(), Some("refund-addr"));
+        assert_eq!(res.messages[0].id, SEND_PACKET_ID);
+        assert_eq!(res.messages[0].reply_on, ReplyOn::Success);
+        reply(deps.as_mut(), mock_env(), send_packet_reply(2)).unwrap();
+        // without one, it is sent as before
+        let res = send(deps.as_mut(), None);
+        assert_eq!(res.messages[0].reply_on, ReplyOn::Never);
+
+        // the timeout refunds to the override
+        let timed_out = mock_sent_packet(send_channel, 1000, denom, "local-sender");
+        let msg = IbcPacketTimeoutMsg::new(timed_out);
+        let res = ibc_packet_timeout(deps.as_mut(), mock_env(), msg).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![native_payment(1000, denom, "refund-addr")]
+        );
+
+        // the other one falls back to the sender
+        let mut timed_out = mock_sent_packet(send_channel, 1000, denom, "local-sender");
+        timed_out.sequence = 3;
+        let msg = IbcPacketTimeoutMsg::new(timed_out);
+        let res = ibc_packet_timeout(deps.as_mut(), mock_env(), msg).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![native_payment(1000, denom, "local-sender")]
+        );
+        let state = query_channel(deps.as_ref(), send_channel.to_string()).unwrap();
+        assert_eq!(state.balances, vec![Amount::native(0, denom)]);
+    }
+
+    #[test]
+    fn unused_relayer_fee_is_refunded() {
+        let send_channel = "channel-9";
+        let mut deps = setup(&[send_channel], &[]);
+        let denom = "uatom";
+        let fee = IbcFee {
+            recv_fee: vec![coin(10, denom)],
+            ack_fee: vec![],
+            timeout_fee: vec![coin(20, denom)],
+        };
+
+        // the fee may be paid in the denom that is sent, only the rest is transferred
+        let funds = coins(1030, denom);
+        send_with_fee(deps.as_mut(), send_channel, &funds, fee.clone());
+        reply(deps.as_mut(), mock_env(), send_packet_reply(2)).unwrap();
+        send_with_fee(deps.as_mut(), send_channel, &funds, fee);
+        reply(deps.as_mut(), mock_env(), send_packet_reply(3)).unwrap();
+        let state = query_channel(deps.as_ref(), send_channel.to_string()).unwrap();
+        assert_eq!(state.balances, vec![Amount::native(2000, denom)]);
+
+        // on ack, the timeout fee goes back to the sender
+        let sent_packet = mock_sent_packet(send_channel, 1000, denom, "local-sender");
+        let msg = IbcPacketAckMsg::new(
+            IbcAcknowledgement::new(ack_success(AckEncoding::Json)),
+            sent_packet,
+        );
+        let res = ibc_packet_ack(deps.as_mut(), mock_env(), msg).unwrap();
+        let paid = BankMsg::Send {
+            to_address: "gov".into(),
+            amount: coins(10, denom),
+        };
+        let refund = BankMsg::Send {
+            to_address: "local-sender".into(),
+            amount: coins(20, denom),
+        };
+        assert_eq!(res.messages, vec![SubMsg::new(paid), SubMsg::new(refund)]);
+
+        // on timeout, the recv and ack fees go back along with the tokens
+        let mut timed_out = mock_sent_packet(send_channel, 1000, denom, "local-sender");
+        timed_out.sequence = 3;
+        let msg = IbcPacketTimeoutMsg::new(timed_out);
+        let res = ibc_packet_timeout(deps.as_mut(), mock_env(), msg).unwrap();
+        let paid = BankMsg::Send {
+            to_address: "gov".into(),
+            amount: coins(20, denom),
+        };
+        let refund = BankMsg::Send {
+            to_address: "local-sender".into(),
+            amount: coins(10, denom),
+        };
+        assert_eq!(
+            res.messages,
+            vec![
+                native_payment(1000, denom, "local-sender"),
+                SubMsg::new(paid),
+                SubMsg::new(refund)
+            ]
+        );
+    }
+
+    #[test]
+    fn global_cap_limits_outstanding() {
+        let (channel_a, channel_b) = ("channel-1", "channel-2");
+        let mut deps = setup(&[channel_a, channel_b], &[]);
+        let denom = "uatom";
+
+        let transfer = |channel: &str, amount: u128| -> (MessageInfo, ExecuteMsg) {
+            let msg = ExecuteMsg::Transfer(TransferMsg {
+                channel: channel.to_string(),
+                remote_address: "remote-rcpt".to_string(),
+                timeout: None,
+                timeout_height: None,
+                memo: None,
+                idempotency_key: None,
+                fee: None,
+                refund_to: None,
+            });
+            (mock_info("local-sender", &coins(amount, denom)), msg)
+        };
+        // the running total must always match the sum over all channels
+        let global = |deps: Deps| -> GlobalCapResponse {
+            let msg = QueryMsg::GlobalCap {
+                denom: denom.to_string(),
+            };
+            let res: GlobalCapResponse =
+                from_binary(&query(deps, mock_env(), msg).unwrap()).unwrap();
+            let tracked = total_outstanding(deps.storage, denom).unwrap();
+            assert_eq!(res.outstanding, tracked);
+            res
+        };
+
+        // only gov can set the cap
+        let set_cap = ExecuteMsg::SetGlobalCap {
+            denom: denom.to_string(),
+            cap: Some(Uint128::new(1000)),
+        };
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("foobar", &[]),
+            set_cap.clone(),
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized);
+        execute(deps.as_mut(), mock_env(), mock_info("gov", &[]), set_cap).unwrap();
+
+        // sends over both channels count against the same cap
+        let (info, msg) = transfer(channel_a, 600);
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        let (info, msg) = transfer(channel_b, 500);
+        let err = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+        let exceeded = ContractError::GlobalCapExceeded {
+            denom: denom.to_string(),
+            cap: Uint128::new(1000),
+        };
+        assert_eq!(err, exceeded);
+        let (info, msg) = transfer(channel_b, 400);
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        let res = global(deps.as_ref());
+        assert_eq!(res.cap, Some(Uint128::new(1000)));
+        assert_eq!(res.outstanding, Uint128::new(1000));
+
+        // an ack keeps the tokens escrowed
+        let sent_packet = mock_sent_packet(channel_a, 600, denom, "local-sender");
+        let msg = IbcPacketAckMsg::new(
+            IbcAcknowledgement::new(ack_success(AckEncoding::Json)),
+            sent_packet,
+        );
+        ibc_packet_ack(deps.as_mut(), mock_env(), msg).unwrap();
+        assert_eq!(global(deps.as_ref()).outstanding, Uint128::new(1000));
+        let (info, msg) = transfer(channel_a, 1);
+        let err = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+        assert_eq!(err, exceeded);
+
+        // redeemed tokens and timed out sends free up room again
+        let recv_packet = mock_receive_packet(channel_a, 250, denom, "local-rcpt");
+        let msg = IbcPacketReceiveMsg::new(recv_packet);
+        ibc_packet_receive(deps.as_mut(), mock_env(), msg).unwrap();
+        assert_eq!(global(deps.as_ref()).outstanding, Uint128::new(750));
+        let mut timed_out = mock_sent_packet(channel_b, 400, denom, "local-sender");
+        timed_out.sequence = 3;
+        let msg = IbcPacketTimeoutMsg::new(timed_out);
+        ibc_packet_timeout(deps.as_mut(), mock_env(), msg).unwrap();
+        assert_eq!(global(deps.as_ref()).outstanding, Uint128::new(350));
+
+        let (info, msg) = transfer(channel_b, 650);
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        assert_eq!(global(deps.as_ref()).outstanding, Uint128::new(1000));
+
+        // without a cap, the total is still tracked
+        let remove_cap = ExecuteMsg::SetGlobalCap {
+            denom: denom.to_string(),
+            cap: None,
+        };
+        execute(deps.as_mut(), mock_env(), mock_info("gov", &[]), remove_cap).unwrap();
+        let (info, msg) = transfer(channel_a, 5000);
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        let res = global(deps.as_ref());
+        assert_eq!(res.cap, None);
+        assert_eq!(res.outstanding, Uint128::new(6000));
+    }
+
+    #[test]
+    fn recent_failures_keep_the_newest() {
+        let channel = "channel-1";
+        let mut deps = setup(&[channel], &[]);
+        let recent = |deps: Deps, limit: Option<u32>| -> Vec<ReceiveFailure> {
+            let msg = QueryMsg::RecentFailures { limit };
+            let res: RecentFailuresResponse =
+                from_binary(&query(deps, mock_env(), msg).unwrap()).unwrap();
+            res.failures
+        };
+        assert_eq!(recent(deps.as_ref(), None), vec![]);
+
+        // nothing was sent, so every receive fails
+        let total = MAX_RECENT_FAILURES + 5;
+        for i in 1..=total {
+            let mut env = mock_env();
+            env.block.height = 100 + i;
+            let packet = mock_receive_packet(channel, i.into(), "uatom", "local-rcpt");
+            let msg = IbcPacketReceiveMsg::new(packet);
+            let res = ibc_packet_receive(deps.as_mut(), env, msg).unwrap();
+            assert!(res.messages.is_empty());
+        }
+
+        // only the most recent ones survive, newest first
+        let failures = recent(deps.as_ref(), None);
+        assert_eq!(failures.len() as u64, MAX_RECENT_FAILURES);
+        let amounts: Vec<u64> = failures.iter().map(|f| f.amount.u128() as u64).collect();
+        let expected: Vec<u64> = (6..=total).rev().collect();
+        assert_eq!(amounts, expected);
+        assert_eq!(
+            failures[0],
+            ReceiveFailure {
+                channel: channel.to_string(),
+                denom: format!("{}/{}/uatom", REMOTE_PORT, "channel-1234"),
+                amount: Uint128::new(total.into()),
+                receiver: "local-rcpt".to_string(),
+                error: not_registered(channel, "uatom").to_string(),
+                height: 100 + total,
+            }
+        );
+        assert_eq!(RECENT_FAILURES.may_load(&deps.storage, 4).unwrap(), None);
+
+        // limit returns fewer
+        let failures = recent(deps.as_ref(), Some(2));
+        assert_eq!(failures.len(), 2);
+        assert_eq!(failures[1].amount, Uint128::new(total as u128 - 1));
+    }
+
+    #[test]
+    fn denied_token_is_blocked() {
+        let send_channel = "channel-9";
+        let cw20_addr = "token-addr";
+        let cw20_denom = "cw20:token-addr";
+        let mut deps = setup(&[send_channel], &[(cw20_addr, 1234567)]);
+
+        let transfer = TransferMsg {
+            channel: send_channel.to_string(),
+            remote_address: "remote-rcpt".to_string(),
+            timeout: None,
+            timeout_height: None,
+            memo: None,
+            idempotency_key: None,
+            fee: None,
+            refund_to: None,
+        };
+        let send = ExecuteMsg::Receive(Cw20ReceiveMsg {
+            sender: "local-sender".to_string(),
+            amount: Uint128::new(5000),
+            msg: to_binary(&transfer).unwrap(),
+        });
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(cw20_addr, &[]),
+            send.clone(),
+        )
+        .unwrap();
+
+        // only gov can deny
+        let deny = ExecuteMsg::Deny {
+            contract: cw20_addr.to_string(),
+        };
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("anyone", &[]),
+            deny.clone(),
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized);
+        execute(deps.as_mut(), mock_env(), mock_info("gov", &[]), deny).unwrap();
+
+        // it stays on the allow list
+        let msg = QueryMsg::Allowed {
+            contract: cw20_addr.to_string(),
+        };
+        let allowed: AllowedResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+        assert!(allowed.is_allowed);
+
+        // but can neither be sent
+        let denied = ContractError::TokenDenied {
+            contract: cw20_addr.to_string(),
+        };
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(cw20_addr, &[]),
+            send.clone(),
+        )
+        .unwrap_err();
+        assert_eq!(err, denied);
+
+        // nor received, the escrow is left untouched
+        let recv_packet = mock_receive_packet(send_channel, 1000, cw20_denom, "local-rcpt");
+        let msg = IbcPacketReceiveMsg::new(recv_packet.clone());
+        let res = ibc_packet_receive(deps.as_mut(), mock_env(), msg).unwrap();
+        assert!(res.messages.is_empty());
+        let ack: Ics20Ack = from_binary(&res.acknowledgement).unwrap();
+        assert_eq!(ack, Ics20Ack::Error(denied.to_string()));
+        let state = query_channel(deps.as_ref(), send_channel.to_string()).unwrap();
+        assert_eq!(state.balances, vec![Amount::cw20(5000, cw20_addr)]);
+
+        // once taken off the deny list, it works again
+        let undeny = ExecuteMsg::Undeny {
+            contract: cw20_addr.to_string(),
+        };
+        execute(deps.as_mut(), mock_env(), mock_info("gov", &[]), undeny).unwrap();
+        execute(deps.as_mut(), mock_env(), mock_info(cw20_addr, &[]), send).unwrap();
+        let msg = IbcPacketReceiveMsg::new(recv_packet);
+        let res = ibc_packet_receive(deps.as_mut(), mock_env(), msg).unwrap();
+        assert_eq!(1, res.messages.len());
+    }
+
+    #[test]
+    fn ack_encoding_per_channel() {
+        let (json_channel, raw_channel) = ("channel-1", "channel-2");
+        let mut deps = setup(&[json_channel, raw_channel], &[]);
+
+        // only gov can configure, and only known channels
+        let set_raw = |channel: &str| ExecuteMsg::SetAckEncoding {
+            channel_id: channel.to_string(),
+            encoding: AckEncoding::Raw,
+        };
+        let info = mock_info("anyone", &[]);
+        let err = execute(deps.as_mut(), mock_env(), info, set_raw(raw_channel)).unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized);
+        let info = mock_info("gov", &[]);
+        let err = execute(deps.as_mut(), mock_env(), info, set_raw("channel-3")).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::NoSuchChannel {
+                id: "channel-3".to_string()
+            }
+        );
+        let info = mock_info("gov", &[]);
+        execute(deps.as_mut(), mock_env(), info, set_raw(raw_channel)).unwrap();
+        let features = query_channel_features(deps.as_ref(), raw_channel.to_string()).unwrap();
+        assert_eq!(features.ack_encoding, AckEncoding::Raw);
+        let features = query_channel_features(deps.as_ref(), json_channel.to_string()).unwrap();
+        assert_eq!(features.ack_encoding, AckEncoding::Json);
+
+        let receive = |deps: DepsMut, channel: &str| -> Binary {
+            let packet = mock_receive_packet(channel, 500, "ucosm", "local-rcpt");
+            let msg = IbcPacketReceiveMsg::new(packet);
+            ibc_packet_receive(deps, mock_env(), msg)
+                .unwrap()
+                .acknowledgement
+        };
+
+        // failures, as nothing is escrowed yet
+        let no_funds = not_registered(json_channel, "ucosm").to_string();
+        let ack = receive(deps.as_mut(), json_channel);
+        assert_eq!(ack, to_binary(&Ics20Ack::Error(no_funds)).unwrap());
+        let no_funds = not_registered(raw_channel, "ucosm").to_string();
+        let ack = receive(deps.as_mut(), raw_channel);
+        assert_eq!(ack, Binary::from(no_funds.as_bytes()));
+
+        // successes, once there is escrow to release
+        for channel in [json_channel, raw_channel] {
+            let transfer = TransferMsg {
+                channel: channel.to_string(),
+                remote_address: "remote-rcpt".to_string(),
+                timeout: None,
+                timeout_height: None,
+                memo: None,
+                idempotency_key: None,
+                fee: None,
+                refund_to: None,
+            };
+            let info = mock_info("local-sender", &coins(1000, "ucosm"));
+            execute(
+                deps.as_mut(),
+                mock_env(),
+                info,
+                ExecuteMsg::Transfer(transfer),
+            )
+            .unwrap();
+        }
+        let ack = receive(deps.as_mut(), json_channel);
+        assert_eq!(ack, to_binary(&Ics20Ack::Result(b"1".into())).unwrap());
+        let ack = receive(deps.as_mut(), raw_channel);
+        assert_eq!(ack, Binary::from(vec![0x01]));
+
+        // the counterparty's acknowledgements are parsed in the same encoding
+        let mut sent_packet = mock_sent_packet(raw_channel, 200, "ucosm", "local-sender");
+        sent_packet.sequence = 4;
+        let ack = IbcAcknowledgement::new(vec![0x01]);
+        let res = ibc_packet_ack(
+            deps.as_mut(),
+            mock_env(),
+            IbcPacketAckMsg::new(ack, sent_packet.clone()),
+        )
+        .unwrap();
+        assert!(res.messages.is_empty());
+        sent_packet.sequence = 5;
+        let ack = IbcAcknowledgement::new(b"bad coin".to_vec());
+        let res = ibc_packet_ack(
+            deps.as_mut(),
+            mock_env(),
+            IbcPacketAckMsg::new(ack, sent_packet),
+        )
+        .unwrap();
+        assert_eq!(1, res.messages.len());
+        assert!(res.attributes.contains(&attr("error", "bad coin")));
+        let state = query_channel(deps.as_ref(), raw_channel.to_string()).unwrap();
+        assert_eq!(state.balances, vec![Amount::native(300, "ucosm")]);
+    }
+
+    #[test]
+    fn volume_buckets() {
+        let channel = "channel-9";
+        let day = 86400;
+        let mut init_msg = mock_init_msg(&[]);
+        init_msg.volume_bucket = Some(day);
+        let mut deps = setup_with_init(&[channel], init_msg);
+
+        let mut sequence = 0;
+        let mut ack = |deps: DepsMut, days: u64, amount: u128, denom: &str| {
+            let mut packet = mock_sent_packet(channel, amount, denom, "local-sender");
+            sequence += 1;
+            packet.sequence = sequence;
+            let ack = IbcAcknowledgement::new(ack_success(AckEncoding::Json));
+            let mut env = mock_env();
+            env.block.time = env.block.time.plus_seconds(days * day);
+            ibc_packet_ack(deps, env, IbcPacketAckMsg::new(ack, packet)).unwrap();
+        };
+        ack(deps.as_mut(), 0, 100, "ucosm");
+        ack(deps.as_mut(), 0, 50, "ucosm");
+        ack(deps.as_mut(), 1, 30, "ucosm");
+        ack(deps.as_mut(), 3, 7, "ucosm");
+        ack(deps.as_mut(), 1, 999, "uatom");
+
+        let now = mock_env().block.time.seconds();
+        let today = now - now % day;
+        let buckets = |deps: Deps, from: u64, to: u64| -> StdResult<VolumeBucketsResponse> {
+            let msg = QueryMsg::VolumeBuckets {
+                channel_id: channel.to_string(),
+                denom: "ucosm".to_string(),
+                from,
+                to,
+            };
+            from_binary(&query(deps, mock_env(), msg)?)
+        };
+        let bucket = |days: u64, amount: u128| VolumeBucket {
+            start: today + days * day,
+            amount: Uint128::new(amount),
+        };
+
+        // sends of the same day add up, days without sends are left out
+        let res = buckets(deps.as_ref(), 0, u64::MAX).unwrap();
+        assert_eq!(res.bucket_len, day);
+        assert_eq!(
+            res.buckets,
+            vec![bucket(0, 150), bucket(1, 30), bucket(3, 7)]
+        );
+
+        // a sub-range, `to` is exclusive
+        let res = buckets(deps.as_ref(), today + day, today + 3 * day).unwrap();
+        assert_eq!(res.buckets, vec![bucket(1, 30)]);
+        let res = buckets(deps.as_ref(), today + 1, today + 3 * day + 1).unwrap();
+        assert_eq!(res.buckets, vec![bucket(1, 30), bucket(3, 7)]);
+
+        // the total is still tracked as before
+        let state = query_channel(deps.as_ref(), channel.to_string()).unwrap();
+        assert_eq!(
+            state.total_sent,
+            vec![Amount::native(999, "uatom"), Amount::native(187, "ucosm")]
+        );
+
+        // without the flag nothing is recorded
+        let mut deps = setup(&[channel], &[]);
+        ack(deps.as_mut(), 0, 100, "ucosm");
+        let err = buckets(deps.as_ref(), 0, u64::MAX).unwrap_err();
+        assert_eq!(err, StdError::generic_err("Volume buckets are not enabled"));
+        let stored = VOLUME_BUCKETS
+            .keys_raw(&deps.storage, None, None, Order::Ascending)
+            .count();
+        assert_eq!(stored, 0);
+    }
+
+    #[test]
+    fn metrics_attributes_on_every_path() {
+        let channel = "channel-9";
+        let mut deps = setup(&[channel], &[]);
+
+        let assert_metrics = |attributes: &[Attribute], amount: &str, direction, outcome| {
+            for (key, value) in [
+                ("channel", channel),
+                ("denom", "ucosm"),
+                ("amount", amount),
+                ("direction", direction),
+                ("outcome", outcome),
+            ] {
+                let found: Vec<_> = attributes.iter().filter(|a| a.key == key).collect();
+                assert_eq!(found, vec![&attr(key, value)], "{}", key);
+            }
+        };
+
+        // a failed receive, nothing is escrowed yet
+        let recv_packet = mock_receive_packet(channel, 300, "ucosm", "local-rcpt");
+        let msg = IbcPacketReceiveMsg::new(recv_packet.clone());
+        let res = ibc_packet_receive(deps.as_mut(), mock_env(), msg).unwrap();
+        assert_metrics(&res.attributes, "300", "in", "failure");
+
+        let transfer = TransferMsg {
+            channel: channel.to_string(),
+            remote_address: "remote-rcpt".to_string(),
+            timeout: None,
+            timeout_height: None,
+            memo: None,
+            idempotency_key: None,
+            fee: None,
+            refund_to: None,
+        };
+        let info = mock_info("local-sender", &coins(1000, "ucosm"));
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            info,
+            ExecuteMsg::Transfer(transfer),
+        )
+        .unwrap();
+
+        // a successful receive
+        let msg = IbcPacketReceiveMsg::new(recv_packet);
+        let res = ibc_packet_receive(deps.as_mut(), mock_env(), msg).unwrap();
+        assert_metrics(&res.attributes, "300", "in", "success");
+
+        // a successful ack, and a duplicate of it
+        let mut sent_packet = mock_sent_packet(channel, 200, "ucosm", "local-sender");
+        let ack = IbcAcknowledgement::new(ack_success(AckEncoding::Json));
+        let msg = IbcPacketAckMsg::new(ack, sent_packet.clone());
+        let res = ibc_packet_ack(deps.as_mut(), mock_env(), msg.clone()).unwrap();
+        assert_metrics(&res.attributes, "200", "out", "success");
+        let res = ibc_packet_ack(deps.as_mut(), mock_env(), msg).unwrap();
+        assert_metrics(&res.attributes, "200", "out", "duplicate");
+
+        // an error ack
+        sent_packet.sequence = 3;
+        let ack = IbcAcknowledgement::new(ack_fail(AckEncoding::Json, "bad coin".into()));
+        let msg = IbcPacketAckMsg::new(ack, sent_packet.clone());
+        let res = ibc_packet_ack(deps.as_mut(), mock_env(), msg).unwrap();
+        assert_metrics(&res.attributes, "200", "out", "failure");
+
+        // a timeout
+        sent_packet.sequence = 4;
+        let msg = IbcPacketTimeoutMsg::new(sent_packet);
+        let res = ibc_packet_timeout(deps.as_mut(), mock_env(), msg).unwrap();
+        assert_metrics(&res.attributes, "200", "out", "timeout");
+    }
+
+    #[test]
+    fn insufficient_funds_reports_available_and_requested() {
+        let send_channel = "channel-9";
+        let mut deps = setup(&[send_channel], &[]);
+
+        let transfer = TransferMsg {
+            channel: send_channel.to_string(),
+            remote_address: "remote-rcpt".to_string(),
+            timeout: None,
+            timeout_height: None,
+            memo: None,
+            idempotency_key: None,
+            fee: None,
+            refund_to: None,
+        };
+        let info = mock_info("local-sender", &coins(700, "ucosm"));
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            info,
+            ExecuteMsg::Transfer(transfer),
+        )
+        .unwrap();
+
+        // ask for more than is escrowed
+        let recv_packet = mock_receive_packet(send_channel, 1000, "ucosm", "local-rcpt");
+        let msg = IbcPacketReceiveMsg::new(recv_packet);
+        let res = ibc_packet_receive(deps.as_mut(), mock_env(), msg).unwrap();
+        let expected = ContractError::InsufficientFunds {
+            available: Uint128::new(700),
+            requested: Uint128::new(1000),
+        };
+        let ack: Ics20Ack = from_binary(&res.acknowledgement).unwrap();
+        assert_eq!(ack, Ics20Ack::Error(expected.to_string()));
+        assert!(expected
+            .to_string()
+            .ends_with("700 available, 1000 requested"));
+
+        // the failure buffer has the same numbers
+        let msg = QueryMsg::RecentFailures { limit: None };
+        let res: RecentFailuresResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+        assert_eq!(res.failures[0].error, expected.to_string());
+    }
+
+    #[test]
+    fn restricted_receivers() {
+        let send_channel = "channel-9";
+        let transfer = TransferMsg {
+            channel: send_channel.to_string(),
+            remote_address: "remote-rcpt".to_string(),
+            timeout: None,
+            timeout_height: None,
+            memo: None,
+            idempotency_key: None,
+            fee: None,
+            refund_to: None,
+        };
+        let escrowed = |restrict_receivers| {
+            let mut init_msg = mock_init_msg(&[]);
+            init_msg.restrict_receivers = restrict_receivers;
+            let mut deps = setup_with_init(&[send_channel], init_msg);
+            let info = mock_info("local-sender", &coins(1000, "ucosm"));
+            let msg = ExecuteMsg::Transfer(transfer.clone());
+            execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+            deps
+        };
+        let receive = |deps: DepsMut, receiver: &str| {
+            let packet = mock_receive_packet(send_channel, 100, "ucosm", receiver);
+            let msg = IbcPacketReceiveMsg::new(packet);
+            ibc_packet_receive(deps, mock_env(), msg).unwrap()
+        };
+
+        // without the flag, anyone can receive
+        let mut deps = escrowed(None);
+        let res = receive(deps.as_mut(), "local-rcpt");
+        assert_eq!(native_payment(100, "ucosm", "local-rcpt"), res.messages[0]);
+
+        let mut deps = escrowed(Some(true));
+        let allow = ExecuteMsg::AllowReceiver {
+            address: "approved".to_string(),
+        };
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("anyone", &[]),
+            allow.clone(),
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized);
+        execute(deps.as_mut(), mock_env(), mock_info("gov", &[]), allow).unwrap();
+
+        // an allowed receiver gets the tokens
+        let res = receive(deps.as_mut(), "approved");
+        assert_eq!(native_payment(100, "ucosm", "approved"), res.messages[0]);
+
+        // others are acknowledged with an error, and the escrow is left untouched
+        let res = receive(deps.as_mut(), "local-rcpt");
+        assert!(res.messages.is_empty());
+        let ack: Ics20Ack = from_binary(&res.acknowledgement).unwrap();
+        let not_allowed = ContractError::ReceiverNotAllowed {
+            receiver: "local-rcpt".to_string(),
+        };
+        assert_eq!(ack, Ics20Ack::Error(not_allowed.to_string()));
+        let state = query_channel(deps.as_ref(), send_channel.to_string()).unwrap();
+        assert_eq!(state.balances, vec![Amount::native(900, "ucosm")]);
+
+        // as is a receiver that was taken off the list again
+        let disallow = ExecuteMsg::DisallowReceiver {
+            address: "approved".to_string(),
+        };
+        execute(deps.as_mut(), mock_env(), mock_info("gov", &[]), disallow).unwrap();
+        let res = receive(deps.as_mut(), "approved");
+        assert!(res.messages.is_empty());
+    }
+
+    #[test]
+    fn denom_alias_round_trip() {
+        let send_channel = "channel-9";
+        let wire = "factory/creator/averylongtokenname";
+        let alias = "ufriendly";
+        let mut deps = setup(&[send_channel], &[]);
+
+        let set_alias = |denom: &str| ExecuteMsg::SetDenomAlias {
+            denom: denom.to_string(),
+            alias: Some(alias.to_string()),
+        };
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("anyone", &[]),
+            set_alias(wire),
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized);
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("gov", &[]),
+            set_alias(wire),
+        )
+        .unwrap();
+
+        // the alias cannot be given to another denom as well
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("gov", &[]),
+            set_alias("uother"),
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::AliasTaken {
+                alias: alias.to_string(),
+                denom: wire.to_string(),
+            }
+        );
+
+        // tokens paid in the alias are escrowed and sent as the wire denom
+        let transfer = TransferMsg {
+            channel: send_channel.to_string(),
+            remote_address: "remote-rcpt".to_string(),
+            timeout: None,
+            timeout_height: None,
+            memo: None,
+            idempotency_key: None,
+            fee: None,
+            refund_to: None,
+        };
+        let info = mock_info("local-sender", &coins(1000, alias));
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            info,
+            ExecuteMsg::Transfer(transfer),
+        )
+        .unwrap();
+        let packet: Ics20Packet = match &res.messages[0].msg {
+            CosmosMsg::Ibc(IbcMsg::SendPacket { data, .. }) => from_binary(data).unwrap(),
+            msg => panic!("Unexpected message: {:?}", msg),
+        };
+        assert_eq!(packet.denom, wire);
+        let state = query_channel(deps.as_ref(), send_channel.to_string()).unwrap();
+        assert_eq!(state.balances, vec![Amount::native(1000, wire)]);
+
+        // coming back, they are released in the alias
+        let recv_packet = mock_receive_packet(send_channel, 400, wire, "local-rcpt");
+        let msg = IbcPacketReceiveMsg::new(recv_packet);
+        let res = ibc_packet_receive(deps.as_mut(), mock_env(), msg).unwrap();
+        assert_eq!(native_payment(400, alias, "local-rcpt"), res.messages[0]);
+
+        // as is a refund
+        let sent_packet = mock_sent_packet(send_channel, 100, wire, "local-sender");
+        let msg = IbcPacketTimeoutMsg::new(sent_packet);
+        let res = ibc_packet_timeout(deps.as_mut(), mock_env(), msg).unwrap();
+        assert_eq!(native_payment(100, alias, "local-sender"), res.messages[0]);
+
+        // the escrow is still tracked in the wire denom, and matches what we hold in the alias
+        let state = query_channel(deps.as_ref(), send_channel.to_string()).unwrap();
+        assert_eq!(state.balances, vec![Amount::native(500, wire)]);
+        deps.querier
+            .update_balance(MOCK_CONTRACT_ADDR, coins(500, alias));
+        let msg = QueryMsg::Reconcile {
+            denoms: vec![wire.to_string(), alias.to_string()],
+        };
+        let res: ReconcileResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+        for denom in res.denoms {
+            assert_eq!(denom.tracked, Uint128::new(500));
+            assert_eq!(denom.actual, Uint128::new(500));
+        }
+    }
+
+    #[test]
+    fn receive_to_self_is_rejected() {
+        let send_channel = "channel-9";
+        let mut deps = setup(&[send_channel], &[]);
+        let denom = "uatom";
+
+        let transfer = TransferMsg {
+            channel: send_channel.to_string(),
+            remote_address: "remote-rcpt".to_string(),
+            timeout: None,
+            timeout_height: None,
+            memo: None,
+            idempotency_key: None,
+            fee: None,
+            refund_to: None,
+        };
+        let info = mock_info("local-sender", &coins(1000, denom));
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            info,
+            ExecuteMsg::Transfer(transfer),
+        )
+        .unwrap();
+
+        let contract = mock_env().contract.address;
+        let recv_packet = mock_receive_packet(send_channel, 300, denom, contract.as_str());
+        let msg = IbcPacketReceiveMsg::new(recv_packet);
+        let res = ibc_packet_receive(deps.as_mut(), mock_env(), msg).unwrap();
+        assert!(res.messages.is_empty());
+        let ack: Ics20Ack = from_binary(&res.acknowledgement).unwrap();
+        let self_receive = Ics20Ack::Error(ContractError::SelfReceive {}.to_string());
+        assert_eq!(ack, self_receive);
+
+        // the escrow is untouched
+        let state = query_channel(deps.as_ref(), send_channel.to_string()).unwrap();
+        assert_eq!(state.balances, vec![Amount::native(1000, denom)]);
+    }
+
+    #[test]
+    fn admin_close_channel() {
+        let send_channel = "channel-9";
+        let mut deps = setup(&[send_channel, "channel-1"], &[]);
+
+        // escrow more denoms than can be refunded at once
+        let denoms: Vec<String> = (0..MAX_CLOSE_REFUNDS + 2)
+            .map(|i| format!("denom{:02}", i))
+            .collect();
+        let transfer = TransferMsg {
+            channel: send_channel.to_string(),
+            remote_address: "remote-rcpt".to_string(),
+            timeout: None,
+            timeout_height: None,
+            memo: None,
+            idempotency_key: None,
+            fee: None,
+            refund_to: None,
+        };
+        for denom in &denoms {
+            let info = mock_info("local-sender", &coins(1000, denom));
+            let msg = ExecuteMsg::Transfer(transfer.clone());
+            execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        }
+
+        let close = ExecuteMsg::AdminCloseChannel {
+            channel_id: send_channel.to_string(),
+        };
+        let gov = mock_info("gov", &[]);
+
+        // only gov, and only while paused
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("anyone", &[]),
+            close.clone(),
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized);
+        let err = execute(deps.as_mut(), mock_env(), gov.clone(), close.clone()).unwrap_err();
+        assert_eq!(err, ContractError::NotPaused);
+        execute(deps.as_mut(), mock_env(), gov.clone(), ExecuteMsg::Pause {}).unwrap();
+
+        // refunds the first batch to gov, and closes the channel
+        let res = execute(deps.as_mut(), mock_env(), gov.clone(), close).unwrap();
+        assert_eq!(MAX_CLOSE_REFUNDS + 1, res.messages.len());
+        assert_eq!(
+            res.messages[0].msg,
+            transfer_msg(Amount::native(1000, &denoms[0]), "gov".to_string())
+        );
+        let close_msg: CosmosMsg = IbcMsg::CloseChannel {
+            channel_id: send_channel.to_string(),
+        }
+        .into();
+        assert_eq!(res.messages[MAX_CLOSE_REFUNDS].msg, close_msg);
+        assert!(res.attributes.contains(&attr("done", "false")));
+
+        // the rest is refunded with ContinueClose
+        let cont = ExecuteMsg::ContinueClose {
+            channel_id: send_channel.to_string(),
+        };
+        let res = execute(deps.as_mut(), mock_env(), gov.clone(), cont).unwrap();
+        assert_eq!(2, res.messages.len());
+        assert!(res.attributes.contains(&attr("done", "true")));
+        let state = query_channel(deps.as_ref(), send_channel.to_string()).unwrap();
+        assert!(state.balances.iter().all(|b| b.amount().is_zero()));
+
+        // the close we started is accepted, other channels still cannot be closed by us
+        let msg = IbcChannelCloseMsg::new_init(mock_channel(send_channel));
+        let res = ibc_channel_close(deps.as_mut(), mock_env(), msg).unwrap();
+        assert!(res.messages.is_empty());
+        let msg = IbcChannelCloseMsg::new_init(mock_channel("channel-1"));
+        let err = ibc_channel_close(deps.as_mut(), mock_env(), msg).unwrap_err();
+        assert_eq!(err, ContractError::CannotCloseChannel {});
+
+        // the channel stays disabled after unpausing
+        execute(deps.as_mut(), mock_env(), gov, ExecuteMsg::Unpause {}).unwrap();
+        let info = mock_info("local-sender", &coins(1000, "ucosm"));
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            info,
+            ExecuteMsg::Transfer(transfer),
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::ChannelDisabled {
+                id: send_channel.to_string()
+            }
+        );
+    }
 }
@@ -1,10 +1,12 @@
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
+use cosmwasm_std::{Coin, CosmosMsg, IbcTimeoutBlock, Uint128};
 use cw20::Cw20ReceiveMsg;
+use cw_utils::{Expiration, NativeBalance};
 
 use crate::amount::Amount;
-use crate::state::ChannelInfo;
+use crate::state::{ChannelInfo, ReceiveFailure};
 
 #[derive(Serialize, Deserialize, Clone, Debug, JsonSchema)]
 pub struct InitMsg {
@@ -14,12 +16,60 @@ pub struct InitMsg {
     pub gov_contract: String,
     /// initial allowlist - all cw20 tokens we will send must be previously allowed by governance
     pub allowlist: Vec<AllowMsg>,
+    /// Maximum length of a packet memo in bytes, on send and receive. Defaults to 32768
+    pub max_memo_len: Option<u32>,
+    /// If set, a receive for more than the escrowed balance releases what is available
+    /// and reports the shortfall, rather than failing the whole packet
+    pub allow_partial_release: Option<bool>,
+    /// If set, any cw20 token can be sent, not only those on the allowlist. Tokens not on the
+    /// allowlist are paid out with default_gas_limit
+    pub allow_all_cw20: Option<bool>,
+    /// Gas limit for cw20 tokens that are not on the allowlist, if allow_all_cw20 is set
+    pub default_gas_limit: Option<u64>,
+    /// How many blocks ahead of the current height a transfer's timeout height may be.
+    /// Higher timeout heights are lowered to this. Defaults to 100000
+    pub max_timeout_height_offset: Option<u64>,
+    /// How many distinct denoms a channel may track. Sends that would add another denom are
+    /// rejected. Unlimited if not set
+    pub max_denoms_per_channel: Option<u32>,
+    /// Pins the counterparty of known channels. Such a channel can only connect to the given
+    /// counterparty port and channel, other channels connect freely
+    pub expected_counterparties: Option<Vec<ExpectedCounterparty>>,
+    /// If set, incoming transfers are only released to receivers the gov contract allowed
+    pub restrict_receivers: Option<bool>,
+    /// If set, critical changes (see `ExecuteMsg::is_critical`) must be proposed with
+    /// ProposeChange, and can only be applied this many seconds later
+    pub admin_timelock: Option<u64>,
+    /// If set, the volume of successful sends is also recorded in buckets of this many
+    /// seconds (eg. 86400 for daily), see `QueryMsg::VolumeBuckets`
+    pub volume_bucket: Option<u64>,
+    /// Maximum size in bytes of a sent packet's data, as serialized. Larger sends are rejected,
+    /// so they do not fail to relay later. Unlimited if not set
+    pub max_packet_size: Option<u32>,
+    /// Maximum number of sent packets a channel can have waiting for an ack or timeout.
+    /// Further sends are rejected until one resolves. Unlimited if not set
+    pub max_in_flight: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ExpectedCounterparty {
+    /// The local channel id
+    pub channel_id: String,
+    pub counterparty_port: String,
+    pub counterparty_channel: String,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct AllowMsg {
     pub contract: String,
     pub gas_limit: Option<u64>,
+    /// Set for tokens that take a fee on transfer. Those must be sent with TransferCw20, so only
+    /// the amount that actually arrived is escrowed and sent. Keeps the current setting if not provided
+    pub fee_on_transfer: Option<bool>,
+    /// Set for tokens that may report success without moving funds, so every payout is checked
+    /// against our balance. Keeps the current setting if not provided
+    #[serde(default)]
+    pub verify_balance: Option<bool>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, JsonSchema)]
@@ -32,8 +82,150 @@ pub enum ExecuteMsg {
     Receive(Cw20ReceiveMsg),
     /// This allows us to transfer *exactly one* native token
     Transfer(TransferMsg),
+    /// This sends a cw20 token that takes a fee on transfer. Instead of a Receive, the contract
+    /// pulls amount with TransferFrom, so the sender must have approved an allowance first.
+    /// Only what actually arrived is escrowed and sent
+    TransferCw20 {
+        token: String,
+        amount: Uint128,
+        transfer: TransferMsg,
+    },
     /// This must be called by gov_contract, will allow a new cw20 token to be sent
     Allow(AllowMsg),
+    /// This must be called by gov_contract, blocks all transfers of a cw20 token in and out,
+    /// even if it is on the allow list
+    Deny { contract: String },
+    /// This must be called by gov_contract, takes a cw20 token off the deny list
+    Undeny { contract: String },
+    /// This must be called by gov_contract, allows incoming transfers to be released to
+    /// address if restrict_receivers is set
+    AllowReceiver { address: String },
+    /// This must be called by gov_contract, takes an address off the receiver allow list
+    DisallowReceiver { address: String },
+    /// This must be called by gov_contract. Native tokens of denom are held and paid out as
+    /// alias locally, while they are still escrowed and sent as denom. Removes the alias if None
+    SetDenomAlias {
+        denom: String,
+        alias: Option<String>,
+    },
+    /// This must be called by gov_contract. Tokens of the remote denom received on channel_id are
+    /// minted as token, which this contract must be the minter of, and token is burned when sent
+    /// back. Switches the denom back to escrow if token is None
+    SetMintedToken {
+        channel_id: String,
+        denom: String,
+        token: Option<String>,
+    },
+    /// This must be called by gov_contract. Stores a critical change, to be applied with
+    /// ApplyPendingChange once admin_timelock has passed
+    ProposeChange { change: Box<ExecuteMsg> },
+    /// This must be called by gov_contract. Applies a proposed change whose timelock has passed
+    ApplyPendingChange { id: u64 },
+    /// This must be called by gov_contract, stops all new transfers in and out
+    Pause {},
+    /// This must be called by gov_contract, resumes transfers after a pause
+    Unpause {},
+    /// This must be called by gov_contract, stops new transfers in and out of one channel,
+    /// without closing it
+    DisableChannel { channel_id: String },
+    /// This must be called by gov_contract, resumes transfers on a disabled channel
+    EnableChannel { channel_id: String },
+    /// This must be called by gov_contract while paused. Pays out the whole outstanding balance
+    /// of one denom on a channel to the recipient, and zeroes it. Escape hatch for stuck channels.
+    /// Pays the recovery address if no recipient is given.
+    ForceRefund {
+        channel_id: String,
+        denom: String,
+        recipient: Option<String>,
+    },
+    /// This must be called by gov_contract. Sets the address ForceRefund and SweepUntracked pay
+    /// if no recipient is given, eg. a cw3 multisig, or removes it if address is None.
+    /// A contract must implement cw2, so we do not send funds to a contract that cannot use them.
+    SetRecovery { address: Option<String> },
+    /// This must be called by gov_contract, sets how acknowledgements on a channel are encoded.
+    /// Channels use AckEncoding::Json unless configured otherwise
+    SetAckEncoding {
+        channel_id: String,
+        encoding: AckEncoding,
+    },
+    /// This must be called by gov_contract while paused. Moves the escrow tracked on channel from
+    /// to channel to, eg. when the relay path was re-established on a new channel. Denoms both
+    /// channels track are added up.
+    MigrateChannelState { from: String, to: String },
+    /// This must be called by gov_contract while paused. Abandons a dead channel: disables it,
+    /// refunds its escrow to gov_contract like a close by the counterparty, and closes it.
+    AdminCloseChannel { channel_id: String },
+    /// This must be called by gov_contract. Sends the native balance of denom that is not
+    /// tracked as outstanding on any channel (eg. tokens bank-sent to the contract) to recipient.
+    /// Escrowed tokens are never touched. Pays the recovery address if no recipient is given.
+    SweepUntracked {
+        denom: String,
+        recipient: Option<String>,
+    },
+    /// This must be called by gov_contract. Refunds the next batch of denoms of a closed channel
+    /// that had too many denoms to be refunded at once.
+    ContinueClose { channel_id: String },
+    /// This must be called by gov_contract. Sets the smallest amount of denom that can be sent
+    /// or received, or removes the floor if amount is None.
+    SetMinTransfer {
+        denom: String,
+        amount: Option<Uint128>,
+    },
+    /// This must be called by gov_contract. Sets the most of denom that can be outstanding over
+    /// all channels together, or removes the cap if cap is None.
+    SetGlobalCap { denom: String, cap: Option<Uint128> },
+    /// This must be called by gov_contract. Sets the gas limit native tokens of denom are
+    /// released with, or removes it if gas_limit is None.
+    SetNativeGasLimit {
+        denom: String,
+        gas_limit: Option<u64>,
+    },
+    /// This must be called by gov_contract. Sets how many seconds received transfers are held
+    /// for their receiver to Claim, or pays them out right away again if grace_period is None.
+    /// Deliveries held before keep their grace period.
+    SetClaimGracePeriod { grace_period: Option<u64> },
+    /// Pays out a received transfer held for the sender, which must be its receiver.
+    /// Works until gov sweeps it, even after the grace period
+    Claim { id: u64 },
+    /// This must be called by gov_contract, once the grace period of a held delivery expired.
+    /// Sends released tokens to recipient, or the recovery address if no recipient is given.
+    /// Minted tokens were never minted, so they are just dropped.
+    SweepUnclaimed { id: u64, recipient: Option<String> },
+    /// This can only be called by the contract itself. Pays out the shares of a received transfer
+    /// that was split over several recipients, so they all succeed or fail together.
+    PayShares { msgs: Vec<CosmosMsg> },
+}
+
+impl ExecuteMsg {
+    /// Changes that could be abused with a compromised gov key. With an admin_timelock, these
+    /// must be proposed first. Pausing and other emergency measures stay immediate, and so do
+    /// payouts to the recovery address, which is timelocked itself. Paying anyone else, or moving
+    /// escrow to another channel, could drain the escrow and is critical.
+    pub fn is_critical(&self) -> bool {
+        matches!(
+            self,
+            ExecuteMsg::Allow(_)
+                | ExecuteMsg::Deny { .. }
+                | ExecuteMsg::Undeny { .. }
+                | ExecuteMsg::SetDenomAlias { .. }
+                | ExecuteMsg::SetMintedToken { .. }
+                | ExecuteMsg::SetGlobalCap { .. }
+                | ExecuteMsg::SetRecovery { .. }
+                | ExecuteMsg::MigrateChannelState { .. }
+                | ExecuteMsg::ForceRefund {
+                    recipient: Some(_),
+                    ..
+                }
+                | ExecuteMsg::SweepUntracked {
+                    recipient: Some(_),
+                    ..
+                }
+                | ExecuteMsg::SweepUnclaimed {
+                    recipient: Some(_),
+                    ..
+                }
+        )
+    }
 }
 
 /// This is the message we accept via Receive
@@ -47,6 +239,80 @@ pub struct TransferMsg {
     pub remote_address: String,
     /// How long the packet lives in seconds. If not specified, use default_timeout
     pub timeout: Option<u64>,
+    /// An optional block height the packet times out at, in addition to the timeout above.
+    /// Must be above the current height, and is capped at max_timeout_height_offset blocks ahead
+    pub timeout_height: Option<IbcTimeoutBlock>,
+    /// An optional memo to add to the ics20 packet
+    pub memo: Option<String>,
+    /// An optional client chosen key. Another transfer of the same sender with the same key
+    /// is rejected for IDEMPOTENCY_WINDOW seconds, so a retried broadcast cannot send twice
+    pub idempotency_key: Option<String>,
+    /// An optional relayer fee, paid in native coins sent along with an `ExecuteMsg::Transfer`
+    /// on top of the transferred coin. Cannot be used with cw20 tokens
+    pub fee: Option<IbcFee>,
+    /// An optional local address the tokens are refunded to if the transfer fails or times out,
+    /// instead of the sender. Useful for contracts sending on behalf of their users
+    #[serde(default)]
+    pub refund_to: Option<String>,
+}
+
+/// How our acknowledgements on a channel are encoded, and how we expect the counterparty's
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AckEncoding {
+    /// `{"result": ...}` or `{"error": ...}`, like the ibctransfer module
+    #[default]
+    Json,
+    /// a single `0x01` byte on success, the plain error message on failure
+    Raw,
+}
+
+/// Relayer fees escrowed along with a transfer, like in ICS29. The recv and ack fees are
+/// consumed once the packet is acknowledged, the timeout fee once it timed out.
+/// The part that is not consumed is refunded to the sender.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct IbcFee {
+    pub recv_fee: Vec<Coin>,
+    pub ack_fee: Vec<Coin>,
+    pub timeout_fee: Vec<Coin>,
+}
+
+impl IbcFee {
+    /// all fees, summed per denom
+    pub fn total(&self) -> NativeBalance {
+        sum_coins(
+            self.recv_fee
+                .iter()
+                .chain(&self.ack_fee)
+                .chain(&self.timeout_fee),
+        )
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.total().is_empty()
+    }
+
+    /// Splits the fees into the consumed and the refunded part, once the packet was either
+    /// acknowledged or timed out
+    pub fn settle(&self, timed_out: bool) -> (NativeBalance, NativeBalance) {
+        let on_ack = sum_coins(self.recv_fee.iter().chain(&self.ack_fee));
+        let on_timeout = sum_coins(&self.timeout_fee);
+        if timed_out {
+            (on_timeout, on_ack)
+        } else {
+            (on_ack, on_timeout)
+        }
+    }
+}
+
+fn sum_coins<'a>(coins: impl IntoIterator<Item = &'a Coin>) -> NativeBalance {
+    let mut sum = NativeBalance::default();
+    for coin in coins {
+        if !coin.amount.is_zero() {
+            sum += coin.clone();
+        }
+    }
+    sum
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -56,9 +322,50 @@ pub enum QueryMsg {
     Port {},
     /// Show all channels we have connected to. Return type is ListChannelsResponse.
     ListChannels {},
+    /// Show all channels we have connected to over the given connection.
+    /// Return type is ListChannelsResponse.
+    ChannelsByConnection { connection_id: String },
     /// Returns the details of the name channel, error if not created.
     /// Return type: ChannelResponse.
     Channel { id: String },
+    /// Returns the balance of one denom on the given channel, zero if never sent.
+    /// Return type: ChannelDenomResponse.
+    ChannelDenom { channel_id: String, denom: String },
+    /// Returns the ics20 version negotiated on the channel and what it supports.
+    /// Return type: ChannelFeaturesResponse.
+    ChannelFeatures { channel_id: String },
+    /// Returns everything Channel and ChannelFeatures do, and whether the channel is enabled,
+    /// in one call. Return type: ChannelFullResponse.
+    ChannelFull { channel_id: String },
+    /// Returns what Channel does for each of the given channels, in the same order. Unknown ids
+    /// are reported as not found rather than failing the query. At most MAX_MULTI_CHANNEL ids.
+    /// Return type: MultiChannelResponse.
+    MultiChannel { channel_ids: Vec<String> },
+    /// Runs all checks of a transfer without executing it. Returns SimulateSendResponse
+    SimulateSend {
+        channel_id: String,
+        amount: Amount,
+        receiver: String,
+    },
+    /// Runs the checks of a failed (eg. timed out) send of amount of denom over the channel,
+    /// without executing it. Returns the refund that would be paid out to sender.
+    /// Return type: SimulateTimeoutResponse
+    SimulateTimeout {
+        channel_id: String,
+        sender: String,
+        denom: String,
+        amount: Uint128,
+    },
+    /// Resolves the local denom an incoming voucher from the given source port / channel would be
+    /// released as. Returns LocalDenomResponse
+    LocalDenom {
+        voucher: String,
+        src_port: String,
+        src_channel: String,
+    },
+    /// Lists the ics20 versions a channel may be opened with, highest first.
+    /// Returns SupportedVersionsResponse
+    SupportedVersions {},
     /// Show the Config. Returns ConfigResponse
     Config {},
     /// Query if a given cw20 contract is allowed. Returns AllowedResponse
@@ -68,6 +375,32 @@ pub enum QueryMsg {
         start_after: Option<String>,
         limit: Option<u32>,
     },
+    /// Query the minimum transfer amount of a denom. Returns MinTransferResponse
+    MinTransfer { denom: String },
+    /// Query the global cap of a denom and how much of it is outstanding over all channels.
+    /// Returns GlobalCapResponse
+    GlobalCap { denom: String },
+    /// Lists the proposed changes that were not applied yet. Returns PendingChangesResponse
+    PendingChanges {},
+    /// Lists the most recent failed receives, newest first. Returns RecentFailuresResponse
+    RecentFailures { limit: Option<u32> },
+    /// Lists the volume sent in each bucket starting in [from, to) (in seconds), oldest first.
+    /// Only buckets with sends are returned, at most MAX_VOLUME_BUCKETS per query.
+    /// Returns VolumeBucketsResponse
+    VolumeBuckets {
+        channel_id: String,
+        denom: String,
+        from: u64,
+        to: u64,
+    },
+    /// Compares the escrow tracked over all channels with the actual balance of the contract,
+    /// for each of the given denoms (eg. "ucosm" or "cw20:token-addr"). Returns ReconcileResponse
+    Reconcile { denoms: Vec<String> },
+    /// Checks the internal accounting over all channels and lists anything that does not add
+    /// up, rather than failing. Returns CheckInvariantsResponse
+    CheckInvariants {},
+    /// Shows a received transfer held for its receiver to claim. Returns PendingDeliveryResponse
+    PendingDelivery { id: u64 },
 }
 
 #[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
@@ -75,8 +408,14 @@ pub struct ListChannelsResponse {
     pub channels: Vec<ChannelInfo>,
 }
 
+/// Bumped whenever the shape of ChannelResponse changes, so clients can tell which one they got
+pub const CHANNEL_RESPONSE_VERSION: u16 = 1;
+
 #[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
 pub struct ChannelResponse {
+    /// The CHANNEL_RESPONSE_VERSION of this response. 0 if the contract predates versioning
+    #[serde(default)]
+    pub response_version: u16,
     /// Information on the channel's connection
     pub info: ChannelInfo,
     /// How many tokens we currently have pending over this channel
@@ -84,6 +423,107 @@ pub struct ChannelResponse {
     /// The total number of tokens that have been sent over this channel
     /// (even if many have been returned, so balance is low)
     pub total_sent: Vec<Amount>,
+    /// The parsed trace of each denom in balances, in the same order
+    pub denom_traces: Vec<DenomTrace>,
+}
+
+/// The ibc path of a denom, as `{port}/{channel}/.../{base_denom}`
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct DenomTrace {
+    /// the denom without any ibc path, eg. "uatom"
+    pub base_denom: String,
+    /// (port, channel) hops the token went through, outermost first. Empty for a local denom
+    pub path: Vec<(String, String)>,
+}
+
+impl DenomTrace {
+    /// The hops as `{port}/{channel}/...`, like the `path` of the ibctransfer module
+    pub fn path_string(&self) -> String {
+        self.path
+            .iter()
+            .map(|(port, channel)| format!("{}/{}", port, channel))
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct ChannelDenomResponse {
+    /// How many tokens of this denom we currently have pending over this channel
+    pub balance: Amount,
+    /// The total number of tokens of this denom that have been sent over this channel
+    pub total_sent: Amount,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct SupportedVersionsResponse {
+    pub versions: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct ChannelFeaturesResponse {
+    /// The negotiated version, eg. "ics20-1"
+    pub version: String,
+    /// The counterparty understands the packet memo. On other channels the memo is still
+    /// sent, but may be dropped by the remote side
+    pub supports_memo: bool,
+    /// Amounts above u64 max can be transferred
+    pub supports_u128: bool,
+    /// How acknowledgements on the channel are encoded
+    pub ack_encoding: AckEncoding,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct ChannelFullResponse {
+    /// The info and balances, as returned by Channel
+    pub channel: ChannelResponse,
+    /// The negotiated version and what it supports, as returned by ChannelFeatures
+    pub features: ChannelFeaturesResponse,
+    /// False if the gov contract disabled the channel
+    pub enabled: bool,
+}
+
+/// The most channels a MultiChannel query may ask for
+pub const MAX_MULTI_CHANNEL: usize = 30;
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct MultiChannelResponse {
+    pub channels: Vec<MultiChannelEntry>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct MultiChannelEntry {
+    pub channel_id: String,
+    /// None if there is no such channel
+    pub channel: Option<ChannelResponse>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct LocalDenomResponse {
+    /// the local denom, if the voucher can be received
+    pub denom: Option<String>,
+    /// the error the receive would fail with otherwise
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct SimulateSendResponse {
+    /// true if the transfer would be accepted
+    pub accepted: bool,
+    /// the error the transfer would fail with otherwise
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct SimulateTimeoutResponse {
+    /// true if the sender would be refunded
+    pub refundable: bool,
+    /// the amount paid back to the sender
+    pub refund: Option<Amount>,
+    /// the gas limit of the refund, None if unlimited
+    pub gas_limit: Option<u64>,
+    /// the error the refund would fail with otherwise
+    pub error: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
@@ -95,12 +535,128 @@ pub struct PortResponse {
 pub struct ConfigResponse {
     pub default_timeout: u64,
     pub gov_contract: String,
+    pub max_memo_len: u32,
+    pub allow_partial_release: bool,
+    pub allow_all_cw20: bool,
+    pub default_gas_limit: Option<u64>,
+    pub max_timeout_height_offset: u64,
+    pub max_denoms_per_channel: Option<u32>,
+    pub restrict_receivers: bool,
+    pub admin_timelock: Option<u64>,
+    pub volume_bucket: Option<u64>,
+    pub max_packet_size: Option<u32>,
+    pub paused: bool,
+    pub recovery: Option<String>,
+    pub claim_grace_period: Option<u64>,
+    pub max_in_flight: Option<u32>,
 }
 
 #[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
 pub struct AllowedResponse {
     pub is_allowed: bool,
     pub gas_limit: Option<u64>,
+    pub fee_on_transfer: bool,
+    pub verify_balance: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct MinTransferResponse {
+    /// None if the denom is unrestricted
+    pub amount: Option<Uint128>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct GlobalCapResponse {
+    /// None if the denom is uncapped
+    pub cap: Option<Uint128>,
+    /// the amount outstanding over all channels
+    pub outstanding: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct PendingChangesResponse {
+    pub changes: Vec<PendingChangeInfo>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct PendingDeliveryResponse {
+    pub channel: String,
+    pub receiver: String,
+    /// the tokens the receiver gets on Claim
+    pub amount: Amount,
+    /// SweepUnclaimed is rejected before this
+    pub claimable_until: Expiration,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct PendingChangeInfo {
+    pub id: u64,
+    pub change: ExecuteMsg,
+    /// ApplyPendingChange is rejected before this
+    pub effective_at: Expiration,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct VolumeBucketsResponse {
+    /// length of the buckets in seconds
+    pub bucket_len: u64,
+    pub buckets: Vec<VolumeBucket>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct VolumeBucket {
+    /// start of the bucket in seconds, a multiple of bucket_len
+    pub start: u64,
+    /// total amount successfully sent in this bucket
+    pub amount: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct RecentFailuresResponse {
+    pub failures: Vec<ReceiveFailure>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct ReconcileResponse {
+    pub denoms: Vec<DenomReconciliation>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct DenomReconciliation {
+    pub denom: String,
+    /// the outstanding balance summed over all channels
+    pub tracked: Uint128,
+    /// the balance the contract actually holds
+    pub actual: Uint128,
+    /// actual - tracked, if more is held than tracked (eg. tokens sent to the contract directly)
+    pub surplus: Uint128,
+    /// tracked - actual, if less is held than tracked. This should never happen
+    pub shortfall: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct CheckInvariantsResponse {
+    pub violations: Vec<InvariantViolation>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum InvariantViolation {
+    /// More is escrowed on the channel than was ever acknowledged as sent, or is still in flight
+    OutstandingExceedsSent {
+        channel_id: String,
+        denom: String,
+        outstanding: Uint128,
+        total_sent: Uint128,
+        in_flight: Uint128,
+    },
+    /// The outstanding total of the denom over all channels differs from the sum of the channel
+    /// balances. This should never happen
+    GlobalOutstandingMismatch {
+        denom: String,
+        global: Uint128,
+        channels: Uint128,
+    },
 }
 
 #[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
@@ -112,4 +668,6 @@ pub struct ListAllowedResponse {
 pub struct AllowedInfo {
     pub contract: String,
     pub gas_limit: Option<u64>,
+    pub fee_on_transfer: bool,
+    pub verify_balance: bool,
 }
@@ -1,23 +1,157 @@
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use cosmwasm_std::{Addr, IbcEndpoint, Uint128};
+use crate::amount::Amount;
+use crate::contract::{DEFAULT_MAX_MEMO_LEN, DEFAULT_MAX_TIMEOUT_HEIGHT_OFFSET};
+use crate::ibc::{ICS20_2_VERSION, ICS20_PAYLOAD_VERSION, ICS20_VERSION};
+use crate::msg::{AckEncoding, ExecuteMsg, IbcFee, TransferMsg};
+use crate::ContractError;
+use cosmwasm_std::{Addr, Coin, IbcEndpoint, Order, StdResult, Storage, Timestamp, Uint128};
 use cw_storage_plus::{Item, Map};
+use cw_utils::Expiration;
+use std::collections::BTreeMap;
 
 pub const CONFIG: Item<Config> = Item::new("ics20_config");
 
+/// the port this contract is bound to, recorded when a channel connects
+pub const BOUND_PORT: Item<String> = Item::new("bound_port");
+
 /// static info on one channel that doesn't change
 pub const CHANNEL_INFO: Map<&str, ChannelInfo> = Map::new("channel_info");
 
+/// how acknowledgements are encoded on a channel, channels without an entry use AckEncoding::Json
+pub const ACK_ENCODING: Map<&str, AckEncoding> = Map::new("ack_encoding");
+
+/// the counterparty a channel must connect to, if pinned on instantiate
+pub const EXPECTED_COUNTERPARTY: Map<&str, IbcEndpoint> = Map::new("expected_counterparty");
+
 /// indexed by (channel_id, denom) maintaining the balance of the channel in that currency
 pub const CHANNEL_STATE: Map<(&str, &str), ChannelState> = Map::new("channel_state");
 
+/// an index of CHANNEL_INFO by (connection_id, channel_id)
+pub const CHANNELS_BY_CONNECTION: Map<(&str, &str), ()> = Map::new("channels_by_connection");
+
+/// where ForceRefund and SweepUntracked pay out if no recipient is given
+pub const RECOVERY: Item<Addr> = Item::new("recovery");
+
+/// while paused, no new transfers are sent or received, but the gov contract can force refunds
+pub const PAUSED: Item<bool> = Item::new("paused");
+
+/// channels the gov contract disabled, no new transfers are sent or received on them.
+/// Channels without an entry are enabled
+pub const CHANNEL_ENABLED: Map<&str, bool> = Map::new("channel_enabled");
+
+/// (channel_id, sequence) of every sent packet we already handled the ack or timeout of
+pub const PROCESSED_ACKS: Map<(&str, u64), ()> = Map::new("processed_acks");
+
+/// the smallest amount of a denom that can be sent or received, denoms without an entry are unrestricted
+pub const MIN_TRANSFER: Map<&str, Uint128> = Map::new("min_transfer");
+
+/// the gas limit native releases of a denom are paid out with, denoms without an entry are unlimited
+pub const NATIVE_GAS_LIMIT: Map<&str, u64> = Map::new("native_gas_limit");
+
+/// wrapped cw20 tokens this contract is the minter of, by (channel_id, remote denom). Tokens of the
+/// remote denom received on the channel are minted rather than released from escrow
+pub const MINTED_TOKENS: Map<(&str, &str), Addr> = Map::new("minted_tokens");
+
+/// the remote denom of a wrapped token by (channel_id, token address), the reverse of MINTED_TOKENS
+pub const MINTED_DENOMS: Map<(&str, &str), String> = Map::new("minted_denoms");
+
+/// relayer fees escrowed with a sent packet, by (channel_id, sequence), until its ack or timeout
+pub const FEE_ESCROW: Map<(&str, u64), FeeEscrow> = Map::new("fee_escrow");
+
+/// the fee escrow of the packet currently being sent, until its sequence is known in the reply
+pub const PENDING_FEE: Item<FeeEscrow> = Item::new("pending_fee");
+
+/// where the tokens of a sent packet are refunded to instead of its sender, by (channel_id, sequence)
+pub const REFUND_TO: Map<(&str, u64), Addr> = Map::new("refund_to");
+
+/// (channel_id, refund address) of the packet currently being sent, until its sequence is known in the reply
+pub const PENDING_REFUND_TO: Item<(String, Addr)> = Item::new("pending_refund_to");
+
+/// the total of all relayer fees in FEE_ESCROW per denom
+pub const FEE_TOTALS: Map<&str, Uint128> = Map::new("fee_totals");
+
+/// the most that can be outstanding of a denom over all channels, denoms without an entry are uncapped
+pub const GLOBAL_CAP: Map<&str, Uint128> = Map::new("global_cap");
+
+/// the outstanding amount of a denom summed over all channels, kept up to date with CHANNEL_STATE
+pub const GLOBAL_OUTSTANDING: Map<&str, Uint128> = Map::new("global_outstanding");
+
+/// the escrow released for the payout currently in flight, restored if that payout fails
+pub const REPLY_ARGS: Item<ReplyArgs> = Item::new("reply_args");
+
+/// a fee-on-transfer token being pulled with TransferFrom, until the reply measures what arrived
+pub const PENDING_PULL: Item<PendingPull> = Item::new("pending_pull");
+
+/// (sender, idempotency_key) of recent transfers, with the time the key can be reused
+pub const TRANSFER_KEYS: Map<(&Addr, &str), Timestamp> = Map::new("transfer_keys");
+
+/// channels the gov contract closed with AdminCloseChannel, the only ones we accept a CloseInit for
+pub const ADMIN_CLOSED: Map<&str, ()> = Map::new("admin_closed");
+
+/// channels closed with more denoms than could be refunded at once, with the last refunded denom
+pub const CLOSE_CURSOR: Map<&str, String> = Map::new("close_cursor");
+
+/// the most recent failed receives, by a running index. Only the last MAX_RECENT_FAILURES are kept
+pub const RECENT_FAILURES: Map<u64, ReceiveFailure> = Map::new("recent_failures");
+
+/// the index the next failed receive is stored at in RECENT_FAILURES
+pub const FAILURE_COUNT: Item<u64> = Item::new("failure_count");
+
+pub const MAX_RECENT_FAILURES: u64 = 20;
+
+/// amount successfully sent by (channel, denom, bucket start in seconds).
+/// Only written if Config::volume_bucket is set
+pub const VOLUME_BUCKETS: Map<(&str, &str, u64), Uint128> = Map::new("volume_buckets");
+
+/// the most buckets returned by one VolumeBuckets query
+pub const MAX_VOLUME_BUCKETS: usize = 100;
+
 /// Every cw20 contract we allow to be sent is stored here, possibly with a gas_limit
 pub const ALLOW_LIST: Map<&Addr, AllowInfo> = Map::new("allow_list");
 
+/// the local denom native tokens are held and paid out in, by the denom used on the wire.
+/// Denoms without an entry are used as they are
+pub const DENOM_ALIAS: Map<&str, String> = Map::new("denom_alias");
+
+/// the reverse of DENOM_ALIAS, the wire denom of each local alias
+pub const DENOM_ALIAS_WIRE: Map<&str, String> = Map::new("denom_alias_wire");
+
+/// critical changes proposed by the gov contract, by id, until they are applied
+pub const PENDING_CHANGES: Map<u64, PendingChange> = Map::new("pending_changes");
+
+/// the id of the last proposed change
+pub const PENDING_CHANGE_COUNT: Item<u64> = Item::new("pending_change_count");
+
+/// receivers incoming transfers may be released to, if restrict_receivers is set
+pub const RECEIVER_ALLOW_LIST: Map<&Addr, ()> = Map::new("receiver_allow_list");
+
+/// cw20 contracts that can neither be sent nor received, whether they are allowed or not
+pub const DENY_LIST: Map<&Addr, ()> = Map::new("deny_list");
+
+/// how many sent packets of each channel are waiting for an ack or timeout
+pub const IN_FLIGHT: Map<&str, u32> = Map::new("in_flight");
+
+/// the escrowed amount of sent packets waiting for an ack or timeout, by (channel_id, denom).
+/// It is part of the outstanding balance, but not yet of total_sent
+pub const IN_FLIGHT_AMOUNT: Map<(&str, &str), Uint128> = Map::new("in_flight_amount");
+
+/// received transfers held for their receiver to claim, by id, if claim_grace_period is set
+pub const PENDING_DELIVERY: Map<u64, PendingDelivery> = Map::new("pending_delivery");
+
+/// the id of the last held delivery
+pub const DELIVERY_COUNT: Item<u64> = Item::new("delivery_count");
+
+/// the sum of the released (not minted) deliveries held by wire denom, they are no longer escrow
+/// of any channel but still not ours to sweep
+pub const DELIVERY_TOTALS: Map<&str, Uint128> = Map::new("delivery_totals");
+
 #[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug, Default)]
 pub struct ChannelState {
+    /// tokens escrowed on this channel, increased as soon as a send is committed
     pub outstanding: Uint128,
+    /// tokens whose sends were successfully acknowledged by the counterparty
     pub total_sent: Uint128,
 }
 
@@ -25,6 +159,51 @@ pub struct ChannelState {
 pub struct Config {
     pub default_timeout: u64,
     pub gov_contract: Addr,
+    /// maximum memo length in bytes accepted on send and receive
+    #[serde(default = "default_max_memo_len")]
+    pub max_memo_len: u32,
+    /// release min(outstanding, requested) on receive instead of failing
+    #[serde(default)]
+    pub allow_partial_release: bool,
+    /// accept cw20 tokens that are not on the allow list
+    #[serde(default)]
+    pub allow_all_cw20: bool,
+    /// gas limit for cw20 tokens that are not on the allow list
+    #[serde(default)]
+    pub default_gas_limit: Option<u64>,
+    /// how many blocks ahead of the current height a timeout height may be set
+    #[serde(default = "default_max_timeout_height_offset")]
+    pub max_timeout_height_offset: u64,
+    /// how many distinct denoms a channel may track, unlimited if None
+    #[serde(default)]
+    pub max_denoms_per_channel: Option<u32>,
+    /// only release received tokens to receivers on RECEIVER_ALLOW_LIST
+    #[serde(default)]
+    pub restrict_receivers: bool,
+    /// seconds a critical change must wait between being proposed and applied, if set
+    #[serde(default)]
+    pub admin_timelock: Option<u64>,
+    /// length in seconds of the VOLUME_BUCKETS successful sends are recorded in, if set
+    #[serde(default)]
+    pub volume_bucket: Option<u64>,
+    /// maximum size in bytes of a sent packet's data, unlimited if None
+    #[serde(default)]
+    pub max_packet_size: Option<u32>,
+    /// seconds a received transfer is held for the receiver to claim before gov can sweep it.
+    /// Received transfers are paid out right away if None
+    #[serde(default)]
+    pub claim_grace_period: Option<u64>,
+    /// how many sent packets a channel may have waiting for an ack or timeout, unlimited if None
+    #[serde(default)]
+    pub max_in_flight: Option<u32>,
+}
+
+fn default_max_memo_len() -> u32 {
+    DEFAULT_MAX_MEMO_LEN
+}
+
+fn default_max_timeout_height_offset() -> u64 {
+    DEFAULT_MAX_TIMEOUT_HEIGHT_OFFSET
 }
 
 #[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
@@ -35,9 +214,517 @@ pub struct ChannelInfo {
     pub counterparty_endpoint: IbcEndpoint,
     /// the connection this exists on (you can use to query client/consensus info)
     pub connection_id: String,
+    /// the negotiated ics20 version. Channels stored before this was tracked are ics20-1
+    #[serde(default = "default_channel_version")]
+    pub version: String,
+}
+
+fn default_channel_version() -> String {
+    ICS20_VERSION.to_string()
+}
+
+impl ChannelInfo {
+    /// ics20-2 counterparties understand the memo field, as do those of IBC v2 payloads
+    pub fn supports_memo(&self) -> bool {
+        self.version == ICS20_2_VERSION || self.version == ICS20_PAYLOAD_VERSION
+    }
+
+    /// ics20-2 failure acknowledgements carry an error code, see `AckError`
+    pub fn supports_error_codes(&self) -> bool {
+        self.version == ICS20_2_VERSION
+    }
+
+    /// ics20-1 limits amounts to u64, ics20-2 and IBC v2 payloads allow the full u128 range
+    pub fn supports_u128(&self) -> bool {
+        self.version == ICS20_2_VERSION || self.version == ICS20_PAYLOAD_VERSION
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct PendingChange {
+    pub change: ExecuteMsg,
+    pub effective_at: Expiration,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct PendingDelivery {
+    pub channel: String,
+    pub receiver: Addr,
+    /// in the wire denom, like escrow
+    pub amount: Amount,
+    /// minted tokens are only minted when claimed, the others were released from escrow
+    pub mint: bool,
+    /// gov can sweep the delivery once this expired
+    pub claimable_until: Expiration,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct FeeEscrow {
+    pub channel: String,
+    /// gets the part of the fee that is not consumed
+    pub sender: Addr,
+    pub fee: IbcFee,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct ReplyArgs {
+    pub channel: String,
+    pub denom: String,
+    pub amount: Uint128,
+    /// Set if the reply must check that the payout actually left our balance
+    #[serde(default)]
+    pub balance_check: Option<BalanceCheck>,
+}
+
+/// Our balance of a cw20 token right before paying some of it out
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct BalanceCheck {
+    pub token: Addr,
+    pub before: Uint128,
+}
+
+/// A TransferCw20 waiting for its tokens to be pulled in
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct PendingPull {
+    pub token: Addr,
+    pub sender: Addr,
+    pub amount: Uint128,
+    /// Our balance of the token right before the pull
+    pub before: Uint128,
+    pub transfer: TransferMsg,
+}
+
+/// An incoming packet we acknowledged with an error. The packet fields are empty if it could not be parsed
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct ReceiveFailure {
+    pub channel: String,
+    pub denom: String,
+    pub amount: Uint128,
+    pub receiver: String,
+    pub error: String,
+    pub height: u64,
 }
 
 #[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
 pub struct AllowInfo {
     pub gas_limit: Option<u64>,
+    /// the token takes a fee on transfer, so we receive less than the stated amount
+    #[serde(default)]
+    pub fee_on_transfer: bool,
+    /// the token may report a successful transfer without moving any funds, so we check our
+    /// balance after every payout
+    #[serde(default)]
+    pub verify_balance: bool,
+}
+
+pub fn is_paused(storage: &dyn Storage) -> StdResult<bool> {
+    Ok(PAUSED.may_load(storage)?.unwrap_or(false))
+}
+
+/// Errors if the gov contract disabled the channel
+pub fn check_channel_enabled(storage: &dyn Storage, channel: &str) -> Result<(), ContractError> {
+    if CHANNEL_ENABLED.may_load(storage, channel)?.unwrap_or(true) {
+        Ok(())
+    } else {
+        Err(ContractError::ChannelDisabled { id: channel.into() })
+    }
+}
+
+/// The local denom native tokens of a wire denom are held and paid out in
+pub fn local_denom(storage: &dyn Storage, denom: &str) -> StdResult<String> {
+    Ok(DENOM_ALIAS
+        .may_load(storage, denom)?
+        .unwrap_or_else(|| denom.to_string()))
+}
+
+/// The denom native tokens held in a local denom are escrowed and sent as
+pub fn wire_denom(storage: &dyn Storage, denom: &str) -> StdResult<String> {
+    Ok(DENOM_ALIAS_WIRE
+        .may_load(storage, denom)?
+        .unwrap_or_else(|| denom.to_string()))
+}
+
+/// An escrowed amount, as it is paid out
+pub fn local_amount(storage: &dyn Storage, amount: Amount) -> StdResult<Amount> {
+    match amount {
+        Amount::Native(coin) => Ok(Amount::Native(Coin {
+            denom: local_denom(storage, &coin.denom)?,
+            amount: coin.amount,
+        })),
+        cw20 => Ok(cw20),
+    }
+}
+
+/// An amount that was paid in, as it is escrowed and sent
+pub fn wire_amount(storage: &dyn Storage, amount: Amount) -> StdResult<Amount> {
+    match amount {
+        Amount::Native(coin) => Ok(Amount::Native(Coin {
+            denom: wire_denom(storage, &coin.denom)?,
+            amount: coin.amount,
+        })),
+        cw20 => Ok(cw20),
+    }
+}
+
+pub fn ack_encoding(storage: &dyn Storage, channel: &str) -> StdResult<AckEncoding> {
+    Ok(ACK_ENCODING.may_load(storage, channel)?.unwrap_or_default())
+}
+
+/// The amount of denom escrowed over all channels, including relayer fees and deliveries held
+/// for their receiver
+pub fn total_outstanding(storage: &dyn Storage, denom: &str) -> StdResult<Uint128> {
+    let mut total = FEE_TOTALS.may_load(storage, denom)?.unwrap_or_default();
+    total += DELIVERY_TOTALS
+        .may_load(storage, denom)?
+        .unwrap_or_default();
+    for channel in CHANNEL_INFO.range_keys(storage, None, None, Order::Ascending) {
+        if let Some(state) = CHANNEL_STATE.may_load(storage, (&channel?, denom))? {
+            total += state.outstanding;
+        }
+    }
+    Ok(total)
+}
+
+/// Errors if sending denom over channel would add a new denom to a channel that already tracks
+/// max denoms. Denoms the channel already tracks are never blocked.
+pub fn check_denom_cap(
+    storage: &dyn Storage,
+    channel: &str,
+    denom: &str,
+    max: Option<u32>,
+) -> Result<(), ContractError> {
+    let max = match max {
+        Some(max) => max,
+        None => return Ok(()),
+    };
+    if CHANNEL_STATE.has(storage, (channel, denom)) {
+        return Ok(());
+    }
+    let tracked = CHANNEL_STATE
+        .prefix(channel)
+        .keys_raw(storage, None, None, Order::Ascending)
+        .take(max as usize)
+        .count();
+    if tracked >= max as usize {
+        Err(ContractError::TooManyDenoms { max })
+    } else {
+        Ok(())
+    }
+}
+
+/// Adds (or removes, once paid out) relayer fees to FEE_TOTALS
+pub fn update_fee_totals(storage: &mut dyn Storage, coins: &[Coin], escrow: bool) -> StdResult<()> {
+    for coin in coins {
+        FEE_TOTALS.update(storage, &coin.denom, |total| -> StdResult<_> {
+            let total = total.unwrap_or_default();
+            if escrow {
+                Ok(total.checked_add(coin.amount)?)
+            } else {
+                Ok(total.checked_sub(coin.amount)?)
+            }
+        })?;
+    }
+    Ok(())
+}
+
+/// Holds a received transfer for its receiver to claim, and returns the id to claim it with
+pub fn hold_delivery(
+    storage: &mut dyn Storage,
+    channel: &str,
+    receiver: Addr,
+    amount: Amount,
+    mint: bool,
+    claimable_until: Expiration,
+) -> StdResult<u64> {
+    let id = DELIVERY_COUNT.may_load(storage)?.unwrap_or_default() + 1;
+    DELIVERY_COUNT.save(storage, &id)?;
+    if !mint {
+        DELIVERY_TOTALS.update(storage, &amount.denom(), |total| -> StdResult<_> {
+            Ok(total.unwrap_or_default().checked_add(amount.amount())?)
+        })?;
+    }
+    let delivery = PendingDelivery {
+        channel: channel.to_string(),
+        receiver,
+        amount,
+        mint,
+        claimable_until,
+    };
+    PENDING_DELIVERY.save(storage, id, &delivery)?;
+    Ok(id)
+}
+
+/// A held delivery, or an error if there is none with this id
+pub fn load_delivery(storage: &dyn Storage, id: u64) -> Result<PendingDelivery, ContractError> {
+    PENDING_DELIVERY
+        .may_load(storage, id)?
+        .ok_or(ContractError::NoPendingDelivery { id })
+}
+
+/// Removes a held delivery, once it is claimed or swept
+pub fn remove_delivery(
+    storage: &mut dyn Storage,
+    id: u64,
+    delivery: &PendingDelivery,
+) -> StdResult<()> {
+    PENDING_DELIVERY.remove(storage, id);
+    if !delivery.mint {
+        let denom = delivery.amount.denom();
+        DELIVERY_TOTALS.update(storage, &denom, |total| -> StdResult<_> {
+            Ok(total
+                .unwrap_or_default()
+                .checked_sub(delivery.amount.amount())?)
+        })?;
+    }
+    Ok(())
+}
+
+/// Errors if channel already has as many sent packets waiting for an ack or timeout as allowed
+pub fn check_in_flight(
+    storage: &dyn Storage,
+    channel: &str,
+    max: Option<u32>,
+) -> Result<(), ContractError> {
+    if let Some(max) = max {
+        let in_flight = IN_FLIGHT.may_load(storage, channel)?.unwrap_or_default();
+        if in_flight >= max {
+            return Err(ContractError::TooManyInFlight {
+                channel: channel.to_string(),
+                max,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Counts a packet sent over channel as in flight until it is acked or times out
+pub fn increase_in_flight(storage: &mut dyn Storage, channel: &str) -> StdResult<()> {
+    IN_FLIGHT.update(storage, channel, |count| -> StdResult<_> {
+        Ok(count.unwrap_or_default().saturating_add(1))
+    })?;
+    Ok(())
+}
+
+/// Frees the in-flight slot of a packet that was acked or timed out. The count never goes below
+/// zero, eg. for packets sent before it was kept. Returns false if there was no slot to free.
+pub fn decrease_in_flight(storage: &mut dyn Storage, channel: &str) -> StdResult<bool> {
+    let count = IN_FLIGHT.may_load(storage, channel)?.unwrap_or_default();
+    match count.checked_sub(1) {
+        Some(count) => {
+            IN_FLIGHT.save(storage, channel, &count)?;
+            Ok(true)
+        }
+        None => Ok(false),
+    }
+}
+
+/// Adds (or removes) escrowed tokens to the running total of a denom over all channels.
+/// Must be called whenever the outstanding balance of a channel changes.
+pub fn update_global_outstanding(
+    storage: &mut dyn Storage,
+    denom: &str,
+    amount: Uint128,
+    escrow: bool,
+) -> StdResult<()> {
+    GLOBAL_OUTSTANDING.update(storage, denom, |total| -> StdResult<_> {
+        let total = total.unwrap_or_default();
+        if escrow {
+            Ok(total.checked_add(amount)?)
+        } else {
+            Ok(total.checked_sub(amount)?)
+        }
+    })?;
+    Ok(())
+}
+
+/// Recomputes GLOBAL_OUTSTANDING from the balances of all channels, eg. after migrating
+/// from a version that did not track it yet
+pub fn rebuild_global_outstanding(storage: &mut dyn Storage) -> StdResult<()> {
+    let mut totals = BTreeMap::<String, Uint128>::new();
+    for item in CHANNEL_STATE.range(storage, None, None, Order::Ascending) {
+        let ((_, denom), state) = item?;
+        *totals.entry(denom).or_default() += state.outstanding;
+    }
+    let stale = GLOBAL_OUTSTANDING
+        .keys(storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<_>>>()?;
+    for denom in stale {
+        GLOBAL_OUTSTANDING.remove(storage, &denom);
+    }
+    for (denom, total) in totals {
+        GLOBAL_OUTSTANDING.save(storage, &denom, &total)?;
+    }
+    Ok(())
+}
+
+/// Indexes all known channels by their connection, as older versions did not keep the index
+pub fn rebuild_connection_index(storage: &mut dyn Storage) -> StdResult<()> {
+    let channels = CHANNEL_INFO
+        .range(storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<_>>>()?;
+    for (_, info) in channels {
+        CHANNELS_BY_CONNECTION.save(storage, (&info.connection_id, &info.id), &())?;
+    }
+    Ok(())
+}
+
+/// Errors if sending amount would raise the outstanding denom over all channels above its cap
+pub fn check_global_cap(
+    storage: &dyn Storage,
+    denom: &str,
+    amount: Uint128,
+) -> Result<(), ContractError> {
+    let cap = match GLOBAL_CAP.may_load(storage, denom)? {
+        Some(cap) => cap,
+        None => return Ok(()),
+    };
+    let outstanding = GLOBAL_OUTSTANDING
+        .may_load(storage, denom)?
+        .unwrap_or_default();
+    match outstanding.checked_add(amount) {
+        Ok(total) if total <= cap => Ok(()),
+        _ => Err(ContractError::GlobalCapExceeded {
+            denom: denom.into(),
+            cap,
+        }),
+    }
+}
+
+/// Stores a failed receive, evicting the oldest one once there are more than MAX_RECENT_FAILURES
+pub fn record_receive_failure(
+    storage: &mut dyn Storage,
+    failure: &ReceiveFailure,
+) -> StdResult<()> {
+    let index = FAILURE_COUNT.may_load(storage)?.unwrap_or_default();
+    RECENT_FAILURES.save(storage, index, failure)?;
+    if index >= MAX_RECENT_FAILURES {
+        RECENT_FAILURES.remove(storage, index - MAX_RECENT_FAILURES);
+    }
+    FAILURE_COUNT.save(storage, &(index + 1))
+}
+
+/// Errors if the amount is below the configured floor of the denom
+pub fn check_min_transfer(
+    storage: &dyn Storage,
+    denom: &str,
+    amount: Uint128,
+) -> Result<(), ContractError> {
+    match MIN_TRANSFER.may_load(storage, denom)? {
+        Some(min) if amount < min => Err(ContractError::BelowMinTransfer { min }),
+        _ => Ok(()),
+    }
+}
+
+/// Escrow the tokens of a send as soon as it is committed, so a receive racing the
+/// acknowledgement already sees them.
+pub fn increase_channel_balance(
+    storage: &mut dyn Storage,
+    channel: &str,
+    denom: &str,
+    amount: Uint128,
+) -> StdResult<()> {
+    CHANNEL_STATE.update(storage, (channel, denom), |orig| -> StdResult<_> {
+        let mut state = orig.unwrap_or_default();
+        state.outstanding += amount;
+        Ok(state)
+    })?;
+    update_global_outstanding(storage, denom, amount, true)?;
+    Ok(())
+}
+
+/// Release escrowed tokens, either because they were redeemed by an incoming packet
+/// or because the send that escrowed them failed.
+pub fn reduce_channel_balance(
+    storage: &mut dyn Storage,
+    channel: &str,
+    denom: &str,
+    amount: Uint128,
+) -> Result<(), ContractError> {
+    CHANNEL_STATE.update(
+        storage,
+        (channel, denom),
+        |orig| -> Result<_, ContractError> {
+            // the denom was never sent out over this channel, so none of it can come back
+            let mut cur = orig.ok_or_else(|| ContractError::DenomNotRegistered {
+                channel: channel.to_string(),
+                denom: denom.to_string(),
+            })?;
+            // this will return error if we don't have the funds there to cover the request
+            cur.outstanding =
+                cur.outstanding
+                    .checked_sub(amount)
+                    .or(Err(ContractError::InsufficientFunds {
+                        available: cur.outstanding,
+                        requested: amount,
+                    }))?;
+            Ok(cur)
+        },
+    )?;
+    update_global_outstanding(storage, denom, amount, false)?;
+    Ok(())
+}
+
+/// Counts the escrowed amount of a sent packet as in flight until it is acked or times out
+pub fn increase_in_flight_amount(
+    storage: &mut dyn Storage,
+    channel: &str,
+    denom: &str,
+    amount: Uint128,
+) -> StdResult<()> {
+    IN_FLIGHT_AMOUNT.update(storage, (channel, denom), |orig| -> StdResult<_> {
+        Ok(orig.unwrap_or_default().checked_add(amount)?)
+    })?;
+    Ok(())
+}
+
+/// Stops counting the amount of a packet that was acked or timed out as in flight. This never
+/// goes below zero, eg. for packets sent before it was kept.
+pub fn decrease_in_flight_amount(
+    storage: &mut dyn Storage,
+    channel: &str,
+    denom: &str,
+    amount: Uint128,
+) -> StdResult<()> {
+    let left = IN_FLIGHT_AMOUNT
+        .may_load(storage, (channel, denom))?
+        .unwrap_or_default()
+        .saturating_sub(amount);
+    if left.is_zero() {
+        IN_FLIGHT_AMOUNT.remove(storage, (channel, denom));
+    } else {
+        IN_FLIGHT_AMOUNT.save(storage, (channel, denom), &left)?;
+    }
+    Ok(())
+}
+
+/// Record a successfully acknowledged send. The tokens were already escrowed on send.
+pub fn record_successful_send(
+    storage: &mut dyn Storage,
+    channel: &str,
+    denom: &str,
+    amount: Uint128,
+) -> StdResult<()> {
+    CHANNEL_STATE.update(storage, (channel, denom), |orig| -> StdResult<_> {
+        let mut state = orig.unwrap_or_default();
+        state.total_sent += amount;
+        Ok(state)
+    })?;
+    Ok(())
+}
+
+/// Adds a successful send at `time` (in seconds) to its volume bucket, if buckets are enabled
+pub fn record_volume(
+    storage: &mut dyn Storage,
+    channel: &str,
+    denom: &str,
+    amount: Uint128,
+    time: u64,
+) -> StdResult<()> {
+    if let Some(bucket_len) = CONFIG.load(storage)?.volume_bucket {
+        let start = time - time % bucket_len;
+        VOLUME_BUCKETS.update(storage, (channel, denom, start), |orig| -> StdResult<_> {
+            Ok(orig.unwrap_or_default().checked_add(amount)?)
+        })?;
+    }
+    Ok(())
 }
@@ -4,21 +4,25 @@ use crate::contract::instantiate;
 use crate::ibc::{ibc_channel_connect, ibc_channel_open, ICS20_ORDERING, ICS20_VERSION};
 use crate::state::ChannelInfo;
 
-use cosmwasm_std::testing::{
-    mock_dependencies, mock_env, mock_info, MockApi, MockQuerier, MockStorage,
-};
+use cosmwasm_std::testing::{mock_env, mock_info, MockApi, MockQuerier, MockStorage};
 use cosmwasm_std::{
-    DepsMut, IbcChannel, IbcChannelConnectMsg, IbcChannelOpenMsg, IbcEndpoint, OwnedDeps,
+    from_slice, Coin, DepsMut, Empty, IbcChannel, IbcChannelConnectMsg, IbcChannelOpenMsg,
+    IbcEndpoint, OwnedDeps, Querier, QuerierResult, QueryRequest, WasmQuery,
 };
+use std::marker::PhantomData;
 
 use crate::msg::{AllowMsg, InitMsg};
 
 pub const DEFAULT_TIMEOUT: u64 = 3600; // 1 hour,
-pub const CONTRACT_PORT: &str = "ibc:wasm1234567890abcdef";
+pub const CONTRACT_PORT: &str = "wasm.cosmos2contract";
 pub const REMOTE_PORT: &str = "transfer";
 pub const CONNECTION_ID: &str = "connection-2";
 
 pub fn mock_channel(channel_id: &str) -> IbcChannel {
+    mock_channel_with_version(channel_id, ICS20_VERSION)
+}
+
+pub fn mock_channel_with_version(channel_id: &str, version: &str) -> IbcChannel {
     IbcChannel::new(
         IbcEndpoint {
             port_id: CONTRACT_PORT.into(),
@@ -29,7 +33,7 @@ pub fn mock_channel(channel_id: &str) -> IbcChannel {
             channel_id: format!("{}5", channel_id),
         },
         ICS20_ORDERING,
-        ICS20_VERSION,
+        version,
         CONNECTION_ID,
     )
 }
@@ -42,38 +46,107 @@ pub fn mock_channel_info(channel_id: &str) -> ChannelInfo {
             channel_id: format!("{}5", channel_id),
         },
         connection_id: CONNECTION_ID.into(),
+        version: ICS20_VERSION.into(),
     }
 }
 
 // we simulate instantiate and ack here
-pub fn add_channel(mut deps: DepsMut, channel_id: &str) {
-    let channel = mock_channel(channel_id);
+pub fn add_channel(deps: DepsMut, channel_id: &str) {
+    add_channel_with_version(deps, channel_id, ICS20_VERSION)
+}
+
+pub fn add_channel_with_version(mut deps: DepsMut, channel_id: &str, version: &str) {
+    let channel = mock_channel_with_version(channel_id, version);
     let open_msg = IbcChannelOpenMsg::new_init(channel.clone());
     ibc_channel_open(deps.branch(), mock_env(), open_msg).unwrap();
-    let connect_msg = IbcChannelConnectMsg::new_ack(channel, ICS20_VERSION);
+    let connect_msg = IbcChannelConnectMsg::new_ack(channel, version);
     ibc_channel_connect(deps.branch(), mock_env(), connect_msg).unwrap();
 }
 
-pub fn setup(
-    channels: &[&str],
-    allow: &[(&str, u64)],
-) -> OwnedDeps<MockStorage, MockApi, MockQuerier> {
-    let mut deps = mock_dependencies();
-
+pub fn mock_init_msg(allow: &[(&str, u64)]) -> InitMsg {
     let allowlist = allow
         .iter()
         .map(|(contract, gas)| AllowMsg {
             contract: contract.to_string(),
             gas_limit: Some(*gas),
+            fee_on_transfer: None,
+            verify_balance: None,
         })
         .collect();
 
-    // instantiate an empty contract
-    let instantiate_msg = InitMsg {
+    InitMsg {
         default_timeout: DEFAULT_TIMEOUT,
         gov_contract: "gov".to_string(),
         allowlist,
-    };
+        max_memo_len: None,
+        allow_partial_release: None,
+        allow_all_cw20: None,
+        default_gas_limit: None,
+        max_timeout_height_offset: None,
+        max_denoms_per_channel: None,
+        expected_counterparties: None,
+        restrict_receivers: None,
+        admin_timelock: None,
+        volume_bucket: None,
+        max_packet_size: None,
+        max_in_flight: None,
+    }
+}
+
+pub type MockDeps = OwnedDeps<MockStorage, MockApi, WasmMockQuerier>;
+
+/// Answers wasm queries, eg. the balance of a mocked cw20 token, with a handler set by the test,
+/// and hands every other query to MockQuerier, which cannot take a wasm handler in this version
+pub struct WasmMockQuerier {
+    base: MockQuerier,
+    wasm: Option<WasmHandler>,
+}
+
+type WasmHandler = Box<dyn Fn(&WasmQuery) -> QuerierResult>;
+
+impl WasmMockQuerier {
+    pub fn update_balance(&mut self, addr: impl Into<String>, balance: Vec<Coin>) {
+        self.base.update_balance(addr, balance);
+    }
+
+    pub fn update_wasm<WH>(&mut self, handler: WH)
+    where
+        WH: Fn(&WasmQuery) -> QuerierResult + 'static,
+    {
+        self.wasm = Some(Box::new(handler));
+    }
+}
+
+impl Querier for WasmMockQuerier {
+    fn raw_query(&self, bin_request: &[u8]) -> QuerierResult {
+        match (&self.wasm, from_slice::<QueryRequest<Empty>>(bin_request)) {
+            (Some(handler), Ok(QueryRequest::Wasm(query))) => handler(&query),
+            _ => self.base.raw_query(bin_request),
+        }
+    }
+}
+
+pub fn mock_deps() -> MockDeps {
+    OwnedDeps {
+        storage: MockStorage::default(),
+        api: MockApi::default(),
+        querier: WasmMockQuerier {
+            base: MockQuerier::default(),
+            wasm: None,
+        },
+        custom_query_type: PhantomData,
+    }
+}
+
+pub fn setup(channels: &[&str], allow: &[(&str, u64)]) -> MockDeps {
+    setup_with_init(channels, mock_init_msg(allow))
+}
+
+// like setup, but with a custom instantiate message
+pub fn setup_with_init(channels: &[&str], instantiate_msg: InitMsg) -> MockDeps {
+    let mut deps = mock_deps();
+
+    // instantiate an empty contract
     let info = mock_info(&String::from("anyone"), &[]);
     let res = instantiate(deps.as_mut(), mock_env(), info, instantiate_msg).unwrap();
     assert_eq!(0, res.messages.len());
@@ -16,8 +16,11 @@ use cw_storage_plus::Bound;
 use cw_utils::{Expiration, ThresholdResponse};
 
 use crate::error::ContractError;
-use crate::msg::{ExecuteMsg, InstantiateMsg, QueryMsg};
-use crate::state::{next_id, Ballot, Config, Proposal, Votes, BALLOTS, CONFIG, PROPOSALS, VOTERS};
+use crate::msg::{ExecuteMsg, InstantiateMsg, QueryMsg, VoterHistoryEntry, VoterHistoryResponse};
+use crate::state::{
+    next_id, save_ballot, Ballot, Config, Proposal, Votes, BALLOTS, CONFIG, PROPOSALS, VOTERS,
+    VOTER_BALLOTS,
+};
 
 // version info for migration info
 const CONTRACT_NAME: &str = "crates.io:cw3-fixed-multisig";
@@ -112,6 +115,7 @@ pub fn execute_propose(
         votes: Votes::yes(vote_power),
         threshold: cfg.threshold,
         total_weight: cfg.total_weight,
+        veto_threshold: None,
     };
     prop.update_status(&env.block);
     let id = next_id(deps.storage)?;
@@ -122,7 +126,7 @@ pub fn execute_propose(
         weight: vote_power,
         vote: Vote::Yes,
     };
-    BALLOTS.save(deps.storage, (id, &info.sender), &ballot)?;
+    save_ballot(deps.storage, id, &info.sender, &ballot)?;
 
     Ok(Response::new()
         .add_attribute("action", "propose")
@@ -154,19 +158,26 @@ pub fn execute_vote(
         return Err(ContractError::Expired {});
     }
 
-    // cast vote if no vote previously cast
-    BALLOTS.update(deps.storage, (proposal_id, &info.sender), |bal| match bal {
-        Some(_) => Err(ContractError::AlreadyVoted {}),
-        None => Ok(Ballot {
-            weight: vote_power,
-            vote,
-        }),
-    })?;
-
-    // update vote tally
-    prop.votes.add_vote(vote, vote_power);
-    prop.update_status(&env.block);
-    PROPOSALS.save(deps.storage, proposal_id, &prop)?;
+    // cast vote, replacing a different vote previously cast
+    let key = (proposal_id, &info.sender);
+    match BALLOTS.may_load(deps.storage, key)? {
+        // voting the same option again is a no-op
+        Some(ballot) if ballot.vote == vote => {}
+        previous => {
+            // update vote tally, taking back the previous vote
+            if let Some(ballot) = previous {
+                prop.votes.remove_vote(ballot.vote, ballot.weight);
+            }
+            let ballot = Ballot {
+                weight: vote_power,
+                vote,
+            };
+            save_ballot(deps.storage, proposal_id, &info.sender, &ballot)?;
+            prop.votes.add_vote(vote, vote_power);
+            prop.update_status(&env.block);
+            PROPOSALS.save(deps.storage, proposal_id, &prop)?;
+        }
+    }
 
     Ok(Response::new()
         .add_attribute("action", "vote")
@@ -253,6 +264,11 @@ pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
         QueryMsg::ListVoters { start_after, limit } => {
             to_binary(&list_voters(deps, start_after, limit)?)
         }
+        QueryMsg::VoterHistory {
+            voter,
+            start_after,
+            limit,
+        } => to_binary(&voter_history(deps, voter, start_after, limit)?),
     }
 }
 
@@ -369,6 +385,32 @@ fn list_votes(
     Ok(VoteListResponse { votes })
 }
 
+fn voter_history(
+    deps: Deps,
+    voter: String,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<VoterHistoryResponse> {
+    let voter = deps.api.addr_validate(&voter)?;
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive_int);
+
+    let votes = VOTER_BALLOTS
+        .prefix(&voter)
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| {
+            item.map(|(proposal_id, ballot)| VoterHistoryEntry {
+                proposal_id,
+                vote: ballot.vote,
+                weight: ballot.weight,
+            })
+        })
+        .collect::<StdResult<_>>()?;
+
+    Ok(VoterHistoryResponse { votes })
+}
+
 fn query_voter(deps: Deps, voter: String) -> StdResult<VoterResponse> {
     let voter = deps.api.addr_validate(&voter)?;
     let weight = VOTERS.may_load(deps.storage, &voter)?;
@@ -678,13 +720,14 @@ mod tests {
         // Get the proposal id from the logs
         let proposal_id: u64 = res.attributes[2].value.parse().unwrap();
 
-        // Owner cannot vote (again)
+        // Owner voting yes again changes nothing
         let yes_vote = ExecuteMsg::Vote {
             proposal_id,
             vote: Vote::Yes,
         };
-        let err = execute(deps.as_mut(), mock_env(), info, yes_vote.clone()).unwrap_err();
-        assert_eq!(err, ContractError::AlreadyVoted {});
+        let tally = get_tally(deps.as_ref(), proposal_id);
+        execute(deps.as_mut(), mock_env(), info, yes_vote.clone()).unwrap();
+        assert_eq!(tally, get_tally(deps.as_ref(), proposal_id));
 
         // Only voters can vote
         let info = mock_info(SOMEBODY, &[]);
@@ -726,14 +769,15 @@ mod tests {
             vote: Vote::Veto,
         };
         let info = mock_info(VOTER3, &[]);
-        execute(deps.as_mut(), mock_env(), info.clone(), veto_vote).unwrap();
+        execute(deps.as_mut(), mock_env(), info.clone(), veto_vote.clone()).unwrap();
 
         // Verify
         assert_eq!(tally, get_tally(deps.as_ref(), proposal_id));
 
-        // Once voted, votes cannot be changed
-        let err = execute(deps.as_mut(), mock_env(), info.clone(), yes_vote.clone()).unwrap_err();
-        assert_eq!(err, ContractError::AlreadyVoted {});
+        // Re-voting the same option is not counted twice
+        execute(deps.as_mut(), mock_env(), info.clone(), veto_vote).unwrap();
+        let prop = PROPOSALS.load(&deps.storage, proposal_id).unwrap();
+        assert_eq!(prop.votes.veto, 3);
         assert_eq!(tally, get_tally(deps.as_ref(), proposal_id));
 
         // Expired proposals cannot be voted
@@ -764,6 +808,141 @@ mod tests {
         assert_eq!(err, ContractError::NotOpen {});
     }
 
+    #[test]
+    fn test_change_vote() {
+        let mut deps = mock_dependencies();
+
+        let threshold = Threshold::AbsoluteCount { weight: 6 };
+        let voting_period = Duration::Time(2000000);
+
+        let info = mock_info(OWNER, &[]);
+        setup_test_case(deps.as_mut(), info, threshold, voting_period).unwrap();
+
+        // Propose, with a yes vote from voter4
+        let proposal = ExecuteMsg::Propose {
+            title: "Pay somebody".to_string(),
+            description: "Do I pay her?".to_string(),
+            msgs: vec![],
+            latest: None,
+        };
+        let info = mock_info(VOTER4, &[]);
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), proposal).unwrap();
+        let proposal_id: u64 = res.attributes[2].value.parse().unwrap();
+        assert_eq!(get_tally(deps.as_ref(), proposal_id), 4);
+
+        // yes -> no moves the weight from one option to the other
+        let no_vote = ExecuteMsg::Vote {
+            proposal_id,
+            vote: Vote::No,
+        };
+        execute(deps.as_mut(), mock_env(), info.clone(), no_vote).unwrap();
+        assert_eq!(get_tally(deps.as_ref(), proposal_id), 0);
+        let prop = PROPOSALS.load(&deps.storage, proposal_id).unwrap();
+        assert_eq!(prop.votes.yes, 0);
+        assert_eq!(prop.votes.no, 4);
+
+        let voter = QueryMsg::Vote {
+            proposal_id,
+            voter: VOTER4.into(),
+        };
+        let vote: VoteResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), voter).unwrap()).unwrap();
+        assert_eq!(vote.vote.unwrap().vote, Vote::No);
+
+        // and back to yes, which can still make the proposal pass
+        let yes_vote = ExecuteMsg::Vote {
+            proposal_id,
+            vote: Vote::Yes,
+        };
+        let voter2 = mock_info(VOTER2, &[]);
+        execute(deps.as_mut(), mock_env(), voter2, yes_vote.clone()).unwrap();
+        let res = execute(deps.as_mut(), mock_env(), info, yes_vote).unwrap();
+        assert_eq!(res.attributes[3].value, "Passed");
+        let prop = PROPOSALS.load(&deps.storage, proposal_id).unwrap();
+        assert_eq!(prop.votes.yes, 6);
+        assert_eq!(prop.votes.no, 0);
+    }
+
+    #[test]
+    fn test_voter_history() {
+        let mut deps = mock_dependencies();
+
+        let threshold = Threshold::AbsoluteCount { weight: 16 };
+        let voting_period = Duration::Time(2000000);
+        let info = mock_info(OWNER, &[]);
+        setup_test_case(deps.as_mut(), info, threshold, voting_period).unwrap();
+
+        let propose = |deps: DepsMut, proposer: &str| -> u64 {
+            let proposal = ExecuteMsg::Propose {
+                title: "Pay somebody".to_string(),
+                description: "Do I pay her?".to_string(),
+                msgs: vec![],
+                latest: None,
+            };
+            let res = execute(deps, mock_env(), mock_info(proposer, &[]), proposal).unwrap();
+            res.attributes[2].value.parse().unwrap()
+        };
+        let vote = |deps: DepsMut, proposal_id: u64, vote: Vote| {
+            let msg = ExecuteMsg::Vote { proposal_id, vote };
+            execute(deps, mock_env(), mock_info(VOTER2, &[]), msg).unwrap();
+        };
+        let history = |deps: Deps, start_after: Option<u64>| -> Vec<VoterHistoryEntry> {
+            let msg = QueryMsg::VoterHistory {
+                voter: VOTER2.into(),
+                start_after,
+                limit: Some(2),
+            };
+            let res: VoterHistoryResponse =
+                from_binary(&query(deps, mock_env(), msg).unwrap()).unwrap();
+            res.votes
+        };
+        let entry = |proposal_id: u64, vote: Vote| VoterHistoryEntry {
+            proposal_id,
+            vote,
+            weight: 2,
+        };
+
+        // voter2 proposes one, votes on some others and skips one
+        let first = propose(deps.as_mut(), VOTER2);
+        let second = propose(deps.as_mut(), VOTER3);
+        vote(deps.as_mut(), second, Vote::No);
+        let skipped = propose(deps.as_mut(), VOTER4);
+        let fourth = propose(deps.as_mut(), OWNER);
+        vote(deps.as_mut(), fourth, Vote::Yes);
+        // a changed vote replaces the earlier one
+        vote(deps.as_mut(), fourth, Vote::Abstain);
+        let fifth = propose(deps.as_mut(), VOTER1);
+        vote(deps.as_mut(), fifth, Vote::Veto);
+        assert_eq!(skipped, 3);
+
+        let page = history(deps.as_ref(), None);
+        assert_eq!(page, vec![entry(first, Vote::Yes), entry(second, Vote::No)]);
+        let page = history(deps.as_ref(), Some(second));
+        assert_eq!(
+            page,
+            vec![entry(fourth, Vote::Abstain), entry(fifth, Vote::Veto)]
+        );
+        let page = history(deps.as_ref(), Some(fifth));
+        assert_eq!(page, vec![]);
+
+        // other voters have their own history
+        let msg = QueryMsg::VoterHistory {
+            voter: VOTER3.into(),
+            start_after: None,
+            limit: None,
+        };
+        let res: VoterHistoryResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+        assert_eq!(
+            res.votes,
+            vec![VoterHistoryEntry {
+                proposal_id: second,
+                vote: Vote::Yes,
+                weight: 3,
+            }]
+        );
+    }
+
     #[test]
     fn test_execute_works() {
         let mut deps = mock_dependencies();
@@ -82,6 +82,9 @@ fn cw3_controls_cw20() {
             cap: None,
         }),
         marketing: None,
+        expected_decimals: None,
+        snapshot_balances: None,
+        freezer: None,
     };
     let cw20_addr = router
         .instantiate_contract(
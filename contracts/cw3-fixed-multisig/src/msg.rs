@@ -74,4 +74,22 @@ pub enum QueryMsg {
         start_after: Option<String>,
         limit: Option<u32>,
     },
+    /// Lists how voter voted on all proposals, by proposal id. Returns VoterHistoryResponse
+    VoterHistory {
+        voter: String,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct VoterHistoryResponse {
+    pub votes: Vec<VoterHistoryEntry>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct VoterHistoryEntry {
+    pub proposal_id: u64,
+    pub vote: Vote,
+    pub weight: u64,
 }
@@ -32,6 +32,9 @@ pub struct Proposal {
     pub total_weight: u64,
     // summary of existing votes
     pub votes: Votes,
+    /// share of the total weight voting no (or veto) that blocks the proposal, if any
+    #[serde(default)]
+    pub veto_threshold: Option<Decimal>,
 }
 
 impl Proposal {
@@ -40,8 +43,12 @@ impl Proposal {
     pub fn current_status(&self, block: &BlockInfo) -> Status {
         let mut status = self.status;
 
-        // if open, check if voting is passed or timed out
-        if status == Status::Open && self.is_passed(block) {
+        // if open, check if voting is vetoed, passed or timed out
+        if status == Status::Open && self.is_vetoed() {
+            status = Status::Vetoed;
+        }
+        // a proposal that could still be vetoed is not passed yet, so it cannot be executed early
+        if status == Status::Open && self.is_passed(block) && !self.can_be_vetoed(block) {
             status = Status::Passed;
         }
         if status == Status::Open && self.expires.is_expired(block) {
@@ -75,19 +82,42 @@ impl Proposal {
                 if self.votes.total() < votes_needed(self.total_weight, quorum) {
                     return false;
                 }
+                // abstain votes count toward the quorum, but a quorum of abstains alone
+                // leaves no opinions to pass the threshold with
                 if self.expires.is_expired(block) {
                     // If expired, we compare Yes votes against the total number of votes (minus abstain).
                     let opinions = self.votes.total() - self.votes.abstain;
-                    self.votes.yes >= votes_needed(opinions, threshold)
+                    opinions > 0 && self.votes.yes >= votes_needed(opinions, threshold)
                 } else {
                     // If not expired, we must assume all non-votes will be cast as No.
                     // We compare threshold against the total weight (minus abstain).
                     let possible_opinions = self.total_weight - self.votes.abstain;
-                    self.votes.yes >= votes_needed(possible_opinions, threshold)
+                    possible_opinions > 0
+                        && self.votes.yes >= votes_needed(possible_opinions, threshold)
                 }
             }
         }
     }
+
+    /// returns true once the no and veto votes reached the veto threshold, if there is one
+    pub fn is_vetoed(&self) -> bool {
+        match self.veto_threshold {
+            Some(veto) => self.votes.no + self.votes.veto >= votes_needed(self.total_weight, veto),
+            None => false,
+        }
+    }
+
+    // returns true if the votes not cast yet could still reach the veto threshold
+    fn can_be_vetoed(&self, block: &BlockInfo) -> bool {
+        match self.veto_threshold {
+            Some(veto) if !self.expires.is_expired(block) => {
+                let outstanding = self.total_weight.saturating_sub(self.votes.total());
+                self.votes.no + self.votes.veto + outstanding
+                    >= votes_needed(self.total_weight, veto)
+            }
+            _ => false,
+        }
+    }
 }
 
 // weight of votes for each option
@@ -123,6 +153,16 @@ impl Votes {
             Vote::Veto => self.veto += weight,
         }
     }
+
+    /// takes back a previously added vote, when a voter changes their mind
+    pub fn remove_vote(&mut self, vote: Vote, weight: u64) {
+        match vote {
+            Vote::Yes => self.yes -= weight,
+            Vote::Abstain => self.abstain -= weight,
+            Vote::No => self.no -= weight,
+            Vote::Veto => self.veto -= weight,
+        }
+    }
 }
 
 // this is a helper function so Decimal works with u64 rather than Uint128
@@ -147,11 +187,24 @@ pub const PROPOSAL_COUNT: Item<u64> = Item::new("proposal_count");
 
 // multiple-item map
 pub const BALLOTS: Map<(u64, &Addr), Ballot> = Map::new("votes");
+// the same ballots indexed by voter, to list the voting history of one member
+pub const VOTER_BALLOTS: Map<(&Addr, u64), Ballot> = Map::new("voter_ballots");
 pub const PROPOSALS: Map<u64, Proposal> = Map::new("proposals");
 
 // multiple-item maps
 pub const VOTERS: Map<&Addr, u64> = Map::new("voters");
 
+/// Stores a ballot along with its entry in the voter's history
+pub fn save_ballot(
+    store: &mut dyn Storage,
+    proposal_id: u64,
+    voter: &Addr,
+    ballot: &Ballot,
+) -> StdResult<()> {
+    BALLOTS.save(store, (proposal_id, voter), ballot)?;
+    VOTER_BALLOTS.save(store, (voter, proposal_id), ballot)
+}
+
 pub fn next_id(store: &mut dyn Storage) -> StdResult<u64> {
     let id: u64 = PROPOSAL_COUNT.may_load(store)?.unwrap_or_default() + 1;
     PROPOSAL_COUNT.save(store, &id)?;
@@ -176,6 +229,12 @@ mod test {
         assert_eq!(votes.no, 10);
         assert_eq!(votes.veto, 20);
         assert_eq!(votes.abstain, 40);
+
+        votes.remove_vote(Vote::Veto, 20);
+        votes.add_vote(Vote::No, 20);
+        assert_eq!(votes.total(), 105);
+        assert_eq!(votes.no, 30);
+        assert_eq!(votes.veto, 0);
     }
 
     #[test]
@@ -213,6 +272,7 @@ mod test {
             threshold,
             total_weight,
             votes,
+            veto_threshold: None,
         };
         prop.is_passed(&block)
     }
@@ -372,4 +432,57 @@ mod test {
         ));
         assert!(check_is_passed(quorum, passes_early, 15, true));
     }
+
+    #[test]
+    fn quorum_with_abstain() {
+        let quorum = Threshold::ThresholdQuorum {
+            threshold: Decimal::percent(60),
+            quorum: Decimal::percent(50),
+        };
+
+        // quorum reached by abstains only never passes
+        let only_abstain = Votes {
+            yes: 0,
+            no: 0,
+            abstain: 10,
+            veto: 0,
+        };
+        assert!(!check_is_passed(
+            quorum.clone(),
+            only_abstain.clone(),
+            15,
+            false
+        ));
+        assert!(!check_is_passed(
+            quorum.clone(),
+            only_abstain.clone(),
+            15,
+            true
+        ));
+        // not even if everybody abstained
+        assert!(!check_is_passed(
+            quorum.clone(),
+            only_abstain.clone(),
+            10,
+            false
+        ));
+        assert!(!check_is_passed(quorum.clone(), only_abstain, 10, true));
+
+        // abstains help reach the quorum, while yes clears the threshold of the rest
+        let abstain_helps = Votes {
+            yes: 2,
+            no: 1,
+            abstain: 6,
+            veto: 0,
+        };
+        assert!(check_is_passed(quorum.clone(), abstain_helps, 15, true));
+        // without them the quorum is missed
+        let no_quorum = Votes {
+            yes: 2,
+            no: 1,
+            abstain: 0,
+            veto: 0,
+        };
+        assert!(!check_is_passed(quorum, no_quorum, 15, true));
+    }
 }
@@ -3,8 +3,8 @@ use std::cmp::Ordering;
 #[cfg(not(feature = "library"))]
 use cosmwasm_std::entry_point;
 use cosmwasm_std::{
-    to_binary, Binary, BlockInfo, CosmosMsg, Deps, DepsMut, Empty, Env, MessageInfo, Order,
-    Response, StdResult,
+    to_binary, Binary, BlockInfo, Coin, CosmosMsg, Decimal, Deps, DepsMut, Empty, Env, MessageInfo,
+    Order, Response, StdResult, Storage, WasmMsg,
 };
 
 use cw2::set_contract_version;
@@ -15,10 +15,10 @@ use cw3::{
 use cw3_fixed_multisig::state::{next_id, Ballot, Proposal, Votes, BALLOTS, PROPOSALS};
 use cw4::{Cw4Contract, MemberChangedHookMsg, MemberDiff};
 use cw_storage_plus::Bound;
-use cw_utils::{maybe_addr, Expiration, ThresholdResponse};
+use cw_utils::{maybe_addr, Expiration, NativeBalance, ThresholdResponse};
 
 use crate::error::ContractError;
-use crate::msg::{ExecuteMsg, InstantiateMsg, QueryMsg};
+use crate::msg::{ExecuteMsg, ExpiredProposalsResponse, InstantiateMsg, QueryMsg};
 use crate::state::{Config, CONFIG};
 
 // version info for migration info
@@ -39,6 +39,11 @@ pub fn instantiate(
     })?);
     let total_weight = group_addr.total_weight(&deps.querier)?;
     msg.threshold.validate(total_weight)?;
+    if let Some(veto) = msg.veto_threshold {
+        if veto.is_zero() || veto > Decimal::one() {
+            return Err(ContractError::InvalidVetoThreshold {});
+        }
+    }
 
     set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
 
@@ -46,6 +51,8 @@ pub fn instantiate(
         threshold: msg.threshold,
         max_voting_period: msg.max_voting_period,
         group_addr,
+        allow_early_execution: msg.allow_early_execution.unwrap_or(true),
+        veto_threshold: msg.veto_threshold,
     };
     CONFIG.save(deps.storage, &cfg)?;
 
@@ -65,25 +72,40 @@ pub fn execute(
             description,
             msgs,
             latest,
-        } => execute_propose(deps, env, info, title, description, msgs, latest),
+            execute_funds,
+        } => execute_propose(
+            deps,
+            env,
+            info,
+            title,
+            description,
+            msgs,
+            latest,
+            execute_funds,
+        ),
         ExecuteMsg::Vote { proposal_id, vote } => execute_vote(deps, env, info, proposal_id, vote),
         ExecuteMsg::Execute { proposal_id } => execute_execute(deps, env, info, proposal_id),
         ExecuteMsg::Close { proposal_id } => execute_close(deps, env, info, proposal_id),
+        ExecuteMsg::CloseExpired { start_after, limit } => {
+            execute_close_expired(deps, env, info, start_after, limit)
+        }
         ExecuteMsg::MemberChangedHook(MemberChangedHookMsg { diffs }) => {
             execute_membership_hook(deps, env, info, diffs)
         }
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn execute_propose(
     deps: DepsMut,
     env: Env,
     info: MessageInfo,
     title: String,
     description: String,
-    msgs: Vec<CosmosMsg>,
+    mut msgs: Vec<CosmosMsg>,
     // we ignore earliest
     latest: Option<Expiration>,
+    execute_funds: Option<Vec<Coin>>,
 ) -> Result<Response<Empty>, ContractError> {
     // only members of the multisig can create a proposal
     let cfg = CONFIG.load(deps.storage)?;
@@ -108,6 +130,10 @@ pub fn execute_propose(
         return Err(ContractError::WrongExpiration {});
     }
 
+    if let Some(funds) = execute_funds {
+        attach_funds(&mut msgs, funds)?;
+    }
+
     // create a proposal
     let mut prop = Proposal {
         title,
@@ -119,6 +145,7 @@ pub fn execute_propose(
         votes: Votes::yes(vote_power),
         threshold: cfg.threshold,
         total_weight: cfg.group_addr.total_weight(&deps.querier)?,
+        veto_threshold: cfg.veto_threshold,
     };
     prop.update_status(&env.block);
     let id = next_id(deps.storage)?;
@@ -188,7 +215,7 @@ pub fn execute_vote(
 
 pub fn execute_execute(
     deps: DepsMut,
-    _env: Env,
+    env: Env,
     info: MessageInfo,
     proposal_id: u64,
 ) -> Result<Response, ContractError> {
@@ -200,6 +227,13 @@ pub fn execute_execute(
     if prop.status != Status::Passed {
         return Err(ContractError::WrongExecuteStatus {});
     }
+    // A proposal only passes before its expiration once no remaining votes can overturn it
+    // (see `Proposal::is_passed`), so executing it right away is safe. Still, it can be disabled.
+    let cfg = CONFIG.load(deps.storage)?;
+    if !cfg.allow_early_execution && !prop.expires.is_expired(&env.block) {
+        return Err(ContractError::EarlyExecutionDisabled {});
+    }
+    check_attached_funds(deps.as_ref(), &env, &prop.msgs)?;
 
     // set it to executed
     prop.status = Status::Executed;
@@ -213,6 +247,51 @@ pub fn execute_execute(
         .add_attribute("proposal_id", proposal_id.to_string()))
 }
 
+/// Adds funds to the first wasm message that can carry them, so they are spent once no matter
+/// how many wasm messages the proposal has. Errors if there is none.
+fn attach_funds(msgs: &mut [CosmosMsg], funds: Vec<Coin>) -> Result<(), ContractError> {
+    let current = msgs
+        .iter_mut()
+        .find_map(|msg| match msg {
+            CosmosMsg::Wasm(WasmMsg::Execute { funds, .. })
+            | CosmosMsg::Wasm(WasmMsg::Instantiate { funds, .. }) => Some(funds),
+            _ => None,
+        })
+        .ok_or(ContractError::NoFundsTarget {})?;
+    let mut balance = NativeBalance(std::mem::take(current));
+    balance.0.extend(funds);
+    balance.normalize();
+    *current = balance.into_vec();
+    Ok(())
+}
+
+/// Ensures the contract holds all funds attached to the proposal's wasm messages, so an
+/// underfunded execution fails with a clear error rather than in the bank module.
+fn check_attached_funds(deps: Deps, env: &Env, msgs: &[CosmosMsg]) -> Result<(), ContractError> {
+    let mut required = NativeBalance::default();
+    for msg in msgs {
+        if let CosmosMsg::Wasm(WasmMsg::Execute { funds, .. })
+        | CosmosMsg::Wasm(WasmMsg::Instantiate { funds, .. }) = msg
+        {
+            required += NativeBalance(funds.clone());
+        }
+    }
+    for coin in required.into_vec() {
+        let available = deps
+            .querier
+            .query_balance(&env.contract.address, &coin.denom)?
+            .amount;
+        if available < coin.amount {
+            return Err(ContractError::InsufficientFunds {
+                denom: coin.denom,
+                required: coin.amount,
+                available,
+            });
+        }
+    }
+    Ok(())
+}
+
 pub fn execute_close(
     deps: DepsMut,
     env: Env,
@@ -222,9 +301,14 @@ pub fn execute_close(
     // anyone can trigger this if the vote passed
 
     let mut prop = PROPOSALS.load(deps.storage, proposal_id)?;
-    if [Status::Executed, Status::Rejected, Status::Passed]
-        .iter()
-        .any(|x| *x == prop.status)
+    if [
+        Status::Executed,
+        Status::Rejected,
+        Status::Passed,
+        Status::Vetoed,
+    ]
+    .iter()
+    .any(|x| *x == prop.status)
     {
         return Err(ContractError::WrongCloseStatus {});
     }
@@ -242,6 +326,79 @@ pub fn execute_close(
         .add_attribute("proposal_id", proposal_id.to_string()))
 }
 
+pub fn execute_close_expired(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> Result<Response<Empty>, ContractError> {
+    // anyone can trigger this, it only cleans up proposals that can no longer be voted on
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let (expired, next_start_after) =
+        expired_proposals(deps.storage, &env.block, start_after, limit)?;
+
+    for (id, mut prop) in expired.iter().cloned() {
+        prop.status = Status::Rejected;
+        PROPOSALS.save(deps.storage, id, &prop)?;
+
+        let voters = BALLOTS
+            .prefix(id)
+            .keys(deps.storage, None, None, Order::Ascending)
+            .collect::<StdResult<Vec<_>>>()?;
+        for voter in voters {
+            BALLOTS.remove(deps.storage, (id, &voter));
+        }
+    }
+
+    let mut res = Response::new()
+        .add_attribute("action", "close_expired")
+        .add_attribute("sender", info.sender)
+        .add_attribute("closed", expired.len().to_string());
+    if let Some(id) = next_start_after {
+        res = res.add_attribute("next_start_after", id.to_string());
+    }
+    Ok(res)
+}
+
+// the most proposals one CloseExpired or ExpiredProposals reads, expired or not
+const MAX_EXPIRED_SCAN: usize = 100;
+
+// matching proposals, and the id of the last one read to continue from
+type ExpiredPage = (Vec<(u64, Proposal)>, Option<u64>);
+
+// proposals after start_after that expired without passing and were never closed, in order of
+// creation. Passed proposals can still be executed after their expiration, so they are left alone.
+// Stops after limit matches or MAX_EXPIRED_SCAN proposals read, and returns the id of the last one
+// read to continue from, or None if there are no more
+fn expired_proposals(
+    storage: &dyn Storage,
+    block: &BlockInfo,
+    start_after: Option<u64>,
+    limit: usize,
+) -> StdResult<ExpiredPage> {
+    let start = start_after.map(Bound::exclusive_int);
+    let mut expired = vec![];
+    let mut read = 0;
+    let mut last = None;
+    for item in PROPOSALS
+        .range(storage, start, None, Order::Ascending)
+        .take(MAX_EXPIRED_SCAN)
+    {
+        let (id, prop) = item?;
+        read += 1;
+        last = Some(id);
+        if prop.status == Status::Open && prop.current_status(block) == Status::Rejected {
+            expired.push((id, prop));
+            if expired.len() == limit {
+                break;
+            }
+        }
+    }
+    let done = expired.len() < limit && read < MAX_EXPIRED_SCAN;
+    Ok((expired, if done { None } else { last }))
+}
+
 pub fn execute_membership_hook(
     deps: DepsMut,
     _env: Env,
@@ -271,6 +428,9 @@ pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
             start_before,
             limit,
         } => to_binary(&reverse_proposals(deps, env, start_before, limit)?),
+        QueryMsg::ExpiredProposals { start_after, limit } => {
+            to_binary(&list_expired_proposals(deps, env, start_after, limit)?)
+        }
         QueryMsg::ListVotes {
             proposal_id,
             start_after,
@@ -342,6 +502,26 @@ fn reverse_proposals(
     Ok(ProposalListResponse { proposals: props? })
 }
 
+fn list_expired_proposals(
+    deps: Deps,
+    env: Env,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<ExpiredProposalsResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let (expired, next_start_after) =
+        expired_proposals(deps.storage, &env.block, start_after, limit)?;
+    let proposals = expired
+        .into_iter()
+        .map(|p| map_proposal(&env.block, Ok(p)))
+        .collect::<StdResult<_>>()?;
+
+    Ok(ExpiredProposalsResponse {
+        proposals,
+        next_start_after,
+    })
+}
+
 fn map_proposal(
     block: &BlockInfo,
     item: StdResult<(u64, Proposal)>,
@@ -426,7 +606,7 @@ fn list_voters(
 
 #[cfg(test)]
 mod tests {
-    use cosmwasm_std::{coin, coins, Addr, BankMsg, Coin, Decimal, Timestamp};
+    use cosmwasm_std::{coin, coins, Addr, BankMsg, Coin, Decimal, Timestamp, Uint128};
 
     use cw2::{query_contract_info, ContractVersion};
     use cw4::{Cw4ExecuteMsg, Member};
@@ -501,6 +681,8 @@ mod tests {
             group_addr: group.to_string(),
             threshold,
             max_voting_period,
+            allow_early_execution: None,
+            veto_threshold: None,
         };
         app.instantiate_contract(flex_id, Addr::unchecked(OWNER), &msg, &[], "flex", None)
             .unwrap()
@@ -593,6 +775,7 @@ mod tests {
             description,
             msgs,
             latest: None,
+            execute_funds: None,
         }
     }
 
@@ -614,6 +797,8 @@ mod tests {
                 quorum: Decimal::percent(1),
             },
             max_voting_period,
+            allow_early_execution: None,
+            veto_threshold: None,
         };
         let err = app
             .instantiate_contract(
@@ -635,6 +820,8 @@ mod tests {
             group_addr: group_addr.to_string(),
             threshold: Threshold::AbsoluteCount { weight: 100 },
             max_voting_period,
+            allow_early_execution: None,
+            veto_threshold: None,
         };
         let err = app
             .instantiate_contract(
@@ -656,6 +843,8 @@ mod tests {
             group_addr: group_addr.to_string(),
             threshold: Threshold::AbsoluteCount { weight: 1 },
             max_voting_period,
+            allow_early_execution: None,
+            veto_threshold: None,
         };
         let flex_addr = app
             .instantiate_contract(
@@ -725,6 +914,7 @@ mod tests {
             description: "Do we reward her?".to_string(),
             msgs,
             latest: Some(Expiration::AtHeight(123456)),
+            execute_funds: None,
         };
         let err = app
             .execute_contract(
@@ -1145,6 +1335,434 @@ mod tests {
         assert_eq!(ContractError::WrongCloseStatus {}, err.downcast().unwrap());
     }
 
+    #[test]
+    fn test_execute_with_funds() {
+        let init_funds = coins(10, "BTC");
+        let mut app = mock_app(&init_funds);
+
+        let threshold = Threshold::ThresholdQuorum {
+            threshold: Decimal::percent(51),
+            quorum: Decimal::percent(1),
+        };
+        let voting_period = Duration::Time(2000000);
+        let (flex_addr, group_addr) =
+            setup_test_case(&mut app, threshold, voting_period, init_funds, true);
+
+        // funds need a wasm message to be attached to
+        let (msgs, title, description) = proposal_info();
+        let bank_proposal = ExecuteMsg::Propose {
+            title,
+            description,
+            msgs,
+            latest: None,
+            execute_funds: Some(coins(3, "BTC")),
+        };
+        let err = app
+            .execute_contract(
+                Addr::unchecked(VOTER4),
+                flex_addr.clone(),
+                &bank_proposal,
+                &[],
+            )
+            .unwrap_err();
+        assert_eq!(ContractError::NoFundsTarget {}, err.downcast().unwrap());
+
+        let funded_proposal = |amount: u128| ExecuteMsg::Propose {
+            title: "Fund the group".to_string(),
+            description: "Send some BTC along".to_string(),
+            msgs: vec![Cw4GroupContract::new(group_addr.clone())
+                .update_members(vec![], vec![])
+                .unwrap()],
+            latest: None,
+            execute_funds: Some(coins(amount, "BTC")),
+        };
+
+        // VOTER4 alone passes both proposals
+        let res = app
+            .execute_contract(
+                Addr::unchecked(VOTER4),
+                flex_addr.clone(),
+                &funded_proposal(30),
+                &[],
+            )
+            .unwrap();
+        let underfunded_id: u64 = res.custom_attrs(1)[2].value.parse().unwrap();
+        let res = app
+            .execute_contract(
+                Addr::unchecked(VOTER4),
+                flex_addr.clone(),
+                &funded_proposal(3),
+                &[],
+            )
+            .unwrap();
+        let funded_id: u64 = res.custom_attrs(1)[2].value.parse().unwrap();
+
+        // the attached funds show up in the proposal
+        let prop: ProposalResponse = app
+            .wrap()
+            .query_wasm_smart(
+                &flex_addr,
+                &QueryMsg::Proposal {
+                    proposal_id: funded_id,
+                },
+            )
+            .unwrap();
+        match &prop.msgs[0] {
+            CosmosMsg::Wasm(WasmMsg::Execute { funds, .. }) => {
+                assert_eq!(funds, &coins(3, "BTC"))
+            }
+            _ => panic!("Wrong message"),
+        }
+
+        // not enough balance for the first one
+        let err = app
+            .execute_contract(
+                Addr::unchecked(SOMEBODY),
+                flex_addr.clone(),
+                &ExecuteMsg::Execute {
+                    proposal_id: underfunded_id,
+                },
+                &[],
+            )
+            .unwrap_err();
+        assert_eq!(
+            ContractError::InsufficientFunds {
+                denom: "BTC".to_string(),
+                required: Uint128::new(30),
+                available: Uint128::new(10),
+            },
+            err.downcast().unwrap()
+        );
+
+        // the second one is dispatched with its funds
+        app.execute_contract(
+            Addr::unchecked(SOMEBODY),
+            flex_addr.clone(),
+            &ExecuteMsg::Execute {
+                proposal_id: funded_id,
+            },
+            &[],
+        )
+        .unwrap();
+        let group_bal = app.wrap().query_balance(&group_addr, "BTC").unwrap();
+        assert_eq!(group_bal, coin(3, "BTC"));
+        let contract_bal = app.wrap().query_balance(&flex_addr, "BTC").unwrap();
+        assert_eq!(contract_bal, coin(7, "BTC"));
+
+        // with two wasm messages, the funds are only attached to the first one
+        let update = Cw4GroupContract::new(group_addr.clone())
+            .update_members(vec![], vec![])
+            .unwrap();
+        let proposal = ExecuteMsg::Propose {
+            title: "Fund the group once".to_string(),
+            description: "Two messages, one payment".to_string(),
+            msgs: vec![update.clone(), update],
+            latest: None,
+            execute_funds: Some(coins(3, "BTC")),
+        };
+        let res = app
+            .execute_contract(Addr::unchecked(VOTER4), flex_addr.clone(), &proposal, &[])
+            .unwrap();
+        let proposal_id: u64 = res.custom_attrs(1)[2].value.parse().unwrap();
+        let prop: ProposalResponse = app
+            .wrap()
+            .query_wasm_smart(&flex_addr, &QueryMsg::Proposal { proposal_id })
+            .unwrap();
+        let attached: Vec<_> = prop
+            .msgs
+            .iter()
+            .map(|msg| match msg {
+                CosmosMsg::Wasm(WasmMsg::Execute { funds, .. }) => funds.clone(),
+                _ => panic!("Wrong message"),
+            })
+            .collect();
+        assert_eq!(attached, vec![coins(3, "BTC"), vec![]]);
+
+        app.execute_contract(
+            Addr::unchecked(SOMEBODY),
+            flex_addr.clone(),
+            &ExecuteMsg::Execute { proposal_id },
+            &[],
+        )
+        .unwrap();
+        let group_bal = app.wrap().query_balance(&group_addr, "BTC").unwrap();
+        assert_eq!(group_bal, coin(6, "BTC"));
+        let contract_bal = app.wrap().query_balance(&flex_addr, "BTC").unwrap();
+        assert_eq!(contract_bal, coin(4, "BTC"));
+    }
+
+    #[test]
+    fn test_early_execution() {
+        let init_funds = coins(10, "BTC");
+        let mut app = mock_app(&coins(11, "BTC"));
+
+        // a vetoable threshold keeps the proposal open
+        let threshold = Threshold::ThresholdQuorum {
+            threshold: Decimal::percent(51),
+            quorum: Decimal::percent(1),
+        };
+        let voting_period = Duration::Time(2000000);
+        let (flex_addr, _) = setup_test_case(&mut app, threshold, voting_period, init_funds, true);
+
+        let proposal = pay_somebody_proposal();
+        let res = app
+            .execute_contract(Addr::unchecked(OWNER), flex_addr.clone(), &proposal, &[])
+            .unwrap();
+        let proposal_id: u64 = res.custom_attrs(1)[2].value.parse().unwrap();
+
+        // 5 of 23 yes, the remaining weight voting no could still reject it
+        let yes = ExecuteMsg::Vote {
+            proposal_id,
+            vote: Vote::Yes,
+        };
+        let res = app
+            .execute_contract(Addr::unchecked(VOTER5), flex_addr.clone(), &yes, &[])
+            .unwrap();
+        assert_eq!(res.custom_attrs(1)[3], ("status", "Open"));
+        let execution = ExecuteMsg::Execute { proposal_id };
+        let err = app
+            .execute_contract(Addr::unchecked(OWNER), flex_addr.clone(), &execution, &[])
+            .unwrap_err();
+        assert_eq!(
+            ContractError::WrongExecuteStatus {},
+            err.downcast().unwrap()
+        );
+
+        // 17 of 23 yes can no longer be overturned, so it is executable right away
+        let res = app
+            .execute_contract(Addr::unchecked(VOTER4), flex_addr.clone(), &yes, &[])
+            .unwrap();
+        assert_eq!(res.custom_attrs(1)[3], ("status", "Passed"));
+        app.execute_contract(Addr::unchecked(OWNER), flex_addr, &execution, &[])
+            .unwrap();
+
+        // with early execution disabled, a passed proposal waits for the voting period to end
+        let group_addr = instantiate_group(&mut app, vec![member(OWNER, 0), member(VOTER4, 12)]);
+        let flex_id = app.store_code(contract_flex());
+        let instantiate_msg = InstantiateMsg {
+            group_addr: group_addr.to_string(),
+            threshold: Threshold::AbsoluteCount { weight: 12 },
+            max_voting_period: voting_period,
+            allow_early_execution: Some(false),
+            veto_threshold: None,
+        };
+        let flex_addr = app
+            .instantiate_contract(
+                flex_id,
+                Addr::unchecked(OWNER),
+                &instantiate_msg,
+                &[],
+                "no early execution",
+                None,
+            )
+            .unwrap();
+        app.send_tokens(Addr::unchecked(OWNER), flex_addr.clone(), &coins(1, "BTC"))
+            .unwrap();
+        app.update_block(next_block);
+
+        let res = app
+            .execute_contract(Addr::unchecked(VOTER4), flex_addr.clone(), &proposal, &[])
+            .unwrap();
+        assert_eq!(res.custom_attrs(1)[3], ("status", "Passed"));
+        let proposal_id: u64 = res.custom_attrs(1)[2].value.parse().unwrap();
+        let execution = ExecuteMsg::Execute { proposal_id };
+        let err = app
+            .execute_contract(Addr::unchecked(OWNER), flex_addr.clone(), &execution, &[])
+            .unwrap_err();
+        assert_eq!(
+            ContractError::EarlyExecutionDisabled {},
+            err.downcast().unwrap()
+        );
+
+        app.update_block(expire(voting_period));
+        app.execute_contract(Addr::unchecked(OWNER), flex_addr, &execution, &[])
+            .unwrap();
+        let some_bal = app.wrap().query_balance(SOMEBODY, "BTC").unwrap();
+        assert_eq!(some_bal, coin(2, "BTC"));
+    }
+
+    #[test]
+    fn test_veto_threshold() {
+        let mut app = mock_app(&coins(10, "BTC"));
+        let voting_period = Duration::Time(2000000);
+        let group_addr = instantiate_group(
+            &mut app,
+            vec![
+                member(OWNER, 0),
+                member(VOTER1, 5),
+                member(VOTER2, 2),
+                member(VOTER3, 3),
+            ],
+        );
+        let flex_id = app.store_code(contract_flex());
+        let mut instantiate_msg = InstantiateMsg {
+            group_addr: group_addr.to_string(),
+            threshold: Threshold::AbsoluteCount { weight: 5 },
+            max_voting_period: voting_period,
+            allow_early_execution: None,
+            veto_threshold: Some(Decimal::percent(101)),
+        };
+        let err = app
+            .instantiate_contract(
+                flex_id,
+                Addr::unchecked(OWNER),
+                &instantiate_msg,
+                &[],
+                "veto",
+                None,
+            )
+            .unwrap_err();
+        assert_eq!(
+            ContractError::InvalidVetoThreshold {},
+            err.downcast().unwrap()
+        );
+
+        // 3 of 10 voting no veto a proposal
+        instantiate_msg.veto_threshold = Some(Decimal::percent(30));
+        let flex_addr = app
+            .instantiate_contract(
+                flex_id,
+                Addr::unchecked(OWNER),
+                &instantiate_msg,
+                &[],
+                "veto",
+                None,
+            )
+            .unwrap();
+        app.send_tokens(Addr::unchecked(OWNER), flex_addr.clone(), &coins(10, "BTC"))
+            .unwrap();
+        app.update_block(next_block);
+        let vote = |proposal_id: u64, vote: Vote| ExecuteMsg::Vote { proposal_id, vote };
+
+        let res = app
+            .execute_contract(
+                Addr::unchecked(OWNER),
+                flex_addr.clone(),
+                &pay_somebody_proposal(),
+                &[],
+            )
+            .unwrap();
+        let vetoed_id: u64 = res.custom_attrs(1)[2].value.parse().unwrap();
+
+        // enough yes votes to pass, but the outstanding 5 could still veto it, so it stays open
+        let res = app
+            .execute_contract(
+                Addr::unchecked(VOTER1),
+                flex_addr.clone(),
+                &vote(vetoed_id, Vote::Yes),
+                &[],
+            )
+            .unwrap();
+        assert_eq!(res.custom_attrs(1)[3], ("status", "Open"));
+        let execution = ExecuteMsg::Execute {
+            proposal_id: vetoed_id,
+        };
+        let err = app
+            .execute_contract(Addr::unchecked(OWNER), flex_addr.clone(), &execution, &[])
+            .unwrap_err();
+        assert_eq!(
+            ContractError::WrongExecuteStatus {},
+            err.downcast().unwrap()
+        );
+
+        // 3 no votes veto it, even though it has the yes votes to pass
+        let res = app
+            .execute_contract(
+                Addr::unchecked(VOTER3),
+                flex_addr.clone(),
+                &vote(vetoed_id, Vote::No),
+                &[],
+            )
+            .unwrap();
+        assert_eq!(res.custom_attrs(1)[3], ("status", "Vetoed"));
+        let err = app
+            .execute_contract(
+                Addr::unchecked(VOTER2),
+                flex_addr.clone(),
+                &vote(vetoed_id, Vote::Yes),
+                &[],
+            )
+            .unwrap_err();
+        assert_eq!(ContractError::NotOpen {}, err.downcast().unwrap());
+        let err = app
+            .execute_contract(Addr::unchecked(OWNER), flex_addr.clone(), &execution, &[])
+            .unwrap_err();
+        assert_eq!(
+            ContractError::WrongExecuteStatus {},
+            err.downcast().unwrap()
+        );
+
+        // 2 no votes do not reach the veto threshold
+        let res = app
+            .execute_contract(
+                Addr::unchecked(OWNER),
+                flex_addr.clone(),
+                &pay_somebody_proposal(),
+                &[],
+            )
+            .unwrap();
+        let passed_id: u64 = res.custom_attrs(1)[2].value.parse().unwrap();
+        app.execute_contract(
+            Addr::unchecked(VOTER1),
+            flex_addr.clone(),
+            &vote(passed_id, Vote::Yes),
+            &[],
+        )
+        .unwrap();
+        let res = app
+            .execute_contract(
+                Addr::unchecked(VOTER2),
+                flex_addr.clone(),
+                &vote(passed_id, Vote::No),
+                &[],
+            )
+            .unwrap();
+        assert_eq!(res.custom_attrs(1)[3], ("status", "Open"));
+        // once the last voter says yes, the veto is out of reach
+        let res = app
+            .execute_contract(
+                Addr::unchecked(VOTER3),
+                flex_addr.clone(),
+                &vote(passed_id, Vote::Yes),
+                &[],
+            )
+            .unwrap();
+        assert_eq!(res.custom_attrs(1)[3], ("status", "Passed"));
+        app.execute_contract(
+            Addr::unchecked(OWNER),
+            flex_addr.clone(),
+            &ExecuteMsg::Execute {
+                proposal_id: passed_id,
+            },
+            &[],
+        )
+        .unwrap();
+
+        // the vetoed proposal stays vetoed, and cannot be closed
+        app.update_block(expire(voting_period));
+        let prop: ProposalResponse = app
+            .wrap()
+            .query_wasm_smart(
+                &flex_addr,
+                &QueryMsg::Proposal {
+                    proposal_id: vetoed_id,
+                },
+            )
+            .unwrap();
+        assert_eq!(prop.status, Status::Vetoed);
+        let err = app
+            .execute_contract(
+                Addr::unchecked(OWNER),
+                flex_addr,
+                &ExecuteMsg::Close {
+                    proposal_id: vetoed_id,
+                },
+                &[],
+            )
+            .unwrap_err();
+        assert_eq!(ContractError::WrongCloseStatus {}, err.downcast().unwrap());
+    }
+
     #[test]
     fn test_close_works() {
         let init_funds = coins(10, "BTC");
@@ -1195,6 +1813,278 @@ mod tests {
         assert_eq!(ContractError::WrongCloseStatus {}, err.downcast().unwrap());
     }
 
+    #[test]
+    fn test_abstain_counts_toward_quorum() {
+        let init_funds = coins(10, "BTC");
+        let mut app = mock_app(&init_funds);
+
+        // total weight is 23, so quorum needs 10
+        let threshold = Threshold::ThresholdQuorum {
+            threshold: Decimal::percent(51),
+            quorum: Decimal::percent(40),
+        };
+        let voting_period = Duration::Height(2000000);
+        let (flex_addr, _) = setup_test_case(&mut app, threshold, voting_period, init_funds, true);
+
+        let proposal = pay_somebody_proposal();
+        let abstain = |app: &mut App, voter: &str, proposal_id: u64| {
+            let vote = ExecuteMsg::Vote {
+                proposal_id,
+                vote: Vote::Abstain,
+            };
+            app.execute_contract(Addr::unchecked(voter), flex_addr.clone(), &vote, &[])
+                .unwrap();
+        };
+
+        // OWNER proposes with 0 weight, VOTER4 brings the quorum by abstaining
+        let res = app
+            .execute_contract(Addr::unchecked(OWNER), flex_addr.clone(), &proposal, &[])
+            .unwrap();
+        let abstain_only_id: u64 = res.custom_attrs(1)[2].value.parse().unwrap();
+        abstain(&mut app, VOTER4, abstain_only_id);
+
+        // VOTER3 proposes with 3 weight, VOTER4 abstains again
+        let res = app
+            .execute_contract(Addr::unchecked(VOTER3), flex_addr.clone(), &proposal, &[])
+            .unwrap();
+        let abstain_helps_id: u64 = res.custom_attrs(1)[2].value.parse().unwrap();
+        abstain(&mut app, VOTER4, abstain_helps_id);
+
+        app.update_block(expire(voting_period));
+        let status = |id| -> Status {
+            let prop: ProposalResponse = app
+                .wrap()
+                .query_wasm_smart(&flex_addr, &QueryMsg::Proposal { proposal_id: id })
+                .unwrap();
+            prop.status
+        };
+        // quorum via abstain only fails
+        assert_eq!(status(abstain_only_id), Status::Rejected);
+        // with the abstain the quorum is reached, and all other votes are yes
+        assert_eq!(status(abstain_helps_id), Status::Passed);
+    }
+
+    #[test]
+    fn test_close_expired_works() {
+        let init_funds = coins(10, "BTC");
+        let mut app = mock_app(&init_funds);
+
+        let threshold = Threshold::ThresholdQuorum {
+            threshold: Decimal::percent(51),
+            quorum: Decimal::percent(1),
+        };
+        let voting_period = Duration::Height(2000000);
+        let (flex_addr, _) = setup_test_case(&mut app, threshold, voting_period, init_funds, true);
+
+        // one open proposal that passes at expiry, one passed but never executed,
+        // and two voted down
+        let proposal = pay_somebody_proposal();
+        let res = app
+            .execute_contract(Addr::unchecked(VOTER1), flex_addr.clone(), &proposal, &[])
+            .unwrap();
+        let open_id: u64 = res.custom_attrs(1)[2].value.parse().unwrap();
+        let res = app
+            .execute_contract(Addr::unchecked(VOTER4), flex_addr.clone(), &proposal, &[])
+            .unwrap();
+        let passed_id: u64 = res.custom_attrs(1)[2].value.parse().unwrap();
+        assert_eq!(res.custom_attrs(1)[3], ("status", "Passed"));
+        let rejected = |app: &mut App| -> u64 {
+            let res = app
+                .execute_contract(Addr::unchecked(VOTER1), flex_addr.clone(), &proposal, &[])
+                .unwrap();
+            let proposal_id: u64 = res.custom_attrs(1)[2].value.parse().unwrap();
+            let no_vote = ExecuteMsg::Vote {
+                proposal_id,
+                vote: Vote::No,
+            };
+            app.execute_contract(Addr::unchecked(VOTER2), flex_addr.clone(), &no_vote, &[])
+                .unwrap();
+            proposal_id
+        };
+        let rejected_ids = [rejected(&mut app), rejected(&mut app)];
+
+        // all expire, then a live one is created
+        app.update_block(expire(voting_period));
+        let res = app
+            .execute_contract(Addr::unchecked(VOTER5), flex_addr.clone(), &proposal, &[])
+            .unwrap();
+        let live_id: u64 = res.custom_attrs(1)[2].value.parse().unwrap();
+
+        // only the ones that did not pass are listed
+        let expired_ids = |app: &App| -> Vec<u64> {
+            let query = QueryMsg::ExpiredProposals {
+                start_after: None,
+                limit: None,
+            };
+            let list: ExpiredProposalsResponse =
+                app.wrap().query_wasm_smart(&flex_addr, &query).unwrap();
+            assert_eq!(list.next_start_after, None);
+            list.proposals.into_iter().map(|p| p.id).collect()
+        };
+        assert_eq!(expired_ids(&app), rejected_ids.to_vec());
+
+        // listing can start after a proposal
+        let query = QueryMsg::ExpiredProposals {
+            start_after: Some(rejected_ids[0]),
+            limit: None,
+        };
+        let list: ExpiredProposalsResponse =
+            app.wrap().query_wasm_smart(&flex_addr, &query).unwrap();
+        let ids: Vec<_> = list.proposals.into_iter().map(|p| p.id).collect();
+        assert_eq!(ids, vec![rejected_ids[1]]);
+
+        // closing is bounded by limit, and tells where to continue
+        let close = ExecuteMsg::CloseExpired {
+            start_after: None,
+            limit: Some(1),
+        };
+        let res = app
+            .execute_contract(Addr::unchecked(SOMEBODY), flex_addr.clone(), &close, &[])
+            .unwrap();
+        assert_eq!(
+            res.custom_attrs(1),
+            [
+                ("action", "close_expired"),
+                ("sender", SOMEBODY),
+                ("closed", "1"),
+                ("next_start_after", rejected_ids[0].to_string().as_str()),
+            ],
+        );
+        assert_eq!(expired_ids(&app), vec![rejected_ids[1]]);
+
+        // the ballots of closed proposals are removed
+        let votes: VoteListResponse = app
+            .wrap()
+            .query_wasm_smart(
+                &flex_addr,
+                &QueryMsg::ListVotes {
+                    proposal_id: rejected_ids[0],
+                    start_after: None,
+                    limit: None,
+                },
+            )
+            .unwrap();
+        assert_eq!(votes.votes, vec![]);
+
+        let close = ExecuteMsg::CloseExpired {
+            start_after: Some(rejected_ids[0]),
+            limit: None,
+        };
+        app.execute_contract(Addr::unchecked(SOMEBODY), flex_addr.clone(), &close, &[])
+            .unwrap();
+        assert_eq!(expired_ids(&app), Vec::<u64>::new());
+
+        // only the rejected ones were closed, passed proposals are never closed
+        let status = |app: &App, id| -> Status {
+            let prop: ProposalResponse = app
+                .wrap()
+                .query_wasm_smart(&flex_addr, &QueryMsg::Proposal { proposal_id: id })
+                .unwrap();
+            prop.status
+        };
+        for id in rejected_ids {
+            assert_eq!(status(&app, id), Status::Rejected);
+        }
+        assert_eq!(status(&app, open_id), Status::Passed);
+        assert_eq!(status(&app, passed_id), Status::Passed);
+        assert_eq!(status(&app, live_id), Status::Open);
+
+        // a rejected proposal cannot be executed anymore, a passed one still can
+        let execution = ExecuteMsg::Execute {
+            proposal_id: rejected_ids[0],
+        };
+        let err = app
+            .execute_contract(
+                Addr::unchecked(SOMEBODY),
+                flex_addr.clone(),
+                &execution,
+                &[],
+            )
+            .unwrap_err();
+        assert_eq!(
+            ContractError::WrongExecuteStatus {},
+            err.downcast().unwrap()
+        );
+        let execution = ExecuteMsg::Execute {
+            proposal_id: passed_id,
+        };
+        app.execute_contract(Addr::unchecked(SOMEBODY), flex_addr, &execution, &[])
+            .unwrap();
+    }
+
+    #[test]
+    fn test_close_expired_reads_bounded() {
+        let init_funds = coins(10, "BTC");
+        let mut app = mock_app(&init_funds);
+
+        let threshold = Threshold::ThresholdQuorum {
+            threshold: Decimal::percent(51),
+            quorum: Decimal::percent(1),
+        };
+        let voting_period = Duration::Height(2000000);
+        let (flex_addr, _) = setup_test_case(&mut app, threshold, voting_period, init_funds, true);
+
+        // a full scan of passed proposals, then one voted down
+        let proposal = pay_somebody_proposal();
+        let mut last_passed = 0;
+        for _ in 0..100 {
+            let res = app
+                .execute_contract(Addr::unchecked(VOTER4), flex_addr.clone(), &proposal, &[])
+                .unwrap();
+            last_passed = res.custom_attrs(1)[2].value.parse().unwrap();
+        }
+        let res = app
+            .execute_contract(Addr::unchecked(VOTER1), flex_addr.clone(), &proposal, &[])
+            .unwrap();
+        let rejected_id: u64 = res.custom_attrs(1)[2].value.parse().unwrap();
+        let no_vote = ExecuteMsg::Vote {
+            proposal_id: rejected_id,
+            vote: Vote::No,
+        };
+        app.execute_contract(Addr::unchecked(VOTER2), flex_addr.clone(), &no_vote, &[])
+            .unwrap();
+        app.update_block(expire(voting_period));
+
+        // the first call stops after a full scan without finding anything
+        let query = |start_after| QueryMsg::ExpiredProposals {
+            start_after,
+            limit: None,
+        };
+        let list: ExpiredProposalsResponse = app
+            .wrap()
+            .query_wasm_smart(&flex_addr, &query(None))
+            .unwrap();
+        assert_eq!(list.proposals, vec![]);
+        assert_eq!(list.next_start_after, Some(last_passed));
+
+        // continuing finds the rejected one, and there is nothing left
+        let list: ExpiredProposalsResponse = app
+            .wrap()
+            .query_wasm_smart(&flex_addr, &query(Some(last_passed)))
+            .unwrap();
+        let ids: Vec<_> = list.proposals.into_iter().map(|p| p.id).collect();
+        assert_eq!(ids, vec![rejected_id]);
+        assert_eq!(list.next_start_after, None);
+
+        let close = ExecuteMsg::CloseExpired {
+            start_after: None,
+            limit: None,
+        };
+        let res = app
+            .execute_contract(Addr::unchecked(SOMEBODY), flex_addr.clone(), &close, &[])
+            .unwrap();
+        assert_eq!(res.custom_attrs(1)[2], ("closed", "0"));
+        let close = ExecuteMsg::CloseExpired {
+            start_after: Some(last_passed),
+            limit: None,
+        };
+        let res = app
+            .execute_contract(Addr::unchecked(SOMEBODY), flex_addr, &close, &[])
+            .unwrap();
+        assert_eq!(res.custom_attrs(1)[2], ("closed", "1"));
+        assert_eq!(res.custom_attrs(1).len(), 3);
+    }
+
     // uses the power from the beginning of the voting period
     #[test]
     fn execute_group_changes_from_external() {
@@ -1344,6 +2234,7 @@ mod tests {
             description: "He's trying to steal our money".to_string(),
             msgs: vec![update_msg],
             latest: None,
+            execute_funds: None,
         };
         let res = app
             .execute_contract(
@@ -1,4 +1,4 @@
-use cosmwasm_std::StdError;
+use cosmwasm_std::{StdError, Uint128};
 use cw_utils::ThresholdError;
 
 use thiserror::Error;
@@ -14,6 +14,9 @@ pub enum ContractError {
     #[error("Group contract invalid address '{addr}'")]
     InvalidGroup { addr: String },
 
+    #[error("Veto threshold must be in the 0-1.0 range, and not zero")]
+    InvalidVetoThreshold {},
+
     #[error("Unauthorized")]
     Unauthorized {},
 
@@ -37,4 +40,17 @@ pub enum ContractError {
 
     #[error("Cannot close completed or passed proposals")]
     WrongCloseStatus {},
+
+    #[error("Proposal can only be executed once its voting period is over")]
+    EarlyExecutionDisabled {},
+
+    #[error("Execute funds need a wasm execute or instantiate message to be attached to")]
+    NoFundsTarget {},
+
+    #[error("Insufficient funds to execute: requires {required}{denom}, has {available}{denom}")]
+    InsufficientFunds {
+        denom: String,
+        required: Uint128,
+        available: Uint128,
+    },
 }
@@ -1,8 +1,8 @@
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use cosmwasm_std::{CosmosMsg, Empty};
-use cw3::Vote;
+use cosmwasm_std::{Coin, CosmosMsg, Decimal, Empty};
+use cw3::{ProposalResponse, Vote};
 use cw4::MemberChangedHookMsg;
 use cw_utils::{Duration, Expiration, Threshold};
 
@@ -12,6 +12,12 @@ pub struct InstantiateMsg {
     pub group_addr: String,
     pub threshold: Threshold,
     pub max_voting_period: Duration,
+    // whether a proposal can be executed as soon as it passed, before its voting period is over.
+    // Defaults to true
+    pub allow_early_execution: Option<bool>,
+    // share of the total weight (0-1.0) whose no votes veto a proposal, even if it has enough
+    // yes votes. No and Veto votes both count toward it
+    pub veto_threshold: Option<Decimal>,
 }
 
 // TODO: add some T variants? Maybe good enough as fixed Empty for now
@@ -24,6 +30,9 @@ pub enum ExecuteMsg {
         msgs: Vec<CosmosMsg<Empty>>,
         // note: we ignore API-spec'd earliest if passed, always opens immediately
         latest: Option<Expiration>,
+        /// Funds from the multisig's balance added to the first wasm execute / instantiate message
+        /// of the proposal. Checked against the balance when the proposal is executed
+        execute_funds: Option<Vec<Coin>>,
     },
     Vote {
         proposal_id: u64,
@@ -35,6 +44,14 @@ pub enum ExecuteMsg {
     Close {
         proposal_id: u64,
     },
+    /// Rejects up to `limit` proposals after `start_after` whose voting period is over without
+    /// them having passed and removes their ballots. Passed proposals are left to be executed.
+    /// Reads at most 100 proposals, the `next_start_after` attribute tells where to continue.
+    /// It is left out once all proposals were read
+    CloseExpired {
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
     /// Handles update hook messages from the group contract
     MemberChangedHook(MemberChangedHookMsg),
 }
@@ -57,6 +74,11 @@ pub enum QueryMsg {
         start_before: Option<u64>,
         limit: Option<u32>,
     },
+    /// Returns ExpiredProposalsResponse with the proposals CloseExpired would close
+    ExpiredProposals {
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
     /// Returns VoteResponse
     Vote { proposal_id: u64, voter: String },
     /// Returns VoteListResponse
@@ -73,3 +95,10 @@ pub enum QueryMsg {
         limit: Option<u32>,
     },
 }
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct ExpiredProposalsResponse {
+    pub proposals: Vec<ProposalResponse>,
+    /// The id of the last proposal read, to continue from. None once all proposals were read
+    pub next_start_after: Option<u64>,
+}
@@ -1,6 +1,7 @@
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
+use cosmwasm_std::Decimal;
 use cw4::Cw4Contract;
 use cw_storage_plus::Item;
 use cw_utils::{Duration, Threshold};
@@ -11,7 +12,36 @@ pub struct Config {
     pub max_voting_period: Duration,
     // Total weight and voters are queried from this contract
     pub group_addr: Cw4Contract,
+    // if false, passed proposals can only be executed once their voting period is over.
+    // Configs stored before this was added keep executing early, like instantiate defaults to
+    #[serde(default = "default_allow_early_execution")]
+    pub allow_early_execution: bool,
+    // share of the total weight whose no votes veto a proposal, if any
+    #[serde(default)]
+    pub veto_threshold: Option<Decimal>,
+}
+
+fn default_allow_early_execution() -> bool {
+    true
 }
 
 // unique items
 pub const CONFIG: Item<Config> = Item::new("config");
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cosmwasm_std::from_slice;
+
+    #[test]
+    fn config_without_new_fields() {
+        let stored = br#"{
+            "threshold": {"absolute_count": {"weight": 2}},
+            "max_voting_period": {"height": 100},
+            "group_addr": "group"
+        }"#;
+        let cfg: Config = from_slice(stored).unwrap();
+        assert!(cfg.allow_early_execution);
+        assert_eq!(cfg.veto_threshold, None);
+    }
+}
@@ -1,8 +1,8 @@
 #[cfg(not(feature = "library"))]
 use cosmwasm_std::entry_point;
 use cosmwasm_std::{
-    attr, to_binary, Addr, Binary, Deps, DepsMut, Env, MessageInfo, Order, Response, StdResult,
-    SubMsg,
+    attr, to_binary, Addr, Binary, Deps, DepsMut, Env, MessageInfo, Order, Response, StdError,
+    StdResult, SubMsg, Uint64,
 };
 use cw2::set_contract_version;
 use cw4::{
@@ -13,8 +13,8 @@ use cw_storage_plus::Bound;
 use cw_utils::maybe_addr;
 
 use crate::error::ContractError;
-use crate::msg::{ExecuteMsg, InstantiateMsg, QueryMsg};
-use crate::state::{ADMIN, HOOKS, MEMBERS, TOTAL};
+use crate::msg::{ExecuteMsg, InstantiateMsg, MemberMetadataResponse, QueryMsg};
+use crate::state::{ADMIN, HOOKS, MAX_METADATA_LEN, MEMBERS, MEMBER_METADATA, TOTAL};
 
 // version info for migration info
 const CONTRACT_NAME: &str = "crates.io:cw4-group";
@@ -76,6 +76,10 @@ pub fn execute(
         ExecuteMsg::UpdateMembers { add, remove } => {
             execute_update_members(deps, env, info, add, remove)
         }
+        ExecuteMsg::AdjustWeights { deltas } => execute_adjust_weights(deps, env, info, deltas),
+        ExecuteMsg::SetMemberMetadata { addr, metadata } => {
+            execute_set_member_metadata(deps, info, addr, metadata)
+        }
         ExecuteMsg::AddHook { addr } => {
             Ok(HOOKS.execute_add_hook(&ADMIN, deps, info, api.addr_validate(&addr)?)?)
         }
@@ -120,35 +124,146 @@ pub fn update_members(
 ) -> Result<MemberChangedHookMsg, ContractError> {
     ADMIN.assert_admin(deps.as_ref(), &sender)?;
 
-    let mut total = TOTAL.load(deps.storage)?;
+    let mut total = Uint64::from(TOTAL.load(deps.storage)?);
     let mut diffs: Vec<MemberDiff> = vec![];
 
+    // Removals are processed before additions, so an address found in both lists
+    // ends up with the weight it is added with
+    for remove in to_remove.into_iter() {
+        let remove_addr = deps.api.addr_validate(&remove)?;
+        let old = MEMBERS.may_load(deps.storage, &remove_addr)?;
+        // Only process this if they were actually in the list before
+        if let Some(weight) = old {
+            diffs.push(MemberDiff::new(remove, Some(weight), None));
+            total = total
+                .checked_sub(weight.into())
+                .map_err(StdError::overflow)?;
+            MEMBERS.remove(deps.storage, &remove_addr, height)?;
+            MEMBER_METADATA.remove(deps.storage, &remove_addr);
+        }
+    }
+
     // add all new members and update total
     for add in to_add.into_iter() {
         let add_addr = deps.api.addr_validate(&add.addr)?;
         MEMBERS.update(deps.storage, &add_addr, height, |old| -> StdResult<_> {
-            total -= old.unwrap_or_default();
-            total += add.weight;
+            total = total
+                .checked_sub(old.unwrap_or_default().into())?
+                .checked_add(add.weight.into())?;
             diffs.push(MemberDiff::new(add.addr, old, Some(add.weight)));
             Ok(add.weight)
         })?;
     }
 
-    for remove in to_remove.into_iter() {
-        let remove_addr = deps.api.addr_validate(&remove)?;
-        let old = MEMBERS.may_load(deps.storage, &remove_addr)?;
-        // Only process this if they were actually in the list before
-        if let Some(weight) = old {
-            diffs.push(MemberDiff::new(remove, Some(weight), None));
-            total -= weight;
-            MEMBERS.remove(deps.storage, &remove_addr, height)?;
+    TOTAL.save(deps.storage, &total.u64())?;
+    Ok(MemberChangedHookMsg { diffs })
+}
+
+pub fn execute_adjust_weights(
+    mut deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    deltas: Vec<(String, i64)>,
+) -> Result<Response, ContractError> {
+    let attributes = vec![
+        attr("action", "adjust_weights"),
+        attr("adjusted", deltas.len().to_string()),
+        attr("sender", &info.sender),
+    ];
+
+    let diff = adjust_weights(deps.branch(), env.block.height, info.sender, deltas)?;
+    let messages = HOOKS.prepare_hooks(deps.storage, |h| {
+        diff.clone().into_cosmos_msg(h).map(SubMsg::new)
+    })?;
+    Ok(Response::new()
+        .add_submessages(messages)
+        .add_attributes(attributes))
+}
+
+// the logic from execute_adjust_weights extracted for easier import.
+// Each delta is applied to the weight left by the previous ones, so repeated addresses add up
+pub fn adjust_weights(
+    deps: DepsMut,
+    height: u64,
+    sender: Addr,
+    deltas: Vec<(String, i64)>,
+) -> Result<MemberChangedHookMsg, ContractError> {
+    ADMIN.assert_admin(deps.as_ref(), &sender)?;
+
+    let mut total = Uint64::from(TOTAL.load(deps.storage)?);
+    let mut diffs: Vec<MemberDiff> = vec![];
+
+    for (addr, delta) in deltas.into_iter() {
+        let member_addr = deps.api.addr_validate(&addr)?;
+        let old = MEMBERS.may_load(deps.storage, &member_addr)?;
+        let old_weight = Uint64::from(old.unwrap_or_default());
+        // lowering a weight saturates at zero, raising it must not overflow
+        let weight = if delta < 0 {
+            old_weight.saturating_sub(delta.unsigned_abs().into())
+        } else {
+            old_weight
+                .checked_add((delta as u64).into())
+                .map_err(StdError::overflow)?
+        };
+        total = total
+            .checked_sub(old_weight)
+            .map_err(StdError::overflow)?
+            .checked_add(weight)
+            .map_err(StdError::overflow)?;
+
+        let new = if delta == 0 {
+            // a no-op delta leaves membership alone, even of a member with weight zero
+            old
+        } else if weight.is_zero() {
+            // only touch the snapshots if there was a member to remove
+            if old.is_some() {
+                MEMBERS.remove(deps.storage, &member_addr, height)?;
+                MEMBER_METADATA.remove(deps.storage, &member_addr);
+            }
+            None
+        } else {
+            MEMBERS.save(deps.storage, &member_addr, &weight.u64(), height)?;
+            Some(weight.u64())
+        };
+        if new != old {
+            diffs.push(MemberDiff::new(addr, old, new));
         }
     }
 
-    TOTAL.save(deps.storage, &total)?;
+    TOTAL.save(deps.storage, &total.u64())?;
     Ok(MemberChangedHookMsg { diffs })
 }
 
+// an empty string clears the metadata. It is cleared as well when the member is removed
+pub fn execute_set_member_metadata(
+    deps: DepsMut,
+    info: MessageInfo,
+    addr: String,
+    metadata: String,
+) -> Result<Response, ContractError> {
+    ADMIN.assert_admin(deps.as_ref(), &info.sender)?;
+
+    let member_addr = deps.api.addr_validate(&addr)?;
+    if MEMBERS.may_load(deps.storage, &member_addr)?.is_none() {
+        return Err(ContractError::NotMember { addr });
+    }
+    if metadata.len() > MAX_METADATA_LEN {
+        return Err(ContractError::MetadataTooLong {
+            max: MAX_METADATA_LEN,
+        });
+    }
+    if metadata.is_empty() {
+        MEMBER_METADATA.remove(deps.storage, &member_addr);
+    } else {
+        MEMBER_METADATA.save(deps.storage, &member_addr, &metadata)?;
+    }
+
+    Ok(Response::new()
+        .add_attribute("action", "set_member_metadata")
+        .add_attribute("member", addr)
+        .add_attribute("sender", info.sender))
+}
+
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
     match msg {
@@ -162,6 +277,7 @@ pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
         QueryMsg::TotalWeight {} => to_binary(&query_total_weight(deps)?),
         QueryMsg::Admin {} => to_binary(&ADMIN.query_admin(deps)?),
         QueryMsg::Hooks {} => to_binary(&HOOKS.query_hooks(deps)?),
+        QueryMsg::MemberMetadata { addr } => to_binary(&query_member_metadata(deps, addr)?),
     }
 }
 
@@ -179,6 +295,13 @@ fn query_member(deps: Deps, addr: String, height: Option<u64>) -> StdResult<Memb
     Ok(MemberResponse { weight })
 }
 
+// metadata is not snapshotted, so there is no height to query it at
+fn query_member_metadata(deps: Deps, addr: String) -> StdResult<MemberMetadataResponse> {
+    let addr = deps.api.addr_validate(&addr)?;
+    let metadata = MEMBER_METADATA.may_load(deps.storage, &addr)?;
+    Ok(MemberMetadataResponse { metadata })
+}
+
 // settings for pagination
 const MAX_LIMIT: u32 = 30;
 const DEFAULT_LIMIT: u32 = 10;
@@ -377,11 +500,11 @@ mod tests {
 
     #[test]
     fn add_and_remove_same_member() {
-        // add will over-write and remove have no effect
+        // removals are processed first, so the add wins
         let mut deps = mock_dependencies();
         do_instantiate(deps.as_mut());
 
-        // USER1 is updated and remove in the same call, we should remove this an add member3
+        // USER1 is removed and re-added in the same call, and member3 is added
         let add = vec![
             Member {
                 addr: USER1.into(),
@@ -404,7 +527,250 @@ mod tests {
             remove,
         )
         .unwrap();
-        assert_users(&deps, None, Some(6), Some(5), None);
+        assert_users(&deps, Some(20), Some(6), Some(5), None);
+    }
+
+    #[test]
+    fn overlapping_updates_keep_total_in_sync() {
+        let mut deps = mock_dependencies();
+        do_instantiate(deps.as_mut());
+        let height = mock_env().block.height;
+
+        // an address removed and added in the same call, and one added twice
+        let add = vec![
+            Member {
+                addr: USER2.into(),
+                weight: 9,
+            },
+            Member {
+                addr: USER3.into(),
+                weight: 3,
+            },
+            Member {
+                addr: USER3.into(),
+                weight: 7,
+            },
+        ];
+        let remove = vec![USER2.into(), USER1.into(), USER1.into()];
+        let msg = update_members(
+            deps.as_mut(),
+            height + 1,
+            Addr::unchecked(INIT_ADMIN),
+            add,
+            remove,
+        )
+        .unwrap();
+        assert_eq!(
+            msg.diffs,
+            vec![
+                MemberDiff::new(USER2, Some(6), None),
+                MemberDiff::new(USER1, Some(11), None),
+                MemberDiff::new(USER2, None, Some(9)),
+                MemberDiff::new(USER3, None, Some(3)),
+                MemberDiff::new(USER3, Some(3), Some(7)),
+            ]
+        );
+        assert_users(&deps, None, Some(9), Some(7), None);
+
+        // the cached total matches a fresh sum over all members
+        let fresh_sum: u64 = MEMBERS
+            .range(&deps.storage, None, None, Order::Ascending)
+            .map(|item| item.unwrap().1)
+            .sum();
+        assert_eq!(TOTAL.load(&deps.storage).unwrap(), fresh_sum);
+        assert_eq!(fresh_sum, 16);
+    }
+
+    #[test]
+    fn adjust_weights_applies_deltas() {
+        let mut deps = mock_dependencies();
+        do_instantiate(deps.as_mut());
+        let height = mock_env().block.height;
+
+        // only the admin can adjust
+        let err = adjust_weights(
+            deps.as_mut(),
+            height + 1,
+            Addr::unchecked(USER1),
+            vec![(USER1.into(), 5)],
+        )
+        .unwrap_err();
+        assert_eq!(err, AdminError::NotAdmin {}.into());
+
+        // raise one, lower another, add a new member
+        let msg = adjust_weights(
+            deps.as_mut(),
+            height + 1,
+            Addr::unchecked(INIT_ADMIN),
+            vec![(USER1.into(), 4), (USER2.into(), -2), (USER3.into(), 3)],
+        )
+        .unwrap();
+        assert_eq!(
+            msg.diffs,
+            vec![
+                MemberDiff::new(USER1, Some(11), Some(15)),
+                MemberDiff::new(USER2, Some(6), Some(4)),
+                MemberDiff::new(USER3, None, Some(3)),
+            ]
+        );
+        assert_users(&deps, Some(15), Some(4), Some(3), None);
+        assert_users(&deps, Some(11), Some(6), None, Some(height + 1));
+
+        // a delta below zero removes the member, lowering a non-member changes nothing
+        let msg = adjust_weights(
+            deps.as_mut(),
+            height + 2,
+            Addr::unchecked(INIT_ADMIN),
+            vec![
+                (USER2.into(), -10),
+                (USER3.into(), 0),
+                ("nobody".into(), -1),
+                (USER1.into(), -5),
+                (USER1.into(), 2),
+            ],
+        )
+        .unwrap();
+        assert_eq!(
+            msg.diffs,
+            vec![
+                MemberDiff::new(USER2, Some(4), None),
+                MemberDiff::new(USER1, Some(15), Some(10)),
+                MemberDiff::new(USER1, Some(10), Some(12)),
+            ]
+        );
+        assert_users(&deps, Some(12), None, Some(3), None);
+
+        // the cached total matches a fresh sum over all members
+        let fresh_sum: u64 = MEMBERS
+            .range(&deps.storage, None, None, Order::Ascending)
+            .map(|item| item.unwrap().1)
+            .sum();
+        assert_eq!(TOTAL.load(&deps.storage).unwrap(), fresh_sum);
+        assert_eq!(fresh_sum, 15);
+
+        // a zero delta keeps a member with weight zero
+        let add = vec![Member {
+            addr: USER2.into(),
+            weight: 0,
+        }];
+        update_members(
+            deps.as_mut(),
+            height + 3,
+            Addr::unchecked(INIT_ADMIN),
+            add,
+            vec![],
+        )
+        .unwrap();
+        let msg = adjust_weights(
+            deps.as_mut(),
+            height + 3,
+            Addr::unchecked(INIT_ADMIN),
+            vec![(USER2.into(), 0)],
+        )
+        .unwrap();
+        assert_eq!(msg.diffs, vec![]);
+        assert_users(&deps, Some(12), Some(0), Some(3), None);
+
+        // an overflowing delta fails the whole call
+        let err = adjust_weights(
+            deps.as_mut(),
+            height + 3,
+            Addr::unchecked(INIT_ADMIN),
+            vec![
+                (USER3.into(), 1),
+                (USER1.into(), i64::MAX),
+                (USER1.into(), i64::MAX),
+            ],
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::Std(StdError::Overflow { .. })));
+    }
+
+    #[test]
+    fn member_metadata() {
+        let mut deps = mock_dependencies();
+        do_instantiate(deps.as_mut());
+        let set = |deps: DepsMut, sender: &str, addr: &str, metadata: &str| {
+            let msg = ExecuteMsg::SetMemberMetadata {
+                addr: addr.into(),
+                metadata: metadata.into(),
+            };
+            execute(deps, mock_env(), mock_info(sender, &[]), msg)
+        };
+        let metadata =
+            |deps: Deps, addr: &str| query_member_metadata(deps, addr.into()).unwrap().metadata;
+
+        // only the admin can set it, and only for members
+        let err = set(deps.as_mut(), USER1, USER1, "treasurer").unwrap_err();
+        assert_eq!(err, AdminError::NotAdmin {}.into());
+        let err = set(deps.as_mut(), INIT_ADMIN, USER3, "treasurer").unwrap_err();
+        assert_eq!(err, ContractError::NotMember { addr: USER3.into() });
+
+        set(deps.as_mut(), INIT_ADMIN, USER1, "treasurer").unwrap();
+        assert_eq!(
+            metadata(deps.as_ref(), USER1),
+            Some("treasurer".to_string())
+        );
+        assert_eq!(metadata(deps.as_ref(), USER2), None);
+
+        // a weight update keeps it, and the cw4 queries are unchanged
+        let add = vec![Member {
+            addr: USER1.into(),
+            weight: 15,
+        }];
+        let height = mock_env().block.height;
+        update_members(
+            deps.as_mut(),
+            height + 1,
+            Addr::unchecked(INIT_ADMIN),
+            add,
+            vec![],
+        )
+        .unwrap();
+        assert_eq!(
+            metadata(deps.as_ref(), USER1),
+            Some("treasurer".to_string())
+        );
+        let member = query_member(deps.as_ref(), USER1.into(), None).unwrap();
+        assert_eq!(member, MemberResponse { weight: Some(15) });
+
+        // length is bounded
+        let err = set(
+            deps.as_mut(),
+            INIT_ADMIN,
+            USER1,
+            &"x".repeat(MAX_METADATA_LEN + 1),
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::MetadataTooLong {
+                max: MAX_METADATA_LEN
+            }
+        );
+        set(
+            deps.as_mut(),
+            INIT_ADMIN,
+            USER1,
+            &"x".repeat(MAX_METADATA_LEN),
+        )
+        .unwrap();
+
+        // an empty string clears it
+        set(deps.as_mut(), INIT_ADMIN, USER1, "").unwrap();
+        assert_eq!(metadata(deps.as_ref(), USER1), None);
+
+        // so does removing the member
+        set(deps.as_mut(), INIT_ADMIN, USER1, "treasurer").unwrap();
+        update_members(
+            deps.as_mut(),
+            height + 2,
+            Addr::unchecked(INIT_ADMIN),
+            vec![],
+            vec![USER1.into()],
+        )
+        .unwrap();
+        assert_eq!(metadata(deps.as_ref(), USER1), None);
     }
 
     #[test]
@@ -521,11 +887,11 @@ mod tests {
 
         // ensure 2 messages for the 2 hooks
         assert_eq!(res.messages.len(), 2);
-        // same order as in the message (adds first, then remove)
+        // same order as they are processed (removes first, then adds)
         let diffs = vec![
+            MemberDiff::new(USER2, Some(6), None),
             MemberDiff::new(USER1, Some(11), Some(20)),
             MemberDiff::new(USER3, None, Some(5)),
-            MemberDiff::new(USER2, Some(6), None),
         ];
         let hook_msg = MemberChangedHookMsg { diffs };
         let msg1 = SubMsg::new(hook_msg.clone().into_cosmos_msg(contract1).unwrap());
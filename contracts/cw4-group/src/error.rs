@@ -16,4 +16,10 @@ pub enum ContractError {
 
     #[error("Unauthorized")]
     Unauthorized {},
+
+    #[error("Member metadata cannot be longer than {max} bytes")]
+    MetadataTooLong { max: usize },
+
+    #[error("{addr} is not a member")]
+    NotMember { addr: String },
 }
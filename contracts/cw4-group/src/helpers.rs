@@ -40,4 +40,9 @@ impl Cw4GroupContract {
         let msg = ExecuteMsg::UpdateMembers { remove, add };
         self.encode_msg(msg)
     }
+
+    pub fn adjust_weights(&self, deltas: Vec<(String, i64)>) -> StdResult<CosmosMsg> {
+        let msg = ExecuteMsg::AdjustWeights { deltas };
+        self.encode_msg(msg)
+    }
 }
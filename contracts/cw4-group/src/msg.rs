@@ -23,6 +23,12 @@ pub enum ExecuteMsg {
         remove: Vec<String>,
         add: Vec<Member>,
     },
+    /// apply signed deltas to the weights of the given addresses, rather than setting them.
+    /// Weights saturate at zero, and members that end up at zero are removed.
+    /// Addresses that are not members yet are added by a positive delta
+    AdjustWeights { deltas: Vec<(String, i64)> },
+    /// Attach a label to a member, eg. a role. An empty string clears it. Must be called by Admin
+    SetMemberMetadata { addr: String, metadata: String },
     /// Add a new hook to be informed of all membership changes. Must be called by Admin
     AddHook { addr: String },
     /// Remove a hook. Must be called by Admin
@@ -48,4 +54,12 @@ pub enum QueryMsg {
     },
     /// Shows all registered hooks. Returns HooksResponse.
     Hooks {},
+    /// Returns MemberMetadataResponse
+    MemberMetadata { addr: String },
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct MemberMetadataResponse {
+    /// None if the member has no metadata, or the address is not a member
+    pub metadata: Option<String>,
 }
@@ -1,7 +1,7 @@
 use cosmwasm_std::Addr;
 use cw4::TOTAL_KEY;
 use cw_controllers::{Admin, Hooks};
-use cw_storage_plus::{Item, SnapshotMap, Strategy};
+use cw_storage_plus::{Item, Map, SnapshotMap, Strategy};
 
 pub const ADMIN: Admin = Admin::new("admin");
 pub const HOOKS: Hooks = Hooks::new("cw4-hooks");
@@ -14,3 +14,9 @@ pub const MEMBERS: SnapshotMap<&Addr, u64> = SnapshotMap::new(
     cw4::MEMBERS_CHANGELOG,
     Strategy::EveryBlock,
 );
+
+/// Maximum length of a member's metadata in bytes
+pub const MAX_METADATA_LEN: usize = 128;
+
+/// Kept apart from MEMBERS, so the raw weight queries stay valid
+pub const MEMBER_METADATA: Map<&Addr, String> = Map::new("members_metadata");
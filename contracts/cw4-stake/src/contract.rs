@@ -1,8 +1,8 @@
 #[cfg(not(feature = "library"))]
 use cosmwasm_std::entry_point;
 use cosmwasm_std::{
-    coins, from_slice, to_binary, Addr, BankMsg, Binary, Deps, DepsMut, Env, MessageInfo, Order,
-    Response, StdResult, Storage, SubMsg, Uint128, WasmMsg,
+    coins, from_slice, to_binary, Addr, BankMsg, Binary, CosmosMsg, Deps, DepsMut, Env,
+    MessageInfo, Order, Response, StdResult, Storage, SubMsg, Uint128, WasmMsg,
 };
 
 use cw2::set_contract_version;
@@ -43,6 +43,8 @@ pub fn instantiate(
         tokens_per_weight: msg.tokens_per_weight,
         min_bond,
         unbonding_period: msg.unbonding_period,
+        slasher: maybe_addr(api, msg.slasher)?,
+        slash_recipient: maybe_addr(api, msg.slash_recipient)?,
     };
     CONFIG.save(deps.storage, &config)?;
     TOTAL.save(deps.storage, &0)?;
@@ -72,6 +74,7 @@ pub fn execute(
         ExecuteMsg::Bond {} => execute_bond(deps, env, Balance::from(info.funds), info.sender),
         ExecuteMsg::Unbond { tokens: amount } => execute_unbond(deps, env, info, amount),
         ExecuteMsg::Claim {} => execute_claim(deps, env, info),
+        ExecuteMsg::Slash { addr, amount } => execute_slash(deps, env, info, addr, amount),
         ExecuteMsg::Receive(msg) => execute_receive(deps, env, info, msg),
     }
 }
@@ -179,6 +182,77 @@ pub fn execute_unbond(
         .add_attribute("sender", info.sender))
 }
 
+pub fn execute_slash(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    addr: String,
+    amount: Uint128,
+) -> Result<Response, ContractError> {
+    let cfg = CONFIG.load(deps.storage)?;
+    if cfg.slasher.as_ref() != Some(&info.sender) {
+        return Err(ContractError::Unauthorized {});
+    }
+    let addr = deps.api.addr_validate(&addr)?;
+
+    // slashing more than is staked takes all of it
+    let stake = STAKE.may_load(deps.storage, &addr)?.unwrap_or_default();
+    let slashed = std::cmp::min(stake, amount);
+    let new_stake = stake - slashed;
+    STAKE.save(deps.storage, &addr, &new_stake)?;
+
+    let mut messages = update_membership(
+        deps.storage,
+        addr.clone(),
+        new_stake,
+        &cfg,
+        env.block.height,
+    )?;
+    if !slashed.is_zero() {
+        messages.push(slashed_tokens_msg(&cfg, slashed)?);
+    }
+
+    Ok(Response::new()
+        .add_submessages(messages)
+        .add_attribute("action", "slash")
+        .add_attribute("amount", slashed)
+        .add_attribute("addr", addr)
+        .add_attribute("sender", info.sender))
+}
+
+/// Sends slashed tokens to the slash_recipient, or burns them if there is none
+fn slashed_tokens_msg(cfg: &Config, amount: Uint128) -> StdResult<SubMsg> {
+    let msg: CosmosMsg = match &cfg.denom {
+        Denom::Native(denom) => {
+            let amount = coins(amount.u128(), denom);
+            match &cfg.slash_recipient {
+                Some(recipient) => BankMsg::Send {
+                    to_address: recipient.into(),
+                    amount,
+                }
+                .into(),
+                None => BankMsg::Burn { amount }.into(),
+            }
+        }
+        Denom::Cw20(addr) => {
+            let msg = match &cfg.slash_recipient {
+                Some(recipient) => Cw20ExecuteMsg::Transfer {
+                    recipient: recipient.into(),
+                    amount,
+                },
+                None => Cw20ExecuteMsg::Burn { amount },
+            };
+            WasmMsg::Execute {
+                contract_addr: addr.into(),
+                msg: to_binary(&msg)?,
+                funds: vec![],
+            }
+            .into()
+        }
+    };
+    Ok(SubMsg::new(msg))
+}
+
 pub fn must_pay_funds(balance: &NativeBalance, denom: &str) -> Result<Uint128, ContractError> {
     match balance.0.len() {
         0 => Err(ContractError::NoFunds {}),
@@ -359,7 +433,7 @@ fn list_members(
 mod tests {
     use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
     use cosmwasm_std::{
-        coin, from_slice, CosmosMsg, OverflowError, OverflowOperation, StdError, Storage,
+        attr, coin, from_slice, CosmosMsg, OverflowError, OverflowOperation, StdError, Storage,
     };
     use cw20::Denom;
     use cw4::{member_key, TOTAL_KEY};
@@ -379,6 +453,8 @@ mod tests {
     const MIN_BOND: Uint128 = Uint128::new(5_000);
     const UNBONDING_BLOCKS: u64 = 100;
     const CW20_ADDRESS: &str = "wasm1234567890";
+    const SLASHER: &str = "slasher";
+    const SLASH_RECIPIENT: &str = "treasury";
 
     fn default_instantiate(deps: DepsMut) {
         do_instantiate(
@@ -401,6 +477,8 @@ mod tests {
             min_bond,
             unbonding_period,
             admin: Some(INIT_ADMIN.into()),
+            slasher: Some(SLASHER.into()),
+            slash_recipient: None,
         };
         let info = mock_info("creator", &[]);
         instantiate(deps, mock_env(), info, msg).unwrap();
@@ -413,6 +491,8 @@ mod tests {
             min_bond: MIN_BOND,
             unbonding_period,
             admin: Some(INIT_ADMIN.into()),
+            slasher: Some(SLASHER.into()),
+            slash_recipient: Some(SLASH_RECIPIENT.into()),
         };
         let info = mock_info("creator", &[]);
         instantiate(deps, mock_env(), info, msg).unwrap();
@@ -843,6 +923,66 @@ mod tests {
         assert_eq!(get_claims(deps.as_ref(), &Addr::unchecked(USER2)), vec![]);
     }
 
+    #[test]
+    fn staggered_unbondings_keep_own_claims() {
+        let mut deps = mock_dependencies();
+        default_instantiate(deps.as_mut());
+        let user = Addr::unchecked(USER1);
+
+        bond(deps.as_mut(), 20_000, 0, 0, 1);
+        assert_eq!(get_member(deps.as_ref(), USER1.into(), None), Some(20));
+
+        // two partial unbondings, 10 blocks apart, are tracked separately
+        unbond(deps.as_mut(), 3_000, 0, 0, 2);
+        // the weight drops right away, not on claim
+        assert_eq!(get_member(deps.as_ref(), USER1.into(), None), Some(17));
+        unbond(deps.as_mut(), 4_000, 0, 0, 12);
+        assert_eq!(get_member(deps.as_ref(), USER1.into(), None), Some(13));
+
+        let mut env = mock_env();
+        env.block.height += 2;
+        let first = Duration::Height(UNBONDING_BLOCKS).after(&env.block);
+        env.block.height += 10;
+        let second = Duration::Height(UNBONDING_BLOCKS).after(&env.block);
+        assert_eq!(
+            get_claims(deps.as_ref(), &user),
+            vec![Claim::new(3_000, first), Claim::new(4_000, second)]
+        );
+
+        // only the first one has matured
+        let mut env = mock_env();
+        env.block.height += 2 + UNBONDING_BLOCKS;
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info(USER1, &[]),
+            ExecuteMsg::Claim {},
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: USER1.into(),
+                amount: coins(3_000, DENOM),
+            })]
+        );
+        assert_eq!(
+            get_claims(deps.as_ref(), &user),
+            vec![Claim::new(4_000, second)]
+        );
+
+        // and the second one cannot be claimed yet
+        let err = execute(
+            deps.as_mut(),
+            env,
+            mock_info(USER1, &[]),
+            ExecuteMsg::Claim {},
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::NothingToClaim {});
+        assert_eq!(get_member(deps.as_ref(), USER1.into(), None), Some(13));
+    }
+
     #[test]
     fn add_remove_hooks() {
         // add will over-write and remove have no effect
@@ -912,6 +1052,92 @@ mod tests {
         assert_eq!(hooks.hooks, vec![contract2]);
     }
 
+    fn slash(deps: DepsMut, addr: &str, amount: u128) -> Result<Response, ContractError> {
+        let msg = ExecuteMsg::Slash {
+            addr: addr.into(),
+            amount: Uint128::new(amount),
+        };
+        execute(deps, mock_env(), mock_info(SLASHER, &[]), msg)
+    }
+
+    #[test]
+    fn slash_reduces_stake_and_weight() {
+        let mut deps = mock_dependencies();
+        default_instantiate(deps.as_mut());
+        bond(deps.as_mut(), 12_000, 7_500, 4_000, 1);
+        assert_users(deps.as_ref(), Some(12), Some(7), None, None);
+
+        // only the slasher can slash
+        let msg = ExecuteMsg::Slash {
+            addr: USER1.into(),
+            amount: Uint128::new(1_000),
+        };
+        let err = execute(deps.as_mut(), mock_env(), mock_info(INIT_ADMIN, &[]), msg).unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+
+        // a partial slash lowers the weight, and the slashed tokens are burned
+        let res = slash(deps.as_mut(), USER1, 4_500).unwrap();
+        assert_stake(deps.as_ref(), 7_500, 7_500, 4_000);
+        assert_users(deps.as_ref(), Some(7), Some(7), None, None);
+        let burn = SubMsg::new(BankMsg::Burn {
+            amount: coins(4_500, DENOM),
+        });
+        assert_eq!(res.messages, vec![burn]);
+
+        // a full slash removes the member
+        slash(deps.as_mut(), USER2, 7_500).unwrap();
+        assert_stake(deps.as_ref(), 7_500, 0, 4_000);
+        assert_users(deps.as_ref(), Some(7), None, None, None);
+
+        // slashing more than is staked takes only what is there
+        let res = slash(deps.as_mut(), USER3, 10_000).unwrap();
+        assert_stake(deps.as_ref(), 7_500, 0, 0);
+        let burn = SubMsg::new(BankMsg::Burn {
+            amount: coins(4_000, DENOM),
+        });
+        assert_eq!(res.messages, vec![burn]);
+        assert_eq!(res.attributes[1], attr("amount", "4000"));
+
+        // and with nothing left, nothing is sent
+        let res = slash(deps.as_mut(), USER3, 10_000).unwrap();
+        assert!(res.messages.is_empty());
+        assert_eq!(res.attributes[1], attr("amount", "0"));
+    }
+
+    #[test]
+    fn slash_redirects_cw20_and_fires_hooks() {
+        let mut deps = mock_dependencies();
+        cw20_instantiate(deps.as_mut(), Duration::Height(2000));
+        let add_msg = ExecuteMsg::AddHook {
+            addr: "hook1".into(),
+        };
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(INIT_ADMIN, &[]),
+            add_msg,
+        )
+        .unwrap();
+        bond_cw20(deps.as_mut(), 12_000, 0, 0, 1);
+
+        let res = slash(deps.as_mut(), USER1, 3_000).unwrap();
+        assert_stake(deps.as_ref(), 9_000, 0, 0);
+        let diff = MemberDiff::new(USER1, Some(12), Some(9));
+        let hook = MemberChangedHookMsg::one(diff)
+            .into_cosmos_msg("hook1")
+            .unwrap();
+        let transfer = Cw20ExecuteMsg::Transfer {
+            recipient: SLASH_RECIPIENT.into(),
+            amount: Uint128::new(3_000),
+        };
+        let transfer = WasmMsg::Execute {
+            contract_addr: CW20_ADDRESS.into(),
+            msg: to_binary(&transfer).unwrap(),
+            funds: vec![],
+        };
+        assert_eq!(res.messages, vec![SubMsg::new(hook), SubMsg::new(transfer)]);
+    }
+
     #[test]
     fn hooks_fire() {
         let mut deps = mock_dependencies();
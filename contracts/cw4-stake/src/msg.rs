@@ -16,6 +16,10 @@ pub struct InstantiateMsg {
 
     // admin can only add/remove hooks, not change other parameters
     pub admin: Option<String>,
+    /// may slash the stake of misbehaving members, slashing is disabled if not set
+    pub slasher: Option<String>,
+    /// receives the slashed tokens, they are burned if not set
+    pub slash_recipient: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
@@ -30,6 +34,9 @@ pub enum ExecuteMsg {
     /// Claim is used to claim your native tokens that you previously "unbonded"
     /// after the contract-defined waiting period (eg. 1 week)
     Claim {},
+    /// Slash reduces the stake of addr by amount (or all of it, if less is staked) and
+    /// updates its membership weight. Must be called by the slasher
+    Slash { addr: String, amount: Uint128 },
 
     /// Change the admin
     UpdateAdmin { admin: Option<String> },
@@ -17,6 +17,12 @@ pub struct Config {
     pub tokens_per_weight: Uint128,
     pub min_bond: Uint128,
     pub unbonding_period: Duration,
+    /// may slash the stake of misbehaving members
+    #[serde(default)]
+    pub slasher: Option<Addr>,
+    /// receives the slashed tokens, they are burned if not set
+    #[serde(default)]
+    pub slash_recipient: Option<Addr>,
 }
 
 pub const ADMIN: Admin = Admin::new("admin");
@@ -4,4 +4,4 @@ mod hooks;
 
 pub use admin::{Admin, AdminError, AdminResponse};
 pub use claim::{Claim, Claims, ClaimsResponse};
-pub use hooks::{HookError, Hooks};
+pub use hooks::{HookError, Hooks, HooksResponse};
@@ -83,6 +83,8 @@ pub enum Status {
     Passed = 4,
     /// voting is over it passed, and the proposal was executed
     Executed = 5,
+    /// enough no votes were cast to block the proposal, regardless of the yes votes
+    Vetoed = 6,
 }
 
 #[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
@@ -73,7 +73,9 @@ where
 
     /// replace writes data to key. old_data must be the current stored value (from a previous load)
     /// and is used to properly update the index. This is used by save, replace, and update
-    /// and can be called directly if you want to optimize
+    /// and can be called directly if you want to optimize.
+    /// All index entries of old_data are removed before any index entry of data is written,
+    /// so an updated value can reuse (or swap) its own secondary keys.
     pub fn replace(
         &self,
         store: &mut dyn Storage,
@@ -860,6 +862,89 @@ mod test {
         assert_eq!(name_count(&map, &store, "Mary"), 1);
     }
 
+    #[test]
+    fn replace_tears_down_old_index_entries() {
+        let mut store = MockStorage::new();
+        let map = build_map();
+
+        let (pks, datas) = save_data(&mut store, &map);
+        let old = datas[0].clone();
+
+        // change the indexed name and age
+        let updated = Data {
+            name: "Mariah".to_string(),
+            age: 43,
+            ..old.clone()
+        };
+        map.replace(&mut store, pks[0], Some(&updated), Some(&old))
+            .unwrap();
+
+        // old secondary keys no longer resolve
+        assert_eq!(map.idx.age.item(&store, old.age).unwrap(), None);
+        let marias: Vec<_> = map
+            .idx
+            .name
+            .prefix(old.name.clone())
+            .keys_raw(&store, None, None, Order::Ascending)
+            .collect();
+        assert_eq!(marias, vec![pks[1].as_bytes().to_vec()]);
+
+        // new ones point to the updated value
+        let (pk, value) = map.idx.age.item(&store, 43).unwrap().unwrap();
+        assert_eq!(pk, pks[0].as_bytes());
+        assert_eq!(value, updated);
+        let mariahs = map
+            .idx
+            .name
+            .prefix("Mariah".to_string())
+            .range(&store, None, None, Order::Ascending)
+            .collect::<StdResult<Vec<_>>>()
+            .unwrap();
+        assert_eq!(mariahs, vec![(pks[0].to_string(), updated.clone())]);
+        assert_eq!(
+            map.idx
+                .name_lastname
+                .item(&store, index_string_tuple(&old.name, &old.last_name))
+                .unwrap(),
+            None
+        );
+
+        // changing only the age keeps (re-writes) the unique name / last name entry
+        let older = Data {
+            age: 44,
+            ..updated.clone()
+        };
+        map.replace(&mut store, pks[0], Some(&older), Some(&updated))
+            .unwrap();
+        assert_eq!(map.idx.age.item(&store, 43).unwrap(), None);
+        let (_, value) = map
+            .idx
+            .name_lastname
+            .item(&store, index_string_tuple(&older.name, &older.last_name))
+            .unwrap()
+            .unwrap();
+        assert_eq!(value, older);
+
+        // and removing it leaves no dangling entries behind
+        map.remove(&mut store, pks[0]).unwrap();
+        assert_eq!(map.idx.age.item(&store, 44).unwrap(), None);
+        assert_eq!(
+            map.idx
+                .name
+                .prefix("Mariah".to_string())
+                .keys_raw(&store, None, None, Order::Ascending)
+                .count(),
+            0
+        );
+        assert_eq!(
+            map.idx
+                .name_lastname
+                .item(&store, index_string_tuple(&older.name, &older.last_name))
+                .unwrap(),
+            None
+        );
+    }
+
     #[test]
     fn range_raw_simple_key_by_unique_index() {
         let mut store = MockStorage::new();
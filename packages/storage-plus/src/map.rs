@@ -179,6 +179,12 @@ where
     {
         self.no_prefix_raw().keys_raw(store, min, max, order)
     }
+
+    /// Returns the number of entries in the whole map between the bounds, without
+    /// deserializing them. Use `prefix(..).count(..)` to count under a prefix.
+    pub fn count(&self, store: &dyn Storage, min: Option<Bound>, max: Option<Bound>) -> usize {
+        self.no_prefix_raw().count(store, min, max)
+    }
 }
 
 #[cfg(feature = "iterator")]
@@ -239,6 +245,27 @@ where
         self.no_prefix().keys(store, min, max, order)
     }
 
+    /// Like `keys`, but only the keys are decoded. Stored values are still read from storage
+    /// with each key, but never deserialized, so this is cheaper when only the key set is needed.
+    pub fn range_keys<'c>(
+        &self,
+        store: &'c dyn Storage,
+        min: Option<Bound>,
+        max: Option<Bound>,
+        order: cosmwasm_std::Order,
+    ) -> Box<dyn Iterator<Item = StdResult<K::Output>> + 'c>
+    where
+        T: 'c,
+        K::Output: 'static,
+    {
+        let de_fn: fn(Vec<u8>) -> StdResult<K::Output> = K::from_vec;
+        let mapped = self
+            .no_prefix_raw()
+            .keys_raw(store, min, max, order)
+            .map(de_fn);
+        Box::new(mapped)
+    }
+
     fn no_prefix(&self) -> Prefix<K, T> {
         Prefix::new(self.namespace, &[])
     }
@@ -462,6 +489,98 @@ mod test {
         assert_eq!(all, vec![(b"john".to_vec(), data)]);
     }
 
+    #[test]
+    #[cfg(feature = "iterator")]
+    fn range_keys_skips_values() {
+        let mut store = MockStorage::new();
+
+        let data = Data {
+            name: "John".to_string(),
+            age: 32,
+        };
+        PEOPLE.save(&mut store, b"john", &data).unwrap();
+        PEOPLE.save(&mut store, b"jim", &data).unwrap();
+        // a value that cannot be decoded as `Data`
+        store.set(&PEOPLE.key(b"joe"), b"not json");
+
+        // range fails on the broken value
+        let all: StdResult<Vec<_>> = PEOPLE.range(&store, None, None, Order::Ascending).collect();
+        assert!(all.is_err());
+
+        // range_keys never touches the values
+        let keys: StdResult<Vec<_>> = PEOPLE
+            .range_keys(&store, None, None, Order::Ascending)
+            .collect();
+        assert_eq!(
+            keys.unwrap(),
+            vec![b"jim".to_vec(), b"joe".to_vec(), b"john".to_vec()]
+        );
+
+        // bounds and order are honoured
+        let keys: StdResult<Vec<_>> = PEOPLE
+            .range_keys(
+                &store,
+                Some(Bound::Exclusive(b"jim".to_vec())),
+                None,
+                Order::Descending,
+            )
+            .collect();
+        assert_eq!(keys.unwrap(), vec![b"john".to_vec(), b"joe".to_vec()]);
+    }
+
+    #[test]
+    #[cfg(feature = "iterator")]
+    fn count_with_bounds() {
+        let mut store = MockStorage::new();
+
+        ALLOWANCE
+            .save(&mut store, (b"owner", b"spender"), &1000)
+            .unwrap();
+        ALLOWANCE
+            .save(&mut store, (b"owner", b"spender2"), &3000)
+            .unwrap();
+        ALLOWANCE
+            .save(&mut store, (b"owner", b"spender3"), &4000)
+            .unwrap();
+        ALLOWANCE
+            .save(&mut store, (b"owner2", b"spender"), &5000)
+            .unwrap();
+
+        // whole map
+        assert_eq!(4, ALLOWANCE.count(&store, None, None));
+
+        // under one prefix
+        let owner = ALLOWANCE.prefix(b"owner");
+        assert_eq!(3, owner.count(&store, None, None));
+        assert_eq!(1, ALLOWANCE.prefix(b"owner2").count(&store, None, None));
+
+        // inclusive and exclusive bounds
+        assert_eq!(
+            2,
+            owner.count(&store, Some(Bound::Inclusive(b"spender2".to_vec())), None)
+        );
+        assert_eq!(
+            1,
+            owner.count(&store, Some(Bound::Exclusive(b"spender2".to_vec())), None)
+        );
+        assert_eq!(
+            2,
+            owner.count(&store, None, Some(Bound::Inclusive(b"spender2".to_vec())))
+        );
+        assert_eq!(
+            1,
+            owner.count(
+                &store,
+                Some(Bound::Exclusive(b"spender".to_vec())),
+                Some(Bound::Exclusive(b"spender3".to_vec())),
+            )
+        );
+
+        // empty prefix
+        assert_eq!(0, ALLOWANCE.prefix(b"nobody").count(&store, None, None));
+        assert_eq!(0, ALLOWANCE.prefix(b"own").count(&store, None, None));
+    }
+
     #[test]
     #[cfg(feature = "iterator")]
     fn range_simple_string_key() {
@@ -177,6 +177,31 @@ where
         Box::new(mapped)
     }
 
+    /// Returns the number of entries between the bounds, without deserializing them
+    pub fn count(&self, store: &dyn Storage, min: Option<Bound>, max: Option<Bound>) -> usize {
+        range_with_prefix(store, &self.storage_prefix, min, max, Order::Ascending).count()
+    }
+
+    /// Removes up to `limit` entries under this prefix, in ascending key order.
+    /// Returns how many were removed, and whether there are more left, so large prefixes
+    /// can be cleared over several calls. Values are not deserialized.
+    ///
+    /// This works on the raw keys, so it must not be used on prefixes of an `IndexedMap`,
+    /// as its indexes would not be updated.
+    pub fn clear(&self, store: &mut dyn Storage, limit: usize) -> (usize, bool) {
+        let keys: Vec<_> =
+            range_with_prefix(store, &self.storage_prefix, None, None, Order::Ascending)
+                .take(limit.saturating_add(1))
+                .map(|(k, _)| concat(&self.storage_prefix, &k))
+                .collect();
+        let more = keys.len() > limit;
+        let removed = keys.len().min(limit);
+        for key in &keys[..removed] {
+            store.remove(key);
+        }
+        (removed, more)
+    }
+
     pub fn range<'a>(
         &self,
         store: &'a dyn Storage,
@@ -321,8 +346,11 @@ fn increment_last_byte(input: &[u8]) -> Vec<u8> {
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::Map;
     use cosmwasm_std::testing::MockStorage;
 
+    const ALLOWANCE: Map<(&[u8], &[u8]), u64> = Map::new("allow");
+
     #[test]
     fn ensure_proper_range_bounds() {
         let mut store = MockStorage::new();
@@ -463,4 +491,44 @@ mod test {
             .collect();
         assert_eq!(res.unwrap().as_slice(), &[]);
     }
+
+    #[test]
+    fn clear_prefix_in_chunks() {
+        let mut store = MockStorage::new();
+
+        for spender in [&b"a"[..], b"b", b"c", b"d", b"e"] {
+            ALLOWANCE
+                .save(&mut store, (b"owner", spender), &1000)
+                .unwrap();
+        }
+        ALLOWANCE
+            .save(&mut store, (b"owner2", b"a"), &5000)
+            .unwrap();
+        ALLOWANCE.save(&mut store, (b"own", b"a"), &6000).unwrap();
+
+        // clear two at a time, in key order
+        let owner = ALLOWANCE.prefix(b"owner");
+        assert_eq!((2, true), owner.clear(&mut store, 2));
+        assert_eq!(3, owner.count(&store, None, None));
+        assert!(!ALLOWANCE.has(&store, (b"owner", b"b")));
+        assert!(ALLOWANCE.has(&store, (b"owner", b"c")));
+        assert_eq!((2, true), owner.clear(&mut store, 2));
+        assert_eq!((1, false), owner.clear(&mut store, 2));
+        assert_eq!((0, false), owner.clear(&mut store, 2));
+        assert_eq!(0, owner.count(&store, None, None));
+
+        // other prefixes, including ones sharing the first bytes, are untouched
+        assert_eq!(ALLOWANCE.load(&store, (b"owner2", b"a")).unwrap(), 5000);
+        assert_eq!(ALLOWANCE.load(&store, (b"own", b"a")).unwrap(), 6000);
+
+        // a limit that covers everything reports nothing left
+        let owner2 = ALLOWANCE.prefix(b"owner2");
+        assert_eq!((1, false), owner2.clear(&mut store, 1));
+        assert_eq!(1, ALLOWANCE.count(&store, None, None));
+
+        // even the largest one
+        let own = ALLOWANCE.prefix(b"own");
+        assert_eq!((1, false), own.clear(&mut store, usize::MAX));
+        assert_eq!(0, ALLOWANCE.count(&store, None, None));
+    }
 }
@@ -95,6 +95,8 @@ where
         self.primary.may_load(store)
     }
 
+    /// Returns the value at the start of the block at `height`, like
+    /// `SnapshotMap::may_load_at_height`
     pub fn may_load_at_height(&self, store: &dyn Storage, height: u64) -> StdResult<Option<T>> {
         let snapshot = self.snapshots.may_load_at_height(store, (), height)?;
 
@@ -114,6 +114,9 @@ where
         self.primary.may_load(store, k)
     }
 
+    /// Returns the value of the key at the start of the block at `height`, so writes made
+    /// at `height` itself are not included. Returns StdError::NotFound if the strategy kept
+    /// no checkpoint for that height.
     pub fn may_load_at_height(
         &self,
         store: &dyn Storage,
@@ -462,6 +465,44 @@ mod tests {
         );
     }
 
+    #[test]
+    fn history_across_heights() {
+        let mut storage = MockStorage::new();
+        let add = |amount: u64| {
+            move |old: Option<u64>| -> StdResult<u64> { Ok(old.unwrap_or_default() + amount) }
+        };
+
+        // nothing before the first write
+        EVERY.update(&mut storage, "A", 10, add(5)).unwrap();
+        assert_eq!(None, EVERY.may_load_at_height(&storage, "A", 9).unwrap());
+        assert_eq!(None, EVERY.may_load_at_height(&storage, "A", 10).unwrap());
+
+        // several writes in one block only keep the value from before the block
+        EVERY.update(&mut storage, "A", 12, add(3)).unwrap();
+        EVERY.update(&mut storage, "A", 12, add(4)).unwrap();
+        EVERY.update(&mut storage, "A", 15, add(1)).unwrap();
+
+        // heights without writes return the value of the last write before them
+        let expected = [
+            (11, 5),
+            (12, 5),
+            (13, 12),
+            (14, 12),
+            (15, 12),
+            (16, 13),
+            (100, 13),
+        ];
+        for (height, value) in expected {
+            assert_eq!(
+                Some(value),
+                EVERY.may_load_at_height(&storage, "A", height).unwrap(),
+                "height {}",
+                height
+            );
+        }
+        assert_eq!(13, EVERY.load(&storage, "A").unwrap());
+    }
+
     #[test]
     #[cfg(feature = "iterator")]
     fn changelog_range_works() {
@@ -1,10 +1,11 @@
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use cosmwasm_std::{BlockInfo, StdError, StdResult, Timestamp};
+use cosmwasm_std::{BlockInfo, Env, StdError, StdResult, Timestamp};
 use std::cmp::Ordering;
 use std::fmt;
 use std::ops::{Add, Mul};
+use std::str::FromStr;
 
 #[derive(Serialize, Deserialize, Clone, Copy, PartialEq, JsonSchema, Debug)]
 #[serde(rename_all = "snake_case")]
@@ -45,6 +46,43 @@ impl Expiration {
             Expiration::Never {} => false,
         }
     }
+
+    /// Returns the earlier of two expirations. Never is later than anything else.
+    /// Errors if one is height-based and the other time-based, as they cannot be compared.
+    pub fn min(self, other: Expiration) -> StdResult<Expiration> {
+        match self.partial_cmp(&other) {
+            Some(Ordering::Greater) => Ok(other),
+            Some(_) => Ok(self),
+            None => Err(StdError::generic_err("Cannot compare height and time")),
+        }
+    }
+
+    /// Compares the expiration point with the current block. `Ordering::Greater` means it is
+    /// still in the future, anything else means it is expired (see `is_expired`).
+    pub fn cmp_with(&self, env: &Env) -> Ordering {
+        match self {
+            Expiration::AtHeight(height) => height.cmp(&env.block.height),
+            Expiration::AtTime(time) => time.cmp(&env.block.time),
+            Expiration::Never {} => Ordering::Greater,
+        }
+    }
+
+    /// Like adding a Duration, but clamps at the maximum height / time instead of overflowing
+    pub fn saturating_add(self, duration: Duration) -> StdResult<Expiration> {
+        match (self, duration) {
+            (Expiration::AtTime(t), Duration::Time(delta)) => {
+                let nanos = t
+                    .nanos()
+                    .saturating_add(delta.saturating_mul(1_000_000_000));
+                Ok(Expiration::AtTime(Timestamp::from_nanos(nanos)))
+            }
+            (Expiration::AtHeight(h), Duration::Height(delta)) => {
+                Ok(Expiration::AtHeight(h.saturating_add(delta)))
+            }
+            (Expiration::Never {}, _) => Ok(Expiration::Never {}),
+            _ => Err(StdError::generic_err("Cannot add height and time")),
+        }
+    }
 }
 
 impl Add<Duration> for Expiration {
@@ -96,12 +134,34 @@ pub enum Duration {
     Time(u64),
 }
 
+/// Formats as "<seconds>s" or "<blocks>h", which can be parsed back with `from_str`
 impl fmt::Display for Duration {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            Duration::Height(height) => write!(f, "height: {}", height),
-            Duration::Time(time) => write!(f, "time: {}", time),
+            Duration::Height(height) => write!(f, "{}h", height),
+            Duration::Time(time) => write!(f, "{}s", time),
+        }
+    }
+}
+
+/// Parses "3600s" as `Duration::Time(3600)` and "100h" as `Duration::Height(100)`.
+/// Note the "h" stands for block height, not hours
+impl FromStr for Duration {
+    type Err = StdError;
+
+    fn from_str(s: &str) -> StdResult<Duration> {
+        let invalid = || StdError::parse_err("Duration", format!("invalid duration '{}'", s));
+        let (value, to_duration): (_, fn(u64) -> Duration) =
+            match (s.strip_suffix('s'), s.strip_suffix('h')) {
+                (Some(value), _) => (value, Duration::Time),
+                (_, Some(value)) => (value, Duration::Height),
+                _ => return Err(invalid()),
+            };
+        // parse would accept a leading '+'
+        if value.is_empty() || !value.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(invalid());
         }
+        value.parse().map(to_duration).map_err(|_| invalid())
     }
 }
 
@@ -149,6 +209,7 @@ impl Mul<u64> for Duration {
 #[cfg(test)]
 mod test {
     use super::*;
+    use cosmwasm_std::testing::mock_env;
 
     #[test]
     fn compare_expiration() {
@@ -187,6 +248,96 @@ mod test {
         assert!(!(Expiration::AtTime(Timestamp::from_seconds(1000)) == Expiration::AtHeight(230)));
     }
 
+    #[test]
+    fn earlier_expiration() {
+        let h = |h| Expiration::AtHeight(h);
+        let t = |t| Expiration::AtTime(Timestamp::from_seconds(t));
+        let never = Expiration::Never {};
+
+        assert_eq!(h(5).min(h(10)).unwrap(), h(5));
+        assert_eq!(h(10).min(h(5)).unwrap(), h(5));
+        assert_eq!(h(7).min(h(7)).unwrap(), h(7));
+        assert_eq!(t(100).min(t(86)).unwrap(), t(86));
+        assert_eq!(never.min(h(500000)).unwrap(), h(500000));
+        assert_eq!(t(500000).min(never).unwrap(), t(500000));
+        assert_eq!(never.min(never).unwrap(), never);
+
+        // mixed finite variants cannot be compared
+        t(1000).min(h(230)).unwrap_err();
+        h(230).min(t(1000)).unwrap_err();
+    }
+
+    #[test]
+    fn compare_with_env() {
+        let mut env = mock_env();
+        env.block.height = 1000;
+        env.block.time = Timestamp::from_seconds(7777);
+
+        assert_eq!(Expiration::AtHeight(999).cmp_with(&env), Ordering::Less);
+        assert_eq!(Expiration::AtHeight(1000).cmp_with(&env), Ordering::Equal);
+        assert_eq!(Expiration::AtHeight(1001).cmp_with(&env), Ordering::Greater);
+        assert_eq!(
+            Expiration::AtTime(Timestamp::from_seconds(7776)).cmp_with(&env),
+            Ordering::Less
+        );
+        assert_eq!(
+            Expiration::AtTime(Timestamp::from_seconds(7777)).cmp_with(&env),
+            Ordering::Equal
+        );
+        assert_eq!(
+            Expiration::AtTime(Timestamp::from_seconds(7778)).cmp_with(&env),
+            Ordering::Greater
+        );
+        assert_eq!(Expiration::Never {}.cmp_with(&env), Ordering::Greater);
+
+        // consistent with is_expired
+        for exp in [
+            Expiration::AtHeight(999),
+            Expiration::AtHeight(1000),
+            Expiration::AtHeight(1001),
+            Expiration::AtTime(Timestamp::from_seconds(7777)),
+            Expiration::AtTime(Timestamp::from_seconds(7778)),
+            Expiration::Never {},
+        ] {
+            assert_eq!(
+                exp.is_expired(&env.block),
+                exp.cmp_with(&env) != Ordering::Greater
+            );
+        }
+    }
+
+    #[test]
+    fn saturating_expiration_addition() {
+        let end = Expiration::AtHeight(12345).saturating_add(Duration::Height(400));
+        assert_eq!(end.unwrap(), Expiration::AtHeight(12745));
+        let end = Expiration::AtHeight(u64::MAX - 1).saturating_add(Duration::Height(400));
+        assert_eq!(end.unwrap(), Expiration::AtHeight(u64::MAX));
+
+        let end = Expiration::AtTime(Timestamp::from_seconds(55544433))
+            .saturating_add(Duration::Time(40300));
+        assert_eq!(
+            end.unwrap(),
+            Expiration::AtTime(Timestamp::from_seconds(55584733))
+        );
+        let end = Expiration::AtTime(Timestamp::from_seconds(55544433))
+            .saturating_add(Duration::Time(u64::MAX));
+        assert_eq!(
+            end.unwrap(),
+            Expiration::AtTime(Timestamp::from_nanos(u64::MAX))
+        );
+
+        let end = Expiration::Never {}.saturating_add(Duration::Height(u64::MAX));
+        assert_eq!(end.unwrap(), Expiration::Never {});
+
+        // mismatched
+        Expiration::AtHeight(12345)
+            .saturating_add(Duration::Time(1500))
+            .unwrap_err();
+        Expiration::AtTime(Timestamp::from_seconds(1500))
+            .saturating_add(Duration::Height(12345))
+            .unwrap_err();
+    }
+
     #[test]
     fn expiration_addition() {
         // height
@@ -236,4 +387,49 @@ mod test {
         let days = DAY * 3;
         assert_eq!(Duration::Time(3 * 24 * 60 * 60), days);
     }
+
+    #[test]
+    fn parse_duration() {
+        assert_eq!("3600s".parse::<Duration>().unwrap(), Duration::Time(3600));
+        assert_eq!("100h".parse::<Duration>().unwrap(), Duration::Height(100));
+        assert_eq!("0s".parse::<Duration>().unwrap(), Duration::Time(0));
+        assert_eq!(
+            format!("{}h", u64::MAX).parse::<Duration>().unwrap(),
+            Duration::Height(u64::MAX)
+        );
+
+        // round trips with Display
+        for duration in [Duration::Time(3600), Duration::Height(100), WEEK] {
+            let parsed: Duration = duration.to_string().parse().unwrap();
+            assert_eq!(parsed, duration);
+        }
+        assert_eq!(Duration::Time(3600).to_string(), "3600s");
+        assert_eq!(Duration::Height(100).to_string(), "100h");
+
+        // malformed
+        for bad in [
+            "",
+            "s",
+            "h",
+            "3600",
+            "3600m",
+            "-5s",
+            "+5s",
+            " 5s",
+            "5 s",
+            "5S",
+            "1.5h",
+            "s100",
+            "5é",
+            "18446744073709551616s",
+        ] {
+            let err = bad.parse::<Duration>().unwrap_err();
+            assert!(
+                matches!(err, StdError::ParseErr { .. }),
+                "{} gave {}",
+                bad,
+                err
+            );
+        }
+    }
 }
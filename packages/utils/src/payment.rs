@@ -38,6 +38,18 @@ pub fn must_pay(info: &MessageInfo, denom: &str) -> Result<Uint128, PaymentError
     }
 }
 
+/// Like must_pay, but accepts any one of the given denoms.
+/// Returns the coin if exactly one denom was sent with a non-zero amount, and it is one
+/// of `denoms`. Paying with several denoms (even if all accepted) is an error.
+pub fn must_pay_one_of(info: &MessageInfo, denoms: &[&str]) -> Result<Coin, PaymentError> {
+    let coin = one_coin(info)?;
+    if denoms.contains(&coin.denom.as_str()) {
+        Ok(coin)
+    } else {
+        Err(PaymentError::MissingDenom(denoms.join(", ")))
+    }
+}
+
 /// Similar to must_pay, but it any payment is optional. Returns an error if a different
 /// denom was sent. Otherwise, returns the amount of `denom` sent, or 0 if nothing sent.
 pub fn may_pay(info: &MessageInfo, denom: &str) -> Result<Uint128, PaymentError> {
@@ -133,4 +145,35 @@ mod test {
         let err = must_pay(&mixed_payment, atom).unwrap_err();
         assert_eq!(err, PaymentError::MultipleDenoms {});
     }
+
+    #[test]
+    fn must_pay_one_of_works() {
+        let atom: &str = "uatom";
+        let eth: &str = "wei";
+        let accepted = [atom, eth];
+        let no_payment = mock_info(SENDER, &[]);
+        let atom_payment = mock_info(SENDER, &coins(100, atom));
+        let eth_payment = mock_info(SENDER, &coins(120, eth));
+        let zero_payment = mock_info(SENDER, &coins(0, atom));
+        let other_payment = mock_info(SENDER, &coins(100, "ujuno"));
+        let mixed_payment = mock_info(SENDER, &[coin(50, atom), coin(120, eth)]);
+
+        let res = must_pay_one_of(&atom_payment, &accepted).unwrap();
+        assert_eq!(res, coin(100, atom));
+
+        let res = must_pay_one_of(&eth_payment, &accepted).unwrap();
+        assert_eq!(res, coin(120, eth));
+
+        let err = must_pay_one_of(&no_payment, &accepted).unwrap_err();
+        assert_eq!(err, PaymentError::NoFunds {});
+
+        let err = must_pay_one_of(&zero_payment, &accepted).unwrap_err();
+        assert_eq!(err, PaymentError::NoFunds {});
+
+        let err = must_pay_one_of(&other_payment, &accepted).unwrap_err();
+        assert_eq!(err, PaymentError::MissingDenom("uatom, wei".to_string()));
+
+        let err = must_pay_one_of(&mixed_payment, &accepted).unwrap_err();
+        assert_eq!(err, PaymentError::MultipleDenoms {});
+    }
 }